@@ -0,0 +1,536 @@
+//! AWS credentials from the standard chain (environment, shared profile
+//! files, SSO cache) and SigV4 request signing.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+/// Refresh temporary credentials this long before they expire.
+const EXPIRY_MARGIN_SECS: i64 = 300;
+
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    /// Unix seconds; `None` for long-lived keys.
+    pub expires_at: Option<i64>,
+}
+
+impl AwsCredentials {
+    pub fn is_expiring(&self) -> bool {
+        self.expires_at
+            .is_some_and(|exp| Utc::now().timestamp() >= exp - EXPIRY_MARGIN_SECS)
+    }
+}
+
+impl std::fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The profile to read: `profile` if given, else `AWS_PROFILE`, else
+/// `default`.
+pub fn profile_name(profile: Option<&str>) -> String {
+    profile
+        .map(str::to_string)
+        .or_else(|| std::env::var("AWS_PROFILE").ok().filter(|p| !p.is_empty()))
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Resolve credentials the way the AWS CLI does: environment variables, then
+/// static keys in the profile, then the profile's SSO session. An explicit
+/// `profile` skips the environment.
+pub async fn resolve_credentials(profile: Option<&str>) -> Result<AwsCredentials> {
+    if profile.is_none() {
+        if let Some(credentials) = env_credentials() {
+            return Ok(credentials);
+        }
+    }
+    let name = profile_name(profile);
+    let files = ProfileFiles::load();
+    if let Some(credentials) = files.static_credentials(&name) {
+        return Ok(credentials);
+    }
+    if let Some(sso) = files.sso_settings(&name) {
+        return sso_credentials(&name, &sso).await;
+    }
+    anyhow::bail!(
+        "No AWS credentials found for profile '{name}'. Set AWS_ACCESS_KEY_ID and \
+         AWS_SECRET_ACCESS_KEY, add the profile to ~/.aws/credentials, or run \
+         `aws sso login --profile {name}`"
+    )
+}
+
+/// Whether [`resolve_credentials`] has anything to work with, without
+/// touching the network.
+pub fn credentials_configured(profile: Option<&str>) -> bool {
+    if profile.is_none() && env_credentials().is_some() {
+        return true;
+    }
+    let name = profile_name(profile);
+    let files = ProfileFiles::load();
+    files.static_credentials(&name).is_some() || files.sso_settings(&name).is_some()
+}
+
+/// `AWS_REGION`, `AWS_DEFAULT_REGION`, then the profile's `region`.
+pub fn resolve_region(profile: Option<&str>) -> Option<String> {
+    ["AWS_REGION", "AWS_DEFAULT_REGION"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|r| !r.is_empty()))
+        .or_else(|| {
+            ProfileFiles::load()
+                .profile(&profile_name(profile))
+                .get("region")
+                .cloned()
+        })
+}
+
+fn env_credentials() -> Option<AwsCredentials> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    Some(AwsCredentials {
+        access_key_id: var("AWS_ACCESS_KEY_ID")?,
+        secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+        session_token: var("AWS_SESSION_TOKEN"),
+        expires_at: None,
+    })
+}
+
+type Section = HashMap<String, String>;
+
+/// `~/.aws/config` and `~/.aws/credentials`, keyed by section name.
+#[derive(Default)]
+struct ProfileFiles {
+    config: HashMap<String, Section>,
+    credentials: HashMap<String, Section>,
+}
+
+struct SsoSettings {
+    start_url: String,
+    region: String,
+    account_id: String,
+    role_name: String,
+}
+
+impl ProfileFiles {
+    fn load() -> Self {
+        let read = |var: &str, file: &str| {
+            std::env::var(var)
+                .ok()
+                .map(PathBuf::from)
+                .or_else(|| dirs::home_dir().map(|h| h.join(".aws").join(file)))
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .unwrap_or_default()
+        };
+        Self::parse(
+            &read("AWS_CONFIG_FILE", "config"),
+            &read("AWS_SHARED_CREDENTIALS_FILE", "credentials"),
+        )
+    }
+
+    fn parse(config: &str, credentials: &str) -> Self {
+        Self {
+            config: parse_ini(config),
+            credentials: parse_ini(credentials),
+        }
+    }
+
+    /// A profile's settings; the credentials file wins over the config file.
+    fn profile(&self, name: &str) -> Section {
+        let config_key = if name == "default" {
+            "default".to_string()
+        } else {
+            format!("profile {name}")
+        };
+        let mut section = self
+            .config
+            .get(&config_key)
+            .or_else(|| self.config.get(name))
+            .cloned()
+            .unwrap_or_default();
+        if let Some(credentials) = self.credentials.get(name) {
+            section.extend(credentials.clone());
+        }
+        section
+    }
+
+    fn static_credentials(&self, name: &str) -> Option<AwsCredentials> {
+        let profile = self.profile(name);
+        Some(AwsCredentials {
+            access_key_id: profile.get("aws_access_key_id")?.clone(),
+            secret_access_key: profile.get("aws_secret_access_key")?.clone(),
+            session_token: profile.get("aws_session_token").cloned(),
+            expires_at: None,
+        })
+    }
+
+    fn sso_settings(&self, name: &str) -> Option<SsoSettings> {
+        let profile = self.profile(name);
+        let session = profile
+            .get("sso_session")
+            .and_then(|s| self.config.get(&format!("sso-session {s}")))
+            .unwrap_or(&profile);
+        Some(SsoSettings {
+            start_url: session.get("sso_start_url")?.clone(),
+            region: session.get("sso_region")?.clone(),
+            account_id: profile.get("sso_account_id")?.clone(),
+            role_name: profile.get("sso_role_name")?.clone(),
+        })
+    }
+}
+
+fn parse_ini(content: &str) -> HashMap<String, Section> {
+    let mut sections: HashMap<String, Section> = HashMap::new();
+    let mut current: Option<String> = None;
+    for raw in content.lines() {
+        // Indented lines continue a nested setting we do not use.
+        if raw.starts_with([' ', '\t']) {
+            continue;
+        }
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+            sections.entry(name.clone()).or_default();
+            current = Some(name);
+        } else if let (Some(section), Some((key, value))) = (&current, line.split_once('=')) {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SsoCachedToken {
+    start_url: Option<String>,
+    access_token: String,
+    expires_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleCredentialsResponse {
+    role_credentials: RoleCredentials,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    /// Unix milliseconds.
+    expiration: i64,
+}
+
+/// Exchange the token `aws sso login` cached for role credentials.
+async fn sso_credentials(profile: &str, sso: &SsoSettings) -> Result<AwsCredentials> {
+    let token = cached_sso_token(&sso.start_url).ok_or_else(|| {
+        anyhow::anyhow!(
+            "AWS SSO session expired or missing. Run `aws sso login --profile {profile}`"
+        )
+    })?;
+    let resp = reqwest::Client::new()
+        .get(format!(
+            "https://portal.sso.{}.amazonaws.com/federation/credentials",
+            sso.region
+        ))
+        .query(&[
+            ("account_id", sso.account_id.as_str()),
+            ("role_name", sso.role_name.as_str()),
+        ])
+        .header("x-amz-sso_bearer_token", token)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        anyhow::bail!("AWS SSO GetRoleCredentials failed ({status}): {body}");
+    }
+    let creds = resp
+        .json::<RoleCredentialsResponse>()
+        .await
+        .context("Invalid AWS SSO response")?
+        .role_credentials;
+    Ok(AwsCredentials {
+        access_key_id: creds.access_key_id,
+        secret_access_key: creds.secret_access_key,
+        session_token: Some(creds.session_token),
+        expires_at: Some(creds.expiration / 1000),
+    })
+}
+
+/// The newest unexpired token in `~/.aws/sso/cache` for `start_url`.
+fn cached_sso_token(start_url: &str) -> Option<String> {
+    let dir = dirs::home_dir()?.join(".aws").join("sso").join("cache");
+    let now = Utc::now();
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| std::fs::read_to_string(entry.ok()?.path()).ok())
+        .filter_map(|content| serde_json::from_str::<SsoCachedToken>(&content).ok())
+        .filter(|t| t.start_url.as_deref() == Some(start_url))
+        .filter_map(|t| Some((parse_sso_expiry(&t.expires_at)?, t.access_token)))
+        .filter(|(expires, _)| *expires > now)
+        .max_by_key(|(expires, _)| *expires)
+        .map(|(_, token)| token)
+}
+
+/// RFC 3339, or the `...UTC` suffix older CLI versions wrote.
+fn parse_sso_expiry(value: &str) -> Option<DateTime<Utc>> {
+    let value = value
+        .strip_suffix("UTC")
+        .map_or(value.to_string(), |v| format!("{v}Z"));
+    DateTime::parse_from_rfc3339(&value)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// The parts of an HTTP request SigV4 covers.
+pub struct SigningRequest<'a> {
+    pub method: &'a str,
+    pub url: &'a reqwest::Url,
+    /// Headers to sign besides `host` and the `x-amz-*` ones added here.
+    pub headers: &'a [(&'a str, &'a str)],
+    pub body: &'a [u8],
+}
+
+/// Sign `request` for `service` in `region`. Returns the headers to send
+/// with it: `x-amz-date`, `x-amz-security-token` for temporary credentials,
+/// and `authorization`.
+pub fn sign(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    request: &SigningRequest<'_>,
+    time: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = time.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = &amz_date[..8];
+
+    let mut added = vec![("x-amz-date", amz_date.clone())];
+    if let Some(token) = &credentials.session_token {
+        added.push(("x-amz-security-token", token.clone()));
+    }
+
+    let host = match request.url.port() {
+        Some(port) => format!("{}:{port}", request.url.host_str().unwrap_or_default()),
+        None => request.url.host_str().unwrap_or_default().to_string(),
+    };
+    let mut headers: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .map(|(k, v)| (k.to_lowercase(), v.to_string()))
+        .chain(std::iter::once(("host".to_string(), host)))
+        .chain(added.iter().map(|(k, v)| (k.to_string(), v.clone())))
+        .map(|(k, v)| (k, v.split_whitespace().collect::<Vec<_>>().join(" ")))
+        .collect();
+    headers.sort();
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers.iter().map(|(k, v)| format!("{k}:{v}\n")).collect();
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{}",
+        request.method,
+        canonical_uri(request.url),
+        canonical_query(request.url),
+        hex(&Sha256::digest(request.body)),
+    );
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "{ALGORITHM}\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = [date, region, service, "aws4_request"].iter().fold(
+        format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+        |key, part| hmac(&key, part.as_bytes()),
+    );
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+    added.push((
+        "authorization",
+        format!(
+            "{ALGORITHM} Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        ),
+    ));
+    added
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters, as SigV4
+/// and AWS path parameters expect.
+pub fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Every service but S3 encodes the already-encoded path a second time.
+fn canonical_uri(url: &reqwest::Url) -> String {
+    let path = url.path();
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_query(url: &reqwest::Url) -> String {
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_credentials() -> AwsCredentials {
+        AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".into(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: None,
+            expires_at: None,
+        }
+    }
+
+    fn example_time() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2015-08-30T12:36:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn signs_aws_test_suite_requests() {
+        // get-vanilla from the AWS SigV4 test suite.
+        let url = reqwest::Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = sign(
+            &example_credentials(),
+            "us-east-1",
+            "service",
+            &SigningRequest {
+                method: "GET",
+                url: &url,
+                headers: &[],
+                body: b"",
+            },
+            example_time(),
+        );
+        assert_eq!(headers[0], ("x-amz-date", "20150830T123600Z".to_string()));
+        assert_eq!(
+            headers[1].1,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+
+        // The IAM ListUsers example from the SigV4 documentation.
+        let url =
+            reqwest::Url::parse("https://iam.amazonaws.com/?Version=2010-05-08&Action=ListUsers")
+                .unwrap();
+        let headers = sign(
+            &example_credentials(),
+            "us-east-1",
+            "iam",
+            &SigningRequest {
+                method: "GET",
+                url: &url,
+                headers: &[(
+                    "Content-Type",
+                    "application/x-www-form-urlencoded; charset=utf-8",
+                )],
+                body: b"",
+            },
+            example_time(),
+        );
+        assert!(headers[1].1.ends_with(
+            "SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        ));
+    }
+
+    #[test]
+    fn path_is_encoded_twice() {
+        let url = reqwest::Url::parse(&format!(
+            "https://bedrock-runtime.us-east-1.amazonaws.com/model/{}/converse",
+            uri_encode("anthropic.claude-v2:1")
+        ))
+        .unwrap();
+        assert_eq!(
+            canonical_uri(&url),
+            "/model/anthropic.claude-v2%253A1/converse"
+        );
+    }
+
+    #[test]
+    fn profiles_merge_config_and_credentials() {
+        let files = ProfileFiles::parse(
+            "[default]\nregion = us-west-2\n\n\
+             [profile work]\nsso_session = corp\nsso_account_id = 123456789012\n\
+             sso_role_name = Developer\nregion = eu-central-1\ns3 =\n  max_concurrent_requests = 4\n\n\
+             [sso-session corp]\nsso_start_url = https://corp.awsapps.com/start\nsso_region = us-east-1\n",
+            "# keys\n[default]\naws_access_key_id = AKID\naws_secret_access_key = secret\n",
+        );
+
+        let default = files.static_credentials("default").unwrap();
+        assert_eq!(default.access_key_id, "AKID");
+        assert_eq!(files.profile("default")["region"], "us-west-2");
+
+        assert!(files.static_credentials("work").is_none());
+        let sso = files.sso_settings("work").unwrap();
+        assert_eq!(sso.start_url, "https://corp.awsapps.com/start");
+        assert_eq!(sso.region, "us-east-1");
+        assert_eq!(sso.account_id, "123456789012");
+        assert_eq!(sso.role_name, "Developer");
+        assert!(!files
+            .profile("work")
+            .contains_key("max_concurrent_requests"));
+
+        assert!(files.sso_settings("missing").is_none());
+    }
+}
@@ -1,7 +1,10 @@
 pub mod api_key;
+pub mod aws;
+pub mod gcp;
 pub mod oauth;
 pub mod plugin_hook;
 pub mod token_store;
+pub mod vault;
 
 mod error;
 
@@ -97,16 +100,60 @@ pub async fn resolve_credential_async(
     .into())
 }
 
-/// Call when a 429 rate limit is received; rotates to the next account if available.
-pub fn handle_rate_limit(provider: &str) -> Result<Option<Credential>> {
-    if let Ok(Some(token)) = token_store::rotate_on_rate_limit(provider, 60) {
-        if token.refresh_token.is_some() {
-            Ok(Some(Credential::Bearer(token.access_token)))
-        } else {
-            Ok(Some(Credential::ApiKey(token.access_token)))
-        }
-    } else {
-        Ok(None)
+/// An account switch made after a rate limit.
+#[derive(Debug, Clone)]
+pub struct AccountSwitch {
+    /// Label of the account that hit the limit.
+    pub from: Option<String>,
+    /// Label of the account now in use.
+    pub to: Option<String>,
+    pub credential: Credential,
+}
+
+/// Call when a 429 rate limit is received; marks the active account limited
+/// for `wait_seconds` and rotates to the next account if one is available.
+pub async fn handle_rate_limit(
+    provider: &str,
+    wait_seconds: u64,
+) -> Result<Option<AccountSwitch>> {
+    let from = token_store::active_account(provider)?.and_then(|e| e.label);
+    let Some(next) = token_store::rotate_on_rate_limit(provider, wait_seconds)? else {
+        return Ok(None);
+    };
+    Ok(Some(AccountSwitch {
+        from,
+        to: next.label,
+        credential: stored_credential(provider, &next.token).await?,
+    }))
+}
+
+/// Switch back to the account labelled `label` once its rate limit has
+/// expired; `None` while it is still cooling down.
+pub async fn restore_account(provider: &str, label: Option<&str>) -> Result<Option<Credential>> {
+    match token_store::restore_account(provider, label)? {
+        Some(entry) => Ok(Some(stored_credential(provider, &entry.token).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Whether `provider`'s credential comes from several stored accounts that
+/// can be rotated through, rather than from config or the environment.
+pub fn has_rotatable_accounts(provider: &str, config_key: Option<&str>) -> bool {
+    config_key.is_none()
+        && api_key::from_env(provider).is_err()
+        && token_store::list_accounts(provider).is_ok_and(|accounts| accounts.len() > 1)
+}
+
+/// The credential of `token`, which must be `provider`'s active account.
+/// OAuth tokens go through the refresh path, since a spare account's access
+/// token has usually expired while it sat unused.
+async fn stored_credential(provider: &str, token: &token_store::StoredToken) -> Result<Credential> {
+    if token.refresh_token.is_none() {
+        return Ok(Credential::ApiKey(token.access_token.clone()));
+    }
+    match oauth::refresh::refresh_if_needed(provider).await? {
+        Some(fresh) => Ok(Credential::Bearer(fresh.access_token)),
+        None => anyhow::bail!("The stored {provider} token expired and could not be refreshed"),
     }
 }
 
@@ -118,5 +165,13 @@ pub fn auth_status(provider: &str) -> &'static str {
     if let Ok(Some(_)) = token_store::load_token(provider) {
         return "connected";
     }
+    let style = nyzhi_config::find_provider_def(provider).map(|d| d.api_style);
+    if style == Some("bedrock") && aws::credentials_configured(None) {
+        return "aws";
+    }
+    let uses_adc = matches!(style, Some("vertex" | "vertex-anthropic"));
+    if uses_adc && gcp::adc_path().is_some_and(|p| p.exists()) {
+        return "adc";
+    }
     "not connected"
 }
@@ -4,6 +4,8 @@ use std::path::PathBuf;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+use crate::vault;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredToken {
     pub access_token: String,
@@ -62,16 +64,40 @@ fn auth_file_path() -> PathBuf {
         .join("auth.json")
 }
 
+fn key_file_path() -> PathBuf {
+    auth_file_path().with_file_name("auth.key")
+}
+
 fn load_store() -> Result<AuthStore> {
     let path = auth_file_path();
     if !path.exists() {
         return Ok(AuthStore::default());
     }
+    vault::restrict_permissions(&path)?;
     let content = std::fs::read_to_string(&path)?;
     if content.trim().is_empty() {
         return Ok(AuthStore::default());
     }
-    let raw: HashMap<String, StoreValue> = serde_json::from_str(&content)?;
+    match vault::Sealed::detect(&content) {
+        Some(sealed) => {
+            let plaintext = vault::open_store(&sealed, &key_file_path())?;
+            parse_store(&plaintext)
+        }
+        None => {
+            // Written before the store was encrypted: seal it in place. If
+            // no key is available yet, keep reading it as it is.
+            let store = parse_store(content.as_bytes())?;
+            match save_store(&store) {
+                Ok(()) => tracing::info!("Encrypted credential store at {}", path.display()),
+                Err(e) => tracing::warn!("Credential store left unencrypted: {e:#}"),
+            }
+            Ok(store)
+        }
+    }
+}
+
+fn parse_store(content: &[u8]) -> Result<AuthStore> {
+    let raw: HashMap<String, StoreValue> = serde_json::from_slice(content)?;
     let entries = raw
         .into_iter()
         .map(|(k, v)| (k, v.into_accounts()))
@@ -80,17 +106,61 @@ fn load_store() -> Result<AuthStore> {
 }
 
 fn save_store(store: &AuthStore) -> Result<()> {
-    let path = auth_file_path();
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)?;
+    let plaintext = serde_json::to_vec(store)?;
+    let sealed = vault::seal_store(&plaintext)?;
+    vault::write_private(&auth_file_path(), &serde_json::to_vec_pretty(&sealed)?)?;
+    // A key file from an earlier version is no longer needed once the store
+    // is sealed with another key.
+    let key_file = key_file_path();
+    if key_file.exists() {
+        std::fs::remove_file(&key_file)?;
+        tracing::info!("Re-sealed credential store; removed {}", key_file.display());
     }
-    let tmp = path.with_extension("json.tmp");
-    let content = serde_json::to_string_pretty(store)?;
-    std::fs::write(&tmp, &content)?;
-    std::fs::rename(&tmp, &path)?;
     Ok(())
 }
 
+/// Every stored credential, sealed with `passphrase` for import on another
+/// machine.
+pub fn export_store(passphrase: &str) -> Result<String> {
+    let plaintext = serde_json::to_vec(&load_store()?)?;
+    let sealed = vault::seal_with_passphrase(&plaintext, passphrase)?;
+    Ok(serde_json::to_string_pretty(&sealed)?)
+}
+
+/// Merge an [`export_store`] file into this store. Accounts with the same
+/// provider and label are replaced. Returns the number of accounts imported.
+pub fn import_store(data: &str, passphrase: &str) -> Result<usize> {
+    let sealed = vault::Sealed::detect(data)
+        .ok_or_else(|| anyhow::anyhow!("Not a nyzhi credential export"))?;
+    let imported = parse_store(&vault::open_with_passphrase(&sealed, passphrase)?)?;
+    let mut store = load_store()?;
+    let count = merge_stores(&mut store, imported);
+    save_store(&store)?;
+    Ok(count)
+}
+
+fn merge_stores(store: &mut AuthStore, imported: AuthStore) -> usize {
+    let mut count = 0;
+    for (provider, entries) in imported.entries {
+        let accounts = store.entries.entry(provider).or_default();
+        for mut entry in entries {
+            count += 1;
+            entry.rate_limited_until = None;
+            match accounts.iter_mut().find(|e| e.label == entry.label) {
+                Some(existing) => {
+                    entry.active = existing.active;
+                    *existing = entry;
+                }
+                None => {
+                    entry.active = accounts.is_empty();
+                    accounts.push(entry);
+                }
+            }
+        }
+    }
+    count
+}
+
 pub fn store_token(provider: &str, token: &StoredToken) -> Result<()> {
     let mut store = load_store()?;
     let accounts = store.entries.entry(provider.to_string()).or_default();
@@ -135,10 +205,7 @@ pub fn load_token(provider: &str) -> Result<Option<StoredToken>> {
         Some(a) if !a.is_empty() => a,
         _ => return Ok(None),
     };
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+    let now = now_secs();
 
     if let Some(entry) = accounts
         .iter()
@@ -187,40 +254,75 @@ pub fn remove_account(provider: &str, label: Option<&str>) -> Result<bool> {
     }
 }
 
-pub fn rotate_on_rate_limit(provider: &str, wait_seconds: u64) -> Result<Option<StoredToken>> {
+/// Mark the active account rate limited for `wait_seconds` and switch to the
+/// next account whose cooldown has passed. Returns the newly active account,
+/// or `None` when every other account is limited too.
+pub fn rotate_on_rate_limit(provider: &str, wait_seconds: u64) -> Result<Option<AccountEntry>> {
     let mut store = load_store()?;
     let accounts = match store.entries.get_mut(provider) {
         Some(a) if a.len() > 1 => a,
         _ => return Ok(None),
     };
 
-    let now = std::time::SystemTime::now()
+    let next = rotate_accounts(accounts, now_secs(), wait_seconds).map(|i| accounts[i].clone());
+    save_store(&store)?;
+    if next.is_some() {
+        tracing::info!("Rotated to next account for {provider}");
+    }
+    Ok(next)
+}
+
+/// Make the account labelled `label` active again once its rate limit has
+/// expired. Returns it, or `None` while it is still cooling down.
+pub fn restore_account(provider: &str, label: Option<&str>) -> Result<Option<AccountEntry>> {
+    let mut store = load_store()?;
+    let Some(accounts) = store.entries.get_mut(provider) else {
+        return Ok(None);
+    };
+    let Some(i) = restore_accounts(accounts, label, now_secs()) else {
+        return Ok(None);
+    };
+    let restored = accounts[i].clone();
+    save_store(&store)?;
+    tracing::info!("Restored original account for {provider}");
+    Ok(Some(restored))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
-        .as_secs();
+        .as_secs()
+}
 
-    if let Some(current) = accounts.iter_mut().find(|e| e.active) {
-        current.active = false;
-        current.rate_limited_until = Some(now + wait_seconds);
+fn rotate_accounts(accounts: &mut [AccountEntry], now: u64, wait_seconds: u64) -> Option<usize> {
+    let current = accounts.iter().position(|e| e.active);
+    if let Some(i) = current {
+        accounts[i].rate_limited_until = Some(now + wait_seconds);
     }
 
-    let next = accounts
-        .iter_mut()
-        .find(|e| !e.active && e.rate_limited_until.map_or(true, |until| now >= until));
+    // When every account is limited the current one stays active.
+    let next = accounts.iter().enumerate().position(|(i, e)| {
+        Some(i) != current && e.rate_limited_until.map_or(true, |until| now >= until)
+    })?;
+    for (i, entry) in accounts.iter_mut().enumerate() {
+        entry.active = i == next;
+    }
+    Some(next)
+}
 
-    if let Some(next_account) = next {
-        next_account.active = true;
-        let token = next_account.token.clone();
-        save_store(&store)?;
-        tracing::info!("Rotated to next account for {provider}");
-        Ok(Some(token))
-    } else {
-        if let Some(first) = accounts.first_mut() {
-            first.active = true;
-        }
-        save_store(&store)?;
-        Ok(None)
+fn restore_accounts(accounts: &mut [AccountEntry], label: Option<&str>, now: u64) -> Option<usize> {
+    let i = accounts
+        .iter()
+        .position(|e| e.label.as_deref() == label)?;
+    if accounts[i].rate_limited_until.is_some_and(|until| now < until) {
+        return None;
+    }
+    for (j, entry) in accounts.iter_mut().enumerate() {
+        entry.active = j == i;
     }
+    accounts[i].rate_limited_until = None;
+    Some(i)
 }
 
 pub fn delete_token(provider: &str) -> Result<()> {
@@ -244,7 +346,8 @@ pub fn list_providers() -> Result<Vec<String>> {
     Ok(store.entries.keys().cloned().collect())
 }
 
-/// Migrate tokens from keyring to auth.json (one-time, best-effort).
+/// Migrate tokens from keyring entries of older versions to the credential
+/// store (one-time, best-effort).
 pub fn migrate_from_keyring() {
     let providers = ["openai", "anthropic", "gemini"];
     for prov in &providers {
@@ -255,10 +358,90 @@ pub fn migrate_from_keyring() {
             if let Ok(json) = entry.get_password() {
                 if let Ok(token) = serde_json::from_str::<StoredToken>(&json) {
                     let _ = store_token(prov, &token);
-                    tracing::info!("Migrated {prov} credential from keyring to auth.json");
+                    tracing::info!("Migrated {prov} credential from keyring to auth store");
                     let _ = entry.delete_credential();
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(label: &str, active: bool, rate_limited_until: Option<u64>) -> AccountEntry {
+        AccountEntry {
+            label: Some(label.to_string()),
+            token: StoredToken {
+                access_token: format!("key-{label}"),
+                refresh_token: None,
+                expires_at: None,
+                provider: "anthropic".to_string(),
+            },
+            active,
+            rate_limited_until,
+        }
+    }
+
+    #[test]
+    fn rotation_skips_limited_accounts() {
+        let mut accounts = vec![
+            account("work", true, None),
+            account("spare", false, Some(2_000)),
+            account("personal", false, Some(500)),
+        ];
+        assert_eq!(rotate_accounts(&mut accounts, 1_000, 60), Some(2));
+        assert!(!accounts[0].active);
+        assert_eq!(accounts[0].rate_limited_until, Some(1_060));
+        assert!(accounts[2].active);
+
+        // Everyone is limited now: the current account stays active.
+        assert_eq!(rotate_accounts(&mut accounts, 1_010, 60), None);
+        assert!(accounts[2].active);
+        assert_eq!(accounts[2].rate_limited_until, Some(1_070));
+        assert_eq!(accounts.iter().filter(|e| e.active).count(), 1);
+    }
+
+    #[test]
+    fn restore_waits_for_cooldown() {
+        let mut accounts = vec![
+            account("work", false, Some(1_060)),
+            account("personal", true, None),
+        ];
+        assert_eq!(restore_accounts(&mut accounts, Some("work"), 1_030), None);
+        assert!(accounts[1].active);
+
+        assert_eq!(restore_accounts(&mut accounts, Some("work"), 1_060), Some(0));
+        assert!(accounts[0].active);
+        assert!(!accounts[1].active);
+        assert_eq!(accounts[0].rate_limited_until, None);
+        assert_eq!(restore_accounts(&mut accounts, Some("gone"), 2_000), None);
+    }
+
+    #[test]
+    fn import_replaces_matching_labels() {
+        let mut store = AuthStore::default();
+        store
+            .entries
+            .insert("anthropic".into(), vec![account("work", true, Some(1_060))]);
+        let mut imported = AuthStore::default();
+        let mut work = account("work", false, Some(9_999));
+        work.token.access_token = "key-new".into();
+        imported
+            .entries
+            .insert("anthropic".into(), vec![work, account("spare", true, None)]);
+        imported
+            .entries
+            .insert("openai".into(), vec![account("main", false, None)]);
+
+        assert_eq!(merge_stores(&mut store, imported), 3);
+        let anthropic = &store.entries["anthropic"];
+        assert_eq!(anthropic.len(), 2);
+        assert_eq!(anthropic[0].token.access_token, "key-new");
+        assert!(anthropic[0].active);
+        assert_eq!(anthropic[0].rate_limited_until, None);
+        assert!(!anthropic[1].active);
+        assert!(store.entries["openai"][0].active);
+    }
+}
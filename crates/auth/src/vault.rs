@@ -0,0 +1,395 @@
+//! At-rest encryption for the credential store.
+//!
+//! The store is sealed with AES-256-GCM. Its key comes from a passphrase in
+//! [`PASSPHRASE_ENV`] (stretched with scrypt) or, without one, a random key
+//! kept in the OS keyring. With neither, credentials are not saved: a key
+//! file next to the store would protect no better than plaintext.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub const PASSPHRASE_ENV: &str = "NYZHI_AUTH_PASSPHRASE";
+
+const KEYRING_SERVICE: &str = "nyzhi";
+/// Keyring account (under the `nyzhi` service) that holds the store key.
+pub const KEYRING_USER: &str = "auth-store-key";
+const FORMAT_VERSION: u32 = 1;
+/// scrypt cost for passphrase keys: 2^15 rounds, 32 MiB.
+const SCRYPT_LOG_N: u8 = 15;
+/// Highest cost accepted from a file: 2^20 rounds, 1 GiB.
+const MAX_SCRYPT_LOG_N: u8 = 20;
+
+/// Where the key of a sealed file comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySource {
+    Passphrase,
+    Keyring,
+    /// A key in a file next to the store, from earlier versions. Such stores
+    /// are still opened but re-sealed with a passphrase or keyring key.
+    Keyfile,
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::Passphrase => write!(f, "passphrase"),
+            KeySource::Keyring => write!(f, "OS keyring"),
+            KeySource::Keyfile => write!(f, "local key file"),
+        }
+    }
+}
+
+/// An encrypted payload as written to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sealed {
+    pub version: u32,
+    pub key_source: KeySource,
+    /// scrypt salt and cost, for passphrase keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_n: Option<u8>,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl Sealed {
+    /// Whether `content` is a sealed file rather than a legacy plaintext one.
+    pub fn detect(content: &str) -> Option<Self> {
+        serde_json::from_str(content).ok()
+    }
+}
+
+/// A resolved key with what is needed to seal with it again.
+#[derive(Clone)]
+struct StoreKey {
+    source: KeySource,
+    salt: Option<Vec<u8>>,
+    log_n: Option<u8>,
+    key: [u8; 32],
+}
+
+/// The store key, kept for the life of the process so scrypt and keyring
+/// lookups happen once.
+static STORE_KEY: Mutex<Option<StoreKey>> = Mutex::new(None);
+
+/// Seal the credential store, reusing the key it was opened with unless
+/// that was a key file.
+pub(crate) fn seal_store(plaintext: &[u8]) -> Result<Sealed> {
+    let mut cached = STORE_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    let key = match cached.as_ref().filter(|k| k.source != KeySource::Keyfile) {
+        Some(key) => key.clone(),
+        None => {
+            let key = new_store_key(env_passphrase())?;
+            *cached = Some(key.clone());
+            key
+        }
+    };
+    seal(plaintext, &key)
+}
+
+/// Open the credential store with the key its header names.
+pub(crate) fn open_store(sealed: &Sealed, keyfile: &Path) -> Result<Vec<u8>> {
+    let mut cached = STORE_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(key) = cached.as_ref().filter(|k| matches_header(k, sealed)) {
+        return open(sealed, &key.key);
+    }
+    let key = existing_store_key(sealed, keyfile)?;
+    let plaintext = open(sealed, &key.key)?;
+    *cached = Some(key);
+    Ok(plaintext)
+}
+
+/// Seal `plaintext` with a key derived from `passphrase`, for moving
+/// credentials between machines.
+pub fn seal_with_passphrase(plaintext: &[u8], passphrase: &str) -> Result<Sealed> {
+    seal(plaintext, &passphrase_key(passphrase, None, SCRYPT_LOG_N)?)
+}
+
+pub fn open_with_passphrase(sealed: &Sealed, passphrase: &str) -> Result<Vec<u8>> {
+    if sealed.key_source != KeySource::Passphrase {
+        anyhow::bail!("This file is not passphrase-protected");
+    }
+    let key = passphrase_key(passphrase, Some(sealed), SCRYPT_LOG_N)?;
+    open(sealed, &key.key)
+}
+
+fn matches_header(key: &StoreKey, sealed: &Sealed) -> bool {
+    key.source == sealed.key_source
+        && key.salt.as_deref().map(|s| STANDARD.encode(s)) == sealed.salt
+}
+
+fn seal(plaintext: &[u8], key: &StoreKey) -> Result<Sealed> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.key));
+    let nonce: [u8; 12] = rand::rng().random();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt credentials"))?;
+    Ok(Sealed {
+        version: FORMAT_VERSION,
+        key_source: key.source,
+        salt: key.salt.as_deref().map(|s| STANDARD.encode(s)),
+        log_n: key.log_n,
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+}
+
+fn open(sealed: &Sealed, key: &[u8; 32]) -> Result<Vec<u8>> {
+    if sealed.version != FORMAT_VERSION {
+        anyhow::bail!("Unsupported credential file version {}", sealed.version);
+    }
+    let nonce = STANDARD.decode(&sealed.nonce).context("Corrupt nonce")?;
+    if nonce.len() != 12 {
+        anyhow::bail!("Corrupt nonce");
+    }
+    let ciphertext = STANDARD
+        .decode(&sealed.ciphertext)
+        .context("Corrupt ciphertext")?;
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow::anyhow!("Wrong key or corrupted credentials ({})", sealed.key_source))
+}
+
+/// Pick the key for a store that has not been encrypted yet, or that was
+/// sealed with a key file.
+fn new_store_key(passphrase: Option<String>) -> Result<StoreKey> {
+    if let Some(passphrase) = passphrase {
+        return passphrase_key(&passphrase, None, SCRYPT_LOG_N);
+    }
+    let key: [u8; 32] = rand::rng().random();
+    if !store_in_keyring(&key) {
+        anyhow::bail!(
+            "No OS keyring is available to hold the credential store key. \
+             Set {PASSPHRASE_ENV} to protect stored credentials with a passphrase."
+        );
+    }
+    Ok(StoreKey {
+        source: KeySource::Keyring,
+        salt: None,
+        log_n: None,
+        key,
+    })
+}
+
+fn existing_store_key(sealed: &Sealed, keyfile: &Path) -> Result<StoreKey> {
+    let encoded = match sealed.key_source {
+        KeySource::Passphrase => {
+            let passphrase = env_passphrase().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "The credential store is passphrase-protected. Set {PASSPHRASE_ENV} to unlock it."
+                )
+            })?;
+            return passphrase_key(&passphrase, Some(sealed), SCRYPT_LOG_N);
+        }
+        KeySource::Keyring => keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+            .and_then(|e| e.get_password())
+            .context("The credential store key is missing from the OS keyring")?,
+        KeySource::Keyfile => std::fs::read_to_string(keyfile)
+            .with_context(|| format!("Failed to read {}", keyfile.display()))?,
+    };
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .context("Corrupt credential store key")?;
+    let key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Corrupt credential store key"))?;
+    Ok(StoreKey {
+        source: sealed.key_source,
+        salt: None,
+        log_n: None,
+        key,
+    })
+}
+
+/// Derive a key from `passphrase`, with the salt and cost from `sealed` when
+/// opening and fresh ones when sealing.
+fn passphrase_key(passphrase: &str, sealed: Option<&Sealed>, log_n: u8) -> Result<StoreKey> {
+    let (salt, log_n) = match sealed {
+        Some(sealed) => (
+            STANDARD
+                .decode(sealed.salt.as_deref().unwrap_or_default())
+                .context("Corrupt salt")?,
+            sealed.log_n.unwrap_or(log_n),
+        ),
+        None => (rand::rng().random::<[u8; 16]>().to_vec(), log_n),
+    };
+    // The cost of an opened file is untrusted: a huge one would hang or
+    // exhaust memory.
+    if !(1..=MAX_SCRYPT_LOG_N).contains(&log_n) {
+        anyhow::bail!("Unsupported key derivation cost {log_n}");
+    }
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|e| anyhow::anyhow!("Invalid scrypt parameters: {e}"))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), &salt, &params, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {e}"))?;
+    Ok(StoreKey {
+        source: KeySource::Passphrase,
+        salt: Some(salt),
+        log_n: Some(log_n),
+        key,
+    })
+}
+
+fn env_passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty())
+}
+
+/// Save the key in the keyring and read it back through a fresh entry, so a
+/// build without a persistent keyring backend is not mistaken for one.
+fn store_in_keyring(key: &[u8; 32]) -> bool {
+    let encoded = STANDARD.encode(key);
+    let stored = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .and_then(|e| e.set_password(&encoded))
+        .is_ok();
+    stored
+        && keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+            .and_then(|e| e.get_password())
+            .is_ok_and(|read| read == encoded)
+}
+
+/// Write `content` to `path` readable by the owner only, replacing it
+/// atomically.
+pub fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = temp_path(path);
+    {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&tmp)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::io::Write::write_all(&mut file, content)?;
+        file.sync_all()?;
+    }
+    restrict_permissions(&tmp)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// `auth.json` -> `auth.json.tmp`, so files that differ only in extension
+/// do not share a temporary file.
+fn temp_path(path: &Path) -> std::path::PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Tighten a file readable by group or others to owner-only.
+pub(crate) fn restrict_permissions(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+            tracing::warn!(
+                "Restricted {} to owner-only access (was {:o})",
+                path.display(),
+                mode & 0o777
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passphrase_round_trip() {
+        let key = passphrase_key("correct horse", None, 4).unwrap();
+        let sealed = seal(b"{\"openai\":[]}", &key).unwrap();
+        assert_eq!(sealed.key_source, KeySource::Passphrase);
+        assert!(!sealed.ciphertext.contains("openai"));
+
+        let reopened = passphrase_key("correct horse", Some(&sealed), SCRYPT_LOG_N).unwrap();
+        assert_eq!(open(&sealed, &reopened.key).unwrap(), b"{\"openai\":[]}");
+
+        let wrong = passphrase_key("battery staple", Some(&sealed), SCRYPT_LOG_N).unwrap();
+        assert!(open(&sealed, &wrong.key).is_err());
+    }
+
+    #[test]
+    fn costs_from_a_file_are_bounded() {
+        let key = passphrase_key("correct horse", None, 4).unwrap();
+        let mut sealed = seal(b"{}", &key).unwrap();
+        for log_n in [0, MAX_SCRYPT_LOG_N + 1, 63, u8::MAX] {
+            sealed.log_n = Some(log_n);
+            assert!(open_with_passphrase(&sealed, "correct horse").is_err());
+        }
+    }
+
+    #[test]
+    fn detects_legacy_plaintext() {
+        let key = passphrase_key("pw", None, 4).unwrap();
+        let sealed = serde_json::to_string(&seal(b"{}", &key).unwrap()).unwrap();
+        assert!(Sealed::detect(&sealed).is_some());
+        assert!(
+            Sealed::detect(r#"{"openai":{"access_token":"sk-1","provider":"openai"}}"#).is_none()
+        );
+    }
+
+    #[test]
+    fn temp_files_are_per_target() {
+        let dir = Path::new("/data/nyzhi");
+        assert_ne!(
+            temp_path(&dir.join("auth.json")),
+            temp_path(&dir.join("auth.key"))
+        );
+        assert_eq!(temp_path(&dir.join("auth.json")), dir.join("auth.json.tmp"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("auth.json");
+        write_private(&path, b"x").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        assert!(!dir.path().join("auth.json.tmp").exists());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        restrict_permissions(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn without_keyring_or_passphrase_the_key_is_refused() {
+        // What a machine without a keyring service gets: a store that
+        // forgets every entry.
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        let Err(err) = new_store_key(None) else {
+            panic!("a key without a keyring or passphrase");
+        };
+        assert!(err.to_string().contains(PASSPHRASE_ENV), "{err}");
+
+        let key = new_store_key(Some("correct horse".into())).unwrap();
+        assert_eq!(key.source, KeySource::Passphrase);
+    }
+}
@@ -96,6 +96,11 @@ enum Commands {
     },
     /// Show current auth status for the active provider
     Whoami,
+    /// Move stored credentials between machines
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
     /// Show current configuration
     Config,
     /// Initialize a .nyzhi/ project directory
@@ -105,9 +110,14 @@ enum Commands {
         #[command(subcommand)]
         action: McpAction,
     },
+    /// Manage plugins
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
+    },
     /// List saved sessions
     Sessions {
-        /// Optional search query to filter by ID or title
+        /// Optional search over IDs, titles and message text
         query: Option<String>,
     },
     /// Export a session to markdown
@@ -182,6 +192,21 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum AuthAction {
+    /// Write every stored credential to a passphrase-protected file
+    Export {
+        /// Output file path (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Merge credentials from a file written by `auth export`
+    Import {
+        /// File to import
+        file: String,
+    },
+}
+
 #[derive(Subcommand)]
 enum TeamsAction {
     /// List all agent teams
@@ -212,6 +237,14 @@ enum SessionAction {
         /// New title for the session
         title: String,
     },
+    /// Copy a saved session into a new one, optionally cut short
+    Fork {
+        /// Session ID prefix or title query
+        id: String,
+        /// Keep only the first N messages (default: all)
+        #[arg(long)]
+        at: Option<usize>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -242,6 +275,44 @@ enum McpAction {
     },
 }
 
+#[derive(Subcommand)]
+enum PluginAction {
+    /// Install a plugin from a local directory or git URL
+    Install {
+        /// Path or git URL of the plugin
+        source: String,
+        /// Scope: "user", "project" or "local" (default: user)
+        #[arg(long, default_value = "user")]
+        scope: String,
+    },
+    /// List installed plugins
+    List,
+    /// Enable a disabled plugin
+    Enable {
+        /// Plugin name
+        name: String,
+        /// Scope: "user", "project" or "local" (default: first that has it)
+        #[arg(long)]
+        scope: Option<String>,
+    },
+    /// Disable a plugin without removing it
+    Disable {
+        /// Plugin name
+        name: String,
+        /// Scope: "user", "project" or "local" (default: first that has it)
+        #[arg(long)]
+        scope: Option<String>,
+    },
+    /// Remove an installed plugin
+    Remove {
+        /// Plugin name
+        name: String,
+        /// Scope: "user", "project" or "local" (default: first that has it)
+        #[arg(long)]
+        scope: Option<String>,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
@@ -312,6 +383,10 @@ async fn main() -> Result<()> {
             handle_mcp_command(action, &workspace, &config).await?;
             return Ok(());
         }
+        Some(Commands::Plugin { action }) => {
+            handle_plugin_command(action, &workspace)?;
+            return Ok(());
+        }
         Some(Commands::Login { provider: prov }) => {
             let prov = match prov {
                 Some(p) => p,
@@ -350,6 +425,10 @@ async fn main() -> Result<()> {
             }
             return Ok(());
         }
+        Some(Commands::Auth { action }) => {
+            handle_auth_command(action)?;
+            return Ok(());
+        }
         Some(Commands::Logout { provider: prov }) => {
             nyzhi_auth::token_store::delete_token(&prov)?;
             println!("Logged out from {prov}.");
@@ -384,7 +463,7 @@ async fn main() -> Result<()> {
                 }
                 println!("{line}");
             }
-            for (name, _entry) in &config.provider.providers {
+            for name in config.provider.providers.keys() {
                 if seen.contains(name) {
                     continue;
                 }
@@ -400,9 +479,15 @@ async fn main() -> Result<()> {
         }
         Some(Commands::Sessions { query }) => {
             let sessions = if let Some(q) = &query {
-                nyzhi_core::session::find_sessions(q)?
+                nyzhi_core::session::search_sessions(q)?
             } else {
                 nyzhi_core::session::list_sessions()?
+                    .into_iter()
+                    .map(|meta| nyzhi_core::session::SessionMatch {
+                        meta,
+                        snippet: None,
+                    })
+                    .collect()
             };
             if sessions.is_empty() {
                 if let Some(q) = &query {
@@ -415,7 +500,8 @@ async fn main() -> Result<()> {
                     "{:<10} {:<40} {:>4}  {:<20} UPDATED",
                     "ID", "TITLE", "MSGS", "PROVIDER/MODEL"
                 );
-                for s in sessions.iter().take(50) {
+                for m in sessions.iter().take(50) {
+                    let s = &m.meta;
                     let title = if s.title.len() > 38 {
                         format!("{}…", &s.title[..37])
                     } else {
@@ -435,6 +521,9 @@ async fn main() -> Result<()> {
                         pm_display,
                         s.updated_at.format("%Y-%m-%d %H:%M"),
                     );
+                    if let Some(snippet) = &m.snippet {
+                        println!("{:<10} {}", "", snippet.replace('\n', " "));
+                    }
                 }
                 if sessions.len() > 50 {
                     println!("... and {} more", sessions.len() - 50);
@@ -523,6 +612,34 @@ async fn main() -> Result<()> {
                         }
                     }
                 }
+                SessionAction::Fork { id, at } => {
+                    let matches = nyzhi_core::session::find_sessions(&id)?;
+                    match matches.len() {
+                        0 => {
+                            eprintln!("No session matching '{id}'");
+                            std::process::exit(1);
+                        }
+                        1 => {
+                            let s = &matches[0];
+                            let forked = nyzhi_core::session::fork_session(&s.id, at)?;
+                            println!(
+                                "Forked session [{}] into [{}] \"{}\" ({} messages)",
+                                &s.id[..8],
+                                &forked.id[..8],
+                                forked.title,
+                                forked.message_count,
+                            );
+                            println!("Resume it with: nyz --session {}", &forked.id[..8]);
+                        }
+                        n => {
+                            eprintln!("Ambiguous: {n} sessions match '{id}'. Be more specific.");
+                            for s in matches.iter().take(10) {
+                                eprintln!("  [{}] {}", &s.id[..8], s.title);
+                            }
+                            std::process::exit(1);
+                        }
+                    }
+                }
             }
             return Ok(());
         }
@@ -792,7 +909,13 @@ async fn main() -> Result<()> {
                 }
             }
 
-            for provider in ["openai", "anthropic", "gemini", "openrouter"] {
+            for provider in [
+                "openai",
+                "anthropic",
+                "gemini",
+                "openrouter",
+                nyzhi_auth::vault::KEYRING_USER,
+            ] {
                 if let Ok(entry) = keyring::Entry::new("nyzhi", provider) {
                     match entry.delete_credential() {
                         Ok(()) => removed.push(format!("  ✓ keyring: nyzhi/{provider}")),
@@ -899,7 +1022,14 @@ async fn main() -> Result<()> {
     let bundle = nyzhi_core::tools::default_registry(None);
     let mut registry = bundle.registry;
     let todo_store = bundle.todo_store;
-    let deferred_index = bundle.deferred_index;
+
+    let mut config = config;
+    let plugin_notices =
+        nyzhi_core::plugins::manager::PluginManager::load_all(&workspace.project_root)
+            .merge_into(&mut config);
+    for notice in &plugin_notices {
+        tracing::warn!("{notice}");
+    }
 
     let mut all_mcp_servers = config.mcp.servers.clone();
     let mcp_json_servers = nyzhi_core::mcp::load_mcp_json(&workspace.project_root);
@@ -911,31 +1041,37 @@ async fn main() -> Result<()> {
                 let all_tools = mgr.all_tools().await;
                 let defer_mcp = all_tools.len() > 15;
                 for (server_name, tool_def) in &all_tools {
-                    let desc = tool_def
-                        .description
-                        .as_deref()
-                        .unwrap_or("MCP tool")
-                        .to_string();
-                    let schema: serde_json::Value =
-                        serde_json::to_value(&*tool_def.input_schema).unwrap_or_default();
-                    let tool = Box::new(nyzhi_core::mcp::tool_adapter::McpTool::new(
+                    let tools = nyzhi_core::mcp::tool_adapter::server_tools(
                         server_name,
-                        &tool_def.name,
-                        &desc,
-                        schema,
-                        mgr.clone(),
-                    ));
-                    if defer_mcp {
-                        registry.register_deferred(tool);
-                    } else {
-                        registry.register(tool);
+                        std::slice::from_ref(tool_def),
+                        &mgr,
+                    );
+                    for tool in tools {
+                        if defer_mcp {
+                            registry.register_deferred(tool);
+                        } else {
+                            registry.register(tool);
+                        }
                     }
                 }
+                registry.register(Box::new(
+                    nyzhi_core::mcp::tool_adapter::McpListResourcesTool::new(mgr.clone()),
+                ));
+                registry.register(Box::new(
+                    nyzhi_core::mcp::tool_adapter::McpReadResourceTool::new(mgr.clone()),
+                ));
+                if let Some(provider) = &provider {
+                    let model = cli
+                        .model
+                        .clone()
+                        .or_else(|| provider.supported_models().first().map(|m| m.id.clone()))
+                        .unwrap_or_default();
+                    mgr.set_sampling_model(provider.clone(), &model);
+                }
+                mgr.allow_sampling(&config.mcp.sampling);
 
                 if defer_mcp {
-                    if let Ok(mut idx) = deferred_index.write() {
-                        *idx = registry.deferred_index();
-                    }
+                    registry.refresh_search_index();
                     let index_dir = workspace
                         .project_root
                         .join(".nyzhi")
@@ -981,7 +1117,6 @@ async fn main() -> Result<()> {
     // Multi-agent tools will be registered per-session with access to the event_tx.
     // The old single-shot `task` tool is replaced by spawn_agent/send_input/wait/close_agent/resume_agent.
 
-    let mut config = config;
     if let Some(trust_str) = &cli.trust {
         match trust_str.parse::<nyzhi_config::TrustMode>() {
             Ok(mode) => config.agent.trust.mode = mode,
@@ -1019,7 +1154,7 @@ async fn main() -> Result<()> {
                     quiet: false,
                     ephemeral: false,
                     sandbox_level: SandboxLevel::FullAccess,
-                    output_file: output_file,
+                    output_file,
                 },
             )
             .await?;
@@ -1151,6 +1286,7 @@ async fn main() -> Result<()> {
             app.mcp_manager = mcp_manager.clone();
             app.initial_session = initial_session;
             app.todo_store = Some(todo_store.clone());
+            app.startup_notices = plugin_notices;
             app.run(provider.clone(), registry, &config).await?;
         }
         Some(Commands::CiFix {
@@ -1252,7 +1388,7 @@ async fn run_once(
 ) -> Result<()> {
     use nyzhi_core::agent::{AgentConfig, AgentEvent};
     use nyzhi_core::conversation::Thread;
-    use nyzhi_core::tools::ToolContext;
+    use nyzhi_core::tools::{CancellationToken, ToolContext};
     use nyzhi_provider::{ContentPart, MessageContent};
 
     let json_mode = opts.json;
@@ -1283,11 +1419,13 @@ async fn run_once(
         agent_name: team_name.map(|_| "team-lead".to_string()),
         auto_context: config.index.auto_context,
         auto_context_chunks: config.index.auto_context_chunks,
+        fallbacks: nyzhi_core::fallback::build_chain(config).await,
         ..AgentConfig::default()
     };
     let (event_tx, mut event_rx) = tokio::sync::broadcast::channel::<AgentEvent>(256);
 
     let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let processes = nyzhi_core::processes::shared_manager();
     let tool_ctx = ToolContext {
         session_id: thread.id.clone(),
         cwd,
@@ -1304,10 +1442,35 @@ async fn run_once(
         todo_store: None,
         index: None,
         sandbox_level: opts.sandbox_level,
+        sandbox_config: (&config.shell.sandbox).into(),
+        approvals: Some(nyzhi_core::tools::permission::shared_memory(
+            &workspace.project_root,
+            config.agent.trust.remember_approvals,
+        )),
         subagent_model_overrides: None,
         shared_context: None,
+        processes: Some(processes.clone()),
+        shell: config.shell.persistent.then(|| {
+            nyzhi_core::persistent_shell::PersistentShell::handle((&config.shell).into())
+        }),
+        cancel: CancellationToken::new(),
     };
 
+    // The first Ctrl+C stops the turn and keeps what it produced; a second
+    // one exits immediately.
+    let cancel = tool_ctx.cancel.clone();
+    let signal_processes = processes.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\nCancelling... (press Ctrl+C again to exit now)");
+            cancel.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                signal_processes.kill_all();
+                std::process::exit(130);
+            }
+        }
+    });
+
     let tx = event_tx.clone();
     let response_text = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
     let response_capture = response_text.clone();
@@ -1355,7 +1518,9 @@ async fn run_once(
                         } else if !quiet {
                             eprintln!("[auto-approved: {tool_name}]");
                         }
-                        let _ = sender.send(true);
+                        let _ = sender.send(
+                            nyzhi_core::tools::permission::ApprovalResponse::once(true),
+                        );
                     }
                 }
                 AgentEvent::Retrying {
@@ -1371,6 +1536,18 @@ async fn run_once(
                         eprintln!("\n[retry {attempt}/{max_retries}] waiting {wait_ms}ms: {reason}");
                     }
                 }
+                AgentEvent::RoutedModel {
+                    model_name,
+                    reason: Some(reason),
+                    ..
+                } => {
+                    if json_mode {
+                        let obj = serde_json::json!({"type": "fallback", "model": model_name, "reason": reason});
+                        println!("{}", obj);
+                    } else if !quiet {
+                        eprintln!("\n[fallback] switching to {model_name}: {reason}");
+                    }
+                }
                 AgentEvent::TurnComplete => {
                     if json_mode {
                         println!("{}", serde_json::json!({"type": "turn_complete"}));
@@ -1449,8 +1626,9 @@ async fn run_once(
 
     let _ = handle.await;
 
+    let cancelled = tool_ctx.cancel.is_cancelled();
     let turn_elapsed = turn_start.elapsed();
-    if !opts.quiet {
+    if !opts.quiet && !cancelled {
         let notify = &config.tui.notify;
         if turn_elapsed.as_millis() as u64 >= notify.min_duration_ms {
             if notify.bell {
@@ -1466,9 +1644,11 @@ async fn run_once(
         }
     }
 
-    if !config.agent.hooks.is_empty() {
+    if !config.agent.hooks.is_empty() && !cancelled {
         let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-        let results = nyzhi_core::hooks::run_after_turn_hooks(&config.agent.hooks, &cwd).await;
+        let results =
+            nyzhi_core::hooks::run_after_turn_hooks(&config.agent.hooks, &cwd, &tool_ctx.launcher())
+                .await;
         for r in results {
             eprintln!("{}", r.summary());
         }
@@ -1497,6 +1677,18 @@ async fn run_once(
         eprintln!();
     }
 
+    // Background processes end with the run that started them.
+    processes.kill_all();
+
+    if cancelled {
+        if opts.json {
+            println!("{}", serde_json::json!({"type": "cancelled"}));
+        } else {
+            eprintln!("Cancelled.");
+        }
+        std::process::exit(130);
+    }
+
     Ok(())
 }
 
@@ -1602,6 +1794,104 @@ async fn handle_mcp_command(
     Ok(())
 }
 
+fn handle_plugin_command(
+    action: PluginAction,
+    workspace: &nyzhi_core::workspace::WorkspaceContext,
+) -> Result<()> {
+    use nyzhi_core::plugins::manager::PluginManager;
+    use nyzhi_core::plugins::PluginScope;
+
+    let root = &workspace.project_root;
+    let parse_scope = |scope: Option<String>| -> Result<Option<PluginScope>> {
+        scope.map(|s| s.parse()).transpose()
+    };
+
+    match action {
+        PluginAction::Install { source, scope } => {
+            let scope: PluginScope = scope.parse()?;
+            let name = PluginManager::install(root, &source, scope.clone())?;
+            println!("Installed plugin '{name}' in {scope} scope");
+        }
+        PluginAction::List => {
+            let installed = PluginManager::list_installed(root);
+            if installed.is_empty() {
+                println!("No plugins installed.");
+                println!("  Install one: nyz plugin install <path-or-git-url>");
+            } else {
+                println!("Plugins ({}):", installed.len());
+                for plugin in &installed {
+                    let status = if plugin.enabled { "enabled" } else { "disabled" };
+                    match &plugin.manifest {
+                        Ok(m) => println!(
+                            "  {}  {}  {status}  {}  {}",
+                            plugin.name, plugin.scope, m.version, m.description
+                        ),
+                        Err(e) => println!("  {}  {}  broken: {e:#}", plugin.name, plugin.scope),
+                    }
+                }
+            }
+            for conflict in &PluginManager::load_all(root).conflicts {
+                println!("  warning: {conflict}");
+            }
+        }
+        PluginAction::Enable { name, scope } => {
+            let scope = PluginManager::set_enabled(root, &name, parse_scope(scope)?, true)?;
+            println!("Enabled plugin '{name}' ({scope} scope)");
+        }
+        PluginAction::Disable { name, scope } => {
+            let scope = PluginManager::set_enabled(root, &name, parse_scope(scope)?, false)?;
+            println!("Disabled plugin '{name}' ({scope} scope)");
+        }
+        PluginAction::Remove { name, scope } => {
+            let scope = PluginManager::uninstall(root, &name, parse_scope(scope)?)?;
+            println!("Removed plugin '{name}' from {scope} scope");
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_auth_command(action: AuthAction) -> anyhow::Result<()> {
+    match action {
+        AuthAction::Export { output } => {
+            let passphrase = read_passphrase(true)?;
+            let data = nyzhi_auth::token_store::export_store(&passphrase)?;
+            match output {
+                Some(path) => {
+                    let path = std::path::Path::new(&path);
+                    nyzhi_auth::vault::write_private(path, data.as_bytes())?;
+                    eprintln!("Exported credentials to {}", path.display());
+                }
+                None => println!("{data}"),
+            }
+        }
+        AuthAction::Import { file } => {
+            let data = std::fs::read_to_string(&file)?;
+            let passphrase = read_passphrase(false)?;
+            let count = nyzhi_auth::token_store::import_store(&data, &passphrase)?;
+            println!("Imported {count} account(s) from {file}");
+        }
+    }
+    Ok(())
+}
+
+/// The export passphrase, from `NYZHI_AUTH_PASSPHRASE` or the terminal.
+fn read_passphrase(confirm: bool) -> anyhow::Result<String> {
+    if let Ok(passphrase) = std::env::var(nyzhi_auth::vault::PASSPHRASE_ENV) {
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+    }
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    if passphrase.is_empty() {
+        anyhow::bail!("A passphrase is required");
+    }
+    if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+        anyhow::bail!("Passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
 fn prompt_api_key(provider: &str) -> anyhow::Result<()> {
     let display = nyzhi_config::find_provider_def(provider)
         .map(|d| d.name)
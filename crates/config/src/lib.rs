@@ -38,6 +38,10 @@ pub struct ShellConfig {
     pub env: HashMap<String, String>,
     #[serde(default)]
     pub startup_commands: Vec<String>,
+    /// Keep one shell per agent alive across `bash` calls, so `cd`,
+    /// exports and sourced scripts persist.
+    #[serde(default)]
+    pub persistent: bool,
     #[serde(default)]
     pub sandbox: SandboxSettings,
 }
@@ -54,6 +58,10 @@ pub struct SandboxSettings {
     pub allow_write: Vec<String>,
     #[serde(default = "default_true")]
     pub block_dotfiles: bool,
+    /// Refuse to start commands when no backend can enforce the sandbox,
+    /// instead of running them unconfined after a warning.
+    #[serde(default)]
+    pub require: bool,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -94,19 +102,30 @@ fn default_auto_context_chunks() -> usize {
 pub struct IndexConfig {
     #[serde(default = "default_true")]
     pub enabled: bool,
-    /// "auto" | "voyage" | "openai" | "perplexity" | "tfidf"
+    /// "auto" | "voyage" | "openai" | "perplexity" | "ollama" | "llamacpp"
+    /// | "endpoint" | "onnx" | "local" | "tfidf"
     #[serde(default = "default_embedding_mode")]
     pub embedding: String,
     /// Override model id (e.g. "voyage-code-3", "text-embedding-3-large").
     /// Empty = use provider default.
     #[serde(default = "default_embedding_model")]
     pub embedding_model: String,
+    /// OpenAI-compatible embeddings server, e.g. "http://localhost:11434/v1".
+    /// Project configs may only point this at a loopback address.
+    #[serde(default)]
+    pub embedding_url: String,
+    /// ONNX embedding model run on CPU; `tokenizer.json` must sit next to it.
+    #[serde(default)]
+    pub embedding_model_path: String,
     #[serde(default = "default_true")]
     pub auto_context: bool,
     #[serde(default = "default_auto_context_chunks")]
     pub auto_context_chunks: usize,
     #[serde(default)]
     pub exclude: Vec<String>,
+    /// Watch the project tree and re-index files as they change on disk.
+    #[serde(default = "default_true")]
+    pub watch: bool,
 }
 
 impl Default for IndexConfig {
@@ -115,9 +134,12 @@ impl Default for IndexConfig {
             enabled: true,
             embedding: default_embedding_mode(),
             embedding_model: default_embedding_model(),
+            embedding_url: String::new(),
+            embedding_model_path: String::new(),
             auto_context: true,
             auto_context_chunks: default_auto_context_chunks(),
             exclude: vec![],
+            watch: true,
         }
     }
 }
@@ -168,6 +190,10 @@ pub struct ExternalNotifyConfig {
 pub struct McpConfig {
     #[serde(default)]
     pub servers: HashMap<String, McpServerConfig>,
+    /// Servers allowed to request sampling from the session's model. Only
+    /// the global config is read, so a project cannot opt itself in.
+    #[serde(default)]
+    pub sampling: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -224,6 +250,10 @@ pub struct AgentSettings {
     /// Model to use for subagent/exploration tasks (cost optimization).
     #[serde(default)]
     pub subagent_model: Option<String>,
+    /// Ordered `provider/model` entries a turn moves to when the current
+    /// model fails for good.
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
     /// Sharing settings for /share command.
     #[serde(default)]
     pub sharing: SharingConfig,
@@ -448,7 +478,7 @@ impl Default for RetrySettings {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrustConfig {
     #[serde(default)]
     pub mode: TrustMode,
@@ -464,10 +494,27 @@ pub struct TrustConfig {
     pub auto_approve: Vec<String>,
     #[serde(default)]
     pub always_ask: Vec<String>,
-    #[serde(default)]
+    /// Save approvals given with the project or always scope and load
+    /// them in later sessions.
+    #[serde(default = "default_true")]
     pub remember_approvals: bool,
 }
 
+impl Default for TrustConfig {
+    fn default() -> Self {
+        Self {
+            mode: TrustMode::default(),
+            allow_tools: Vec::new(),
+            allow_paths: Vec::new(),
+            deny_tools: Vec::new(),
+            deny_paths: Vec::new(),
+            auto_approve: Vec::new(),
+            always_ask: Vec::new(),
+            remember_approvals: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TrustMode {
@@ -621,6 +668,42 @@ pub const BUILT_IN_PROVIDERS: &[ProviderDef] = &[
         category: "agents",
         supports_oauth: true,
     },
+    ProviderDef {
+        id: "bedrock",
+        name: "AWS Bedrock",
+        env_var: "AWS_BEARER_TOKEN_BEDROCK",
+        default_base_url: "",
+        api_style: "bedrock",
+        category: "cloud",
+        supports_oauth: false,
+    },
+    ProviderDef {
+        id: "azure-openai",
+        name: "Azure OpenAI",
+        env_var: "AZURE_OPENAI_API_KEY",
+        default_base_url: "",
+        api_style: "azure-openai",
+        category: "cloud",
+        supports_oauth: false,
+    },
+    ProviderDef {
+        id: "vertex",
+        name: "Google Vertex AI",
+        env_var: "GOOGLE_APPLICATION_CREDENTIALS",
+        default_base_url: "",
+        api_style: "vertex",
+        category: "cloud",
+        supports_oauth: false,
+    },
+    ProviderDef {
+        id: "vertex-anthropic",
+        name: "Claude on Vertex AI",
+        env_var: "GOOGLE_APPLICATION_CREDENTIALS",
+        default_base_url: "",
+        api_style: "vertex-anthropic",
+        category: "cloud",
+        supports_oauth: false,
+    },
     ProviderDef {
         id: "groq",
         name: "Groq",
@@ -733,6 +816,16 @@ pub struct ProviderEntry {
     pub api_style: Option<String>,
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
+    /// Cloud region, for providers with regional endpoints (`bedrock`,
+    /// `vertex`, `vertex-anthropic`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Google Cloud project for the `vertex` styles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// `api-version` query parameter for `azure-openai`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1099,6 +1192,11 @@ impl Config {
                     .subagent_model
                     .clone()
                     .or(global.agent.subagent_model.clone()),
+                fallback_models: if project.agent.fallback_models.is_empty() {
+                    global.agent.fallback_models.clone()
+                } else {
+                    project.agent.fallback_models.clone()
+                },
                 sharing: if project.agent.sharing.enabled {
                     project.agent.sharing.clone()
                 } else {
@@ -1112,6 +1210,7 @@ impl Config {
             },
             mcp: McpConfig {
                 servers: mcp_servers,
+                sampling: global.mcp.sampling.clone(),
             },
             external_notify: ExternalNotifyConfig {
                 webhook_url: project
@@ -1156,6 +1255,7 @@ impl Config {
                 } else {
                     global.shell.startup_commands.clone()
                 },
+                persistent: project.shell.persistent || global.shell.persistent,
                 sandbox: if project.shell.sandbox.enabled {
                     project.shell.sandbox.clone()
                 } else {
@@ -1199,6 +1299,19 @@ impl Config {
                 } else {
                     global.index.embedding_model.clone()
                 },
+                // A project may not send code to a remote embeddings server.
+                embedding_url: if !project.index.embedding_url.is_empty()
+                    && is_loopback_url(&project.index.embedding_url)
+                {
+                    project.index.embedding_url.clone()
+                } else {
+                    global.index.embedding_url.clone()
+                },
+                embedding_model_path: if !project.index.embedding_model_path.is_empty() {
+                    project.index.embedding_model_path.clone()
+                } else {
+                    global.index.embedding_model_path.clone()
+                },
                 auto_context: global.index.auto_context && project.index.auto_context,
                 auto_context_chunks: if project.index.auto_context_chunks
                     != default_auto_context_chunks()
@@ -1214,11 +1327,35 @@ impl Config {
                     exc.dedup();
                     exc
                 },
+                watch: global.index.watch && project.index.watch,
             },
         }
     }
 }
 
+/// Whether `url` points at this machine (`localhost`, 127.0.0.0/8 or `::1`).
+pub fn is_loopback_url(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if authority.contains('@') {
+        return false;
+    }
+    let host = if let Some(v6) = authority.strip_prefix('[') {
+        v6.split(']').next().unwrap_or_default()
+    } else {
+        authority.split(':').next().unwrap_or_default()
+    };
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
 fn merge_provider_entry(global: &ProviderEntry, project: &ProviderEntry) -> ProviderEntry {
     ProviderEntry {
         api_key: project.api_key.clone().or_else(|| global.api_key.clone()),
@@ -1230,5 +1367,11 @@ fn merge_provider_entry(global: &ProviderEntry, project: &ProviderEntry) -> Prov
             .or_else(|| global.api_style.clone()),
         max_tokens: project.max_tokens.or(global.max_tokens),
         temperature: project.temperature.or(global.temperature),
+        region: project.region.clone().or_else(|| global.region.clone()),
+        project: project.project.clone().or_else(|| global.project.clone()),
+        api_version: project
+            .api_version
+            .clone()
+            .or_else(|| global.api_version.clone()),
     }
 }
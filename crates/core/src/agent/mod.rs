@@ -51,7 +51,14 @@ pub enum AgentEvent {
     ApprovalRequest {
         tool_name: String,
         args_summary: String,
-        respond: std::sync::Arc<tokio::sync::Mutex<Option<tokio::sync::oneshot::Sender<bool>>>>,
+        /// Rules remembered when the call is approved beyond this once,
+        /// e.g. `bash: cargo test *`; one per command for compound lines.
+        suggested_rules: Vec<String>,
+        respond: std::sync::Arc<
+            tokio::sync::Mutex<
+                Option<tokio::sync::oneshot::Sender<crate::tools::permission::ApprovalResponse>>,
+            >,
+        >,
     },
     Retrying {
         attempt: u32,
@@ -66,6 +73,8 @@ pub enum AgentEvent {
     RoutedModel {
         model_name: String,
         tier: String,
+        /// Why the model changed, e.g. a fallback after a failure.
+        reason: Option<String>,
     },
     SubAgentSpawned {
         id: String,
@@ -93,6 +102,9 @@ pub enum AgentEvent {
         respond: std::sync::Arc<tokio::sync::Mutex<Option<tokio::sync::oneshot::Sender<String>>>>,
     },
     Usage(SessionUsage),
+    /// The persistent shell changed directory; later tool calls and turns
+    /// should run there.
+    CwdChanged(std::path::PathBuf),
     SystemMessage(String),
     TurnComplete,
     Error(String),
@@ -135,11 +147,13 @@ impl std::fmt::Debug for AgentEvent {
             Self::ApprovalRequest {
                 tool_name,
                 args_summary,
+                suggested_rules,
                 ..
             } => f
                 .debug_struct("ApprovalRequest")
                 .field("tool_name", tool_name)
                 .field("args_summary", args_summary)
+                .field("suggested_rules", suggested_rules)
                 .finish(),
             Self::Retrying {
                 attempt,
@@ -161,10 +175,15 @@ impl std::fmt::Debug for AgentEvent {
                 .field("estimated_tokens", estimated_tokens)
                 .field("context_window", context_window)
                 .finish(),
-            Self::RoutedModel { model_name, tier } => f
+            Self::RoutedModel {
+                model_name,
+                tier,
+                reason,
+            } => f
                 .debug_struct("RoutedModel")
                 .field("model_name", model_name)
                 .field("tier", tier)
+                .field("reason", reason)
                 .finish(),
             Self::SubAgentSpawned { id, nickname, role } => f
                 .debug_struct("SubAgentSpawned")
@@ -212,6 +231,7 @@ impl std::fmt::Debug for AgentEvent {
                 .field("allow_custom", allow_custom)
                 .finish(),
             Self::Usage(u) => f.debug_struct("Usage").field("usage", u).finish(),
+            Self::CwdChanged(p) => f.debug_tuple("CwdChanged").field(p).finish(),
             Self::SystemMessage(s) => f.debug_tuple("SystemMessage").field(s).finish(),
             Self::TurnComplete => write!(f, "TurnComplete"),
             Self::Error(s) => f.debug_tuple("Error").field(s).finish(),
@@ -242,6 +262,8 @@ pub struct AgentConfig {
     pub auto_context_chunks: usize,
     /// Model ID override for subagent tasks (cheaper model for exploration).
     pub subagent_model: Option<String>,
+    /// Models to move to, in order, when the turn's model fails for good.
+    pub fallbacks: Vec<crate::fallback::FallbackModel>,
 }
 
 impl Default for AgentConfig {
@@ -267,6 +289,7 @@ impl Default for AgentConfig {
             auto_context: true,
             auto_context_chunks: 5,
             subagent_model: None,
+            fallbacks: Vec::new(),
         }
     }
 }
@@ -309,6 +332,16 @@ pub async fn run_turn_with_content(
     model_info: Option<&ModelInfo>,
     session_usage: &mut SessionUsage,
 ) -> Result<()> {
+    let allowed_tool_names = &ctx.allowed_tool_names;
+    // Replaced by the next entry of `config.fallbacks` when a model fails.
+    let mut provider = provider;
+    let mut model_info = model_info;
+    let mut fallbacks = config.fallbacks.iter();
+    let mut on_fallback = false;
+    // A `cd` in the persistent shell moves every later tool call this turn.
+    let mut turn_ctx = ctx.clone();
+    let mut ctx = &turn_ctx;
+
     let final_content = if config.auto_context {
         if let Some(ref index) = ctx.index {
             if index.is_ready() {
@@ -350,7 +383,7 @@ pub async fn run_turn_with_content(
     let build_tool_defs = || -> Vec<nyzhi_provider::ToolDefinition> {
         if config.plan_mode {
             registry.definitions_read_only()
-        } else if let Some(allowed) = &ctx.allowed_tool_names {
+        } else if let Some(allowed) = allowed_tool_names {
             registry.definitions_filtered(allowed)
         } else {
             registry.definitions()
@@ -371,7 +404,7 @@ pub async fn run_turn_with_content(
     } else {
         config.system_prompt.clone()
     };
-    let max_tokens = config
+    let mut max_tokens = config
         .max_tokens
         .or_else(|| model_info.map(|m| m.max_output_tokens));
 
@@ -386,6 +419,9 @@ pub async fn run_turn_with_content(
         .join(&ctx.session_id);
     let context_dir = ctx.project_root.join(".nyzhi").join("context");
     let mut compact_count: u32 = 0;
+    let mut counter = model_info
+        .map(|mi| crate::context::TokenCounter::for_model(&mi.provider, &mi.id))
+        .unwrap_or_default();
 
     for step in 0..config.max_steps {
         // Inbox polling: inject unread teammate messages before the LLM call
@@ -402,7 +438,7 @@ pub async fn run_turn_with_content(
         }
 
         if let Some(mi) = model_info {
-            let est = thread.estimated_tokens(&system_prompt);
+            let est = thread.estimated_tokens(&counter, &system_prompt);
             let _ = event_tx.send(AgentEvent::ContextUpdate {
                 estimated_tokens: est,
                 context_window: mi.context_window,
@@ -411,13 +447,20 @@ pub async fn run_turn_with_content(
             let threshold = config.auto_compact_threshold.unwrap_or(0.85);
 
             // Phase 1: Progressive pruning (dedup, supersede writes, error prune, microcompact)
-            let savings = crate::context::progressive_compact(
-                thread.messages_mut(),
-                &microcompact_dir,
-                est,
-                mi.context_window,
-                threshold,
-            );
+            let savings =
+                if crate::context::needs_progressive_compact(est, mi.context_window, threshold) {
+                    thread.edit_messages(|messages| {
+                        crate::context::progressive_compact(
+                            messages,
+                            &microcompact_dir,
+                            est,
+                            mi.context_window,
+                            threshold,
+                        )
+                    })
+                } else {
+                    Vec::new()
+                };
             for (desc, saved) in &savings {
                 let _ = event_tx.send(AgentEvent::SystemMessage(format!(
                     "compaction: {desc} (saved ~{saved} tokens)"
@@ -425,7 +468,7 @@ pub async fn run_turn_with_content(
             }
 
             // Phase 2: Full summarization if still over threshold
-            let est_after = thread.estimated_tokens(&system_prompt);
+            let est_after = thread.estimated_tokens(&counter, &system_prompt);
             if crate::context::needs_full_compact(
                 est_after,
                 mi.context_window,
@@ -450,11 +493,8 @@ pub async fn run_turn_with_content(
                 let previous_summary = if compact_count > 1 {
                     thread.messages().first().and_then(|m| {
                         let t = m.content.as_text();
-                        if t.starts_with("[Conversation summary]") {
-                            Some(t[22..].to_string())
-                        } else {
-                            None
-                        }
+                        t.strip_prefix("[Conversation summary]")
+                            .map(|s| s.to_string())
                     })
                 } else {
                     None
@@ -480,7 +520,12 @@ pub async fn run_turn_with_content(
                     stream: false,
                     thinking: None,
                 };
-                if let Ok(resp) = provider.chat(&summary_request).await {
+                let summary = tokio::select! {
+                    biased;
+                    _ = ctx.cancel.cancelled() => None,
+                    resp = provider.chat(&summary_request) => resp.ok(),
+                };
+                if let Some(resp) = summary {
                     let mut summary_text = resp.message.content.as_text().to_string();
                     if let Some(ref hist_path) = history_ref {
                         summary_text.push_str(&format!(
@@ -501,7 +546,7 @@ pub async fn run_turn_with_content(
                                 entries
                                     .filter_map(|e| e.ok())
                                     .filter(|e| {
-                                        e.path().extension().map_or(false, |ext| ext == "md")
+                                        e.path().extension().is_some_and(|ext| ext == "md")
                                     })
                                     .max_by_key(|e| {
                                         e.metadata().ok().and_then(|m| m.modified().ok())
@@ -528,7 +573,7 @@ pub async fn run_turn_with_content(
                         notepad_content.as_deref(),
                     );
 
-                    let new_est = thread.estimated_tokens(&system_prompt);
+                    let new_est = thread.estimated_tokens(&counter, &system_prompt);
                     let _ = event_tx.send(AgentEvent::SystemMessage(format!(
                         "Full compaction complete: {} → {} tokens ({} messages kept)",
                         format_tokens(est_after),
@@ -552,7 +597,7 @@ pub async fn run_turn_with_content(
             None
         };
 
-        let request = ChatRequest {
+        let mut request = ChatRequest {
             model: model_id.clone(),
             messages: thread.messages().to_vec(),
             tools: build_tool_defs(),
@@ -562,42 +607,72 @@ pub async fn run_turn_with_content(
             stream: true,
             thinking,
         };
+        if let (true, Some(mi)) = (on_fallback, model_info) {
+            crate::fallback::translate_request(&mut request, mi);
+        }
+        let request_tokens = counter.request(&request);
 
+        let restored = match provider.accounts() {
+            Some(accounts) => accounts.restore_if_due().await,
+            None => None,
+        };
+        if let Some(change) = restored {
+            tracing::info!(
+                from = %change.from,
+                to = %change.to,
+                "Rate limit cooldown passed, restored account"
+            );
+        }
+
+        let started = std::time::Instant::now();
         let mut stream_attempt = 0u32;
         let acc = 'stream_retry: loop {
-            let mut stream = match provider.chat_stream(&request).await {
+            let opened = tokio::select! {
+                biased;
+                _ = ctx.cancel.cancelled() => {
+                    record_cancelled(thread, "");
+                    break 'stream_retry Ok(None);
+                }
+                opened = provider.chat_stream(&request) => opened,
+            };
+            let mut stream = match opened {
                 Ok(s) => s,
                 Err(e) => {
-                    if let Some(pe) = e.downcast_ref::<ProviderError>() {
-                        if pe.is_retryable() && stream_attempt < config.retry.max_retries {
-                            stream_attempt += 1;
-                            let wait = pe
-                                .retry_after_ms()
-                                .unwrap_or_else(|| {
-                                    config
-                                        .retry
-                                        .initial_backoff_ms
-                                        .saturating_mul(2u64.saturating_pow(stream_attempt - 1))
-                                })
-                                .min(config.retry.max_backoff_ms);
-                            let _ = event_tx.send(AgentEvent::Retrying {
-                                attempt: stream_attempt,
-                                max_retries: config.retry.max_retries,
-                                wait_ms: wait,
-                                reason: pe.to_string(),
-                            });
-                            tokio::time::sleep(std::time::Duration::from_millis(wait)).await;
-                            continue 'stream_retry;
+                    match prepare_retry(
+                        provider,
+                        &e,
+                        &mut stream_attempt,
+                        &config.retry,
+                        ctx,
+                        event_tx,
+                    )
+                    .await
+                    {
+                        Retry::Again => continue 'stream_retry,
+                        Retry::Cancelled => {
+                            record_cancelled(thread, "");
+                            break 'stream_retry Ok(None);
                         }
+                        Retry::GiveUp => break 'stream_retry Err(e),
                     }
-                    return Err(e);
                 }
             };
 
             let mut acc = StreamAccumulator::new();
             let mut stream_err: Option<anyhow::Error> = None;
 
-            while let Some(event) = stream.next().await {
+            loop {
+                let next = tokio::select! {
+                    biased;
+                    _ = ctx.cancel.cancelled() => {
+                        record_cancelled(thread, &acc.text);
+                        break 'stream_retry Ok(None);
+                    }
+                    next = stream.next() => next,
+                };
+                let Some(event) = next else {
+                    break;
+                };
                 let event = match event {
                     Ok(ev) => ev,
                     Err(e) => {
@@ -639,35 +714,46 @@ pub async fn run_turn_with_content(
             }
 
             if let Some(e) = stream_err {
-                if let Some(pe) = e.downcast_ref::<ProviderError>() {
-                    if pe.is_retryable() && stream_attempt < config.retry.max_retries {
-                        stream_attempt += 1;
-                        let wait = pe
-                            .retry_after_ms()
-                            .unwrap_or_else(|| {
-                                config
-                                    .retry
-                                    .initial_backoff_ms
-                                    .saturating_mul(2u64.saturating_pow(stream_attempt - 1))
-                            })
-                            .min(config.retry.max_backoff_ms);
-                        let _ = event_tx.send(AgentEvent::Retrying {
-                            attempt: stream_attempt,
-                            max_retries: config.retry.max_retries,
-                            wait_ms: wait,
-                            reason: pe.to_string(),
-                        });
-                        tokio::time::sleep(std::time::Duration::from_millis(wait)).await;
-                        continue 'stream_retry;
+                match prepare_retry(
+                    provider,
+                    &e,
+                    &mut stream_attempt,
+                    &config.retry,
+                    ctx,
+                    event_tx,
+                )
+                .await
+                {
+                    Retry::Again => continue 'stream_retry,
+                    Retry::Cancelled => {
+                        record_cancelled(thread, "");
+                        break 'stream_retry Ok(None);
                     }
+                    Retry::GiveUp => break 'stream_retry Err(e),
                 }
-                return Err(e);
             }
 
-            break acc;
+            break Ok(Some(acc));
+        };
+        let acc = match acc {
+            Ok(Some(acc)) => acc,
+            Ok(None) => break,
+            Err(e) => {
+                let Some(next) = next_fallback(&mut fallbacks, &e, model_info, event_tx) else {
+                    return Err(e);
+                };
+                provider = &*next.provider;
+                model_info = Some(&next.model);
+                counter =
+                    crate::context::TokenCounter::for_model(&next.model.provider, &next.model.id);
+                max_tokens = config.max_tokens.or(Some(next.model.max_output_tokens));
+                on_fallback = true;
+                continue;
+            }
         };
 
         if let Some(usage) = &acc.usage {
+            counter.observe(request_tokens, usage.input_tokens);
             session_usage.turn_input_tokens = session_usage
                 .turn_input_tokens
                 .saturating_add(usage.input_tokens);
@@ -689,6 +775,23 @@ pub async fn run_turn_with_content(
                 let step_cost = mi.cost_usd(usage);
                 session_usage.turn_cost_usd += step_cost;
                 session_usage.total_cost_usd += step_cost;
+                // Logged per step so a turn that fell back is split across
+                // the models that actually answered.
+                let entry = crate::analytics::UsageEntry {
+                    timestamp: crate::analytics::now_ts(),
+                    session_id: ctx.session_id.clone(),
+                    provider: mi.provider.clone(),
+                    model: mi.id.clone(),
+                    input_tokens: usage.input_tokens as u64,
+                    output_tokens: usage.output_tokens as u64,
+                    cache_read_tokens: usage.cache_read_tokens as u64,
+                    cache_creation_tokens: usage.cache_creation_tokens as u64,
+                    cost_usd: step_cost,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                };
+                if let Err(e) = crate::analytics::log_usage(&entry) {
+                    tracing::warn!(error = %e, "Failed to record usage");
+                }
             }
 
             let _ = event_tx.send(AgentEvent::Usage(session_usage.clone()));
@@ -765,7 +868,17 @@ pub async fn run_turn_with_content(
                 )
                 .await
                 {
-                    Ok(r) => r.output,
+                    Ok(r) => {
+                        let cwd = r.metadata.get("cwd").and_then(|v| v.as_str());
+                        if let Some(cwd) = cwd.map(std::path::PathBuf::from) {
+                            if cwd != ctx.cwd {
+                                turn_ctx.cwd = cwd.clone();
+                                ctx = &turn_ctx;
+                                let _ = event_tx.send(AgentEvent::CwdChanged(cwd));
+                            }
+                        }
+                        r.output
+                    }
                     Err(e) => format!("Error executing tool: {e}"),
                 };
                 let elapsed_ms = start.elapsed().as_millis() as u64;
@@ -812,6 +925,9 @@ pub async fn run_turn_with_content(
                 role: Role::User,
                 content: MessageContent::Parts(tool_result_parts),
             });
+            if ctx.cancel.is_cancelled() {
+                break;
+            }
         } else {
             if !acc.text.is_empty() {
                 thread.push_message(Message {
@@ -852,6 +968,133 @@ pub async fn run_turn_with_content(
     Ok(())
 }
 
+/// Appended to the partial response of a cancelled turn.
+const CANCELLED_NOTE: &str = "[Turn cancelled by user]";
+
+/// How long an account sits out after a 429 that gave no Retry-After.
+const DEFAULT_RATE_LIMIT_COOLDOWN_SECS: u64 = 60;
+
+/// Mark where a cancelled turn stopped, keeping any partial response so the
+/// model can see on the next turn what was interrupted.
+fn record_cancelled(thread: &mut Thread, partial: &str) {
+    let text = if partial.trim().is_empty() {
+        CANCELLED_NOTE.to_string()
+    } else {
+        format!("{partial}\n\n{CANCELLED_NOTE}")
+    };
+    thread.push_message(Message {
+        role: Role::Assistant,
+        content: MessageContent::Text(text),
+    });
+}
+
+/// Take the next model in the fallback chain after `error`, announcing the
+/// switch. `None` when the error should surface or the chain is used up.
+fn next_fallback<'a>(
+    fallbacks: &mut std::slice::Iter<'a, crate::fallback::FallbackModel>,
+    error: &anyhow::Error,
+    current: Option<&ModelInfo>,
+    event_tx: &broadcast::Sender<AgentEvent>,
+) -> Option<&'a crate::fallback::FallbackModel> {
+    if !crate::fallback::should_fall_back(error) {
+        return None;
+    }
+    let next = fallbacks.next()?;
+    let failed = current.map_or_else(|| "model".to_string(), crate::fallback::model_label);
+    tracing::warn!(
+        from = %failed,
+        to = %next.label(),
+        error = %error,
+        "Falling back to next model"
+    );
+    let _ = event_tx.send(AgentEvent::RoutedModel {
+        model_name: next.label(),
+        tier: next.model.tier.to_string(),
+        reason: Some(format!("{failed} failed: {error}")),
+    });
+    Some(next)
+}
+
+/// On a 429, move the provider to its next stored account that is not rate
+/// limited. Returns a note naming the switch when it happened.
+async fn rotate_account(provider: &dyn Provider, error: &ProviderError) -> Option<String> {
+    if !matches!(error, ProviderError::RateLimited { .. }) {
+        return None;
+    }
+    let cooldown_secs = error
+        .retry_after_ms()
+        .map_or(DEFAULT_RATE_LIMIT_COOLDOWN_SECS, |ms| {
+            ms.div_ceil(1000).max(1)
+        });
+    let change = provider.accounts()?.rotate(cooldown_secs).await?;
+    Some(format!(
+        "switched account from {} to {}",
+        change.from, change.to
+    ))
+}
+
+/// What to do after a failed attempt to stream a response.
+enum Retry {
+    Again,
+    Cancelled,
+    GiveUp,
+}
+
+/// Decide whether `error` is retried. If so, count the attempt, switch
+/// accounts on a rate limit or else wait out the backoff, and report the
+/// retry.
+async fn prepare_retry(
+    provider: &dyn Provider,
+    error: &anyhow::Error,
+    attempt: &mut u32,
+    retry: &nyzhi_config::RetrySettings,
+    ctx: &ToolContext,
+    event_tx: &broadcast::Sender<AgentEvent>,
+) -> Retry {
+    let Some(pe) = error.downcast_ref::<ProviderError>() else {
+        return Retry::GiveUp;
+    };
+    if !pe.is_retryable() || *attempt >= retry.max_retries {
+        return Retry::GiveUp;
+    }
+    *attempt += 1;
+    let switched = rotate_account(provider, pe).await;
+    // A fresh account can be used straight away.
+    let wait = if switched.is_some() {
+        0
+    } else {
+        pe.retry_after_ms()
+            .unwrap_or_else(|| {
+                retry
+                    .initial_backoff_ms
+                    .saturating_mul(2u64.saturating_pow(*attempt - 1))
+            })
+            .min(retry.max_backoff_ms)
+    };
+    let _ = event_tx.send(AgentEvent::Retrying {
+        attempt: *attempt,
+        max_retries: retry.max_retries,
+        wait_ms: wait,
+        reason: match switched {
+            Some(note) => format!("{pe}; {note}"),
+            None => pe.to_string(),
+        },
+    });
+    if backoff(ctx, wait).await {
+        Retry::Again
+    } else {
+        Retry::Cancelled
+    }
+}
+
+/// Wait before a retry; false if the turn was cancelled meanwhile.
+async fn backoff(ctx: &ToolContext, wait_ms: u64) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(std::time::Duration::from_millis(wait_ms)) => true,
+        _ = ctx.cancel.cancelled() => false,
+    }
+}
+
 async fn execute_with_permission(
     registry: &ToolRegistry,
     tool_name: &str,
@@ -865,6 +1108,13 @@ async fn execute_with_permission(
         .get(tool_name)
         .ok_or_else(|| anyhow::anyhow!("Unknown tool: {tool_name}"))?;
 
+    if ctx.cancel.is_cancelled() {
+        return Ok(crate::tools::ToolResult::cancelled(
+            tool_name,
+            "Not run: the turn was cancelled",
+        ));
+    }
+
     if plan_mode && tool.permission() == ToolPermission::NeedsApproval {
         return Ok(crate::tools::ToolResult {
             output: format!(
@@ -884,8 +1134,26 @@ async fn execute_with_permission(
         });
     }
 
-    let target_path = extract_target_path(tool_name, &args);
-    if crate::tools::permission::check_deny(tool_name, target_path.as_deref(), trust) {
+    // Tools such as `lsp_rename` only know which files they change once the
+    // language server has answered; each of those files is checked too.
+    let mut args = args;
+    crate::tools::strip_prepared_args(&mut args);
+    let original_args = args.clone();
+    let (args, changed_paths) = match tool.prepare(&args, ctx).await? {
+        Some(crate::tools::Prepared::Done(result)) => return Ok(result),
+        Some(crate::tools::Prepared::Run { args, paths }) => (args, paths),
+        None => (args, Vec::new()),
+    };
+    let mut targets = vec![extract_target_path(tool_name, &original_args)];
+    targets.extend(
+        changed_paths
+            .iter()
+            .map(|p| Some(p.to_string_lossy().to_string())),
+    );
+
+    if targets.iter().any(|target| {
+        crate::tools::permission::check_deny(tool_name, target.as_deref(), &ctx.project_root, trust)
+    }) {
         return Ok(crate::tools::ToolResult {
             output: format!("Tool `{tool_name}` is blocked by deny rules"),
             title: format!("{tool_name} (blocked)"),
@@ -893,46 +1161,154 @@ async fn execute_with_permission(
         });
     }
 
-    if tool.permission() == ToolPermission::NeedsApproval
-        && !should_auto_approve(trust, tool_name, &args)
-    {
-        let args_summary = summarize_args(tool_name, &args).await;
+    let approved_for = |target: Option<&str>| match crate::tools::permission::check_auto_approve(
+        tool_name,
+        target,
+        &ctx.project_root,
+        trust,
+    ) {
+        Some(approved) => approved,
+        None => {
+            should_auto_approve(trust, tool_name, target, &ctx.project_root)
+                || ctx.approvals.as_ref().is_some_and(|memory| {
+                    memory
+                        .lock()
+                        .is_ok_and(|m| m.allows(tool_name, target, &ctx.project_root))
+                })
+        }
+    };
+    let needs_approval = tool.permission() == ToolPermission::NeedsApproval
+        && !targets.iter().all(|target| approved_for(target.as_deref()));
+
+    if needs_approval {
+        let mut args_summary = summarize_args(tool_name, &original_args).await;
+        if !changed_paths.is_empty() {
+            args_summary.push_str("\nChanges:");
+            for path in &changed_paths {
+                let rel = path.strip_prefix(&ctx.project_root).unwrap_or(path);
+                args_summary.push_str(&format!("\n  {}", rel.display()));
+            }
+        }
+        let mut suggested_rules = Vec::new();
+        for target in &targets {
+            for rule in crate::tools::permission::PermissionRule::suggest(
+                tool_name,
+                target.as_deref(),
+                &ctx.project_root,
+            ) {
+                if !suggested_rules.contains(&rule) {
+                    suggested_rules.push(rule);
+                }
+            }
+        }
         let (tx, rx) = tokio::sync::oneshot::channel();
         let respond = std::sync::Arc::new(tokio::sync::Mutex::new(Some(tx)));
 
         let _ = event_tx.send(AgentEvent::ApprovalRequest {
             tool_name: tool_name.to_string(),
             args_summary,
+            suggested_rules: suggested_rules.iter().map(ToString::to_string).collect(),
             respond,
         });
 
-        let approved = rx.await.unwrap_or(false);
-        if !approved {
+        let response = tokio::select! {
+            response = rx => response.unwrap_or_default(),
+            _ = ctx.cancel.cancelled() => {
+                return Ok(crate::tools::ToolResult::cancelled(
+                    tool_name,
+                    "Not run: the turn was cancelled while waiting for approval",
+                ));
+            }
+        };
+        if !response.approved {
             return Ok(crate::tools::ToolResult {
                 output: "Tool execution denied by user".to_string(),
                 title: format!("{tool_name} (denied)"),
                 metadata: serde_json::json!({ "denied": true }),
             });
         }
+        remember_approval(ctx, trust, event_tx, response, suggested_rules);
     }
 
     registry.execute(tool_name, args, ctx).await
 }
 
-fn should_auto_approve(trust: &TrustConfig, tool_name: &str, args: &serde_json::Value) -> bool {
+/// Record an approval given beyond this once. Project and always scopes
+/// fall back to the session when `remember_approvals` is off.
+fn remember_approval(
+    ctx: &ToolContext,
+    trust: &TrustConfig,
+    event_tx: &broadcast::Sender<AgentEvent>,
+    response: crate::tools::permission::ApprovalResponse,
+    suggested: Vec<crate::tools::permission::PermissionRule>,
+) {
+    use crate::tools::permission::{ApprovalScope, PermissionRule};
+
+    let scope = match response.scope {
+        ApprovalScope::Project | ApprovalScope::Always if !trust.remember_approvals => {
+            ApprovalScope::Session
+        }
+        scope => scope,
+    };
+    if scope == ApprovalScope::Once {
+        return;
+    }
+    let Some(memory) = &ctx.approvals else {
+        return;
+    };
+    let rules = match response.rule.as_deref() {
+        Some(text) => match PermissionRule::parse(text) {
+            Some(rule) => vec![rule],
+            None => {
+                let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                    "Approval rule `{text}` is not valid; approved once only"
+                )));
+                return;
+            }
+        },
+        None => suggested,
+    };
+    let Ok(mut memory) = memory.lock() else {
+        return;
+    };
+    for rule in rules {
+        if let Err(e) = memory.remember(rule.clone(), scope, &ctx.project_root) {
+            let _ = event_tx.send(AgentEvent::SystemMessage(format!(
+                "Could not save approval rule `{rule}`: {e}"
+            )));
+        }
+    }
+}
+
+fn should_auto_approve(
+    trust: &TrustConfig,
+    tool_name: &str,
+    target: Option<&str>,
+    project_root: &std::path::Path,
+) -> bool {
+    // `allow_tools` entries may be rules such as `bash: cargo *`.
+    let tool_listed = || {
+        trust.allow_tools.is_empty()
+            || crate::tools::permission::rules_match(
+                &crate::tools::permission::parse_rules(&trust.allow_tools),
+                tool_name,
+                target,
+                project_root,
+                true,
+            )
+    };
     match trust.mode {
         TrustMode::Full => true,
         TrustMode::Limited => {
-            let tool_allowed =
-                trust.allow_tools.is_empty() || trust.allow_tools.iter().any(|t| t == tool_name);
+            let tool_allowed = tool_listed();
             if !tool_allowed {
                 return false;
             }
             if trust.allow_paths.is_empty() {
                 return true;
             }
-            if let Some(path) = extract_target_path(tool_name, args) {
-                trust.allow_paths.iter().any(|p| path.starts_with(p))
+            if let Some(path) = target {
+                trust.allow_paths.iter().any(|p| path.starts_with(p.as_str()))
             } else {
                 tool_allowed
             }
@@ -947,14 +1323,14 @@ fn should_auto_approve(trust: &TrustConfig, tool_name: &str, args: &serde_json::
                 "move_file",
                 "copy_file",
                 "create_dir",
+                "lsp_rename",
+                "lsp_apply_code_action",
             ];
-            let read_tools_auto =
-                trust.allow_tools.is_empty() || trust.allow_tools.iter().any(|t| t == tool_name);
+            let read_tools_auto = tool_listed();
             if write_tools.contains(&tool_name) && read_tools_auto {
                 true
             } else {
-                let tool_allowed = trust.allow_tools.is_empty()
-                    || trust.allow_tools.iter().any(|t| t == tool_name);
+                let tool_allowed = tool_listed();
                 if !tool_allowed {
                     return false;
                 }
@@ -965,12 +1341,15 @@ fn should_auto_approve(trust: &TrustConfig, tool_name: &str, args: &serde_json::
     }
 }
 
+/// Tools refused outright in read-only mode. `bash` is not listed: its
+/// commands run under an OS sandbox that enforces the level itself.
 const WRITE_TOOLS: &[&str] = &[
     "write", "edit", "multi_edit", "apply_patch", "batch_apply",
     "delete_file", "move_file", "copy_file", "create_dir",
-    "bash", "git_commit", "git_checkout",
+    "git_commit", "git_checkout",
     "web_fetch", "web_search", "browser_open", "browser_screenshot", "browser_evaluate",
     "create_pr", "instrument", "remove_instrumentation",
+    "lsp_rename", "lsp_apply_code_action",
 ];
 
 fn sandbox_blocks_tool(
@@ -1044,7 +1423,11 @@ fn extract_target_path(tool_name: &str, args: &serde_json::Value) -> Option<Stri
             .get("file_path")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
-        "bash" => args
+        "lsp_rename" | "lsp_apply_code_action" => args
+            .get("file")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        "bash" | "process_start" => args
             .get("command")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
@@ -1054,7 +1437,7 @@ fn extract_target_path(tool_name: &str, args: &serde_json::Value) -> Option<Stri
 
 async fn summarize_args(tool_name: &str, args: &serde_json::Value) -> String {
     match tool_name {
-        "bash" => args
+        "bash" | "process_start" => args
             .get("command")
             .and_then(|v| v.as_str())
             .unwrap_or("(unknown command)")
@@ -1150,4 +1533,24 @@ mod tests {
         assert!(config.auto_context);
         assert_eq!(config.auto_context_chunks, 5);
     }
+
+    #[tokio::test]
+    async fn only_rate_limits_with_stored_accounts_rotate() {
+        use nyzhi_provider::ProviderError;
+
+        let provider = nyzhi_provider::anthropic::AnthropicProvider::new("key".into(), None, None);
+        let limited = ProviderError::RateLimited {
+            retry_after_ms: 1_500,
+        };
+        assert!(super::rotate_account(&provider, &limited).await.is_none());
+
+        let rotating = provider.with_accounts(Some("nyzhi-test-no-such-provider"));
+        let overloaded = ProviderError::ServerError {
+            status: 529,
+            body: String::new(),
+        };
+        assert!(super::rotate_account(&rotating, &overloaded).await.is_none());
+        // No accounts are stored under that key, so there is nothing to switch to.
+        assert!(super::rotate_account(&rotating, &limited).await.is_none());
+    }
 }
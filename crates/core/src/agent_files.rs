@@ -20,57 +20,64 @@ fn scan_agents_dir(dir: &Path) -> HashMap<String, AgentRoleConfig> {
         if path.extension().and_then(|e| e.to_str()) != Some("md") {
             continue;
         }
-
-        let content = match std::fs::read_to_string(&path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-
-        let file_name = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("unknown")
-            .to_string();
-
-        let (fm, body) = parse_frontmatter(&content);
-
-        let name = fm.get("name").cloned().unwrap_or_else(|| file_name.clone());
-        let description = fm.get("description").cloned();
-        let model_override = fm.get("model").cloned();
-        let max_steps_override = fm.get("max_steps").and_then(|v| v.parse::<u32>().ok());
-        let read_only = fm.get("read_only").map(|v| v == "true").unwrap_or(false);
-        let allowed_tools = fm.get("allowed_tools").map(|v| parse_yaml_list(v));
-        let disallowed_tools = fm.get("disallowed_tools").map(|v| parse_yaml_list(v));
-
-        let system_prompt = if body.trim().is_empty() {
-            None
-        } else {
-            Some(body.trim().to_string())
-        };
-
-        roles.insert(
-            file_name,
-            AgentRoleConfig {
-                name,
-                description,
-                system_prompt_override: system_prompt,
-                model_override,
-                max_steps_override,
-                read_only,
-                allowed_tools,
-                disallowed_tools,
-                config_file: Some(path.display().to_string()),
-            },
-        );
+        if let Some((file_name, role)) = load_role_file(&path) {
+            roles.insert(file_name, role);
+        }
     }
 
     roles
 }
 
-/// Parse agent role files from `.nyzhi/agents/` and `.claude/agents/`.
+/// Parse one agent file, keyed by its file stem.
+pub(crate) fn load_role_file(path: &Path) -> Option<(String, AgentRoleConfig)> {
+    let content = std::fs::read_to_string(path).ok()?;
+
+    let file_name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let (fm, body) = parse_frontmatter(&content);
+
+    let name = fm.get("name").cloned().unwrap_or_else(|| file_name.clone());
+    let description = fm.get("description").cloned();
+    let model_override = fm.get("model").cloned();
+    let max_steps_override = fm.get("max_steps").and_then(|v| v.parse::<u32>().ok());
+    let read_only = fm.get("read_only").map(|v| v == "true").unwrap_or(false);
+    let allowed_tools = fm.get("allowed_tools").map(|v| parse_yaml_list(v));
+    let disallowed_tools = fm.get("disallowed_tools").map(|v| parse_yaml_list(v));
+
+    let system_prompt = if body.trim().is_empty() {
+        None
+    } else {
+        Some(body.trim().to_string())
+    };
+
+    Some((
+        file_name,
+        AgentRoleConfig {
+            name,
+            description,
+            system_prompt_override: system_prompt,
+            model_override,
+            max_steps_override,
+            read_only,
+            allowed_tools,
+            disallowed_tools,
+            config_file: Some(path.display().to_string()),
+        },
+    ))
+}
+
+/// Parse agent role files from `.nyzhi/agents/` and `.claude/agents/`, plus
+/// the namespaced roles of enabled plugins.
 /// `.nyzhi/agents/` takes priority on name collisions.
 pub fn load_file_based_roles(project_root: &Path) -> HashMap<String, AgentRoleConfig> {
-    let mut roles = scan_agents_dir(&project_root.join(".claude").join("agents"));
+    let mut roles = crate::plugins::manager::PluginManager::load_all(project_root).agent_roles();
+    roles.extend(scan_agents_dir(
+        &project_root.join(".claude").join("agents"),
+    ));
     let primary = scan_agents_dir(&project_root.join(".nyzhi").join("agents"));
     roles.extend(primary);
     roles
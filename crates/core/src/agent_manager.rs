@@ -5,12 +5,12 @@ use std::sync::Arc;
 use anyhow::Result;
 use rand::prelude::IndexedRandom;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{broadcast, oneshot, watch, Mutex};
+use tokio::sync::{broadcast, watch, Mutex};
 use tokio::task::JoinHandle;
 
 use crate::agent::{run_turn, AgentConfig, AgentEvent, SessionUsage};
 use crate::conversation::Thread;
-use crate::tools::{ToolContext, ToolRegistry};
+use crate::tools::{CancellationToken, ToolContext, ToolRegistry};
 
 pub type AgentId = String;
 
@@ -149,7 +149,9 @@ struct AgentHandle {
     pub depth: u32,
     pub status_tx: watch::Sender<AgentStatus>,
     pub status_rx: watch::Receiver<AgentStatus>,
-    pub cancel_tx: Option<oneshot::Sender<()>>,
+    /// Child of the spawning turn's token, so stopping that turn stops the
+    /// agent too.
+    pub cancel: CancellationToken,
     pub join_handle: Option<JoinHandle<()>>,
     pub thread: Arc<Mutex<Thread>>,
 }
@@ -279,7 +281,7 @@ impl AgentManager {
         let nickname = self.nicknames.lock().await.reserve();
 
         let (status_tx, status_rx) = watch::channel(AgentStatus::PendingInit);
-        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+        let cancel = parent_ctx.cancel.child_token();
 
         let thread = Arc::new(Mutex::new(Thread::new()));
 
@@ -289,7 +291,7 @@ impl AgentManager {
             depth: child_depth,
             status_tx: status_tx.clone(),
             status_rx: status_rx.clone(),
-            cancel_tx: Some(cancel_tx),
+            cancel: cancel.clone(),
             join_handle: None,
             thread: thread.clone(),
         };
@@ -323,8 +325,13 @@ impl AgentManager {
             todo_store: parent_ctx.todo_store.clone(),
             index: parent_ctx.index.clone(),
             sandbox_level: parent_ctx.sandbox_level,
+            sandbox_config: parent_ctx.sandbox_config.clone(),
+            approvals: parent_ctx.approvals.clone(),
             subagent_model_overrides: parent_ctx.subagent_model_overrides.clone(),
             shared_context: parent_ctx.shared_context.clone(),
+            processes: parent_ctx.processes.clone(),
+            shell: parent_ctx.shell.as_ref().map(|s| s.fork()),
+            cancel,
         };
 
         let join_handle = tokio::spawn(async move {
@@ -371,6 +378,8 @@ impl AgentManager {
                                     elapsed_ms,
                                 },
                                 AgentEvent::TurnComplete => break,
+                                // The agent's shell is its own.
+                                AgentEvent::CwdChanged(_) => continue,
                                 other => other,
                             };
                             let _ = fwd_parent_tx.send(forwarded);
@@ -384,22 +393,25 @@ impl AgentManager {
             let mut child_thread = thread.lock().await;
             let mut session_usage = SessionUsage::default();
 
-            let result = tokio::select! {
-                r = run_turn(
-                    &*provider,
-                    &mut child_thread,
-                    &prompt,
-                    &agent_config,
-                    &child_event_tx,
-                    &registry,
-                    &child_ctx,
-                    None,
-                    &mut session_usage,
-                ) => r,
-                _ = cancel_rx => {
+            let result = run_turn(
+                &*provider,
+                &mut child_thread,
+                &prompt,
+                &agent_config,
+                &child_event_tx,
+                &registry,
+                &child_ctx,
+                None,
+                &mut session_usage,
+            )
+            .await
+            .and_then(|()| {
+                if child_ctx.cancel.is_cancelled() {
                     Err(anyhow::anyhow!("Agent cancelled"))
+                } else {
+                    Ok(())
                 }
-            };
+            });
 
             let final_status = match &result {
                 Ok(()) => {
@@ -488,9 +500,7 @@ impl AgentManager {
             .get_mut(agent_id)
             .ok_or_else(|| anyhow::anyhow!("Agent with id {agent_id} not found"))?;
 
-        if let Some(cancel_tx) = handle.cancel_tx.take() {
-            let _ = cancel_tx.send(());
-        }
+        handle.cancel.cancel();
         let _ = handle.status_tx.send(AgentStatus::Shutdown);
         let status = handle.status_rx.borrow().clone();
 
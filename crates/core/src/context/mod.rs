@@ -1,8 +1,11 @@
+pub mod tokenizer;
+
 use std::path::{Path, PathBuf};
 
 use nyzhi_provider::{ContentPart, Message, MessageContent, Role};
 
-const CHARS_PER_TOKEN: usize = 4;
+pub use tokenizer::TokenCounter;
+
 const MICROCOMPACT_THRESHOLD: usize = 4000;
 const HOT_TAIL_COUNT: usize = 5;
 const OUTPUT_HEADROOM_TOKENS: usize = 16384;
@@ -68,34 +71,18 @@ pub fn save_history_file(
     Some(file_path)
 }
 
+/// Model-independent estimates, for sizing things relative to each other.
+/// Use a `TokenCounter` when comparing against a model's context window.
 pub fn estimate_tokens(text: &str) -> usize {
-    text.len() / CHARS_PER_TOKEN + 1
+    TokenCounter::default().text(text)
 }
 
-pub const IMAGE_TOKEN_ESTIMATE: usize = 1000;
-
 pub fn estimate_message_tokens(msg: &Message) -> usize {
-    let content_tokens = match &msg.content {
-        MessageContent::Text(text) => estimate_tokens(text),
-        MessageContent::Parts(parts) => parts
-            .iter()
-            .map(|p| match p {
-                ContentPart::Text { text } => estimate_tokens(text),
-                ContentPart::Image { .. } => IMAGE_TOKEN_ESTIMATE,
-                ContentPart::ToolUse { name, input, .. } => {
-                    estimate_tokens(name) + estimate_tokens(&input.to_string())
-                }
-                ContentPart::ToolResult { content, .. } => estimate_tokens(content),
-            })
-            .sum(),
-    };
-    content_tokens + 4
+    TokenCounter::default().message(msg)
 }
 
 pub fn estimate_thread_tokens(messages: &[Message], system_prompt: &str) -> usize {
-    let system_tokens = estimate_tokens(system_prompt);
-    let message_tokens: usize = messages.iter().map(estimate_message_tokens).sum();
-    system_tokens + message_tokens
+    TokenCounter::default().thread(messages, system_prompt)
 }
 
 pub fn should_compact(estimated_tokens: usize, context_window: u32) -> bool {
@@ -291,7 +278,7 @@ pub fn microcompact(messages: &mut [Message], storage_dir: &Path) -> usize {
 
 /// Deduplicate tool results: if the same file was read/written multiple times,
 /// keep only the most recent result. Returns number of entries deduplicated.
-pub fn dedup_tool_results(messages: &mut Vec<Message>) -> usize {
+pub fn dedup_tool_results(messages: &mut [Message]) -> usize {
     let mut seen_files: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
     let mut to_collapse: Vec<usize> = Vec::new();
 
@@ -378,7 +365,7 @@ pub fn prune_old_errors(messages: &mut [Message], age_threshold: usize) -> usize
 
 /// Collapse write tool results when the file was subsequently read.
 /// The read result supersedes the write content.
-pub fn supersede_writes(messages: &mut Vec<Message>) -> usize {
+pub fn supersede_writes(messages: &mut [Message]) -> usize {
     let mut write_indices: std::collections::HashMap<String, Vec<usize>> =
         std::collections::HashMap::new();
     let mut read_files: std::collections::HashSet<String> = std::collections::HashSet::new();
@@ -429,7 +416,7 @@ pub fn supersede_writes(messages: &mut Vec<Message>) -> usize {
 /// Run the full progressive compaction pipeline.
 /// Returns (tokens_saved, description) for transparency.
 pub fn progressive_compact(
-    messages: &mut Vec<Message>,
+    messages: &mut [Message],
     storage_dir: &Path,
     estimated_tokens: usize,
     context_window: u32,
@@ -438,7 +425,7 @@ pub fn progressive_compact(
     let mut savings: Vec<(String, usize)> = Vec::new();
     let target = (context_window as f64 * threshold) as usize;
 
-    if estimated_tokens <= target {
+    if !needs_progressive_compact(estimated_tokens, context_window, threshold) {
         return savings;
     }
 
@@ -491,6 +478,15 @@ pub fn progressive_compact(
 }
 
 /// Check if full compaction is still needed after progressive passes.
+/// Whether `progressive_compact` has anything to do at this usage.
+pub fn needs_progressive_compact(
+    estimated_tokens: usize,
+    context_window: u32,
+    threshold: f64,
+) -> bool {
+    estimated_tokens > (context_window as f64 * threshold) as usize
+}
+
 pub fn needs_full_compact(
     estimated_tokens: usize,
     context_window: u32,
@@ -504,6 +500,9 @@ pub fn needs_full_compact(
 
 /// Compute detailed context breakdown for `/context` display.
 pub struct ContextBreakdown {
+    pub tokenizer: &'static str,
+    /// Calibration factor learned from provider-reported usage.
+    pub calibration: f64,
     pub system_prompt_tokens: usize,
     pub message_tokens: usize,
     pub message_count: usize,
@@ -515,23 +514,24 @@ pub struct ContextBreakdown {
 
 impl ContextBreakdown {
     pub fn compute(
+        counter: &TokenCounter,
         messages: &[Message],
         system_prompt: &str,
         context_window: u32,
         threshold: f64,
     ) -> Self {
-        let system_prompt_tokens = estimate_tokens(system_prompt);
+        let system_prompt_tokens = counter.text(system_prompt);
         let mut message_tokens = 0usize;
         let mut tool_result_tokens = 0usize;
 
         for msg in messages {
-            let msg_tokens = estimate_message_tokens(msg);
+            let msg_tokens = counter.message(msg);
             message_tokens += msg_tokens;
 
             if let MessageContent::Parts(parts) = &msg.content {
                 for part in parts {
                     if let ContentPart::ToolResult { content, .. } = part {
-                        tool_result_tokens += estimate_tokens(content) + 4;
+                        tool_result_tokens += counter.text(content) + 4;
                     }
                 }
             }
@@ -540,6 +540,8 @@ impl ContextBreakdown {
         let total_tokens = system_prompt_tokens + message_tokens;
 
         Self {
+            tokenizer: counter.tokenizer_name(),
+            calibration: counter.factor(),
             system_prompt_tokens,
             message_tokens: message_tokens - tool_result_tokens,
             message_count: messages.len(),
@@ -571,6 +573,7 @@ impl ContextBreakdown {
         let compact_at = self.compact_at_tokens();
         format!(
             "Context Usage ({} / {} tokens = {:.1}%)\n\
+             \x20 Tokenizer:      {} (calibration x{:.2})\n\
              \x20 System prompt:  {} tokens\n\
              \x20 Messages ({}):  {} tokens\n\
              \x20 Tool results:   {} tokens\n\
@@ -579,6 +582,8 @@ impl ContextBreakdown {
             format_token_count(self.total_tokens),
             format_token_count(self.context_window as usize),
             pct,
+            self.tokenizer,
+            self.calibration,
             format_token_count(self.system_prompt_tokens),
             self.message_count,
             format_token_count(self.message_tokens),
@@ -750,7 +755,8 @@ mod tests {
             text_msg(Role::User, "Hello"),
             text_msg(Role::Assistant, "Hi there"),
         ];
-        let bd = ContextBreakdown::compute(&messages, "system", 200_000, 0.85);
+        let bd =
+            ContextBreakdown::compute(&TokenCounter::default(), &messages, "system", 200_000, 0.85);
         assert!(bd.total_tokens > 0);
         assert!(bd.usage_percent() < 1.0);
         assert_eq!(bd.compact_at_tokens(), 170_000);
@@ -0,0 +1,518 @@
+//! Token counting per model family.
+//!
+//! OpenAI models use their real BPE vocabularies (embedded, built on first
+//! use). Families without a public tokenizer get a script-aware estimate.
+//! Either way the count is scaled by a factor learned from the input token
+//! counts providers report, so estimates converge on what the model sees.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use base64::Engine;
+use nyzhi_provider::{ChatRequest, ContentPart, Message, MessageContent};
+
+/// Per-message framing (role markers, separators).
+const MESSAGE_OVERHEAD: usize = 4;
+/// Used when an image's size cannot be read from its header.
+const IMAGE_FALLBACK_TOKENS: usize = 1000;
+/// How far into an image to look for its size. JPEG puts it after EXIF data.
+const IMAGE_HEADER_BYTES: usize = 256 * 1024;
+/// Weight of each new observation in the calibration factor.
+const CALIBRATION_WEIGHT: f64 = 0.3;
+
+pub trait Tokenizer: Send + Sync {
+    /// Name shown in `/context`.
+    fn name(&self) -> &'static str;
+
+    fn count(&self, text: &str) -> usize;
+
+    /// Tokens the model is billed for an image of this size.
+    fn image_tokens(&self, width: u32, height: u32) -> usize;
+}
+
+/// An OpenAI BPE vocabulary, loaded the first time it is used.
+struct Bpe {
+    name: &'static str,
+    load: fn() -> &'static tiktoken_rs::CoreBPE,
+}
+
+impl Tokenizer for Bpe {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn count(&self, text: &str) -> usize {
+        (self.load)().encode_ordinary(text).len()
+    }
+
+    fn image_tokens(&self, width: u32, height: u32) -> usize {
+        openai_image_tokens(width, height)
+    }
+}
+
+/// Script-aware estimate for tokenizers we cannot run. `scale` adjusts the
+/// base estimate (tuned on cl100k) to the family's vocabulary.
+struct Estimate {
+    name: &'static str,
+    scale: f64,
+    image: fn(u32, u32) -> usize,
+}
+
+impl Tokenizer for Estimate {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn count(&self, text: &str) -> usize {
+        (estimate(text) * self.scale).ceil() as usize
+    }
+
+    fn image_tokens(&self, width: u32, height: u32) -> usize {
+        (self.image)(width, height)
+    }
+}
+
+static O200K: Bpe = Bpe {
+    name: "o200k_base",
+    load: tiktoken_rs::o200k_base_singleton,
+};
+static CL100K: Bpe = Bpe {
+    name: "cl100k_base",
+    load: tiktoken_rs::cl100k_base_singleton,
+};
+static CLAUDE: Estimate = Estimate {
+    name: "claude (estimate)",
+    scale: 1.1,
+    image: anthropic_image_tokens,
+};
+static GEMINI: Estimate = Estimate {
+    name: "gemini (estimate)",
+    scale: 0.95,
+    image: gemini_image_tokens,
+};
+static GENERIC: Estimate = Estimate {
+    name: "generic (estimate)",
+    scale: 1.0,
+    image: anthropic_image_tokens,
+};
+
+/// Pick the tokenizer for a model, by model id first and provider second.
+pub fn tokenizer_for(provider: &str, model_id: &str) -> &'static dyn Tokenizer {
+    let id = model_id.to_lowercase();
+    if id.contains("claude") {
+        return &CLAUDE;
+    }
+    if id.contains("gemini") || id.contains("gemma") {
+        return &GEMINI;
+    }
+    if id.contains("gpt-4o")
+        || id.contains("gpt-4.1")
+        || id.contains("gpt-4.5")
+        || id.contains("gpt-5")
+        || id.contains("gpt-oss")
+        || id.contains("codex")
+        || id.starts_with("o1")
+        || id.starts_with("o3")
+        || id.starts_with("o4")
+    {
+        return &O200K;
+    }
+    if id.contains("gpt-4") || id.contains("gpt-3.5") {
+        return &CL100K;
+    }
+    match provider {
+        "anthropic" | "claude-sdk" => &CLAUDE,
+        "gemini" => &GEMINI,
+        "openai" | "codex" => &O200K,
+        _ => &GENERIC,
+    }
+}
+
+fn calibration() -> &'static Mutex<HashMap<String, f64>> {
+    static CALIBRATION: OnceLock<Mutex<HashMap<String, f64>>> = OnceLock::new();
+    CALIBRATION.get_or_init(Default::default)
+}
+
+/// Counts tokens for one model, scaled by that model's calibration factor.
+#[derive(Clone)]
+pub struct TokenCounter {
+    tokenizer: &'static dyn Tokenizer,
+    /// Calibration key, `provider/model`.
+    key: Arc<str>,
+}
+
+impl Default for TokenCounter {
+    fn default() -> Self {
+        Self {
+            tokenizer: &GENERIC,
+            key: Arc::from(""),
+        }
+    }
+}
+
+impl TokenCounter {
+    pub fn for_model(provider: &str, model_id: &str) -> Self {
+        Self {
+            tokenizer: tokenizer_for(provider, model_id),
+            key: Arc::from(format!("{provider}/{model_id}")),
+        }
+    }
+
+    pub fn tokenizer_name(&self) -> &'static str {
+        self.tokenizer.name()
+    }
+
+    /// Reported tokens per counted token, from past turns; 1.0 until the
+    /// provider has reported usage for this model.
+    pub fn factor(&self) -> f64 {
+        calibration()
+            .lock()
+            .unwrap()
+            .get(&*self.key)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    fn scaled(&self, raw: usize) -> usize {
+        (raw as f64 * self.factor()).round() as usize
+    }
+
+    pub fn text(&self, text: &str) -> usize {
+        self.scaled(self.tokenizer.count(text))
+    }
+
+    pub fn message(&self, msg: &Message) -> usize {
+        self.scaled(self.raw_message(msg))
+    }
+
+    pub fn thread(&self, messages: &[Message], system_prompt: &str) -> usize {
+        let raw = self.tokenizer.count(system_prompt)
+            + messages.iter().map(|m| self.raw_message(m)).sum::<usize>();
+        self.scaled(raw)
+    }
+
+    /// Everything the provider counts as input: system prompt, messages and
+    /// tool definitions.
+    pub fn request(&self, request: &ChatRequest) -> usize {
+        let mut raw = request
+            .system
+            .as_deref()
+            .map(|s| self.tokenizer.count(s))
+            .unwrap_or(0);
+        raw += request
+            .messages
+            .iter()
+            .map(|m| self.raw_message(m))
+            .sum::<usize>();
+        for tool in &request.tools {
+            raw += self.tokenizer.count(&tool.name)
+                + self.tokenizer.count(&tool.description)
+                + self.tokenizer.count(&tool.parameters.to_string());
+        }
+        self.scaled(raw)
+    }
+
+    /// Fold in the input tokens a provider reported for a request this
+    /// counter estimated at `estimated`.
+    pub fn observe(&self, estimated: usize, reported: u32) {
+        if estimated == 0 || reported == 0 || self.key.is_empty() {
+            return;
+        }
+        let mut table = calibration().lock().unwrap();
+        let factor = table.entry(self.key.to_string()).or_insert(1.0);
+        let raw = estimated as f64 / *factor;
+        let ratio = (reported as f64 / raw).clamp(0.5, 2.0);
+        *factor = *factor * (1.0 - CALIBRATION_WEIGHT) + ratio * CALIBRATION_WEIGHT;
+    }
+
+    fn raw_message(&self, msg: &Message) -> usize {
+        let content = match &msg.content {
+            MessageContent::Text(text) => self.tokenizer.count(text),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .map(|p| match p {
+                    ContentPart::Text { text } => self.tokenizer.count(text),
+                    ContentPart::Image { media_type, data } => {
+                        match image_dimensions(media_type, data) {
+                            Some((w, h)) => self.tokenizer.image_tokens(w, h),
+                            None => IMAGE_FALLBACK_TOKENS,
+                        }
+                    }
+                    ContentPart::ToolUse { name, input, .. } => {
+                        self.tokenizer.count(name) + self.tokenizer.count(&input.to_string())
+                    }
+                    ContentPart::ToolResult { content, .. } => self.tokenizer.count(content),
+                })
+                .sum(),
+        };
+        content + MESSAGE_OVERHEAD
+    }
+}
+
+/// The estimate behind the non-BPE tokenizers: a token per common word and
+/// more for long ones, digits in groups of three, a bit over half a token per
+/// ASCII symbol (BPE merges pairs like `();`), and a token per CJK character.
+pub(crate) fn estimate(text: &str) -> f64 {
+    #[derive(PartialEq)]
+    enum Run {
+        None,
+        Letters,
+        Digits,
+        OtherLetters,
+        Space,
+    }
+
+    let mut tokens = 0.0;
+    let mut run = Run::None;
+    let mut run_len = 0usize;
+    let flush = |run: &Run, len: usize| -> f64 {
+        match run {
+            Run::Letters => 1.0 + len.saturating_sub(6) as f64 / 8.0,
+            Run::Digits => (len as f64 / 3.0).ceil(),
+            Run::OtherLetters => len as f64 / 2.5,
+            // A single space joins the next word; longer runs (indentation)
+            // are a token of their own.
+            Run::Space if len > 1 => 1.0,
+            _ => 0.0,
+        }
+    };
+
+    for c in text.chars() {
+        let kind = if c.is_ascii_alphabetic() {
+            Run::Letters
+        } else if c.is_ascii_digit() {
+            Run::Digits
+        } else if c == ' ' || c == '\t' {
+            Run::Space
+        } else if is_cjk(c) {
+            tokens += flush(&run, run_len) + 1.0;
+            run = Run::None;
+            run_len = 0;
+            continue;
+        } else if c.is_alphabetic() {
+            Run::OtherLetters
+        } else {
+            // Newlines, punctuation, operators, emoji.
+            tokens += flush(&run, run_len) + if c.is_ascii() { 0.6 } else { 2.0 };
+            run = Run::None;
+            run_len = 0;
+            continue;
+        };
+        if kind != run {
+            tokens += flush(&run, run_len);
+            run = kind;
+            run_len = 0;
+        }
+        run_len += 1;
+    }
+    tokens + flush(&run, run_len)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // Hiragana, Katakana
+        | 0x3400..=0x4DBF    // CJK Extension A
+        | 0x4E00..=0x9FFF    // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF    // Hangul syllables
+        | 0xF900..=0xFAFF    // CJK Compatibility Ideographs
+        | 0x20000..=0x2FFFF) // CJK Extensions B-F
+}
+
+/// Anthropic: images are scaled to fit 1568px on the long edge, then cost
+/// about one token per 750 pixels.
+fn anthropic_image_tokens(width: u32, height: u32) -> usize {
+    let (w, h) = fit(width, height, 1568, 1568);
+    ((w * h) / 750.0).ceil().max(1.0) as usize
+}
+
+/// OpenAI high detail: fit in 2048x2048, scale the short side to 768, then
+/// 170 tokens per 512px tile plus 85.
+fn openai_image_tokens(width: u32, height: u32) -> usize {
+    let (w, h) = fit(width, height, 2048, 2048);
+    let short = w.min(h);
+    let (w, h) = if short > 768.0 {
+        (w * 768.0 / short, h * 768.0 / short)
+    } else {
+        (w, h)
+    };
+    let tiles = (w / 512.0).ceil() * (h / 512.0).ceil();
+    (tiles * 170.0 + 85.0) as usize
+}
+
+/// Gemini: 258 tokens for small images, otherwise 258 per 768px tile.
+fn gemini_image_tokens(width: u32, height: u32) -> usize {
+    if width <= 384 && height <= 384 {
+        return 258;
+    }
+    let tiles = (width as f64 / 768.0).ceil() * (height as f64 / 768.0).ceil();
+    (tiles * 258.0) as usize
+}
+
+fn fit(width: u32, height: u32, max_w: u32, max_h: u32) -> (f64, f64) {
+    let (w, h) = (width as f64, height as f64);
+    let scale = (max_w as f64 / w).min(max_h as f64 / h).min(1.0);
+    (w * scale, h * scale)
+}
+
+/// Width and height from the header of a base64-encoded PNG, JPEG, GIF or
+/// WebP image.
+pub(crate) fn image_dimensions(media_type: &str, data: &str) -> Option<(u32, u32)> {
+    let prefix_len = data.len().min(IMAGE_HEADER_BYTES / 3 * 4) / 4 * 4;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(&data[..prefix_len])
+        .ok()?;
+    let be16 = |i: usize| -> Option<u32> {
+        Some(u16::from_be_bytes(bytes.get(i..i + 2)?.try_into().ok()?) as u32)
+    };
+    let le16 = |i: usize| -> Option<u32> {
+        Some(u16::from_le_bytes(bytes.get(i..i + 2)?.try_into().ok()?) as u32)
+    };
+    let be32 = |i: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?))
+    };
+    let le24 = |i: usize| -> Option<u32> {
+        let b = bytes.get(i..i + 3)?;
+        Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+    };
+
+    match media_type {
+        "image/png" if bytes.starts_with(b"\x89PNG") => Some((be32(16)?, be32(20)?)),
+        "image/gif" if bytes.starts_with(b"GIF") => Some((le16(6)?, le16(8)?)),
+        "image/webp" if bytes.get(8..12) == Some(b"WEBP") => match bytes.get(12..16)? {
+            b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        },
+        "image/jpeg" | "image/jpg" if bytes.starts_with(&[0xff, 0xd8]) => {
+            let mut i = 2;
+            while i + 9 < bytes.len() {
+                if bytes[i] != 0xff {
+                    return None;
+                }
+                let marker = bytes[i + 1];
+                // Start-of-frame markers, excluding DHT, JPG and DAC.
+                if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+                    return Some((be16(i + 7)?, be16(i + 5)?));
+                }
+                i += 2 + be16(i + 2)? as usize;
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROSE: &str = "The quick brown fox jumps over the lazy dog. Context windows \
+        are measured in tokens, not characters, so a good estimate matters when \
+        deciding whether to compact the conversation before the next request.";
+
+    const CODE: &str = r#"fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <path>", args[0]);
+        std::process::exit(1);
+    }
+    let total: u64 = (0..1024).map(|i| i * 2).sum();
+    println!("{total}");
+}"#;
+
+    const CJK: &str =
+        "上下文窗口是按令牌计算的，而不是按字符计算的。中文文本的每个字符通常对应一个或多个令牌。";
+
+    fn within(estimate: usize, actual: usize, tolerance: f64) -> bool {
+        let ratio = estimate as f64 / actual as f64;
+        (1.0 - tolerance..=1.0 + tolerance).contains(&ratio)
+    }
+
+    #[test]
+    fn estimate_tracks_bpe_across_scripts() {
+        for text in [PROSE, CODE, CJK] {
+            let actual = CL100K.count(text);
+            let estimated = GENERIC.count(text);
+            assert!(
+                within(estimated, actual, 0.3),
+                "estimate {estimated} vs cl100k {actual} for {text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn picks_tokenizer_by_model_family() {
+        assert_eq!(tokenizer_for("openai", "gpt-4o-mini").name(), "o200k_base");
+        assert_eq!(tokenizer_for("openai", "gpt-4-turbo").name(), "cl100k_base");
+        assert_eq!(
+            tokenizer_for("openrouter", "anthropic/claude-sonnet-4").name(),
+            "claude (estimate)"
+        );
+        assert_eq!(
+            tokenizer_for("gemini", "some-new-model").name(),
+            "gemini (estimate)"
+        );
+        assert_eq!(
+            tokenizer_for("ollama", "llama3").name(),
+            "generic (estimate)"
+        );
+    }
+
+    #[test]
+    fn calibration_converges_on_reported_usage() {
+        let counter = TokenCounter::for_model("test", "calibration-model");
+        let messages = vec![Message {
+            role: nyzhi_provider::Role::User,
+            content: MessageContent::Text(PROSE.repeat(20)),
+        }];
+        let raw = counter.thread(&messages, "");
+        for _ in 0..20 {
+            let estimated = counter.thread(&messages, "");
+            counter.observe(estimated, (raw as f64 * 1.5) as u32);
+        }
+        let calibrated = counter.thread(&messages, "");
+        assert!(
+            within(calibrated, raw * 3 / 2, 0.02),
+            "{calibrated} vs {raw}"
+        );
+    }
+
+    #[test]
+    fn reads_image_dimensions() {
+        let b64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(1920u32.to_be_bytes());
+        png.extend(1080u32.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0]);
+        assert_eq!(
+            image_dimensions("image/png", &b64(&png)),
+            Some((1920, 1080))
+        );
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend(640u16.to_le_bytes());
+        gif.extend(480u16.to_le_bytes());
+        gif.extend([0, 0, 0]);
+        assert_eq!(image_dimensions("image/gif", &b64(&gif)), Some((640, 480)));
+
+        // SOI, an APP0 segment to skip, then SOF0 with height 600, width 800.
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend([0xff, 0xc0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20, 0x03]);
+        jpeg.extend([0u8; 12]);
+        assert_eq!(
+            image_dimensions("image/jpeg", &b64(&jpeg)),
+            Some((800, 600))
+        );
+
+        assert_eq!(image_dimensions("image/png", &b64(b"not an image")), None);
+
+        assert_eq!(anthropic_image_tokens(1000, 1000), 1334);
+        assert_eq!(openai_image_tokens(1024, 1024), 765);
+        assert_eq!(gemini_image_tokens(300, 300), 258);
+    }
+}
@@ -15,11 +15,11 @@ pub struct ContextFile {
     pub truncated: bool,
 }
 
-/// Extract `@path` mentions from user input.
+/// Extract `@path` and `@server:uri` mentions from user input.
 ///
 /// Skips email-like patterns where the character before `@` is alphanumeric.
 pub fn parse_mentions(input: &str) -> Vec<String> {
-    let re = Regex::new(r"@([\w./~-][\w./~-]*)").unwrap();
+    let re = Regex::new(r"@([\w./~-][\w./~-]*)(:[^\s]+)?").unwrap();
     let mut mentions = Vec::new();
 
     for cap in re.captures_iter(input) {
@@ -33,7 +33,16 @@ pub fn parse_mentions(input: &str) -> Vec<String> {
             }
         }
 
-        let path = cap[1].to_string();
+        let path = match cap.get(2) {
+            Some(rest) if parse_mcp_mention(&cap[0][1..]).is_some() => {
+                // Leave sentence punctuation after the URI out of it.
+                let uri = rest
+                    .as_str()
+                    .trim_end_matches(['.', ',', ';', '!', '?', ')']);
+                format!("{}{uri}", &cap[1])
+            }
+            _ => cap[1].to_string(),
+        };
         if !mentions.contains(&path) {
             mentions.push(path);
         }
@@ -41,6 +50,44 @@ pub fn parse_mentions(input: &str) -> Vec<String> {
     mentions
 }
 
+/// Split a `server:uri` mention of an MCP resource. The server part is a
+/// bare name, so `src/main.rs:12` and `Cargo.toml:3` stay file mentions.
+pub fn parse_mcp_mention(mention: &str) -> Option<(&str, &str)> {
+    let (server, uri) = mention.split_once(':')?;
+    let is_name = !server.is_empty()
+        && server
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    let is_line_number = uri.chars().all(|c| c.is_ascii_digit());
+    (is_name && !uri.is_empty() && !is_line_number).then_some((server, uri))
+}
+
+/// Context entry for the text of an MCP resource, truncated like files.
+pub fn resource_context(mention: &str, content: String) -> ContextFile {
+    let truncated = content.len() as u64 > MAX_FILE_SIZE;
+    let content = if truncated {
+        let mut end = MAX_FILE_SIZE as usize;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        let end = content[..end].rfind('\n').unwrap_or(end);
+        format!(
+            "{}\n... (truncated, resource exceeds 100KB)",
+            &content[..end]
+        )
+    } else {
+        content
+    };
+    ContextFile {
+        path: PathBuf::from(mention),
+        display_path: mention.to_string(),
+        line_count: content.lines().count(),
+        content,
+        is_dir: false,
+        truncated,
+    }
+}
+
 /// Resolve mention strings to actual file/directory contents.
 pub fn resolve_context_files(
     mentions: &[String],
@@ -50,6 +97,9 @@ pub fn resolve_context_files(
     let mut files = Vec::new();
 
     for mention in mentions {
+        if parse_mcp_mention(mention).is_some() {
+            continue;
+        }
         let expanded: PathBuf = if let Some(rest) = mention.strip_prefix('~') {
             if let Some(home) = dirs::home_dir() {
                 home.join(rest.strip_prefix('/').unwrap_or(rest))
@@ -280,6 +330,18 @@ mod tests {
         assert_eq!(mentions, vec!["~/config.toml"]);
     }
 
+    #[test]
+    fn parse_mcp_resource_mention() {
+        let mentions = parse_mentions("describe @db:postgres://main/users, and @src/lib.rs:12");
+        assert_eq!(mentions, vec!["db:postgres://main/users", "src/lib.rs"]);
+        assert_eq!(
+            parse_mcp_mention(&mentions[0]),
+            Some(("db", "postgres://main/users"))
+        );
+        assert_eq!(parse_mcp_mention("Cargo.toml:3"), None);
+        assert_eq!(parse_mcp_mention("notes:12"), None);
+    }
+
     #[test]
     fn parse_no_mentions() {
         let mentions = parse_mentions("no mentions here");
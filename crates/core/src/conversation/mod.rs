@@ -1,49 +1,391 @@
 use std::path::PathBuf;
 
 use chrono::{DateTime, Utc};
-use nyzhi_provider::{Message, MessageContent, Role};
+use nyzhi_provider::{ContentPart, Message, MessageContent, Role};
 use serde::{Deserialize, Serialize};
 
 use crate::context;
 
+/// One message in the conversation tree.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Node {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    pub message: Message,
+}
+
+/// A line of conversation, identified by its newest message. Branches share
+/// every message before the point where they were forked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Branch {
+    pub head: Option<usize>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Summary of a branch for listings.
+#[derive(Debug, Clone)]
+pub struct BranchInfo {
+    pub index: usize,
+    pub active: bool,
+    pub message_count: usize,
+    /// Messages this branch shares with the active branch.
+    pub shared_with_active: usize,
+    /// The branch's latest user prompt.
+    pub last_prompt: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A conversation stored as a tree of messages. The active branch is the
+/// root-to-head path that gets sent to the model; other branches are kept so
+/// the user can switch back to them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "ThreadFile", into = "ThreadFile")]
 pub struct Thread {
     pub id: String,
     pub created_at: DateTime<Utc>,
+    nodes: Vec<Node>,
+    branches: Vec<Branch>,
+    active: usize,
+    /// Node ids on the active branch, root first.
+    path: Vec<usize>,
+    /// Messages on the active branch, kept in step with `path`.
     messages: Vec<Message>,
+    /// Bumped whenever existing nodes change rather than new ones being
+    /// appended, so the session store knows to rewrite instead of append.
+    generation: u64,
+}
+
+/// On-disk form of a thread. Files written before threads became trees only
+/// have `messages`, which load as a single branch.
+#[derive(Serialize, Deserialize)]
+struct ThreadFile {
+    id: String,
+    created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    messages: Vec<Message>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    branches: Vec<Branch>,
+    #[serde(default)]
+    active: usize,
+}
+
+impl From<ThreadFile> for Thread {
+    fn from(file: ThreadFile) -> Self {
+        let mut thread = Thread {
+            id: file.id,
+            created_at: file.created_at,
+            nodes: file.nodes,
+            branches: file.branches,
+            active: file.active,
+            path: Vec::new(),
+            messages: Vec::new(),
+            generation: 0,
+        };
+        if thread.nodes.is_empty() {
+            thread.branches.clear();
+            for message in file.messages {
+                let parent = thread.nodes.len().checked_sub(1);
+                thread.nodes.push(Node { parent, message });
+            }
+        }
+        let node_count = thread.nodes.len();
+        for node in &mut thread.nodes {
+            node.parent = node.parent.filter(|p| *p < node_count);
+        }
+        thread
+            .branches
+            .retain(|b| !matches!(b.head, Some(h) if h >= node_count));
+        if thread.branches.is_empty() {
+            thread.branches.push(Branch {
+                head: node_count.checked_sub(1),
+                created_at: thread.created_at,
+            });
+        }
+        if thread.active >= thread.branches.len() {
+            thread.active = 0;
+        }
+        thread.rebuild_path();
+        thread
+    }
+}
+
+impl From<Thread> for ThreadFile {
+    fn from(thread: Thread) -> Self {
+        ThreadFile {
+            id: thread.id,
+            created_at: thread.created_at,
+            messages: Vec::new(),
+            nodes: thread.nodes,
+            branches: thread.branches,
+            active: thread.active,
+        }
+    }
 }
 
 impl Thread {
     pub fn new() -> Self {
+        let created_at = Utc::now();
         Self {
             id: uuid::Uuid::new_v4().to_string(),
-            created_at: Utc::now(),
+            created_at,
+            nodes: Vec::new(),
+            branches: vec![Branch {
+                head: None,
+                created_at,
+            }],
+            active: 0,
+            path: Vec::new(),
             messages: Vec::new(),
+            generation: 0,
         }
     }
 
     pub fn push_message(&mut self, message: Message) {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            parent: self.path.last().copied(),
+            message: message.clone(),
+        });
+        self.branches[self.active].head = Some(id);
+        self.path.push(id);
         self.messages.push(message);
     }
 
+    /// Messages on the active branch.
     pub fn messages(&self) -> &[Message] {
         &self.messages
     }
 
-    pub fn messages_mut(&mut self) -> &mut Vec<Message> {
-        &mut self.messages
+    /// Edit the active branch's messages in place. Edits land on the shared
+    /// nodes, so branches forked below an edited message see it too.
+    pub fn edit_messages<R>(&mut self, f: impl FnOnce(&mut [Message]) -> R) -> R {
+        let result = f(&mut self.messages);
+        for (id, message) in self.path.iter().zip(&self.messages) {
+            self.nodes[*id].message = message.clone();
+        }
+        self.generation += 1;
+        result
     }
 
     pub fn message_count(&self) -> usize {
         self.messages.len()
     }
 
+    /// Drop every branch and start over with an empty conversation.
     pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.branches = vec![Branch {
+            head: None,
+            created_at: Utc::now(),
+        }];
+        self.active = 0;
+        self.path.clear();
         self.messages.clear();
+        self.generation += 1;
+    }
+
+    /// Indices of user prompts on the active branch, skipping tool results.
+    pub fn prompt_indices(&self) -> Vec<usize> {
+        self.messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| is_prompt(m))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Start a new branch that keeps the first `at` messages of the active
+    /// branch and make it active. The old branch is left untouched.
+    /// Returns the new branch's index.
+    pub fn fork(&mut self, at: usize) -> usize {
+        let at = at.min(self.path.len());
+        let head = at.checked_sub(1).map(|i| self.path[i]);
+        self.branches.push(Branch {
+            head,
+            created_at: Utc::now(),
+        });
+        self.active = self.branches.len() - 1;
+        self.path.truncate(at);
+        self.messages.truncate(at);
+        self.active
+    }
+
+    /// A new, single-branch thread holding the first `at` messages of the
+    /// active branch.
+    pub fn fork_into_new(&self, at: usize) -> Thread {
+        let mut thread = Thread::new();
+        for message in &self.messages[..at.min(self.messages.len())] {
+            thread.push_message(message.clone());
+        }
+        thread
+    }
+
+    pub fn branch_count(&self) -> usize {
+        self.branches.len()
+    }
+
+    pub fn active_branch(&self) -> usize {
+        self.active
+    }
+
+    pub fn switch_branch(&mut self, index: usize) -> anyhow::Result<()> {
+        if index >= self.branches.len() {
+            anyhow::bail!(
+                "No branch {index} (this thread has {} branches)",
+                self.branches.len()
+            );
+        }
+        self.active = index;
+        self.rebuild_path();
+        Ok(())
+    }
+
+    pub fn branches(&self) -> Vec<BranchInfo> {
+        self.branches
+            .iter()
+            .enumerate()
+            .map(|(index, branch)| {
+                let path = self.path_to(branch.head);
+                let shared_with_active = path
+                    .iter()
+                    .zip(&self.path)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                let last_prompt = path
+                    .iter()
+                    .rev()
+                    .map(|id| &self.nodes[*id].message)
+                    .find(|m| is_prompt(m))
+                    .map(|m| m.content.as_text().to_string())
+                    .unwrap_or_default();
+                BranchInfo {
+                    index,
+                    active: index == self.active,
+                    message_count: path.len(),
+                    shared_with_active,
+                    last_prompt,
+                    created_at: branch.created_at,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn from_parts(
+        id: String,
+        created_at: DateTime<Utc>,
+        nodes: Vec<Node>,
+        branches: Vec<Branch>,
+        active: usize,
+    ) -> Self {
+        ThreadFile {
+            id,
+            created_at,
+            messages: Vec::new(),
+            nodes,
+            branches,
+            active,
+        }
+        .into()
+    }
+
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
     }
 
-    pub fn estimated_tokens(&self, system_prompt: &str) -> usize {
-        context::estimate_thread_tokens(&self.messages, system_prompt)
+    pub(crate) fn branch_heads(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn path_to(&self, head: Option<usize>) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut current = head;
+        while let Some(id) = current {
+            // Parent links come from disk, so guard against cycles.
+            if path.len() > self.nodes.len() {
+                break;
+            }
+            path.push(id);
+            current = self.nodes[id].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    fn rebuild_path(&mut self) {
+        self.path = self.path_to(self.branches[self.active].head);
+        self.messages = self
+            .path
+            .iter()
+            .map(|id| self.nodes[*id].message.clone())
+            .collect();
+    }
+
+    /// Replace the first `split` messages of the active branch with `prefix`.
+    /// Messages after the split are re-parented, so branches forked below
+    /// them keep sharing them; branches forked inside the replaced part keep
+    /// the original messages.
+    fn replace_prefix(&mut self, split: usize, prefix: Vec<Message>) {
+        let rest = self.path.split_off(split);
+        self.path.clear();
+        for message in prefix {
+            let id = self.nodes.len();
+            self.nodes.push(Node {
+                parent: self.path.last().copied(),
+                message,
+            });
+            self.path.push(id);
+        }
+        match rest.first() {
+            Some(first) => self.nodes[*first].parent = self.path.last().copied(),
+            None => self.branches[self.active].head = self.path.last().copied(),
+        }
+        self.prune();
+        self.rebuild_path();
+        self.generation += 1;
+    }
+
+    /// Drop nodes no branch can reach and renumber the rest.
+    fn prune(&mut self) {
+        let mut keep = vec![false; self.nodes.len()];
+        for branch in &self.branches {
+            for id in self.path_to(branch.head) {
+                keep[id] = true;
+            }
+        }
+        if keep.iter().all(|k| *k) {
+            return;
+        }
+        let mut remap = vec![None; self.nodes.len()];
+        let mut next = 0;
+        for (id, kept) in keep.iter().enumerate() {
+            if *kept {
+                remap[id] = Some(next);
+                next += 1;
+            }
+        }
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes
+            .into_iter()
+            .zip(&keep)
+            .filter(|(_, kept)| **kept)
+            .map(|(mut node, _)| {
+                node.parent = node.parent.and_then(|p| remap[p]);
+                node
+            })
+            .collect();
+        for branch in &mut self.branches {
+            branch.head = branch.head.and_then(|h| remap[h]);
+        }
+    }
+
+    pub fn estimated_tokens(&self, counter: &context::TokenCounter, system_prompt: &str) -> usize {
+        counter.thread(&self.messages, system_prompt)
     }
 
     /// Replace older messages with a summary, keeping the most recent `keep_recent` messages.
@@ -77,13 +419,10 @@ impl Thread {
             return;
         }
         let split = self.messages.len() - keep_recent;
-        let recent: Vec<Message> = self.messages.drain(split..).collect();
-        self.messages.clear();
-
-        self.messages.push(Message {
+        let mut prefix = vec![Message {
             role: Role::User,
             content: MessageContent::Text(format!("[Conversation summary]\n{summary}")),
-        });
+        }];
 
         let mut state_block = String::new();
 
@@ -116,7 +455,7 @@ impl Thread {
         }
 
         if !state_block.is_empty() {
-            self.messages.push(Message {
+            prefix.push(Message {
                 role: Role::User,
                 content: MessageContent::Text(format!(
                     "[Working state restored after compaction]{state_block}"
@@ -137,7 +476,7 @@ impl Thread {
         }
 
         if !restoration.is_empty() {
-            self.messages.push(Message {
+            prefix.push(Message {
                 role: Role::User,
                 content: MessageContent::Text(format!(
                     "[Recently accessed files restored after compaction]{restoration}"
@@ -145,12 +484,12 @@ impl Thread {
             });
         }
 
-        self.messages.push(Message {
+        prefix.push(Message {
             role: Role::User,
             content: MessageContent::Text(context::CONTINUATION_MESSAGE.to_string()),
         });
 
-        self.messages.extend(recent);
+        self.replace_prefix(split, prefix);
     }
 }
 
@@ -159,3 +498,118 @@ impl Default for Thread {
         Self::new()
     }
 }
+
+fn is_prompt(message: &Message) -> bool {
+    message.role == Role::User
+        && match &message.content {
+            MessageContent::Text(_) => true,
+            MessageContent::Parts(parts) => !parts
+                .iter()
+                .any(|p| matches!(p, ContentPart::ToolResult { .. })),
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(text: &str) -> Message {
+        Message {
+            role: Role::User,
+            content: MessageContent::Text(text.into()),
+        }
+    }
+
+    fn assistant(text: &str) -> Message {
+        Message {
+            role: Role::Assistant,
+            content: MessageContent::Text(text.into()),
+        }
+    }
+
+    fn texts(thread: &Thread) -> Vec<&str> {
+        thread
+            .messages()
+            .iter()
+            .map(|m| m.content.as_text())
+            .collect()
+    }
+
+    #[test]
+    fn fork_keeps_both_branches() {
+        let mut thread = Thread::new();
+        thread.push_message(user("a"));
+        thread.push_message(assistant("A"));
+        thread.push_message(user("b"));
+        thread.push_message(assistant("B"));
+
+        let prompts = thread.prompt_indices();
+        assert_eq!(prompts, vec![0, 2]);
+        let branch = thread.fork(prompts[1]);
+        thread.push_message(user("b2"));
+        assert_eq!(texts(&thread), vec!["a", "A", "b2"]);
+
+        let branches = thread.branches();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].shared_with_active, 2);
+        assert_eq!(branches[0].last_prompt, "b");
+
+        thread.switch_branch(0).unwrap();
+        assert_eq!(texts(&thread), vec!["a", "A", "b", "B"]);
+        thread.switch_branch(branch).unwrap();
+        assert_eq!(texts(&thread), vec!["a", "A", "b2"]);
+        assert!(thread.switch_branch(5).is_err());
+    }
+
+    #[test]
+    fn loads_linear_session_files() {
+        let json = r#"{
+            "id": "t1",
+            "created_at": "2025-01-01T00:00:00Z",
+            "messages": [
+                {"role": "user", "content": "hi"},
+                {"role": "assistant", "content": "hello"}
+            ]
+        }"#;
+        let thread: Thread = serde_json::from_str(json).unwrap();
+        assert_eq!(texts(&thread), vec!["hi", "hello"]);
+        assert_eq!(thread.branch_count(), 1);
+
+        let mut thread = thread;
+        thread.fork(1);
+        thread.push_message(assistant("hey"));
+        let saved = serde_json::to_string(&thread).unwrap();
+        let reloaded: Thread = serde_json::from_str(&saved).unwrap();
+        assert_eq!(texts(&reloaded), vec!["hi", "hey"]);
+        assert_eq!(reloaded.branch_count(), 2);
+        assert_eq!(reloaded.active_branch(), 1);
+    }
+
+    #[test]
+    fn compaction_keeps_other_branches() {
+        let mut thread = Thread::new();
+        for i in 0..4 {
+            thread.push_message(user(&format!("q{i}")));
+            thread.push_message(assistant(&format!("a{i}")));
+        }
+        // One branch forked inside the part that gets summarized, one in the
+        // part that is kept.
+        thread.fork(2);
+        thread.push_message(user("early"));
+        thread.switch_branch(0).unwrap();
+        thread.fork(7);
+        thread.push_message(assistant("late"));
+
+        thread.compact("summary", 3);
+        let active = texts(&thread);
+        assert!(active[0].contains("summary"));
+        assert_eq!(active[active.len() - 3..], ["a2", "q3", "late"]);
+
+        thread.switch_branch(1).unwrap();
+        assert_eq!(texts(&thread), vec!["q0", "a0", "early"]);
+        thread.switch_branch(0).unwrap();
+        let main = texts(&thread);
+        assert!(main[0].contains("summary"));
+        assert_eq!(main[main.len() - 3..], ["a2", "q3", "a3"]);
+    }
+}
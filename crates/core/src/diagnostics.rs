@@ -126,6 +126,21 @@ pub fn run_diagnostics(
         message: format!("{skill_count} skill(s) in .nyzhi/skills/"),
     });
 
+    // Sandbox
+    results.push(match crate::sandbox::backend() {
+        Some(backend) => DiagnosticResult {
+            name: "Sandbox".into(),
+            status: DiagStatus::Pass,
+            message: backend,
+        },
+        None => DiagnosticResult {
+            name: "Sandbox".into(),
+            status: DiagStatus::Warn,
+            message: "No backend; commands run unconfined unless [shell.sandbox] require is set"
+                .into(),
+        },
+    });
+
     // OS info
     results.push(DiagnosticResult {
         name: "Platform".into(),
@@ -0,0 +1,324 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use nyzhi_provider::{
+    ChatRequest, ContentPart, Message, MessageContent, ModelInfo, ModelTier, Provider,
+    ProviderError,
+};
+
+/// OpenAI rejects tool call ids longer than this.
+const MAX_TOOL_CALL_ID_LEN: usize = 40;
+
+/// A provider and model a turn moves to when the one before it in the chain
+/// fails for good.
+#[derive(Clone)]
+pub struct FallbackModel {
+    pub provider: Arc<dyn Provider>,
+    pub model: ModelInfo,
+}
+
+impl FallbackModel {
+    /// `provider/model`, as written in config.
+    pub fn label(&self) -> String {
+        model_label(&self.model)
+    }
+}
+
+pub fn model_label(model: &ModelInfo) -> String {
+    format!("{}/{}", model.provider, model.id)
+}
+
+/// Split a `provider/model` entry. Only the first `/` separates them, so
+/// OpenRouter ids such as `openrouter/anthropic/claude-sonnet-4` work.
+pub fn parse_entry(entry: &str) -> Option<(&str, &str)> {
+    let (provider, model) = entry.trim().split_once('/')?;
+    if provider.is_empty() || model.is_empty() {
+        return None;
+    }
+    Some((provider, model))
+}
+
+/// Build the chain from `agent.fallback_models`. Entries whose provider
+/// cannot be created are skipped with a warning so one missing key does not
+/// disable the rest.
+pub async fn build_chain(config: &nyzhi_config::Config) -> Vec<FallbackModel> {
+    let mut chain = Vec::new();
+    for entry in &config.agent.fallback_models {
+        match build_entry(entry, config).await {
+            Ok(fallback) => chain.push(fallback),
+            Err(e) => tracing::warn!(entry = %entry, error = %e, "Skipping fallback model"),
+        }
+    }
+    chain
+}
+
+async fn build_entry(entry: &str, config: &nyzhi_config::Config) -> Result<FallbackModel> {
+    let (provider_id, model_id) = parse_entry(entry)
+        .ok_or_else(|| anyhow::anyhow!("expected `provider/model`, got `{entry}`"))?;
+    let provider: Arc<dyn Provider> = nyzhi_provider::create_provider_async(provider_id, config)
+        .await?
+        .into();
+    let mut model = provider
+        .supported_models()
+        .iter()
+        .find(|m| m.id == model_id)
+        .cloned()
+        .or_else(|| {
+            nyzhi_provider::ModelRegistry::new()
+                .find(provider_id, model_id)
+                .cloned()
+        })
+        .unwrap_or_else(|| unknown_model(provider_id, model_id));
+    // Usage is attributed to the provider as configured, not its API style.
+    model.provider = provider_id.to_string();
+    Ok(FallbackModel { provider, model })
+}
+
+/// Conservative limits for a model the registry does not know; its cost is
+/// recorded as zero.
+fn unknown_model(provider: &str, id: &str) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        provider: provider.to_string(),
+        context_window: 128_000,
+        max_output_tokens: 8192,
+        supports_tools: true,
+        supports_streaming: true,
+        supports_vision: false,
+        input_price_per_m: 0.0,
+        output_price_per_m: 0.0,
+        cache_read_price_per_m: 0.0,
+        cache_write_price_per_m: 0.0,
+        tier: ModelTier::Medium,
+        thinking: None,
+    }
+}
+
+/// Whether a failed request should move on to the next model. A context
+/// overflow is left to compaction instead.
+pub fn should_fall_back(error: &anyhow::Error) -> bool {
+    !matches!(
+        error.downcast_ref::<ProviderError>(),
+        Some(ProviderError::ContextOverflow(_))
+    )
+}
+
+/// Adapt a request built from the thread to a model it was not written for:
+/// drop what the model cannot take and make tool call ids acceptable to
+/// every provider. The thread itself is left untouched.
+pub fn translate_request(request: &mut ChatRequest, model: &ModelInfo) {
+    request.model = model.id.clone();
+    request.max_tokens = request.max_tokens.map(|n| n.min(model.max_output_tokens));
+    if model.thinking.is_none() {
+        request.thinking = None;
+    }
+    if !model.supports_tools {
+        request.tools.clear();
+    }
+    for message in &mut request.messages {
+        translate_message(message, model);
+    }
+}
+
+fn translate_message(message: &mut Message, model: &ModelInfo) {
+    let MessageContent::Parts(parts) = &mut message.content else {
+        return;
+    };
+    for part in parts.iter_mut() {
+        match part {
+            ContentPart::Image { media_type, .. } if !model.supports_vision => {
+                *part = ContentPart::Text {
+                    text: format!("[{media_type} image omitted: model does not accept images]"),
+                };
+            }
+            ContentPart::ToolUse { id, name, input } => {
+                if model.supports_tools {
+                    *id = tool_call_id(id);
+                } else {
+                    *part = ContentPart::Text {
+                        text: format!("[Called tool {name} with {input}]"),
+                    };
+                }
+            }
+            ContentPart::ToolResult {
+                tool_use_id,
+                tool_name,
+                content,
+            } => {
+                if model.supports_tools {
+                    *tool_use_id = tool_call_id(tool_use_id);
+                } else {
+                    *part = ContentPart::Text {
+                        text: format!(
+                            "[Result of {}]\n{content}",
+                            tool_name.as_deref().unwrap_or("tool")
+                        ),
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Ids from one provider may use characters or lengths another rejects.
+/// The mapping is deterministic so calls and results still pair up. An id
+/// that had to change keeps a readable prefix and ends in a hash of the
+/// original, so distinct ids stay distinct.
+fn tool_call_id(id: &str) -> String {
+    let sanitized: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized == id && id.len() <= MAX_TOOL_CALL_ID_LEN {
+        return sanitized;
+    }
+
+    use sha2::{Digest, Sha256};
+    let hash = hex::encode(&Sha256::digest(id.as_bytes())[..6]);
+    let prefix: String = sanitized
+        .chars()
+        .take(MAX_TOOL_CALL_ID_LEN - hash.len() - 1)
+        .collect();
+    format!("{prefix}_{hash}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nyzhi_provider::Role;
+
+    #[test]
+    fn parse_entry_splits_at_first_slash() {
+        assert_eq!(
+            parse_entry("openrouter/anthropic/claude-sonnet-4"),
+            Some(("openrouter", "anthropic/claude-sonnet-4"))
+        );
+        assert_eq!(parse_entry(" openai/gpt-4.1 "), Some(("openai", "gpt-4.1")));
+        assert_eq!(parse_entry("gpt-4.1"), None);
+        assert_eq!(parse_entry("openai/"), None);
+    }
+
+    #[test]
+    fn context_overflow_does_not_fall_back() {
+        let overflow = anyhow::Error::new(ProviderError::ContextOverflow("too long".into()));
+        assert!(!should_fall_back(&overflow));
+        let outage = anyhow::Error::new(ProviderError::ServerError {
+            status: 529,
+            body: "overloaded".into(),
+        });
+        assert!(should_fall_back(&outage));
+        assert!(should_fall_back(&anyhow::anyhow!("connection refused")));
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "claude-sonnet".into(),
+            messages: vec![
+                Message {
+                    role: Role::User,
+                    content: MessageContent::Parts(vec![
+                        ContentPart::Text {
+                            text: "What is in this screenshot?".into(),
+                        },
+                        ContentPart::Image {
+                            media_type: "image/png".into(),
+                            data: "aGk=".into(),
+                        },
+                    ]),
+                },
+                Message {
+                    role: Role::Assistant,
+                    content: MessageContent::Parts(vec![ContentPart::ToolUse {
+                        id: "toolu_01:abc.def/0123456789012345678901234567890".into(),
+                        name: "read".into(),
+                        input: serde_json::json!({ "path": "a.rs" }),
+                    }]),
+                },
+                Message {
+                    role: Role::User,
+                    content: MessageContent::Parts(vec![ContentPart::ToolResult {
+                        tool_use_id: "toolu_01:abc.def/0123456789012345678901234567890".into(),
+                        tool_name: Some("read".into()),
+                        content: "fn main() {}".into(),
+                    }]),
+                },
+            ],
+            tools: vec![nyzhi_provider::ToolDefinition {
+                name: "read".into(),
+                description: "Read a file".into(),
+                parameters: serde_json::json!({}),
+            }],
+            max_tokens: Some(64_000),
+            temperature: None,
+            system: None,
+            stream: true,
+            thinking: None,
+        }
+    }
+
+    #[test]
+    fn translation_keeps_tool_pairs_matched() {
+        let model = unknown_model("openai", "gpt-4.1");
+        let mut request = request();
+        translate_request(&mut request, &model);
+
+        assert_eq!(request.model, "gpt-4.1");
+        assert_eq!(request.max_tokens, Some(8192));
+        assert_eq!(request.tools.len(), 1);
+        let MessageContent::Parts(user) = &request.messages[0].content else {
+            panic!("expected parts");
+        };
+        assert!(matches!(&user[1], ContentPart::Text { text } if text.contains("omitted")));
+
+        let (MessageContent::Parts(call), MessageContent::Parts(result)) =
+            (&request.messages[1].content, &request.messages[2].content)
+        else {
+            panic!("expected parts");
+        };
+        let (ContentPart::ToolUse { id, .. }, ContentPart::ToolResult { tool_use_id, .. }) =
+            (&call[0], &result[0])
+        else {
+            panic!("expected a tool call and its result");
+        };
+        assert_eq!(id, tool_use_id);
+        assert!(id.len() <= MAX_TOOL_CALL_ID_LEN);
+        assert!(id.starts_with("toolu_01_abc_def_"));
+    }
+
+    #[test]
+    fn rewritten_tool_call_ids_do_not_collide() {
+        let base = "call_".to_string() + &"x".repeat(40);
+        let (a, b) = (tool_call_id(&format!("{base}a")), tool_call_id(&format!("{base}b")));
+        assert_ne!(a, b);
+        assert_eq!(a.len(), MAX_TOOL_CALL_ID_LEN);
+        assert!(a.starts_with("call_xxx"));
+
+        assert_ne!(tool_call_id("call.1"), tool_call_id("call_1"));
+        assert_eq!(tool_call_id("call_1"), "call_1");
+        assert_eq!(tool_call_id("call.1"), tool_call_id("call.1"));
+    }
+
+    #[test]
+    fn models_without_tools_get_calls_as_text() {
+        let model = ModelInfo {
+            supports_tools: false,
+            ..unknown_model("ollama", "llama3")
+        };
+        let mut request = request();
+        translate_request(&mut request, &model);
+
+        assert!(request.tools.is_empty());
+        assert_eq!(
+            request.messages[2].content.as_text(),
+            "[Result of read]\nfn main() {}"
+        );
+    }
+}
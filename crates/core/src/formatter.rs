@@ -1,6 +1,8 @@
 use std::path::Path;
 use std::process::Command;
 
+use crate::sandbox::Launcher;
+
 pub struct FormatResult {
     pub file: String,
     pub formatter: String,
@@ -96,7 +98,11 @@ fn which_exists(cmd: &str) -> bool {
         .unwrap_or(false)
 }
 
-pub fn format_file(file_path: &str, project_root: &Path) -> Option<FormatResult> {
+pub fn format_file(
+    file_path: &str,
+    project_root: &Path,
+    launcher: &Launcher,
+) -> Option<FormatResult> {
     let def = detect_formatter(file_path, project_root)?;
 
     let abs_path = if Path::new(file_path).is_absolute() {
@@ -108,9 +114,9 @@ pub fn format_file(file_path: &str, project_root: &Path) -> Option<FormatResult>
     let args_str = def.args_template.replace("{file}", &abs_path);
     let args: Vec<&str> = args_str.split_whitespace().collect();
 
-    let result = Command::new(def.command)
-        .args(&args)
-        .current_dir(project_root)
+    let result = launcher
+        .program(def.command, &args, project_root)
+        .ok()?
         .output();
 
     match result {
@@ -131,8 +137,12 @@ pub fn format_file(file_path: &str, project_root: &Path) -> Option<FormatResult>
     }
 }
 
-pub async fn format_file_async(file_path: String, project_root: std::path::PathBuf) -> Option<FormatResult> {
-    tokio::task::spawn_blocking(move || format_file(&file_path, &project_root))
+pub async fn format_file_async(
+    file_path: String,
+    project_root: std::path::PathBuf,
+    launcher: Launcher,
+) -> Option<FormatResult> {
+    tokio::task::spawn_blocking(move || format_file(&file_path, &project_root, &launcher))
         .await
         .ok()?
 }
@@ -2,7 +2,8 @@ use std::path::Path;
 use std::time::Duration;
 
 use nyzhi_config::{HookConfig, HookEvent, HookType};
-use tokio::process::Command;
+
+use crate::sandbox::Launcher;
 
 pub struct HookResult {
     pub command: String,
@@ -80,6 +81,7 @@ pub async fn run_after_edit_hooks(
     hooks: &[HookConfig],
     changed_file: &str,
     cwd: &Path,
+    launcher: &Launcher,
 ) -> Vec<HookResult> {
     let mut results = Vec::new();
     for hook in hooks {
@@ -92,18 +94,22 @@ pub async fn run_after_edit_hooks(
             }
         }
         let command = hook.command.replace("{file}", changed_file);
-        results.push(run_hook(hook, Some(&command), cwd, None).await);
+        results.push(run_hook(hook, Some(&command), cwd, None, launcher).await);
     }
     results
 }
 
-pub async fn run_after_turn_hooks(hooks: &[HookConfig], cwd: &Path) -> Vec<HookResult> {
+pub async fn run_after_turn_hooks(
+    hooks: &[HookConfig],
+    cwd: &Path,
+    launcher: &Launcher,
+) -> Vec<HookResult> {
     let mut results = Vec::new();
     for hook in hooks {
         if hook.event != HookEvent::AfterTurn {
             continue;
         }
-        results.push(run_hook(hook, None, cwd, None).await);
+        results.push(run_hook(hook, None, cwd, None, launcher).await);
     }
     results
 }
@@ -113,6 +119,7 @@ pub async fn run_hooks_for_event(
     event: HookEvent,
     context: &serde_json::Value,
     cwd: &Path,
+    launcher: &Launcher,
 ) -> Vec<HookResult> {
     let mut results = Vec::new();
     for hook in hooks {
@@ -138,7 +145,7 @@ pub async fn run_hooks_for_event(
             }
         }
         let stdin_json = serde_json::to_string(context).unwrap_or_default();
-        results.push(run_hook(hook, None, cwd, Some(&stdin_json)).await);
+        results.push(run_hook(hook, None, cwd, Some(&stdin_json), launcher).await);
     }
     results
 }
@@ -149,11 +156,12 @@ async fn run_hook(
     command_override: Option<&str>,
     cwd: &Path,
     stdin_data: Option<&str>,
+    launcher: &Launcher,
 ) -> HookResult {
     let fallback_cmd = command_override.unwrap_or(&hook.command).trim();
     match hook.hook_type {
         HookType::Command => {
-            let mut result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data).await;
+            let mut result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data, launcher).await;
             result.hook_type = HookType::Command;
             result
         }
@@ -165,14 +173,14 @@ async fn run_hook(
                 .unwrap_or("");
 
             if prompt_text.is_empty() && !fallback_cmd.is_empty() {
-                let mut result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data).await;
+                let mut result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data, launcher).await;
                 result.hook_type = HookType::Prompt;
                 return result;
             }
 
             let mut output = prompt_text.to_string();
             if !fallback_cmd.is_empty() {
-                let cmd_result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data).await;
+                let cmd_result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data, launcher).await;
                 if !cmd_result.stdout.is_empty() {
                     output.push_str("\n\n");
                     output.push_str(&cmd_result.stdout);
@@ -196,7 +204,7 @@ async fn run_hook(
                 .unwrap_or("");
 
             if instructions.is_empty() && !fallback_cmd.is_empty() {
-                let mut result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data).await;
+                let mut result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data, launcher).await;
                 result.hook_type = HookType::Agent;
                 return result;
             }
@@ -214,7 +222,7 @@ async fn run_hook(
 
             let mut cmd_context = String::new();
             if !fallback_cmd.is_empty() {
-                let cmd_result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data).await;
+                let cmd_result = run_hook_command(fallback_cmd, hook.timeout, cwd, stdin_data, launcher).await;
                 if !cmd_result.stdout.is_empty() {
                     cmd_context = cmd_result.stdout;
                 }
@@ -244,12 +252,14 @@ pub async fn run_pre_tool_hooks(
     tool_name: &str,
     tool_args: &serde_json::Value,
     cwd: &Path,
+    launcher: &Launcher,
 ) -> (Vec<HookResult>, bool) {
     let context = serde_json::json!({
         "tool_name": tool_name,
         "tool_args": tool_args,
     });
-    let results = run_hooks_for_event(hooks, HookEvent::PreToolUse, &context, cwd).await;
+    let results =
+        run_hooks_for_event(hooks, HookEvent::PreToolUse, &context, cwd, launcher).await;
     let blocked = results
         .iter()
         .any(|r| r.exit_code.map(|c| c != 0).unwrap_or(false))
@@ -266,6 +276,7 @@ pub async fn run_post_tool_hooks(
     output: &str,
     success: bool,
     cwd: &Path,
+    launcher: &Launcher,
 ) -> Vec<HookResult> {
     let event = if success {
         HookEvent::PostToolUse
@@ -278,7 +289,7 @@ pub async fn run_post_tool_hooks(
         "output": output,
         "success": success,
     });
-    run_hooks_for_event(hooks, event, &context, cwd).await
+    run_hooks_for_event(hooks, event, &context, cwd, launcher).await
 }
 
 /// Run TeammateIdle hooks. If any hook exits with code 2, returns
@@ -288,13 +299,15 @@ pub async fn run_teammate_idle_hooks(
     teammate_name: &str,
     team_name: &str,
     cwd: &Path,
+    launcher: &Launcher,
 ) -> Option<String> {
     let context = serde_json::json!({
         "hook_event_name": "TeammateIdle",
         "teammate_name": teammate_name,
         "team_name": team_name,
     });
-    let results = run_hooks_for_event(hooks, HookEvent::TeammateIdle, &context, cwd).await;
+    let results =
+        run_hooks_for_event(hooks, HookEvent::TeammateIdle, &context, cwd, launcher).await;
     for r in &results {
         if r.exit_code == Some(2) {
             return Some(r.stderr.clone());
@@ -312,6 +325,7 @@ pub async fn run_task_completed_hooks(
     teammate_name: &str,
     team_name: &str,
     cwd: &Path,
+    launcher: &Launcher,
 ) -> Option<String> {
     let context = serde_json::json!({
         "hook_event_name": "TaskCompleted",
@@ -321,7 +335,8 @@ pub async fn run_task_completed_hooks(
         "teammate_name": teammate_name,
         "team_name": team_name,
     });
-    let results = run_hooks_for_event(hooks, HookEvent::TaskCompleted, &context, cwd).await;
+    let results =
+        run_hooks_for_event(hooks, HookEvent::TaskCompleted, &context, cwd, launcher).await;
     for r in &results {
         if r.exit_code == Some(2) {
             return Some(r.stderr.clone());
@@ -335,12 +350,23 @@ async fn run_hook_command(
     timeout_secs: u64,
     cwd: &Path,
     stdin_data: Option<&str>,
+    launcher: &Launcher,
 ) -> HookResult {
     #![allow(unused_variables)]
-    let mut child = match Command::new("sh")
-        .arg("-c")
-        .arg(command)
-        .current_dir(cwd)
+    let mut command_builder = match launcher.shell(command, cwd) {
+        Ok(c) => c,
+        Err(e) => {
+            return HookResult {
+                command: command.to_string(),
+                stdout: String::new(),
+                stderr: format!("Failed to spawn hook: {e}"),
+                exit_code: None,
+                timed_out: false,
+                hook_type: HookType::Command,
+            };
+        }
+    };
+    let mut child = match command_builder
         .stdin(if stdin_data.is_some() {
             std::process::Stdio::piped()
         } else {
@@ -420,6 +446,14 @@ mod tests {
         }
     }
 
+    fn launcher() -> Launcher {
+        Launcher::new(
+            nyzhi_config::SandboxLevel::FullAccess,
+            Default::default(),
+            Path::new("."),
+        )
+    }
+
     #[test]
     fn pattern_matches_extension() {
         assert!(matches_pattern("*.rs", "src/main.rs"));
@@ -448,7 +482,7 @@ mod tests {
     #[tokio::test]
     async fn prompt_hook_without_command_returns_empty() {
         let hooks = vec![make_hook(HookEvent::AfterTurn, HookType::Prompt, "")];
-        let results = run_after_turn_hooks(&hooks, Path::new(".")).await;
+        let results = run_after_turn_hooks(&hooks, Path::new("."), &launcher()).await;
         assert_eq!(results.len(), 1);
         let r = &results[0];
         assert_eq!(r.hook_type, HookType::Prompt);
@@ -458,7 +492,7 @@ mod tests {
     #[tokio::test]
     async fn agent_hook_without_instructions_errors() {
         let hooks = vec![make_hook(HookEvent::AfterTurn, HookType::Agent, "")];
-        let results = run_after_turn_hooks(&hooks, Path::new(".")).await;
+        let results = run_after_turn_hooks(&hooks, Path::new("."), &launcher()).await;
         assert_eq!(results.len(), 1);
         let r = &results[0];
         assert_eq!(r.hook_type, HookType::Agent);
@@ -469,7 +503,7 @@ mod tests {
     #[tokio::test]
     async fn prompt_hook_with_command_uses_command_fallback() {
         let hooks = vec![make_hook(HookEvent::AfterTurn, HookType::Prompt, "printf ok")];
-        let results = run_after_turn_hooks(&hooks, Path::new(".")).await;
+        let results = run_after_turn_hooks(&hooks, Path::new("."), &launcher()).await;
         assert_eq!(results.len(), 1);
         let r = &results[0];
         assert_eq!(r.hook_type, HookType::Prompt);
@@ -13,7 +13,7 @@ pub struct Ticket {
     pub provider: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TicketUpdate {
     pub status: Option<String>,
     pub comment: Option<String>,
@@ -21,17 +21,6 @@ pub struct TicketUpdate {
     pub labels_remove: Vec<String>,
 }
 
-impl Default for TicketUpdate {
-    fn default() -> Self {
-        Self {
-            status: None,
-            comment: None,
-            labels_add: Vec::new(),
-            labels_remove: Vec::new(),
-        }
-    }
-}
-
 /// Linear API client.
 pub struct LinearClient {
     api_key: String,
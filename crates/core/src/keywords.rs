@@ -2,19 +2,14 @@ use std::collections::HashSet;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum AgentMode {
+    #[default]
     Execute,
     Plan,
     Debug,
 }
 
-impl Default for AgentMode {
-    fn default() -> Self {
-        Self::Execute
-    }
-}
-
 impl std::fmt::Display for AgentMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
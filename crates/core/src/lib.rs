@@ -12,6 +12,7 @@ pub mod context_files;
 pub mod deep_mode;
 pub mod deepinit;
 pub mod diagnostics;
+pub mod fallback;
 pub mod formatter;
 pub mod git_undo;
 pub mod hooks;
@@ -27,8 +28,10 @@ pub mod memory;
 pub mod notepad;
 pub mod notify;
 pub mod persistence;
+pub mod persistent_shell;
 pub mod planning;
 pub mod plugins;
+pub mod processes;
 pub mod prompt;
 pub mod replay;
 pub mod routing;
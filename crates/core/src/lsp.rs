@@ -1,11 +1,23 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, Command};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex, Notify};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// JSON-RPC error code servers return while a document is being re-analyzed.
+const CONTENT_MODIFIED: i64 = -32801;
+const MAX_ATTEMPTS: u32 = 3;
+/// How long a server that failed to start is left alone before retrying.
+const FAILED_RETRY_AFTER: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LspPosition {
@@ -25,12 +37,22 @@ pub struct LspLocation {
     pub range: LspRange,
 }
 
+impl LspLocation {
+    pub fn path(&self) -> Option<PathBuf> {
+        uri_to_path(&self.uri)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LspDiagnostic {
     pub range: LspRange,
     pub severity: Option<u32>,
     pub message: String,
     pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,24 +62,173 @@ pub struct LspSymbol {
     pub range: LspRange,
 }
 
+/// A symbol returned by `workspace/symbol`.
+#[derive(Debug, Clone)]
+pub struct LspWorkspaceSymbol {
+    pub name: String,
+    pub kind: u32,
+    pub container_name: Option<String>,
+    pub location: LspLocation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LspTextEdit {
+    pub range: LspRange,
+    #[serde(rename = "newText")]
+    pub new_text: String,
+}
+
+/// One step of a `WorkspaceEdit`, in the order the server wants them applied.
+#[derive(Debug, Clone)]
+pub enum EditOperation {
+    Edit {
+        path: PathBuf,
+        edits: Vec<LspTextEdit>,
+    },
+    Create {
+        path: PathBuf,
+        overwrite: bool,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        overwrite: bool,
+    },
+    Delete {
+        path: PathBuf,
+    },
+}
+
+/// Error object from a JSON-RPC response.
+#[derive(Debug, Clone)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+type PendingMap =
+    std::sync::Mutex<HashMap<i64, oneshot::Sender<std::result::Result<Value, ResponseError>>>>;
+
+/// Diagnostics per document URI, tagged with a generation counter so callers
+/// can wait for a publish that happened after their own change.
+type DiagnosticsMap = std::sync::Mutex<HashMap<String, (u64, Vec<LspDiagnostic>)>>;
+
+/// State shared between an `LspClient` and its stdout reader task.
+#[derive(Default)]
+struct ServerState {
+    pending: PendingMap,
+    diagnostics: DiagnosticsMap,
+    diagnostics_changed: Notify,
+    progress: std::sync::Mutex<HashSet<String>>,
+    progress_changed: Notify,
+    /// Edits the server pushed via `workspace/applyEdit` (e.g. while running a command).
+    server_edits: std::sync::Mutex<Vec<Value>>,
+}
+
+/// Everything needed to launch a language server for a family of files.
+#[derive(Debug, Clone, Copy)]
+pub struct ServerSpec {
+    pub language: &'static str,
+    pub command: &'static str,
+    pub args: &'static [&'static str],
+    /// Files that mark a workspace root for this language.
+    pub root_markers: &'static [&'static str],
+    /// Use the outermost marker instead of the nearest one (e.g. cargo workspaces).
+    pub outermost_root: bool,
+}
+
+const SERVERS: &[ServerSpec] = &[
+    ServerSpec {
+        language: "rust",
+        command: "rust-analyzer",
+        args: &[],
+        root_markers: &["Cargo.toml"],
+        outermost_root: true,
+    },
+    ServerSpec {
+        language: "typescript",
+        command: "typescript-language-server",
+        args: &["--stdio"],
+        root_markers: &["tsconfig.json", "jsconfig.json", "package.json"],
+        outermost_root: false,
+    },
+    ServerSpec {
+        language: "python",
+        command: "pylsp",
+        args: &[],
+        root_markers: &[
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "requirements.txt",
+        ],
+        outermost_root: false,
+    },
+    ServerSpec {
+        language: "go",
+        command: "gopls",
+        args: &["serve"],
+        root_markers: &["go.work", "go.mod"],
+        outermost_root: true,
+    },
+];
+
 pub struct LspClient {
-    child: Child,
-    next_id: i64,
+    child: Mutex<Child>,
+    stdin: Arc<Mutex<ChildStdin>>,
+    next_id: AtomicI64,
+    state: Arc<ServerState>,
+    /// Open documents: URI -> (version, last text sent).
+    documents: Mutex<HashMap<String, (i32, String)>>,
+    pub language: &'static str,
+    pub root: PathBuf,
+}
+
+fn detect_server_command(file_ext: &str) -> Option<ServerSpec> {
+    let language = match file_ext {
+        "rs" => "rust",
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => "typescript",
+        "py" => "python",
+        "go" => "go",
+        _ => return None,
+    };
+    SERVERS.iter().find(|s| s.language == language).copied()
 }
 
-fn detect_server_command(file_ext: &str) -> Option<(&'static str, Vec<&'static str>)> {
+fn language_id(file_ext: &str) -> &'static str {
     match file_ext {
-        "rs" => Some(("rust-analyzer", vec![])),
-        "ts" | "tsx" | "js" | "jsx" => Some(("typescript-language-server", vec!["--stdio"])),
-        "py" => Some(("pylsp", vec![])),
-        "go" => Some(("gopls", vec!["serve"])),
-        _ => None,
+        "rs" => "rust",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "jsx" => "javascriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "py" => "python",
+        "go" => "go",
+        _ => "plaintext",
     }
 }
 
 impl LspClient {
     pub async fn start(server_cmd: &str, args: &[&str], cwd: &Path) -> Result<Self> {
-        let child = Command::new(server_cmd)
+        Self::spawn(server_cmd, args, cwd, "unknown").await
+    }
+
+    pub async fn start_for_file(file_path: &Path, cwd: &Path) -> Result<Self> {
+        let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+        let spec = detect_server_command(ext)
+            .ok_or_else(|| anyhow::anyhow!("No LSP server known for .{ext} files"))?;
+
+        Self::spawn(spec.command, spec.args, cwd, spec.language).await
+    }
+
+    async fn spawn(
+        server_cmd: &str,
+        args: &[&str],
+        cwd: &Path,
+        language: &'static str,
+    ) -> Result<Self> {
+        let mut child = Command::new(server_cmd)
             .args(args)
             .current_dir(cwd)
             .stdin(Stdio::piped())
@@ -67,74 +238,938 @@ impl LspClient {
             .spawn()
             .context(format!("Failed to start LSP server: {server_cmd}"))?;
 
-        Ok(Self { child, next_id: 1 })
+        let stdin = Arc::new(Mutex::new(child.stdin.take().context("No stdin")?));
+        let stdout = child.stdout.take().context("No stdout")?;
+        let state = Arc::new(ServerState::default());
+        spawn_reader(stdout, stdin.clone(), state.clone());
+
+        Ok(Self {
+            child: Mutex::new(child),
+            stdin,
+            next_id: AtomicI64::new(1),
+            state,
+            documents: Mutex::new(HashMap::new()),
+            language,
+            root: cwd.to_path_buf(),
+        })
     }
 
-    pub async fn start_for_file(file_path: &Path, cwd: &Path) -> Result<Self> {
-        let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    /// Whether the server process is still running.
+    pub async fn is_alive(&self) -> bool {
+        matches!(self.child.lock().await.try_wait(), Ok(None))
+    }
 
-        let (cmd, args) = detect_server_command(ext)
-            .ok_or_else(|| anyhow::anyhow!("No LSP server known for .{ext} files"))?;
+    async fn send_request(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<std::result::Result<Value, ResponseError>> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.state
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, tx);
 
-        Self::start(cmd, &args, cwd).await
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        if let Err(e) = write_message(&self.stdin, &msg).await {
+            self.forget(id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => anyhow::bail!("LSP server exited before answering {method}"),
+            Err(_) => {
+                self.forget(id);
+                anyhow::bail!("LSP request {method} timed out after {REQUEST_TIMEOUT:?}")
+            }
+        }
     }
 
-    async fn send_request(&mut self, method: &str, params: Value) -> Result<Value> {
-        let id = self.next_id;
-        self.next_id += 1;
+    fn forget(&self, id: i64) {
+        self.state
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+    }
 
+    /// Send a request, retrying while the server reports the content as modified.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.send_request(method, params.clone()).await? {
+                Ok(value) => return Ok(value),
+                Err(e) if e.code == CONTENT_MODIFIED && attempt < MAX_ATTEMPTS => {
+                    tokio::time::sleep(Duration::from_millis(300 * attempt as u64)).await;
+                }
+                Err(e) => anyhow::bail!("{method} failed: {} (code {})", e.message, e.code),
+            }
+        }
+    }
+
+    pub async fn notify(&self, method: &str, params: Value) -> Result<()> {
         let msg = json!({
             "jsonrpc": "2.0",
-            "id": id,
             "method": method,
             "params": params,
         });
+        write_message(&self.stdin, &msg).await
+    }
+
+    pub async fn initialize(&self, root_uri: &str) -> Result<Value> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "rootUri": root_uri,
+                    "workspaceFolders": [{ "uri": root_uri, "name": "root" }],
+                    "capabilities": client_capabilities(),
+                }),
+            )
+            .await?;
+        self.notify("initialized", json!({})).await?;
+        Ok(result)
+    }
+
+    pub async fn shutdown(&self) -> Result<()> {
+        let _ = self.request("shutdown", Value::Null).await;
+        let _ = self.notify("exit", Value::Null).await;
+        Ok(())
+    }
+
+    /// Wait until the server has no work-done progress in flight (indexing,
+    /// loading the workspace, ...). Gives up silently after `IDLE_TIMEOUT`.
+    pub async fn wait_until_idle(&self) {
+        let _ = tokio::time::timeout(IDLE_TIMEOUT, async {
+            loop {
+                let notified = self.state.progress_changed.notified();
+                if self
+                    .state
+                    .progress
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .is_empty()
+                {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await;
+    }
+
+    /// Open the document or, if it is already open, push its current contents.
+    /// Returns the document URI.
+    pub async fn sync_document(&self, path: &Path) -> Result<String> {
+        let uri = path_to_uri(path)?;
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let mut docs = self.documents.lock().await;
+        match docs.get_mut(&uri) {
+            Some((_, last)) if *last == text => {}
+            Some((version, last)) => {
+                *version += 1;
+                self.notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": { "uri": uri, "version": *version },
+                        "contentChanges": [{ "text": text }],
+                    }),
+                )
+                .await?;
+                *last = text;
+            }
+            None => {
+                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                self.notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language_id(ext),
+                            "version": 1,
+                            "text": text,
+                        }
+                    }),
+                )
+                .await?;
+                docs.insert(uri.clone(), (1, text));
+            }
+        }
+        Ok(uri)
+    }
+
+    async fn position_request(
+        &self,
+        method: &str,
+        path: &Path,
+        position: &LspPosition,
+        extra: Value,
+    ) -> Result<Value> {
+        let uri = self.sync_document(path).await?;
+        self.wait_until_idle().await;
+        let mut params = json!({
+            "textDocument": { "uri": uri },
+            "position": position,
+        });
+        if let (Some(obj), Value::Object(extra)) = (params.as_object_mut(), extra) {
+            obj.extend(extra);
+        }
+        self.request(method, params).await
+    }
 
-        let content = serde_json::to_string(&msg)?;
-        let header = format!("Content-Length: {}\r\n\r\n", content.len());
+    pub async fn definition(
+        &self,
+        path: &Path,
+        position: &LspPosition,
+    ) -> Result<Vec<LspLocation>> {
+        let result = self
+            .position_request("textDocument/definition", path, position, Value::Null)
+            .await?;
+        Ok(parse_locations(&result))
+    }
 
-        let stdin = self.child.stdin.as_mut().context("No stdin")?;
-        stdin.write_all(header.as_bytes()).await?;
-        stdin.write_all(content.as_bytes()).await?;
-        stdin.flush().await?;
+    pub async fn references(
+        &self,
+        path: &Path,
+        position: &LspPosition,
+        include_declaration: bool,
+    ) -> Result<Vec<LspLocation>> {
+        let result = self
+            .position_request(
+                "textDocument/references",
+                path,
+                position,
+                json!({ "context": { "includeDeclaration": include_declaration } }),
+            )
+            .await?;
+        Ok(parse_locations(&result))
+    }
 
-        let stdout = self.child.stdout.as_mut().context("No stdout")?;
-        let mut reader = BufReader::new(stdout);
+    pub async fn hover(&self, path: &Path, position: &LspPosition) -> Result<Option<String>> {
+        let result = self
+            .position_request("textDocument/hover", path, position, Value::Null)
+            .await?;
+        Ok(result.get("contents").and_then(hover_text))
+    }
 
-        let mut header_line = String::new();
-        reader.read_line(&mut header_line).await?;
-        let content_length: usize = header_line
-            .trim()
-            .strip_prefix("Content-Length: ")
-            .unwrap_or("0")
-            .parse()
-            .unwrap_or(0);
+    /// Ask the server for a `WorkspaceEdit` renaming the symbol at `position`.
+    /// Returns `None` when the server has nothing to rename there.
+    pub async fn rename(
+        &self,
+        path: &Path,
+        position: &LspPosition,
+        new_name: &str,
+    ) -> Result<Option<Value>> {
+        let result = self
+            .position_request(
+                "textDocument/rename",
+                path,
+                position,
+                json!({ "newName": new_name }),
+            )
+            .await?;
+        Ok((!result.is_null()).then_some(result))
+    }
+
+    /// Code actions (or bare commands) available for `range`, optionally
+    /// restricted to the given kinds (`quickfix`, `refactor.extract`, ...).
+    pub async fn code_actions(
+        &self,
+        path: &Path,
+        range: &LspRange,
+        only: Option<&[String]>,
+    ) -> Result<Vec<Value>> {
+        let uri = self.sync_document(path).await?;
+        self.wait_until_idle().await;
 
-        let mut empty_line = String::new();
-        reader.read_line(&mut empty_line).await?;
+        let diagnostics: Vec<LspDiagnostic> = self
+            .state
+            .diagnostics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&uri)
+            .map(|(_, d)| {
+                d.iter()
+                    .filter(|d| {
+                        d.range.start.line <= range.end.line && d.range.end.line >= range.start.line
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
 
-        let mut body = vec![0u8; content_length];
-        tokio::io::AsyncReadExt::read_exact(&mut reader, &mut body).await?;
+        let mut context = json!({ "diagnostics": diagnostics });
+        if let Some(only) = only {
+            context["only"] = json!(only);
+        }
+        let result = self
+            .request(
+                "textDocument/codeAction",
+                json!({
+                    "textDocument": { "uri": uri },
+                    "range": range,
+                    "context": context,
+                }),
+            )
+            .await?;
+        Ok(result.as_array().cloned().unwrap_or_default())
+    }
 
-        let response: Value = serde_json::from_slice(&body)?;
-        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    /// Fill in the `edit` of a lazily-resolved code action.
+    pub async fn resolve_code_action(&self, action: &Value) -> Result<Value> {
+        if action.get("edit").is_some() || action.get("data").is_none() {
+            return Ok(action.clone());
+        }
+        self.request("codeAction/resolve", action.clone()).await
     }
 
-    pub async fn initialize(&mut self, root_uri: &str) -> Result<Value> {
-        self.send_request(
-            "initialize",
+    /// Run a server command and return any edits it pushed back through
+    /// `workspace/applyEdit` while executing.
+    pub async fn execute_command(&self, command: &Value) -> Result<Vec<Value>> {
+        self.take_server_edits();
+        self.request(
+            "workspace/executeCommand",
             json!({
-                "processId": std::process::id(),
-                "rootUri": root_uri,
-                "capabilities": {},
+                "command": command["command"],
+                "arguments": command.get("arguments").cloned().unwrap_or(json!([])),
             }),
         )
-        .await
+        .await?;
+        Ok(self.take_server_edits())
     }
 
-    pub async fn shutdown(&mut self) -> Result<()> {
-        let _ = self.send_request("shutdown", Value::Null).await;
-        Ok(())
+    fn take_server_edits(&self) -> Vec<Value> {
+        std::mem::take(
+            &mut *self
+                .state
+                .server_edits
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+        )
+    }
+
+    pub async fn workspace_symbols(&self, query: &str) -> Result<Vec<LspWorkspaceSymbol>> {
+        self.wait_until_idle().await;
+        let result = self
+            .request("workspace/symbol", json!({ "query": query }))
+            .await?;
+        Ok(parse_workspace_symbols(&result))
+    }
+
+    /// Diagnostics for a file, waiting up to `wait` for the server to publish
+    /// a fresh set after the document has been synced.
+    pub async fn diagnostics(&self, path: &Path, wait: Duration) -> Result<Vec<LspDiagnostic>> {
+        let uri = path_to_uri(path)?;
+        let generation = self.diagnostics_generation(&uri);
+        self.sync_document(path).await?;
+
+        let _ = tokio::time::timeout(wait, async {
+            loop {
+                let notified = self.state.diagnostics_changed.notified();
+                if self.diagnostics_generation(&uri) > generation {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await;
+
+        Ok(self
+            .state
+            .diagnostics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&uri)
+            .map(|(_, d)| d.clone())
+            .unwrap_or_default())
+    }
+
+    fn diagnostics_generation(&self, uri: &str) -> u64 {
+        self.state
+            .diagnostics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(uri)
+            .map(|(g, _)| *g)
+            .unwrap_or(0)
+    }
+}
+
+fn client_capabilities() -> Value {
+    json!({
+        "textDocument": {
+            "synchronization": { "didSave": true, "dynamicRegistration": false },
+            "definition": { "linkSupport": true },
+            "references": {},
+            "hover": { "contentFormat": ["markdown", "plaintext"] },
+            "publishDiagnostics": { "relatedInformation": false },
+            "rename": { "prepareSupport": true },
+            "codeAction": {
+                "codeActionLiteralSupport": {
+                    "codeActionKind": {
+                        "valueSet": [
+                            "", "quickfix", "refactor", "refactor.extract", "refactor.inline",
+                            "refactor.rewrite", "source", "source.organizeImports",
+                        ]
+                    }
+                },
+                "dataSupport": true,
+                "resolveSupport": { "properties": ["edit"] },
+            },
+        },
+        "window": { "workDoneProgress": true },
+        "workspace": {
+            "configuration": true,
+            "workspaceFolders": true,
+            "applyEdit": true,
+            "symbol": {},
+            "executeCommand": {},
+            "workspaceEdit": {
+                "documentChanges": true,
+                "resourceOperations": ["create", "rename", "delete"],
+            },
+        },
+        "general": { "positionEncodings": ["utf-16"] },
+    })
+}
+
+async fn write_message(stdin: &Mutex<ChildStdin>, msg: &Value) -> Result<()> {
+    let content = serde_json::to_string(msg)?;
+    let header = format!("Content-Length: {}\r\n\r\n", content.len());
+
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(header.as_bytes()).await?;
+    stdin.write_all(content.as_bytes()).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+/// Read one `Content-Length` framed JSON-RPC message. Returns `None` on EOF.
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>> {
+    let mut content_length: Option<usize> = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0u8; content_length.unwrap_or(0)];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn spawn_reader(stdout: ChildStdout, stdin: Arc<Mutex<ChildStdin>>, state: Arc<ServerState>) {
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            let msg = match read_message(&mut reader).await {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::debug!("LSP reader stopped: {e}");
+                    break;
+                }
+            };
+            dispatch_message(msg, &stdin, &state).await;
+        }
+        // Wake anyone still waiting; dropping the senders fails their requests.
+        state
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        state
+            .progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        state.progress_changed.notify_waiters();
+    });
+}
+
+async fn dispatch_message(msg: Value, stdin: &Mutex<ChildStdin>, state: &ServerState) {
+    let method = msg.get("method").and_then(|m| m.as_str());
+    let id = msg.get("id").cloned();
+
+    match (method, id) {
+        // Server -> client request: answer so the server doesn't stall.
+        (Some(method), Some(id)) => {
+            let result = match method {
+                "workspace/configuration" => {
+                    let items = msg["params"]["items"].as_array().map_or(0, |a| a.len());
+                    Value::Array(vec![Value::Null; items])
+                }
+                "workspace/applyEdit" => {
+                    state
+                        .server_edits
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push(msg["params"]["edit"].clone());
+                    json!({ "applied": true })
+                }
+                _ => Value::Null,
+            };
+            let reply = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            if let Err(e) = write_message(stdin, &reply).await {
+                tracing::debug!("Failed to answer LSP request {method}: {e}");
+            }
+        }
+        (Some("textDocument/publishDiagnostics"), None) => {
+            let params = &msg["params"];
+            let Some(uri) = params["uri"].as_str() else {
+                return;
+            };
+            let diags: Vec<LspDiagnostic> =
+                serde_json::from_value(params["diagnostics"].clone()).unwrap_or_default();
+            {
+                let mut map = state.diagnostics.lock().unwrap_or_else(|e| e.into_inner());
+                let entry = map.entry(uri.to_string()).or_insert((0, Vec::new()));
+                entry.0 += 1;
+                entry.1 = diags;
+            }
+            state.diagnostics_changed.notify_waiters();
+        }
+        (Some("$/progress"), None) => {
+            let token = match &msg["params"]["token"] {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            let kind = msg["params"]["value"]["kind"].as_str().unwrap_or("");
+            {
+                let mut progress = state.progress.lock().unwrap_or_else(|e| e.into_inner());
+                match kind {
+                    "begin" => {
+                        progress.insert(token);
+                    }
+                    "end" => {
+                        progress.remove(&token);
+                    }
+                    _ => {}
+                }
+            }
+            state.progress_changed.notify_waiters();
+        }
+        (Some(_), None) => {}
+        (None, Some(id)) => {
+            let Some(id) = id.as_i64() else {
+                return;
+            };
+            let tx = state
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&id);
+            if let Some(tx) = tx {
+                let result = match msg.get("error") {
+                    Some(err) => Err(ResponseError {
+                        code: err["code"].as_i64().unwrap_or(0),
+                        message: err["message"]
+                            .as_str()
+                            .unwrap_or("unknown error")
+                            .to_string(),
+                    }),
+                    None => Ok(msg.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                let _ = tx.send(result);
+            }
+        }
+        (None, None) => {}
+    }
+}
+
+/// Parse the `Location | Location[] | LocationLink[] | null` shapes servers return.
+pub fn parse_locations(value: &Value) -> Vec<LspLocation> {
+    let items: Vec<&Value> = match value {
+        Value::Array(arr) => arr.iter().collect(),
+        Value::Object(_) => vec![value],
+        _ => Vec::new(),
+    };
+    items
+        .into_iter()
+        .filter_map(|item| {
+            if let Some(target) = item.get("targetUri") {
+                let range = item
+                    .get("targetSelectionRange")
+                    .or_else(|| item.get("targetRange"))?;
+                Some(LspLocation {
+                    uri: target.as_str()?.to_string(),
+                    range: serde_json::from_value(range.clone()).ok()?,
+                })
+            } else {
+                serde_json::from_value(item.clone()).ok()
+            }
+        })
+        .collect()
+}
+
+fn parse_workspace_symbols(value: &Value) -> Vec<LspWorkspaceSymbol> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let location = item.get("location")?;
+                    // WorkspaceSymbol may omit the range until resolved.
+                    let range = location.get("range").cloned().unwrap_or_else(|| {
+                        json!({"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 0}})
+                    });
+                    Some(LspWorkspaceSymbol {
+                        name: item.get("name")?.as_str()?.to_string(),
+                        kind: item.get("kind").and_then(|k| k.as_u64()).unwrap_or(0) as u32,
+                        container_name: item
+                            .get("containerName")
+                            .and_then(|c| c.as_str())
+                            .filter(|c| !c.is_empty())
+                            .map(String::from),
+                        location: LspLocation {
+                            uri: location.get("uri")?.as_str()?.to_string(),
+                            range: serde_json::from_value(range).ok()?,
+                        },
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Flatten a `WorkspaceEdit` (`changes` map or `documentChanges` array with
+/// resource operations) into an ordered list of operations.
+pub fn parse_workspace_edit(edit: &Value) -> Result<Vec<EditOperation>> {
+    let mut ops = Vec::new();
+
+    if let Some(changes) = edit.get("documentChanges").and_then(|c| c.as_array()) {
+        for change in changes {
+            let kind = change.get("kind").and_then(|k| k.as_str());
+            let overwrite = change["options"]["overwrite"].as_bool().unwrap_or(false);
+            let op = match kind {
+                Some("create") => EditOperation::Create {
+                    path: uri_field(change, "uri")?,
+                    overwrite,
+                },
+                Some("rename") => EditOperation::Rename {
+                    from: uri_field(change, "oldUri")?,
+                    to: uri_field(change, "newUri")?,
+                    overwrite,
+                },
+                Some("delete") => EditOperation::Delete {
+                    path: uri_field(change, "uri")?,
+                },
+                Some(other) => anyhow::bail!("Unsupported resource operation: {other}"),
+                None => EditOperation::Edit {
+                    path: uri_field(&change["textDocument"], "uri")?,
+                    edits: serde_json::from_value(change["edits"].clone())
+                        .context("Malformed text edits")?,
+                },
+            };
+            ops.push(op);
+        }
+    } else if let Some(changes) = edit.get("changes").and_then(|c| c.as_object()) {
+        for (uri, edits) in changes {
+            ops.push(EditOperation::Edit {
+                path: uri_to_path(uri).ok_or_else(|| anyhow::anyhow!("Not a file URI: {uri}"))?,
+                edits: serde_json::from_value(edits.clone()).context("Malformed text edits")?,
+            });
+        }
+    }
+
+    Ok(ops)
+}
+
+fn uri_field(value: &Value, field: &str) -> Result<PathBuf> {
+    let uri = value
+        .get(field)
+        .and_then(|u| u.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing {field} in workspace edit"))?;
+    uri_to_path(uri).ok_or_else(|| anyhow::anyhow!("Not a file URI: {uri}"))
+}
+
+/// Apply LSP text edits to `text`. Edits are interpreted against the original
+/// text; edits sharing a start position are applied in array order.
+pub fn apply_text_edits(text: &str, edits: &[LspTextEdit]) -> Result<String> {
+    let mut line_starts = vec![0];
+    line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+
+    let offset = |pos: &LspPosition| -> usize {
+        let Some(&start) = line_starts.get(pos.line as usize) else {
+            return text.len();
+        };
+        let line = text[start..].split('\n').next().unwrap_or("");
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut units = 0u32;
+        for (i, c) in line.char_indices() {
+            if units >= pos.character {
+                return start + i;
+            }
+            units += c.len_utf16() as u32;
+        }
+        start + line.len()
+    };
+
+    let mut spans: Vec<(usize, usize, usize)> = edits
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (offset(&e.range.start), offset(&e.range.end), i))
+        .collect();
+    spans.sort_by_key(|&(start, end, i)| (start, end, i));
+
+    for pair in spans.windows(2) {
+        if pair[1].0 < pair[0].1 {
+            anyhow::bail!("Overlapping text edits");
+        }
+    }
+
+    let mut out = text.to_string();
+    for &(start, end, i) in spans.iter().rev() {
+        if start > end {
+            anyhow::bail!("Text edit range ends before it starts");
+        }
+        out.replace_range(start..end, &edits[i].new_text);
+    }
+    Ok(out)
+}
+
+/// Flatten hover `contents` (MarkupContent, MarkedString or MarkedString[]) to text.
+pub fn hover_text(contents: &Value) -> Option<String> {
+    let text = match contents {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(hover_text)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(obj) => {
+            let value = obj.get("value")?.as_str()?;
+            match obj.get("language").and_then(|l| l.as_str()) {
+                Some(lang) => format!("```{lang}\n{value}\n```"),
+                None => value.to_string(),
+            }
+        }
+        _ => return None,
+    };
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+pub fn path_to_uri(path: &Path) -> Result<String> {
+    url::Url::from_file_path(path)
+        .map(|u| u.to_string())
+        .map_err(|_| anyhow::anyhow!("Not an absolute path: {}", path.display()))
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    url::Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+/// Convert a 0-based character column into the UTF-16 offset LSP expects.
+pub fn utf16_column(line: &str, char_col: usize) -> u32 {
+    line.chars()
+        .take(char_col)
+        .map(|c| c.len_utf16() as u32)
+        .sum()
+}
+
+/// Convert an LSP UTF-16 offset back into a 0-based character column.
+pub fn char_column(line: &str, utf16_col: u32) -> usize {
+    let mut units = 0u32;
+    for (i, c) in line.chars().enumerate() {
+        if units >= utf16_col {
+            return i;
+        }
+        units += c.len_utf16() as u32;
+    }
+    line.chars().count()
+}
+
+/// Pick the workspace root a server should be started in for `file`.
+/// Searches upward from the file, staying inside `project_root` when the
+/// file lives there, and falls back to `project_root` when no marker exists.
+pub fn find_workspace_root(spec: &ServerSpec, file: &Path, project_root: &Path) -> PathBuf {
+    let inside_project = file.starts_with(project_root);
+    let mut found: Option<PathBuf> = None;
+    let mut dir = file.parent();
+    while let Some(d) = dir {
+        if inside_project && !d.starts_with(project_root) {
+            break;
+        }
+        if spec.root_markers.iter().any(|m| d.join(m).exists()) {
+            found = Some(d.to_path_buf());
+            if !spec.outermost_root {
+                break;
+            }
+        }
+        dir = d.parent();
+    }
+    found.unwrap_or_else(|| {
+        if inside_project {
+            project_root.to_path_buf()
+        } else {
+            file.parent().unwrap_or(project_root).to_path_buf()
+        }
+    })
+}
+
+fn server_installed(cmd: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| dir.join(cmd).is_file())
+}
+
+/// A server's language and workspace root.
+type ServerKey = (&'static str, PathBuf);
+
+/// Long-lived language servers, one per (language, workspace root).
+pub struct LspPool {
+    clients: Mutex<HashMap<ServerKey, Arc<LspClient>>>,
+    /// Held while a server starts, so it is started once while requests for
+    /// other servers go on.
+    starting: Mutex<HashMap<ServerKey, Arc<Mutex<()>>>>,
+    /// Servers that failed to start and when, so we don't respawn them on
+    /// every call.
+    failed: Mutex<HashMap<ServerKey, (String, std::time::Instant)>>,
+}
+
+pub type LspPoolHandle = Arc<LspPool>;
+
+pub fn shared_pool() -> LspPoolHandle {
+    Arc::new(LspPool::new())
+}
+
+impl LspPool {
+    pub fn new() -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            starting: Mutex::new(HashMap::new()),
+            failed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get (or start and initialize) the server responsible for `file`.
+    /// Returns `Ok(None)` when no server is known or installed for the file type.
+    pub async fn client_for(
+        &self,
+        file: &Path,
+        project_root: &Path,
+    ) -> Result<Option<Arc<LspClient>>> {
+        let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let Some(spec) = detect_server_command(ext) else {
+            return Ok(None);
+        };
+        if !server_installed(spec.command) {
+            return Ok(None);
+        }
+
+        let root = find_workspace_root(&spec, file, project_root);
+        let key = (spec.language, root.clone());
+        if let Some(client) = self.running_client(&key).await {
+            return Ok(Some(client));
+        }
+
+        let gate = self
+            .starting
+            .lock()
+            .await
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let _starting = gate.lock().await;
+        // Another call may have started it, or failed to, while we waited.
+        if let Some(client) = self.running_client(&key).await {
+            return Ok(Some(client));
+        }
+        {
+            let mut failed = self.failed.lock().await;
+            match failed.get(&key) {
+                Some((err, at)) if at.elapsed() < FAILED_RETRY_AFTER => anyhow::bail!("{err}"),
+                Some(_) => {
+                    failed.remove(&key);
+                }
+                None => {}
+            }
+        }
+
+        let started = async {
+            let client = LspClient::spawn(spec.command, spec.args, &root, spec.language).await?;
+            client
+                .initialize(&path_to_uri(&root)?)
+                .await
+                .with_context(|| format!("Failed to initialize {}", spec.command))?;
+            anyhow::Ok(client)
+        }
+        .await;
+        let client = match started {
+            Ok(client) => Arc::new(client),
+            Err(e) => {
+                self.failed
+                    .lock()
+                    .await
+                    .insert(key, (format!("{e:#}"), std::time::Instant::now()));
+                return Err(e);
+            }
+        };
+        self.clients.lock().await.insert(key, client.clone());
+        Ok(Some(client))
+    }
+
+    /// The server for `key` if it is running; forgets it if it exited.
+    async fn running_client(&self, key: &ServerKey) -> Option<Arc<LspClient>> {
+        let mut clients = self.clients.lock().await;
+        let client = clients.get(key)?.clone();
+        if client.is_alive().await {
+            return Some(client);
+        }
+        clients.remove(key);
+        None
+    }
+
+    /// (language, root) pairs of servers currently running.
+    pub async fn running(&self) -> Vec<(String, PathBuf)> {
+        self.clients
+            .lock()
+            .await
+            .keys()
+            .map(|(lang, root)| (lang.to_string(), root.clone()))
+            .collect()
+    }
+
+    pub async fn shutdown_all(&self) {
+        let clients: Vec<_> = self.clients.lock().await.drain().map(|(_, c)| c).collect();
+        for client in clients {
+            let _ = client.shutdown().await;
+        }
+    }
+}
+
+impl Default for LspPool {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -158,23 +1193,151 @@ pub fn symbol_kind_name(kind: u32) -> &'static str {
 }
 
 pub fn detect_available_servers() -> HashMap<String, String> {
-    let servers = [
-        ("rust", "rust-analyzer"),
-        ("typescript", "typescript-language-server"),
-        ("python", "pylsp"),
-        ("go", "gopls"),
-    ];
-
     let mut available = HashMap::new();
-    for (lang, cmd) in &servers {
-        if std::process::Command::new("which")
-            .arg(cmd)
-            .output()
-            .map(|o| o.status.success())
-            .unwrap_or(false)
-        {
-            available.insert(lang.to_string(), cmd.to_string());
+    for spec in SERVERS {
+        if server_installed(spec.command) {
+            available.insert(spec.language.to_string(), spec.command.to_string());
         }
     }
     available
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_message_parses_framed_bodies() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+        let raw = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{body}",
+            body.len()
+        );
+        let mut reader = raw.as_bytes();
+        let msg = read_message(&mut reader).await.unwrap().unwrap();
+        assert_eq!(msg["id"], 1);
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_locations_handles_links_and_single_location() {
+        let range =
+            json!({"start": {"line": 3, "character": 4}, "end": {"line": 3, "character": 9}});
+        let single = json!({"uri": "file:///a.rs", "range": range});
+        assert_eq!(parse_locations(&single).len(), 1);
+
+        let links = json!([{
+            "targetUri": "file:///b.rs",
+            "targetRange": range,
+            "targetSelectionRange": range,
+        }]);
+        let locs = parse_locations(&links);
+        assert_eq!(locs[0].uri, "file:///b.rs");
+        assert_eq!(locs[0].range.start.line, 3);
+
+        assert!(parse_locations(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn hover_text_flattens_marked_strings() {
+        let markup = json!({"kind": "markdown", "value": "```rust\nfn new()\n```"});
+        assert_eq!(hover_text(&markup).unwrap(), "```rust\nfn new()\n```");
+
+        let marked = json!([{"language": "rust", "value": "struct Config"}, "docs"]);
+        assert_eq!(
+            hover_text(&marked).unwrap(),
+            "```rust\nstruct Config\n```\n\ndocs"
+        );
+        assert!(hover_text(&json!("")).is_none());
+    }
+
+    fn edit(sl: u32, sc: u32, el: u32, ec: u32, text: &str) -> LspTextEdit {
+        LspTextEdit {
+            range: LspRange {
+                start: LspPosition {
+                    line: sl,
+                    character: sc,
+                },
+                end: LspPosition {
+                    line: el,
+                    character: ec,
+                },
+            },
+            new_text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn apply_text_edits_uses_original_positions() {
+        let text = "fn foo() {}\nfn bar() { foo() }\n";
+        let edits = vec![edit(0, 3, 0, 6, "renamed"), edit(1, 11, 1, 14, "renamed")];
+        assert_eq!(
+            apply_text_edits(text, &edits).unwrap(),
+            "fn renamed() {}\nfn bar() { renamed() }\n"
+        );
+
+        let inserts = vec![edit(0, 0, 0, 0, "a"), edit(0, 0, 0, 0, "b")];
+        assert_eq!(apply_text_edits("x", &inserts).unwrap(), "abx");
+
+        let overlapping = vec![edit(0, 0, 0, 3, ""), edit(0, 2, 0, 4, "")];
+        assert!(apply_text_edits("abcdef", &overlapping).is_err());
+    }
+
+    #[test]
+    fn apply_text_edits_counts_utf16_units() {
+        let text = "let 🦀 = x;";
+        let edits = vec![edit(0, 9, 0, 10, "y")];
+        assert_eq!(apply_text_edits(text, &edits).unwrap(), "let 🦀 = y;");
+    }
+
+    #[test]
+    fn parse_workspace_edit_reads_changes_and_resource_ops() {
+        let range =
+            json!({"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 1}});
+        let changes = json!({ "changes": { "file:///a.rs": [{"range": range, "newText": "x"}] } });
+        let ops = parse_workspace_edit(&changes).unwrap();
+        assert!(
+            matches!(&ops[0], EditOperation::Edit { path, edits } if path == Path::new("/a.rs") && edits.len() == 1)
+        );
+
+        let doc_changes = json!({ "documentChanges": [
+            {"textDocument": {"uri": "file:///a.rs", "version": 1}, "edits": [{"range": range, "newText": "y"}]},
+            {"kind": "rename", "oldUri": "file:///a.rs", "newUri": "file:///b.rs"},
+            {"kind": "delete", "uri": "file:///c.rs"},
+        ]});
+        let ops = parse_workspace_edit(&doc_changes).unwrap();
+        assert_eq!(ops.len(), 3);
+        assert!(matches!(&ops[1], EditOperation::Rename { to, .. } if to == Path::new("/b.rs")));
+        assert!(matches!(&ops[2], EditOperation::Delete { .. }));
+    }
+
+    #[test]
+    fn utf16_columns_round_trip() {
+        let line = "let 🦀 = né;";
+        assert_eq!(utf16_column(line, 4), 4);
+        assert_eq!(utf16_column(line, 5), 6);
+        assert_eq!(char_column(line, 6), 5);
+        assert_eq!(char_column(line, 100), line.chars().count());
+    }
+
+    #[test]
+    fn workspace_root_prefers_outermost_cargo_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let krate = root.join("crates/foo/src");
+        std::fs::create_dir_all(&krate).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+        std::fs::write(root.join("crates/foo/Cargo.toml"), "").unwrap();
+        let file = krate.join("lib.rs");
+
+        let rust = detect_server_command("rs").unwrap();
+        assert_eq!(find_workspace_root(&rust, &file, root), root);
+
+        std::fs::write(root.join("crates/foo/package.json"), "").unwrap();
+        let ts = detect_server_command("ts").unwrap();
+        assert_eq!(
+            find_workspace_root(&ts, &krate.join("x.ts"), root),
+            root.join("crates/foo")
+        );
+    }
+}
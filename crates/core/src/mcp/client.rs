@@ -0,0 +1,254 @@
+use std::sync::{Arc, RwLock};
+
+use nyzhi_provider::{ChatRequest, ContentPart, Message, MessageContent, Provider};
+use rmcp::model::{
+    ClientCapabilities, ClientInfo, CreateMessageRequestParams, CreateMessageResult,
+    ErrorData as McpError, Implementation, Prompt, Role, SamplingCapability, SamplingMessage,
+    SamplingMessageContent, Tool as McpToolDef,
+};
+use rmcp::service::{NotificationContext, RequestContext, RoleClient};
+use rmcp::ClientHandler;
+use tokio::sync::broadcast;
+
+/// What one server offers, refreshed when it announces a list change.
+#[derive(Default)]
+pub(crate) struct ServerCatalog {
+    pub tools: RwLock<Vec<McpToolDef>>,
+    pub prompts: RwLock<Vec<Prompt>>,
+}
+
+/// The provider and model that answer `sampling/createMessage` requests.
+#[derive(Clone)]
+pub(crate) struct SamplingTarget {
+    pub provider: Arc<dyn Provider>,
+    pub model: String,
+}
+
+/// Upper bound on `maxTokens` for one sampling request.
+const MAX_SAMPLING_TOKENS: u32 = 4096;
+
+/// State shared by the manager and every server's client handler.
+pub(crate) struct ClientShared {
+    pub sampling: RwLock<Option<SamplingTarget>>,
+    /// Servers the user allowed to request sampling.
+    pub sampling_servers: RwLock<Vec<String>>,
+    /// Carries the name of a server whose tool list changed.
+    pub tool_changes: broadcast::Sender<String>,
+}
+
+impl ClientShared {
+    pub fn new() -> Self {
+        Self {
+            sampling: RwLock::new(None),
+            sampling_servers: RwLock::new(Vec::new()),
+            tool_changes: broadcast::channel(16).0,
+        }
+    }
+}
+
+/// Client side of one MCP connection: answers sampling requests and keeps
+/// the server's catalog current.
+pub(crate) struct McpClient {
+    pub server: String,
+    pub catalog: Arc<ServerCatalog>,
+    pub shared: Arc<ClientShared>,
+}
+
+impl ClientHandler for McpClient {
+    fn get_info(&self) -> ClientInfo {
+        ClientInfo {
+            capabilities: ClientCapabilities {
+                sampling: Some(SamplingCapability::default()),
+                ..Default::default()
+            },
+            client_info: Implementation {
+                name: "nyzhi".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Implementation::from_build_env()
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn create_message(
+        &self,
+        params: CreateMessageRequestParams,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<CreateMessageResult, McpError> {
+        let allowed = self
+            .shared
+            .sampling_servers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&self.server);
+        if !allowed {
+            tracing::warn!(server = %self.server, "Refused MCP sampling request");
+            return Err(McpError::invalid_request(
+                format!(
+                    "Sampling is not enabled for '{}'. Add it to `[mcp] sampling` in the global config",
+                    self.server
+                ),
+                None,
+            ));
+        }
+        let target = self
+            .shared
+            .sampling
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| McpError::internal_error("No model is available for sampling", None))?;
+        tracing::info!(
+            server = %self.server,
+            model = %target.model,
+            "Answering MCP sampling request"
+        );
+
+        let request = sampling_request(&params, &target.model);
+        let response = target
+            .provider
+            .chat(&request)
+            .await
+            .map_err(|e| McpError::internal_error(format!("Sampling failed: {e}"), None))?;
+        let usage = response.usage.clone().unwrap_or_default();
+        tracing::info!(
+            server = %self.server,
+            model = %target.model,
+            input_tokens = usage.input_tokens,
+            output_tokens = usage.output_tokens,
+            "MCP sampling request answered"
+        );
+
+        let stop_reason = match response.finish_reason.as_deref() {
+            Some("max_tokens" | "length") => CreateMessageResult::STOP_REASON_END_MAX_TOKEN,
+            _ => CreateMessageResult::STOP_REASON_END_TURN,
+        };
+        Ok(CreateMessageResult {
+            model: target.model,
+            stop_reason: Some(stop_reason.to_string()),
+            message: SamplingMessage::assistant_text(response.message.content.as_text()),
+        })
+    }
+
+    async fn on_tool_list_changed(&self, context: NotificationContext<RoleClient>) {
+        match context.peer.list_all_tools().await {
+            Ok(tools) => {
+                tracing::info!(server = %self.server, tools = tools.len(), "MCP tool list changed");
+                *self
+                    .catalog
+                    .tools
+                    .write()
+                    .unwrap_or_else(|e| e.into_inner()) = tools;
+                let _ = self.shared.tool_changes.send(self.server.clone());
+            }
+            Err(e) => {
+                tracing::warn!(server = %self.server, error = %e, "Failed to refresh MCP tools");
+            }
+        }
+    }
+
+    async fn on_prompt_list_changed(&self, context: NotificationContext<RoleClient>) {
+        match context.peer.list_all_prompts().await {
+            Ok(prompts) => {
+                *self
+                    .catalog
+                    .prompts
+                    .write()
+                    .unwrap_or_else(|e| e.into_inner()) = prompts;
+            }
+            Err(e) => {
+                tracing::warn!(server = %self.server, error = %e, "Failed to refresh MCP prompts");
+            }
+        }
+    }
+}
+
+/// Translate a server's sampling request into a tool-less chat request,
+/// with `maxTokens` capped at [`MAX_SAMPLING_TOKENS`]. Audio and tool
+/// content are not supported and are left out.
+fn sampling_request(params: &CreateMessageRequestParams, model: &str) -> ChatRequest {
+    let messages = params
+        .messages
+        .iter()
+        .map(|m| {
+            let parts: Vec<ContentPart> = m
+                .content
+                .iter()
+                .filter_map(|c| match c {
+                    SamplingMessageContent::Text(t) => Some(ContentPart::Text {
+                        text: t.text.clone(),
+                    }),
+                    SamplingMessageContent::Image(img) => Some(ContentPart::Image {
+                        media_type: img.mime_type.clone(),
+                        data: img.data.clone(),
+                    }),
+                    _ => None,
+                })
+                .collect();
+            Message {
+                role: match m.role {
+                    Role::User => nyzhi_provider::Role::User,
+                    Role::Assistant => nyzhi_provider::Role::Assistant,
+                },
+                content: match parts.as_slice() {
+                    [ContentPart::Text { text }] => MessageContent::Text(text.clone()),
+                    _ => MessageContent::Parts(parts),
+                },
+            }
+        })
+        .collect();
+
+    ChatRequest {
+        model: model.to_string(),
+        messages,
+        tools: vec![],
+        max_tokens: Some(params.max_tokens.min(MAX_SAMPLING_TOKENS)),
+        temperature: params.temperature,
+        system: params.system_prompt.clone(),
+        stream: false,
+        thinking: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_request_keeps_roles_and_limits() {
+        let mut params = CreateMessageRequestParams {
+            meta: None,
+            task: None,
+            messages: vec![
+                SamplingMessage::user_text("Summarize the schema"),
+                SamplingMessage::assistant_text("Which table?"),
+            ],
+            model_preferences: None,
+            system_prompt: Some("Be brief".to_string()),
+            include_context: None,
+            temperature: Some(0.2),
+            max_tokens: 200,
+            stop_sequences: None,
+            metadata: None,
+            tools: None,
+            tool_choice: None,
+        };
+        let request = sampling_request(&params, "some-model");
+
+        assert_eq!(request.model, "some-model");
+        assert_eq!(request.max_tokens, Some(200));
+        assert_eq!(request.system.as_deref(), Some("Be brief"));
+        assert!(request.tools.is_empty());
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, nyzhi_provider::Role::User);
+        assert_eq!(
+            request.messages[0].content.as_text(),
+            "Summarize the schema"
+        );
+        assert_eq!(request.messages[1].role, nyzhi_provider::Role::Assistant);
+
+        params.max_tokens = 1_000_000;
+        let request = sampling_request(&params, "some-model");
+        assert_eq!(request.max_tokens, Some(MAX_SAMPLING_TOKENS));
+    }
+}
@@ -1,3 +1,4 @@
+mod client;
 pub mod tool_adapter;
 
 use std::collections::HashMap;
@@ -6,22 +7,37 @@ use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use nyzhi_config::McpServerConfig;
-use rmcp::model::{CallToolRequestParams, Tool as McpToolDef};
+use nyzhi_provider::Provider;
+use rmcp::model::{
+    CallToolRequestParams, GetPromptRequestParams, PromptMessageContent, ReadResourceRequestParams,
+    ResourceContents, Tool as McpToolDef,
+};
 use rmcp::service::{RoleClient, RunningService, ServiceExt};
 use rmcp::transport::ConfigureCommandExt;
 use rmcp::transport::StreamableHttpClientTransport;
 use rmcp::transport::TokioChildProcess;
 use tokio::process::Command;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+
+use client::{ClientShared, McpClient, SamplingTarget, ServerCatalog};
 
 struct McpConnection {
     name: String,
-    service: RunningService<RoleClient, ()>,
-    tools: Vec<McpToolDef>,
+    service: RunningService<RoleClient, McpClient>,
+    catalog: Arc<ServerCatalog>,
+}
+
+impl McpConnection {
+    fn supports_resources(&self) -> bool {
+        self.service
+            .peer_info()
+            .is_some_and(|info| info.capabilities.resources.is_some())
+    }
 }
 
 pub struct McpManager {
     connections: RwLock<Vec<McpConnection>>,
+    shared: Arc<ClientShared>,
 }
 
 /// Summary of a connected MCP server, safe to share.
@@ -30,18 +46,94 @@ pub struct McpServerInfo {
     pub name: String,
     pub tool_count: usize,
     pub tool_names: Vec<String>,
+    pub prompt_count: usize,
+}
+
+/// A resource a server exposes, addressed in prompts as `@server:uri`.
+#[derive(Debug, Clone)]
+pub struct McpResourceInfo {
+    pub server: String,
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+/// A prompt template a server exposes, run as `/mcp__<server>__<name>`.
+#[derive(Debug, Clone)]
+pub struct McpPromptInfo {
+    pub server: String,
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<McpPromptArgument>,
+}
+
+#[derive(Debug, Clone)]
+pub struct McpPromptArgument {
+    pub name: String,
+    pub required: bool,
+}
+
+impl McpPromptInfo {
+    /// Slash command name, without the leading `/`.
+    pub fn command_name(&self) -> String {
+        format!("mcp__{}__{}", self.server, self.name)
+    }
+
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.command_name());
+        for arg in &self.arguments {
+            if arg.required {
+                usage.push_str(&format!(" <{}>", arg.name));
+            } else {
+                usage.push_str(&format!(" [{}]", arg.name));
+            }
+        }
+        usage
+    }
+
+    /// Map whitespace-separated `input` onto the prompt's arguments in
+    /// order; the last argument takes the rest of the line.
+    pub fn arguments_from(
+        &self,
+        input: &str,
+    ) -> Result<serde_json::Map<String, serde_json::Value>> {
+        let mut rest = input.trim();
+        let mut values = serde_json::Map::new();
+        for (i, arg) in self.arguments.iter().enumerate() {
+            if rest.is_empty() {
+                if arg.required {
+                    anyhow::bail!("Missing argument `{}`. Usage: {}", arg.name, self.usage());
+                }
+                continue;
+            }
+            let value = if i + 1 == self.arguments.len() {
+                std::mem::take(&mut rest)
+            } else {
+                let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                rest = tail.trim_start();
+                word
+            };
+            values.insert(arg.name.clone(), value.into());
+        }
+        if !rest.is_empty() {
+            anyhow::bail!("Too many arguments. Usage: {}", self.usage());
+        }
+        Ok(values)
+    }
 }
 
 impl McpManager {
     pub async fn start_all(configs: &HashMap<String, McpServerConfig>) -> Result<Arc<Self>> {
+        let shared = Arc::new(ClientShared::new());
         let mut connections = Vec::new();
 
         for (name, cfg) in configs {
-            match Self::connect(name, cfg).await {
+            match Self::connect(name, cfg, &shared).await {
                 Ok(conn) => {
                     tracing::info!(
                         server = %name,
-                        tools = conn.tools.len(),
+                        tools = conn.catalog.tools.read().unwrap().len(),
                         "MCP server connected"
                     );
                     connections.push(conn);
@@ -54,15 +146,26 @@ impl McpManager {
 
         Ok(Arc::new(Self {
             connections: RwLock::new(connections),
+            shared,
         }))
     }
 
-    async fn connect(name: &str, config: &McpServerConfig) -> Result<McpConnection> {
-        match config {
+    async fn connect(
+        name: &str,
+        config: &McpServerConfig,
+        shared: &Arc<ClientShared>,
+    ) -> Result<McpConnection> {
+        let catalog = Arc::new(ServerCatalog::default());
+        let client = McpClient {
+            server: name.to_string(),
+            catalog: catalog.clone(),
+            shared: shared.clone(),
+        };
+        let service = match config {
             McpServerConfig::Stdio { command, args, env } => {
                 let env_clone = env.clone();
                 let args_clone = args.clone();
-                let service = ()
+                client
                     .serve(TokioChildProcess::new(Command::new(command).configure(
                         move |cmd| {
                             for arg in &args_clone {
@@ -74,18 +177,7 @@ impl McpManager {
                         },
                     ))?)
                     .await
-                    .with_context(|| format!("MCP stdio init failed for '{name}'"))?;
-
-                let tools_result = service
-                    .list_tools(Default::default())
-                    .await
-                    .with_context(|| format!("MCP tools/list failed for '{name}'"))?;
-
-                Ok(McpConnection {
-                    name: name.to_string(),
-                    service,
-                    tools: tools_result.tools,
-                })
+                    .with_context(|| format!("MCP stdio init failed for '{name}'"))?
             }
             McpServerConfig::Http {
                 url,
@@ -93,23 +185,36 @@ impl McpManager {
             } => {
                 let transport = StreamableHttpClientTransport::from_uri(url.as_str());
 
-                let service: RunningService<RoleClient, ()> = ()
+                client
                     .serve(transport)
                     .await
-                    .with_context(|| format!("MCP HTTP init failed for '{name}'"))?;
+                    .with_context(|| format!("MCP HTTP init failed for '{name}'"))?
+            }
+        };
 
-                let tools_result = service
-                    .list_tools(Default::default())
-                    .await
-                    .with_context(|| format!("MCP tools/list failed for '{name}'"))?;
+        let tools_result = service
+            .list_tools(Default::default())
+            .await
+            .with_context(|| format!("MCP tools/list failed for '{name}'"))?;
+        *catalog.tools.write().unwrap() = tools_result.tools;
 
-                Ok(McpConnection {
-                    name: name.to_string(),
-                    service,
-                    tools: tools_result.tools,
-                })
+        let supports_prompts = service
+            .peer_info()
+            .is_some_and(|info| info.capabilities.prompts.is_some());
+        if supports_prompts {
+            match service.list_all_prompts().await {
+                Ok(prompts) => *catalog.prompts.write().unwrap() = prompts,
+                Err(e) => {
+                    tracing::warn!(server = %name, error = %e, "MCP prompts/list failed");
+                }
             }
         }
+
+        Ok(McpConnection {
+            name: name.to_string(),
+            service,
+            catalog,
+        })
     }
 
     pub async fn stop_all(&self) {
@@ -126,13 +231,191 @@ impl McpManager {
         let conns = self.connections.read().await;
         let mut result = Vec::new();
         for conn in conns.iter() {
-            for tool in &conn.tools {
+            for tool in conn.catalog.tools.read().unwrap().iter() {
                 result.push((conn.name.clone(), tool.clone()));
             }
         }
         result
     }
 
+    /// Tools currently offered by one server.
+    pub async fn server_tools(&self, server_name: &str) -> Vec<McpToolDef> {
+        let conns = self.connections.read().await;
+        conns
+            .iter()
+            .find(|c| c.name == server_name)
+            .map(|c| c.catalog.tools.read().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// Names of servers whose tool list changed since subscribing.
+    pub fn subscribe_tool_changes(&self) -> broadcast::Receiver<String> {
+        self.shared.tool_changes.subscribe()
+    }
+
+    /// Answer servers' sampling requests with `model` on `provider`.
+    pub fn set_sampling_model(&self, provider: Arc<dyn Provider>, model: &str) {
+        *self.shared.sampling.write().unwrap() = Some(SamplingTarget {
+            provider,
+            model: model.to_string(),
+        });
+    }
+
+    /// Let `servers` request sampling; requests from any other server are
+    /// refused.
+    pub fn allow_sampling(&self, servers: &[String]) {
+        *self.shared.sampling_servers.write().unwrap() = servers.to_vec();
+    }
+
+    /// Prompt templates of every connected server.
+    pub fn prompts(&self) -> Vec<McpPromptInfo> {
+        let Ok(conns) = self.connections.try_read() else {
+            return Vec::new();
+        };
+        conns
+            .iter()
+            .flat_map(|c| {
+                let prompts = c.catalog.prompts.read().unwrap().clone();
+                prompts.into_iter().map(|p| McpPromptInfo {
+                    server: c.name.clone(),
+                    name: p.name,
+                    description: p.description.unwrap_or_default(),
+                    arguments: p
+                        .arguments
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|a| McpPromptArgument {
+                            name: a.name,
+                            required: a.required.unwrap_or(false),
+                        })
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+
+    /// Render `prompt` with arguments parsed from `input` into the text of
+    /// a user message.
+    pub async fn get_prompt(&self, prompt: &McpPromptInfo, input: &str) -> Result<String> {
+        let arguments = prompt.arguments_from(input)?;
+        let conns = self.connections.read().await;
+        let conn = conns
+            .iter()
+            .find(|c| c.name == prompt.server)
+            .ok_or_else(|| anyhow::anyhow!("MCP server '{}' not found", prompt.server))?;
+
+        let result = conn
+            .service
+            .get_prompt(GetPromptRequestParams {
+                meta: None,
+                name: prompt.name.clone(),
+                arguments: (!arguments.is_empty()).then_some(arguments),
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "MCP prompts/get failed for '{}' on '{}'",
+                    prompt.name, prompt.server
+                )
+            })?;
+
+        let mut parts = Vec::new();
+        for message in &result.messages {
+            match &message.content {
+                PromptMessageContent::Text { text } => parts.push(text.clone()),
+                PromptMessageContent::Resource { resource } => {
+                    parts.push(resource_text(&resource.resource))
+                }
+                PromptMessageContent::ResourceLink { link } => {
+                    parts.push(format!("@{}:{}", prompt.server, link.uri))
+                }
+                PromptMessageContent::Image { .. } => {}
+            }
+        }
+        if parts.is_empty() {
+            anyhow::bail!("MCP prompt '{}' returned no text", prompt.name);
+        }
+        Ok(parts.join("\n\n"))
+    }
+
+    /// Resources of every connected server that supports them.
+    pub async fn list_resources(&self) -> Vec<McpResourceInfo> {
+        let conns = self.connections.read().await;
+        let mut result = Vec::new();
+        for conn in conns.iter().filter(|c| c.supports_resources()) {
+            match conn.service.list_all_resources().await {
+                Ok(resources) => {
+                    result.extend(resources.into_iter().map(|r| McpResourceInfo {
+                        server: conn.name.clone(),
+                        uri: r.raw.uri,
+                        name: r.raw.name,
+                        description: r.raw.description,
+                        mime_type: r.raw.mime_type,
+                    }));
+                }
+                Err(e) => {
+                    tracing::warn!(server = %conn.name, error = %e, "MCP resources/list failed");
+                }
+            }
+        }
+        result
+    }
+
+    pub async fn read_resource(&self, server_name: &str, uri: &str) -> Result<String> {
+        let conns = self.connections.read().await;
+        let conn = conns
+            .iter()
+            .find(|c| c.name == server_name)
+            .ok_or_else(|| anyhow::anyhow!("MCP server '{server_name}' not found"))?;
+
+        let result = conn
+            .service
+            .read_resource(ReadResourceRequestParams {
+                meta: None,
+                uri: uri.to_string(),
+            })
+            .await
+            .with_context(|| format!("MCP resources/read failed for '{uri}' on '{server_name}'"))?;
+
+        Ok(result
+            .contents
+            .iter()
+            .map(resource_text)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Read the `@server:uri` mentions among `mentions` that name a
+    /// connected server. Unreadable resources are logged and skipped.
+    pub async fn resolve_mentions(
+        &self,
+        mentions: &[String],
+    ) -> Vec<crate::context_files::ContextFile> {
+        let servers: Vec<String> = self
+            .connections
+            .read()
+            .await
+            .iter()
+            .map(|c| c.name.clone())
+            .collect();
+        let mut files = Vec::new();
+        for mention in mentions {
+            let Some((server, uri)) = crate::context_files::parse_mcp_mention(mention) else {
+                continue;
+            };
+            if !servers.iter().any(|s| s == server) {
+                continue;
+            }
+            match self.read_resource(server, uri).await {
+                Ok(content) => files.push(crate::context_files::resource_context(mention, content)),
+                Err(e) => {
+                    tracing::warn!(mention = %mention, error = %e, "Failed to read MCP resource")
+                }
+            }
+        }
+        files
+    }
+
     pub async fn call_tool(
         &self,
         server_name: &str,
@@ -177,8 +460,8 @@ impl McpManager {
 
     /// Hot-add a single MCP server at runtime. Returns the number of tools discovered.
     pub async fn connect_server(&self, name: &str, config: &McpServerConfig) -> Result<usize> {
-        let conn = Self::connect(name, config).await?;
-        let tool_count = conn.tools.len();
+        let conn = Self::connect(name, config, &self.shared).await?;
+        let tool_count = conn.catalog.tools.read().unwrap().len();
         tracing::info!(
             server = %name,
             tools = tool_count,
@@ -195,11 +478,15 @@ impl McpManager {
             Ok(conns) => conns
                 .iter()
                 .flat_map(|c| {
-                    c.tools.iter().map(|t| crate::prompt::McpToolSummary {
-                        server_name: c.name.clone(),
-                        tool_name: t.name.to_string(),
-                        description: t.description.as_deref().unwrap_or("").to_string(),
-                    })
+                    let tools = c.catalog.tools.read().unwrap();
+                    tools
+                        .iter()
+                        .map(|t| crate::prompt::McpToolSummary {
+                            server_name: c.name.clone(),
+                            tool_name: t.name.to_string(),
+                            description: t.description.as_deref().unwrap_or("").to_string(),
+                        })
+                        .collect::<Vec<_>>()
                 })
                 .collect(),
             Err(_) => Vec::new(),
@@ -210,15 +497,35 @@ impl McpManager {
         let conns = self.connections.read().await;
         conns
             .iter()
-            .map(|c| McpServerInfo {
-                name: c.name.clone(),
-                tool_count: c.tools.len(),
-                tool_names: c.tools.iter().map(|t| t.name.to_string()).collect(),
+            .map(|c| {
+                let tools = c.catalog.tools.read().unwrap();
+                McpServerInfo {
+                    name: c.name.clone(),
+                    tool_count: tools.len(),
+                    tool_names: tools.iter().map(|t| t.name.to_string()).collect(),
+                    prompt_count: c.catalog.prompts.read().unwrap().len(),
+                }
             })
             .collect()
     }
 }
 
+fn resource_text(contents: &ResourceContents) -> String {
+    match contents {
+        ResourceContents::TextResourceContents { text, .. } => text.clone(),
+        ResourceContents::BlobResourceContents {
+            uri,
+            mime_type,
+            blob,
+            ..
+        } => format!(
+            "[binary resource {uri} ({}, {} bytes base64) not shown]",
+            mime_type.as_deref().unwrap_or("unknown type"),
+            blob.len()
+        ),
+    }
+}
+
 /// Load `.mcp.json` from a directory (Claude Code / Codex compatibility format).
 pub fn load_mcp_json(root: &Path) -> HashMap<String, McpServerConfig> {
     let path = root.join(".mcp.json");
@@ -234,6 +541,17 @@ pub fn load_mcp_json(root: &Path) -> HashMap<String, McpServerConfig> {
         }
     };
 
+    match parse_mcp_json(&content) {
+        Ok(servers) => servers,
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to parse .mcp.json");
+            HashMap::new()
+        }
+    }
+}
+
+/// Parse servers from `.mcp.json` content (`{"mcpServers": {...}}`).
+pub fn parse_mcp_json(content: &str) -> Result<HashMap<String, McpServerConfig>> {
     #[derive(serde::Deserialize)]
     struct McpJson {
         #[serde(default, alias = "mcpServers")]
@@ -252,15 +570,9 @@ pub fn load_mcp_json(root: &Path) -> HashMap<String, McpServerConfig> {
         headers: HashMap<String, String>,
     }
 
-    let parsed: McpJson = match serde_json::from_str(&content) {
-        Ok(p) => p,
-        Err(e) => {
-            tracing::warn!(path = %path.display(), error = %e, "Failed to parse .mcp.json");
-            return HashMap::new();
-        }
-    };
+    let parsed: McpJson = serde_json::from_str(content)?;
 
-    parsed
+    Ok(parsed
         .mcp_servers
         .into_iter()
         .filter_map(|(name, server)| {
@@ -286,5 +598,47 @@ pub fn load_mcp_json(root: &Path) -> HashMap<String, McpServerConfig> {
                 None
             }
         })
-        .collect()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(args: &[(&str, bool)]) -> McpPromptInfo {
+        McpPromptInfo {
+            server: "tracker".to_string(),
+            name: "triage".to_string(),
+            description: String::new(),
+            arguments: args
+                .iter()
+                .map(|(name, required)| McpPromptArgument {
+                    name: name.to_string(),
+                    required: *required,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn prompt_arguments_are_positional_with_trailing_rest() {
+        let p = prompt(&[("ticket", true), ("note", false)]);
+        let args = p.arguments_from("ENG-12  look at the crash log").unwrap();
+        assert_eq!(args["ticket"], "ENG-12");
+        assert_eq!(args["note"], "look at the crash log");
+
+        let args = p.arguments_from("ENG-12").unwrap();
+        assert!(!args.contains_key("note"));
+    }
+
+    #[test]
+    fn prompt_arguments_report_usage() {
+        let p = prompt(&[("ticket", true)]);
+        let err = p.arguments_from("").unwrap_err().to_string();
+        assert!(err.contains("/mcp__tracker__triage <ticket>"), "{err}");
+
+        let none = prompt(&[]);
+        assert!(none.arguments_from("").unwrap().is_empty());
+        assert!(none.arguments_from("extra").is_err());
+    }
 }
@@ -1,12 +1,13 @@
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 
 use anyhow::Result;
 use async_trait::async_trait;
-use serde_json::Value;
+use rmcp::model::Tool as McpToolDef;
+use serde_json::{json, Value};
 
 use super::McpManager;
 use crate::tools::permission::ToolPermission;
-use crate::tools::{Tool, ToolContext, ToolResult};
+use crate::tools::{Tool, ToolContext, ToolRegistry, ToolResult};
 
 /// Bridges an MCP server tool into the local `ToolRegistry`.
 pub struct McpTool {
@@ -73,3 +74,174 @@ impl Tool for McpTool {
         })
     }
 }
+
+/// Wrap one server's tool definitions as registry tools.
+pub fn server_tools(
+    server_name: &str,
+    defs: &[McpToolDef],
+    manager: &Arc<McpManager>,
+) -> Vec<Box<dyn Tool>> {
+    defs.iter()
+        .map(|def| {
+            let desc = def.description.as_deref().unwrap_or("MCP tool");
+            let schema = serde_json::to_value(&*def.input_schema).unwrap_or_default();
+            Box::new(McpTool::new(
+                server_name,
+                &def.name,
+                desc,
+                schema,
+                manager.clone(),
+            )) as Box<dyn Tool>
+        })
+        .collect()
+}
+
+/// Re-register a server's tools in `registry` whenever it announces that
+/// its tool list changed. Stops once the manager or registry is dropped.
+pub fn keep_registry_in_sync(manager: &Arc<McpManager>, registry: &Arc<ToolRegistry>) {
+    let mut changes = manager.subscribe_tool_changes();
+    let manager: Weak<McpManager> = Arc::downgrade(manager);
+    let registry: Weak<ToolRegistry> = Arc::downgrade(registry);
+    tokio::spawn(async move {
+        loop {
+            let server = match changes.recv().await {
+                Ok(server) => server,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            let (Some(manager), Some(registry)) = (manager.upgrade(), registry.upgrade()) else {
+                break;
+            };
+            let defs = manager.server_tools(&server).await;
+            let tools = server_tools(&server, &defs, &manager);
+            registry.replace_prefixed(&format!("mcp__{server}__"), tools);
+        }
+    });
+}
+
+/// Lists the resources MCP servers expose.
+pub struct McpListResourcesTool {
+    manager: Arc<McpManager>,
+}
+
+impl McpListResourcesTool {
+    pub fn new(manager: Arc<McpManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Tool for McpListResourcesTool {
+    fn name(&self) -> &str {
+        "mcp_list_resources"
+    }
+
+    fn description(&self) -> &str {
+        "List the resources (schemas, documents, tickets, ...) that connected MCP servers \
+         expose, with their server and URI. Read one with mcp_read_resource."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "server": {
+                    "type": "string",
+                    "description": "Only list resources of this server"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
+        let server = args.get("server").and_then(|v| v.as_str());
+        let resources: Vec<_> = self
+            .manager
+            .list_resources()
+            .await
+            .into_iter()
+            .filter(|r| server.map_or(true, |s| r.server == s))
+            .collect();
+
+        let output = if resources.is_empty() {
+            "No MCP resources available.".to_string()
+        } else {
+            resources
+                .iter()
+                .map(|r| {
+                    let mut line = format!("{}:{}  {}", r.server, r.uri, r.name);
+                    if let Some(mime) = &r.mime_type {
+                        line.push_str(&format!(" ({mime})"));
+                    }
+                    if let Some(desc) = &r.description {
+                        line.push_str(&format!(" - {desc}"));
+                    }
+                    line
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        Ok(ToolResult {
+            output,
+            title: format!("mcp_list_resources ({})", resources.len()),
+            metadata: json!({ "count": resources.len() }),
+        })
+    }
+}
+
+/// Reads one resource from an MCP server.
+pub struct McpReadResourceTool {
+    manager: Arc<McpManager>,
+}
+
+impl McpReadResourceTool {
+    pub fn new(manager: Arc<McpManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Tool for McpReadResourceTool {
+    fn name(&self) -> &str {
+        "mcp_read_resource"
+    }
+
+    fn description(&self) -> &str {
+        "Read a resource from an MCP server by its URI, as listed by mcp_list_resources."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "server": {
+                    "type": "string",
+                    "description": "Name of the MCP server"
+                },
+                "uri": {
+                    "type": "string",
+                    "description": "URI of the resource"
+                }
+            },
+            "required": ["server", "uri"]
+        })
+    }
+
+    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
+        let server = args
+            .get("server")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: server"))?;
+        let uri = args
+            .get("uri")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: uri"))?;
+
+        let output = self.manager.read_resource(server, uri).await?;
+        Ok(ToolResult {
+            output,
+            title: format!("mcp:{server}:{uri}"),
+            metadata: json!({ "mcp_server": server, "uri": uri }),
+        })
+    }
+}
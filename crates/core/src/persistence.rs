@@ -1,5 +1,6 @@
 use std::path::Path;
 
+use crate::sandbox::Launcher;
 use crate::verify::{self, VerifyReport};
 
 pub struct PersistenceConfig {
@@ -18,7 +19,12 @@ impl Default for PersistenceConfig {
     }
 }
 
-pub async fn run_qa_cycle(project_root: &Path, cwd: &Path, max_cycles: u32) -> Vec<VerifyReport> {
+pub async fn run_qa_cycle(
+    project_root: &Path,
+    cwd: &Path,
+    max_cycles: u32,
+    launcher: &Launcher,
+) -> Vec<VerifyReport> {
     let checks = verify::detect_checks(project_root);
     if checks.is_empty() {
         return vec![];
@@ -26,7 +32,7 @@ pub async fn run_qa_cycle(project_root: &Path, cwd: &Path, max_cycles: u32) -> V
 
     let mut reports = vec![];
     for _cycle in 0..max_cycles {
-        let report = verify::run_all_checks(&checks, cwd).await;
+        let report = verify::run_all_checks(&checks, cwd, launcher).await;
         let passed = report.all_passed();
         reports.push(report);
         if passed {
@@ -0,0 +1,749 @@
+//! A long-lived shell behind a pseudo-terminal, used by the `bash` tool when
+//! `[shell] persistent = true`, so `cd`, exported variables, activated
+//! virtualenvs and `source`d scripts carry over from one command to the
+//! next.
+//!
+//! Each command is followed by a `printf` of a random marker with the exit
+//! code and working directory, which is how the end of a command is found.
+//! A command that outlives its timeout is interrupted with Ctrl+C; a shell
+//! that does not answer after that, or that exits, is killed and started
+//! again on the next command.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::sandbox::Launcher;
+
+/// How long a fresh shell may take to run its startup commands, and an
+/// interrupted command to return to the prompt.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most output kept for one command; earlier output is dropped past it.
+const MAX_KEPT_OUTPUT: usize = 1024 * 1024;
+
+pub type PersistentShellHandle = Arc<PersistentShell>;
+
+/// How to start the shell, from `[shell]` in the config.
+#[derive(Debug, Clone, Default)]
+pub struct ShellOptions {
+    /// Shell program; `bash` when available, else `/bin/sh`.
+    pub program: Option<String>,
+    pub env: HashMap<String, String>,
+    /// Run once each time the shell starts.
+    pub startup_commands: Vec<String>,
+}
+
+impl From<&nyzhi_config::ShellConfig> for ShellOptions {
+    fn from(config: &nyzhi_config::ShellConfig) -> Self {
+        Self {
+            program: config.path.clone(),
+            env: config.env.clone(),
+            startup_commands: config.startup_commands.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellOutcome {
+    Completed,
+    TimedOut,
+    Cancelled,
+}
+
+#[derive(Debug)]
+pub struct ShellRun {
+    pub output: String,
+    /// `None` when the command did not finish.
+    pub exit_code: Option<i32>,
+    /// The shell's working directory after the command.
+    pub cwd: Option<PathBuf>,
+    pub outcome: ShellOutcome,
+    /// The shell stopped responding or exited and was discarded; the next
+    /// command starts a fresh one.
+    pub reset: bool,
+}
+
+/// One agent's shell. The process is started on first use and after a
+/// reset.
+pub struct PersistentShell {
+    options: ShellOptions,
+    process: Mutex<Option<imp::ShellProcess>>,
+}
+
+impl PersistentShell {
+    pub fn new(options: ShellOptions) -> Self {
+        Self {
+            options,
+            process: Mutex::new(None),
+        }
+    }
+
+    pub fn handle(options: ShellOptions) -> PersistentShellHandle {
+        Arc::new(Self::new(options))
+    }
+
+    /// A separate shell with the same options, for a sub-agent.
+    pub fn fork(&self) -> PersistentShellHandle {
+        Self::handle(self.options.clone())
+    }
+
+    /// Run `command` and wait for it to finish, time out or be cancelled.
+    /// `on_output` receives output as it arrives. A shell started under a
+    /// different sandbox level than `launcher`'s is replaced first.
+    pub async fn run(
+        &self,
+        command: &str,
+        launcher: &Launcher,
+        cwd: &Path,
+        timeout: Duration,
+        cancel: &CancellationToken,
+        on_output: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<ShellRun> {
+        let mut process = self.process.lock().await;
+        if process
+            .as_mut()
+            .is_some_and(|p| !p.is_alive() || p.level() != launcher.level())
+        {
+            *process = None;
+        }
+        if process.is_none() {
+            *process = Some(imp::ShellProcess::spawn(&self.options, launcher, cwd).await?);
+        }
+
+        let run = process
+            .as_mut()
+            .unwrap()
+            .run(command, timeout, cancel, on_output)
+            .await;
+        if run.reset {
+            *process = None;
+        }
+        Ok(run)
+    }
+
+    /// Kill the shell; the next command starts a new one.
+    pub async fn reset(&self) {
+        self.process.lock().await.take();
+    }
+}
+
+/// Splits shell output at the end-of-command marker.
+#[derive(Debug)]
+struct Marker {
+    tag: String,
+}
+
+impl Marker {
+    fn new() -> Self {
+        Self {
+            tag: format!("__nyz_{:016x}", rand::random::<u64>()),
+        }
+    }
+
+    /// Shell code that prints the marker line with the exit status in
+    /// `status` and the working directory. The tag is split so the command
+    /// text itself never matches.
+    fn print(&self, status: &str) -> String {
+        let (head, tail) = self.tag.split_at(6);
+        format!("printf '\\n%s%s:%s:%s\\n' '{head}' '{tail}' \"{status}\" \"$PWD\"")
+    }
+
+    /// The output before the marker, the exit code and the directory, once
+    /// the full marker line has arrived.
+    fn find(&self, buf: &str) -> Option<(String, i32, PathBuf)> {
+        let start = buf.find(&self.tag)?;
+        let rest = &buf[start + self.tag.len()..];
+        let line = &rest[..rest.find('\n')?];
+        let (code, cwd) = line.strip_prefix(':')?.split_once(':')?;
+        let output = buf[..start].strip_suffix('\n').unwrap_or(&buf[..start]);
+        Some((
+            output.to_string(),
+            code.trim().parse().unwrap_or(-1),
+            PathBuf::from(cwd.trim_end()),
+        ))
+    }
+}
+
+/// Output of one command as it arrives. Each chunk is decoded once and only
+/// new text is searched for the marker, so a chatty command costs linear
+/// time, and at most [`MAX_KEPT_OUTPUT`] bytes are kept.
+#[derive(Debug, Default)]
+struct OutputBuffer {
+    /// Decoded output without `\r`.
+    text: String,
+    /// An incomplete UTF-8 sequence at the end of the last chunk.
+    pending: Vec<u8>,
+    /// Bytes of `text` already passed to `on_output`.
+    streamed: usize,
+    /// Bytes of `text` in which the marker cannot start any more.
+    scanned: usize,
+    /// Where the marker starts, once its tag has arrived.
+    marker_at: Option<usize>,
+    dropped: bool,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, chunk: &[u8]) {
+        self.pending.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let decoded = String::from_utf8_lossy(&self.pending[..valid]);
+        self.text.extend(decoded.chars().filter(|&c| c != '\r'));
+        self.pending.drain(..valid);
+    }
+
+    /// The output before the marker, the exit code and the directory, once
+    /// the full marker line has arrived.
+    fn find(&mut self, marker: &Marker) -> Option<(String, i32, PathBuf)> {
+        if self.marker_at.is_none() {
+            let mut from = self.scanned;
+            while !self.text.is_char_boundary(from) {
+                from -= 1;
+            }
+            self.marker_at = self.text[from..].find(&marker.tag).map(|i| from + i);
+            // A tag cut off at the end may still be completed.
+            self.scanned = self.text.len().saturating_sub(marker.tag.len());
+        }
+        let at = self.marker_at?;
+        if !self.text[at..].contains('\n') {
+            return None;
+        }
+        let (output, code, cwd) = marker.find(&self.text)?;
+        Some((self.noted(output), code, cwd))
+    }
+
+    /// Pass on the whole lines that arrived, holding back a possible
+    /// marker, and drop old output beyond [`MAX_KEPT_OUTPUT`].
+    fn stream(&mut self, on_output: &mut (dyn FnMut(&str) + Send)) {
+        if self.marker_at.is_some() {
+            return;
+        }
+        if let Some(end) = self.text[self.streamed..].rfind('\n') {
+            if end > 0 {
+                on_output(&self.text[self.streamed..self.streamed + end]);
+            }
+            self.streamed += end;
+        }
+        if self.text.len() <= MAX_KEPT_OUTPUT {
+            return;
+        }
+        let mut cut = self.text.len() - MAX_KEPT_OUTPUT / 2;
+        while !self.text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        if cut > self.streamed {
+            on_output(&self.text[self.streamed..cut]);
+            self.streamed = cut;
+        }
+        self.text.drain(..cut);
+        self.streamed -= cut;
+        self.scanned = self.scanned.saturating_sub(cut);
+        self.dropped = true;
+    }
+
+    /// Everything kept so far, for a command that did not finish.
+    fn into_output(mut self) -> String {
+        let mut text = std::mem::take(&mut self.text);
+        text.push_str(&String::from_utf8_lossy(&self.pending));
+        self.noted(text)
+    }
+
+    fn noted(&self, output: String) -> String {
+        if self.dropped {
+            format!("... (earlier output dropped)\n{output}")
+        } else {
+            output
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io::{Read, Write};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::path::Path;
+    use std::process::Stdio;
+    use std::time::Duration;
+
+    use anyhow::{Context, Result};
+    use nyzhi_config::SandboxLevel;
+    use tokio::sync::mpsc;
+    use tokio_util::sync::CancellationToken;
+
+    use super::{Marker, OutputBuffer, ShellOptions, ShellOutcome, ShellRun, RESPONSE_TIMEOUT};
+    use crate::sandbox::{Launcher, ProcessGroup};
+
+    pub(super) struct ShellProcess {
+        master: std::fs::File,
+        output: mpsc::UnboundedReceiver<Vec<u8>>,
+        child: tokio::process::Child,
+        _group: ProcessGroup,
+        level: SandboxLevel,
+    }
+
+    enum Wait {
+        Marker(String, i32, std::path::PathBuf),
+        TimedOut(String),
+        Cancelled(String),
+        Closed(String),
+    }
+
+    impl ShellProcess {
+        pub(super) async fn spawn(
+            options: &ShellOptions,
+            launcher: &Launcher,
+            cwd: &Path,
+        ) -> Result<Self> {
+            let (master, slave) = open_pty()?;
+
+            let program = options.program.clone().unwrap_or_else(|| {
+                if Path::new("/bin/bash").exists() {
+                    "/bin/bash".to_string()
+                } else {
+                    "/bin/sh".to_string()
+                }
+            });
+            let args: &[&str] = match Path::new(&program).file_name().and_then(|n| n.to_str()) {
+                Some("bash") => &["--noprofile", "--norc"],
+                Some("zsh") => &["-f"],
+                _ => &[],
+            };
+            let mut command = launcher.program(&program, args, cwd)?;
+            command
+                .stdin(Stdio::from(slave.try_clone()?))
+                .stdout(Stdio::from(slave.try_clone()?))
+                .stderr(Stdio::from(slave))
+                .env("TERM", "dumb")
+                .env("PAGER", "cat")
+                .env("GIT_PAGER", "cat")
+                .env("PS1", "")
+                .env("PS2", "")
+                .envs(&options.env);
+            {
+                use std::os::unix::process::CommandExt;
+                // SAFETY: setsid and ioctl are async-signal-safe. The new
+                // session makes the pty the shell's controlling terminal, so
+                // Ctrl+C written to it interrupts the running command.
+                unsafe {
+                    command.pre_exec(|| {
+                        if libc::setsid() < 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        if libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        Ok(())
+                    });
+                }
+            }
+            let mut command: tokio::process::Command = command.into();
+            command.kill_on_drop(true);
+            let child = command
+                .spawn()
+                .with_context(|| format!("Failed to start persistent shell {program}"))?;
+            let group = ProcessGroup::new(&child);
+
+            let output = spawn_reader(master.try_clone()?);
+            let mut shell = Self {
+                master,
+                output,
+                child,
+                _group: group,
+                level: launcher.level(),
+            };
+
+            // No job control, so the shell and its commands share the
+            // terminal's foreground group, and no prompts in the output.
+            let mut setup = vec![
+                "set +m".to_string(),
+                "PS1=''; PS2=''; unset PROMPT_COMMAND".to_string(),
+            ];
+            setup.extend(options.startup_commands.iter().cloned());
+            let marker = Marker::new();
+            shell.write(&format!("{}\n{}\n", setup.join("\n"), marker.print("$?")))?;
+            match shell
+                .wait_for(
+                    &marker,
+                    RESPONSE_TIMEOUT,
+                    &CancellationToken::new(),
+                    &mut |_| {},
+                )
+                .await
+            {
+                Wait::Marker(..) => Ok(shell),
+                Wait::Closed(out) | Wait::TimedOut(out) | Wait::Cancelled(out) => {
+                    anyhow::bail!("Persistent shell {program} did not start: {}", out.trim())
+                }
+            }
+        }
+
+        pub(super) fn is_alive(&mut self) -> bool {
+            matches!(self.child.try_wait(), Ok(None))
+        }
+
+        pub(super) fn level(&self) -> SandboxLevel {
+            self.level
+        }
+
+        pub(super) async fn run(
+            &mut self,
+            command: &str,
+            timeout: Duration,
+            cancel: &CancellationToken,
+            on_output: &mut (dyn FnMut(&str) + Send),
+        ) -> ShellRun {
+            // Whatever arrived since the last command, e.g. late output of
+            // something interrupted, belongs to no one.
+            while self.output.try_recv().is_ok() {}
+
+            let marker = Marker::new();
+            // Commands read from /dev/null so they cannot swallow the marker
+            // line; the group runs in the shell itself, so `cd` and `export`
+            // persist.
+            let script = format!(
+                "{{ {command}\n}} < /dev/null; __nyz_status=$?; {}\n",
+                marker.print("$__nyz_status")
+            );
+            if let Err(e) = self.write(&script) {
+                return ShellRun {
+                    output: format!("Failed to write to the persistent shell: {e}"),
+                    exit_code: None,
+                    cwd: None,
+                    outcome: ShellOutcome::Completed,
+                    reset: true,
+                };
+            }
+
+            let (output, outcome) = match self.wait_for(&marker, timeout, cancel, on_output).await {
+                Wait::Marker(output, code, cwd) => {
+                    return ShellRun {
+                        output,
+                        exit_code: Some(code),
+                        cwd: Some(cwd),
+                        outcome: ShellOutcome::Completed,
+                        reset: false,
+                    };
+                }
+                Wait::Closed(output) => {
+                    return ShellRun {
+                        output,
+                        exit_code: None,
+                        cwd: None,
+                        outcome: ShellOutcome::Completed,
+                        reset: true,
+                    };
+                }
+                Wait::TimedOut(output) => (output, ShellOutcome::TimedOut),
+                Wait::Cancelled(output) => (output, ShellOutcome::Cancelled),
+            };
+
+            // Interrupt the command and check that the shell is back at its
+            // prompt; otherwise it is stuck and gets replaced.
+            // Ctrl+C also discards pending input, so the check is written
+            // once the interrupt has been handled.
+            let marker = Marker::new();
+            let interrupted = self.write("\x03").is_ok();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let recovered = interrupted
+                && self.write(&format!("{}\n", marker.print("$?"))).is_ok()
+                && matches!(
+                    self.wait_for(
+                        &marker,
+                        RESPONSE_TIMEOUT,
+                        &CancellationToken::new(),
+                        &mut |_| {}
+                    )
+                    .await,
+                    Wait::Marker(..)
+                );
+            ShellRun {
+                output,
+                exit_code: None,
+                cwd: None,
+                outcome,
+                reset: !recovered,
+            }
+        }
+
+        fn write(&mut self, text: &str) -> std::io::Result<()> {
+            self.master.write_all(text.as_bytes())?;
+            self.master.flush()
+        }
+
+        async fn wait_for(
+            &mut self,
+            marker: &Marker,
+            timeout: Duration,
+            cancel: &CancellationToken,
+            on_output: &mut (dyn FnMut(&str) + Send),
+        ) -> Wait {
+            let deadline = tokio::time::Instant::now() + timeout;
+            let mut buffer = OutputBuffer::default();
+            loop {
+                if let Some((output, code, cwd)) = buffer.find(marker) {
+                    return Wait::Marker(output, code, cwd);
+                }
+                buffer.stream(on_output);
+
+                let chunk = tokio::select! {
+                    chunk = self.output.recv() => chunk,
+                    _ = tokio::time::sleep_until(deadline) => {
+                        return Wait::TimedOut(buffer.into_output());
+                    }
+                    _ = cancel.cancelled() => return Wait::Cancelled(buffer.into_output()),
+                };
+                match chunk {
+                    Some(chunk) => buffer.push(&chunk),
+                    None => return Wait::Closed(buffer.into_output()),
+                }
+            }
+        }
+    }
+
+    fn open_pty() -> Result<(std::fs::File, OwnedFd)> {
+        let mut master: libc::c_int = -1;
+        let mut slave: libc::c_int = -1;
+        let size = libc::winsize {
+            ws_row: 50,
+            ws_col: 200,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: openpty writes two new descriptors, which are owned below.
+        let rc = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &size,
+            )
+        };
+        if rc != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to open a pty");
+        }
+        // SAFETY: both descriptors were just opened and are not owned
+        // elsewhere.
+        let (master, slave) = unsafe {
+            (
+                std::fs::File::from_raw_fd(master),
+                OwnedFd::from_raw_fd(slave),
+            )
+        };
+
+        // Commands are written to the pty; without this they would be
+        // echoed back into the output.
+        // SAFETY: termios is plain data filled in by tcgetattr.
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(slave.as_raw_fd(), &mut termios) == 0 {
+                termios.c_lflag &= !(libc::ECHO | libc::ECHONL);
+                libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios);
+            }
+        }
+        Ok((master, slave))
+    }
+
+    /// Read the pty on a thread of its own until the shell side closes.
+    fn spawn_reader(mut master: std::fs::File) -> mpsc::UnboundedReceiver<Vec<u8>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            loop {
+                match master.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        rx
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use nyzhi_config::SandboxLevel;
+    use tokio_util::sync::CancellationToken;
+
+    use super::{ShellOptions, ShellRun};
+    use crate::sandbox::Launcher;
+
+    pub(super) enum ShellProcess {}
+
+    impl ShellProcess {
+        pub(super) async fn spawn(_: &ShellOptions, _: &Launcher, _: &Path) -> Result<Self> {
+            anyhow::bail!("A persistent shell is not supported on this platform")
+        }
+
+        pub(super) fn is_alive(&mut self) -> bool {
+            match *self {}
+        }
+
+        pub(super) fn level(&self) -> SandboxLevel {
+            match *self {}
+        }
+
+        pub(super) async fn run(
+            &mut self,
+            _: &str,
+            _: Duration,
+            _: &CancellationToken,
+            _: &mut (dyn FnMut(&str) + Send),
+        ) -> ShellRun {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nyzhi_config::SandboxLevel;
+
+    #[test]
+    fn marker_splits_output_code_and_cwd() {
+        let marker = Marker::new();
+        let text = format!("hello\nworld\n\n{}:3:/tmp/x\n", marker.tag);
+        let (output, code, cwd) = marker.find(&text).unwrap();
+        assert_eq!(output, "hello\nworld\n");
+        assert_eq!(code, 3);
+        assert_eq!(cwd, PathBuf::from("/tmp/x"));
+
+        // The command text does not contain the tag in one piece.
+        assert!(!marker.print("$?").contains(&marker.tag));
+        assert!(marker.find(&format!("{}:0:/tm", marker.tag)).is_none());
+    }
+
+    #[test]
+    fn output_is_decoded_incrementally_and_capped() {
+        let marker = Marker::new();
+        let mut buffer = OutputBuffer::default();
+        let mut streamed = String::new();
+        let mut on_output = |text: &str| streamed.push_str(text);
+
+        // A multi-byte character and the marker, both split across chunks.
+        let tail = format!("\n{}:0:/tmp\n", marker.tag);
+        let (tag_head, tag_tail) = tail.split_at(8);
+        for chunk in [&b"caf\xc3"[..], b"\xa9\r\n", tag_head.as_bytes()] {
+            buffer.push(chunk);
+            assert!(buffer.find(&marker).is_none());
+            buffer.stream(&mut on_output);
+        }
+        buffer.push(tag_tail.as_bytes());
+        let (output, code, _) = buffer.find(&marker).unwrap();
+        assert_eq!(output, "café\n");
+        assert_eq!(code, 0);
+
+        let mut buffer = OutputBuffer::default();
+        let line = "x".repeat(1023) + "\n";
+        for _ in 0..(MAX_KEPT_OUTPUT / 1024) * 3 {
+            buffer.push(line.as_bytes());
+            buffer.stream(&mut on_output);
+            assert!(buffer.text.len() <= MAX_KEPT_OUTPUT);
+        }
+        buffer.push(tail.as_bytes());
+        let (output, code, _) = buffer.find(&marker).unwrap();
+        assert!(output.starts_with("... (earlier output dropped)\n"));
+        assert!(output.len() <= MAX_KEPT_OUTPUT + 64);
+        assert_eq!(code, 0);
+        assert_eq!(
+            streamed.matches('x').count(),
+            1023 * (MAX_KEPT_OUTPUT / 1024) * 3
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn state_persists_between_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let launcher = Launcher::new(
+            SandboxLevel::FullAccess,
+            crate::sandbox::SandboxConfig::default(),
+            dir.path(),
+        );
+        let shell = PersistentShell::new(ShellOptions::default());
+        let cancel = CancellationToken::new();
+        let timeout = Duration::from_secs(10);
+        let run = |command: &'static str| {
+            let (shell, launcher, cancel) = (&shell, &launcher, &cancel);
+            let cwd = dir.path().to_path_buf();
+            async move {
+                shell
+                    .run(command, launcher, &cwd, timeout, cancel, &mut |_| {})
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let first = run("cd sub && export NYZ_TEST=kept").await;
+        assert_eq!(first.exit_code, Some(0));
+        assert_eq!(
+            first.cwd.unwrap().canonicalize().unwrap(),
+            dir.path().join("sub").canonicalize().unwrap()
+        );
+
+        let second = run("echo \"$NYZ_TEST $(basename \"$PWD\")\"; false").await;
+        assert_eq!(second.output.trim(), "kept sub");
+        assert_eq!(second.exit_code, Some(1));
+        assert!(!second.reset);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timed_out_command_is_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let launcher = Launcher::new(
+            SandboxLevel::FullAccess,
+            crate::sandbox::SandboxConfig::default(),
+            dir.path(),
+        );
+        let shell = PersistentShell::new(ShellOptions::default());
+        let cancel = CancellationToken::new();
+
+        let slow = shell
+            .run(
+                "export NYZ_TEST=kept; echo started; sleep 30",
+                &launcher,
+                dir.path(),
+                Duration::from_millis(500),
+                &cancel,
+                &mut |_| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(slow.outcome, ShellOutcome::TimedOut);
+        assert_eq!(slow.output.trim(), "started");
+        assert!(!slow.reset);
+
+        let next = shell
+            .run(
+                "echo $NYZ_TEST",
+                &launcher,
+                dir.path(),
+                Duration::from_secs(10),
+                &cancel,
+                &mut |_| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(next.output.trim(), "kept");
+    }
+}
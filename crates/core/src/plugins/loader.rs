@@ -1,17 +1,22 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use nyzhi_config::{HookConfig, McpServerConfig};
+use serde::Deserialize;
 
-use super::manifest::PluginManifest;
+use super::manifest::{PluginManifest, PLUGIN_ROOT_VAR};
 use crate::skills::Skill;
 
+/// A plugin's contributions, with names as the plugin declares them.
 #[derive(Debug, Clone)]
 pub struct LoadedPlugin {
     pub manifest: PluginManifest,
+    pub root: PathBuf,
     pub skills: Vec<Skill>,
-    pub agent_files: Vec<String>,
-    pub hook_file: Option<String>,
-    pub mcp_config: Option<String>,
+    pub agent_files: Vec<PathBuf>,
+    pub hooks: Vec<HookConfig>,
+    pub mcp_servers: HashMap<String, McpServerConfig>,
 }
 
 /// Load a single plugin from its directory.
@@ -25,37 +30,25 @@ pub fn load_plugin(plugin_root: &Path) -> Result<LoadedPlugin> {
             if let Ok(entries) = std::fs::read_dir(&skills_dir) {
                 for entry in entries.flatten() {
                     let path = entry.path();
-                    if path.is_dir() {
-                        let skill_file = path.join("SKILL.md");
-                        if skill_file.exists() {
-                            if let Ok(content) = std::fs::read_to_string(&skill_file) {
-                                let name = path
-                                    .file_name()
-                                    .and_then(|n| n.to_str())
-                                    .unwrap_or("unknown")
-                                    .to_string();
-                                skills.push(Skill {
-                                    name,
-                                    content,
-                                    path: skill_file,
-                                    description: None,
-                                });
-                            }
-                        }
+                    let (name, skill_file) = if path.is_dir() {
+                        (path.file_name(), path.join("SKILL.md"))
                     } else if path.extension().and_then(|e| e.to_str()) == Some("md") {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            let name = path
-                                .file_stem()
-                                .and_then(|n| n.to_str())
-                                .unwrap_or("unknown")
-                                .to_string();
-                            skills.push(Skill {
-                                name,
-                                content,
-                                path: path.clone(),
-                                description: None,
-                            });
-                        }
+                        (path.file_stem(), path.clone())
+                    } else {
+                        continue;
+                    };
+                    let name = name
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("unknown")
+                        .to_string();
+                    if let Ok(content) = std::fs::read_to_string(&skill_file) {
+                        let description = crate::skills::extract_description(&content);
+                        skills.push(Skill {
+                            name,
+                            content,
+                            path: skill_file,
+                            description,
+                        });
                     }
                 }
             }
@@ -71,7 +64,7 @@ pub fn load_plugin(plugin_root: &Path) -> Result<LoadedPlugin> {
                     for entry in entries.flatten() {
                         if let Some(name) = entry.file_name().to_str() {
                             if name.ends_with(".md") {
-                                agent_files.push(entry.path().display().to_string());
+                                agent_files.push(entry.path());
                             }
                         }
                     }
@@ -79,39 +72,81 @@ pub fn load_plugin(plugin_root: &Path) -> Result<LoadedPlugin> {
             }
         }
     }
+    agent_files.sort();
 
-    let hook_file = manifest
-        .hooks
-        .as_ref()
-        .map(|h| manifest.resolve_path(plugin_root, h).display().to_string());
+    let hooks = match manifest.hooks {
+        Some(ref h) => load_hooks(&manifest.resolve_path(plugin_root, h), plugin_root)?,
+        None => Vec::new(),
+    };
 
-    let mcp_config = manifest.mcp_servers.as_ref().and_then(|m| {
-        let path = manifest.resolve_path(plugin_root, m);
-        std::fs::read_to_string(&path).ok()
-    });
+    let mcp_servers = match manifest.mcp_servers {
+        Some(ref m) => {
+            let path = manifest.resolve_path(plugin_root, m);
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let content = content.replace(PLUGIN_ROOT_VAR, &plugin_root.display().to_string());
+            crate::mcp::parse_mcp_json(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        }
+        None => HashMap::new(),
+    };
 
     Ok(LoadedPlugin {
         manifest,
+        root: plugin_root.to_path_buf(),
         skills,
         agent_files,
-        hook_file,
-        mcp_config,
+        hooks,
+        mcp_servers,
     })
 }
 
-/// Scan a directory for plugins.
-pub fn scan_plugins(dir: &Path) -> Vec<LoadedPlugin> {
+/// Hooks file: `{"hooks": [...]}` as JSON, or `[[hooks]]` tables as TOML,
+/// with entries shaped like `[[agent.hooks]]` in the config.
+fn load_hooks(path: &Path, plugin_root: &Path) -> Result<Vec<HookConfig>> {
+    #[derive(Deserialize)]
+    struct HooksFile {
+        #[serde(default)]
+        hooks: Vec<HookConfig>,
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let parsed: HooksFile = if path.extension().and_then(|e| e.to_str()) == Some("toml") {
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?
+    } else {
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?
+    };
+
+    let root = plugin_root.display().to_string();
+    Ok(parsed
+        .hooks
+        .into_iter()
+        .map(|mut hook| {
+            hook.command = hook.command.replace(PLUGIN_ROOT_VAR, &root);
+            hook
+        })
+        .collect())
+}
+
+/// Scan a directory for plugins. Directories that fail to load are returned
+/// as errors so callers can report them.
+pub fn scan_plugins(dir: &Path) -> Vec<Result<LoadedPlugin>> {
     if !dir.exists() {
         return vec![];
     }
     let mut plugins = Vec::new();
     if let Ok(entries) = std::fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
-                if let Ok(plugin) = load_plugin(&entry.path()) {
-                    plugins.push(plugin);
-                }
-            }
+        let mut dirs: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|e| e.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
+            .map(|e| e.path())
+            .collect();
+        dirs.sort();
+        for path in dirs {
+            plugins
+                .push(load_plugin(&path).with_context(|| format!("plugin at {}", path.display())));
         }
     }
     plugins
@@ -1,41 +1,95 @@
+use std::collections::HashMap;
 use std::path::Path;
 
 use anyhow::{Context, Result};
 
 use super::loader::{self, LoadedPlugin};
-use super::{local_plugins_dir, project_plugins_dir, user_plugins_dir, PluginScope};
+use super::manifest::{validate_name, PluginManifest};
+use super::{namespaced, namespaced_server, PluginScope, PluginState};
+use crate::agent_roles::AgentRoleConfig;
 
 #[derive(Debug)]
 pub struct PluginManager {
+    /// Enabled plugins, at most one per name.
     pub plugins: Vec<(PluginScope, LoadedPlugin)>,
+    /// Plugins that failed to load or were shadowed by a more specific scope.
+    pub conflicts: Vec<String>,
+}
+
+/// An installed plugin as shown by `nyz plugin list`.
+#[derive(Debug)]
+pub struct InstalledPlugin {
+    pub name: String,
+    pub scope: PluginScope,
+    pub enabled: bool,
+    pub manifest: Result<PluginManifest>,
 }
 
 impl PluginManager {
     /// Load all enabled plugins from all scopes (local > project > user).
     pub fn load_all(project_root: &Path) -> Self {
-        let mut plugins = Vec::new();
+        let mut plugins: Vec<(PluginScope, LoadedPlugin)> = Vec::new();
+        let mut conflicts = Vec::new();
 
-        for p in loader::scan_plugins(&local_plugins_dir(project_root)) {
-            plugins.push((PluginScope::Local, p));
-        }
-        for p in loader::scan_plugins(&project_plugins_dir(project_root)) {
-            plugins.push((PluginScope::Project, p));
+        for scope in PluginScope::ALL {
+            let dir = scope.dir(project_root);
+            let state = PluginState::load(&dir);
+            for result in loader::scan_plugins(&dir) {
+                let plugin = match result {
+                    Ok(plugin) => plugin,
+                    Err(e) => {
+                        conflicts.push(format!("Failed to load {e:#}"));
+                        continue;
+                    }
+                };
+                let name = &plugin.manifest.name;
+                if state.disabled.contains(name) {
+                    continue;
+                }
+                if let Some((winner, _)) = plugins.iter().find(|(_, p)| &p.manifest.name == name) {
+                    conflicts.push(format!(
+                        "Plugin '{name}' in {scope} scope is shadowed by the {winner} one"
+                    ));
+                    continue;
+                }
+                plugins.push((scope.clone(), plugin));
+            }
         }
-        for p in loader::scan_plugins(&user_plugins_dir()) {
-            plugins.push((PluginScope::User, p));
+
+        Self { plugins, conflicts }
+    }
+
+    /// Install a plugin from a local directory or a git URL.
+    pub fn install(project_root: &Path, source: &str, scope: PluginScope) -> Result<String> {
+        if !is_git_url(source) {
+            return Self::install_local(project_root, Path::new(source), scope);
         }
 
-        Self { plugins }
+        let temp = tempfile::Builder::new()
+            .prefix("nyzhi-plugin-")
+            .tempdir()
+            .context("Failed to create a directory for the clone")?;
+        let checkout = temp.path().join("plugin");
+        // `--` keeps a source such as `--upload-pack=...` from being read as
+        // an option.
+        let output = std::process::Command::new("git")
+            .args(["clone", "--depth", "1", "--quiet", "--", source])
+            .arg(&checkout)
+            .output()
+            .context("Failed to run git")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git clone {source} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Self::install_local(project_root, &checkout, scope)
     }
 
     /// Install a plugin from a local path.
     pub fn install_local(project_root: &Path, source: &Path, scope: PluginScope) -> Result<String> {
         let manifest = super::manifest::PluginManifest::load(source)?;
-        let dest_dir = match scope {
-            PluginScope::Local => local_plugins_dir(project_root),
-            PluginScope::Project => project_plugins_dir(project_root),
-            PluginScope::User => user_plugins_dir(),
-        };
+        let dest_dir = scope.dir(project_root);
 
         let plugin_dir = dest_dir.join(&manifest.name);
         if plugin_dir.exists() {
@@ -50,62 +104,157 @@ impl PluginManager {
         Ok(manifest.name)
     }
 
-    /// Uninstall a plugin by name from a scope.
-    pub fn uninstall(project_root: &Path, name: &str, scope: PluginScope) -> Result<()> {
-        let dir = match scope {
-            PluginScope::Local => local_plugins_dir(project_root),
-            PluginScope::Project => project_plugins_dir(project_root),
-            PluginScope::User => user_plugins_dir(),
-        };
+    /// Uninstall a plugin by name from a scope, or from the most specific
+    /// scope that has it.
+    pub fn uninstall(
+        project_root: &Path,
+        name: &str,
+        scope: Option<PluginScope>,
+    ) -> Result<PluginScope> {
+        let (scope, dir) = find_installed(project_root, name, scope)?;
+        std::fs::remove_dir_all(dir.join(name))
+            .with_context(|| format!("Failed to remove plugin '{}'", name))?;
 
-        let plugin_dir = dir.join(name);
-        if plugin_dir.exists() {
-            std::fs::remove_dir_all(&plugin_dir)
-                .with_context(|| format!("Failed to remove plugin '{}'", name))?;
+        let mut state = PluginState::load(&dir);
+        if state.disabled.iter().any(|n| n == name) {
+            state.disabled.retain(|n| n != name);
+            state.save(&dir)?;
         }
-        Ok(())
+        Ok(scope)
     }
 
-    /// List installed plugin names with scope.
-    pub fn list_installed(project_root: &Path) -> Vec<(String, PluginScope)> {
+    /// Enable or disable an installed plugin. Takes effect in new sessions.
+    pub fn set_enabled(
+        project_root: &Path,
+        name: &str,
+        scope: Option<PluginScope>,
+        enabled: bool,
+    ) -> Result<PluginScope> {
+        let (scope, dir) = find_installed(project_root, name, scope)?;
+        let mut state = PluginState::load(&dir);
+        state.disabled.retain(|n| n != name);
+        if !enabled {
+            state.disabled.push(name.to_string());
+        }
+        state.save(&dir)?;
+        Ok(scope)
+    }
+
+    /// List installed plugins in every scope, including disabled and broken ones.
+    pub fn list_installed(project_root: &Path) -> Vec<InstalledPlugin> {
         let mut installed = Vec::new();
 
-        for (scope, dir) in [
-            (PluginScope::Local, local_plugins_dir(project_root)),
-            (PluginScope::Project, project_plugins_dir(project_root)),
-            (PluginScope::User, user_plugins_dir()),
-        ] {
-            if let Ok(entries) = std::fs::read_dir(&dir) {
-                for entry in entries.flatten() {
-                    if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
-                        if let Some(name) = entry.file_name().to_str() {
-                            installed.push((name.to_string(), scope.clone()));
-                        }
-                    }
+        for scope in PluginScope::ALL {
+            let dir = scope.dir(project_root);
+            let state = PluginState::load(&dir);
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut entries: Vec<_> = entries
+                .flatten()
+                .filter(|e| e.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
+                .collect();
+            entries.sort_by_key(|e| e.file_name());
+            for entry in entries {
+                if let Some(name) = entry.file_name().to_str() {
+                    installed.push(InstalledPlugin {
+                        name: name.to_string(),
+                        scope: scope.clone(),
+                        enabled: !state.disabled.iter().any(|n| n == name),
+                        manifest: PluginManifest::load(&entry.path()),
+                    });
                 }
             }
         }
         installed
     }
 
-    /// Get all skills from loaded plugins.
+    /// Get all skills from loaded plugins, named `plugin:skill`.
     pub fn all_skills(&self) -> Vec<crate::skills::Skill> {
         let mut skills = Vec::new();
-        let mut seen = std::collections::HashSet::new();
         for (_scope, plugin) in &self.plugins {
             for skill in &plugin.skills {
-                if seen.insert(skill.name.clone()) {
-                    skills.push(skill.clone());
-                }
+                let mut skill = skill.clone();
+                skill.name = namespaced(&plugin.manifest.name, &skill.name);
+                skills.push(skill);
             }
         }
         skills
     }
+
+    /// Agent roles from loaded plugins, keyed `plugin:role`.
+    pub fn agent_roles(&self) -> HashMap<String, AgentRoleConfig> {
+        let mut roles = HashMap::new();
+        for (_scope, plugin) in &self.plugins {
+            for path in &plugin.agent_files {
+                if let Some((key, mut role)) = crate::agent_files::load_role_file(path) {
+                    role.name = namespaced(&plugin.manifest.name, &role.name);
+                    roles.insert(namespaced(&plugin.manifest.name, &key), role);
+                }
+            }
+        }
+        roles
+    }
+
+    /// Add plugin hooks and MCP servers to `config`. Returns the load
+    /// conflicts plus any server whose name is already configured, which is
+    /// skipped.
+    pub fn merge_into(&self, config: &mut nyzhi_config::Config) -> Vec<String> {
+        let mut conflicts = self.conflicts.clone();
+        for (_scope, plugin) in &self.plugins {
+            let plugin_name = &plugin.manifest.name;
+            config.agent.hooks.extend(plugin.hooks.iter().cloned());
+
+            let mut servers: Vec<_> = plugin.mcp_servers.iter().collect();
+            servers.sort_by_key(|(name, _)| name.as_str());
+            for (server, server_config) in servers {
+                let name = namespaced_server(plugin_name, server);
+                if config.mcp.servers.contains_key(&name) {
+                    conflicts.push(format!(
+                        "MCP server '{name}' from plugin '{plugin_name}' is already configured; keeping the configured one"
+                    ));
+                    continue;
+                }
+                config.mcp.servers.insert(name, server_config.clone());
+            }
+        }
+        conflicts
+    }
+}
+
+fn find_installed(
+    project_root: &Path,
+    name: &str,
+    scope: Option<PluginScope>,
+) -> Result<(PluginScope, std::path::PathBuf)> {
+    // The name becomes a path component that is later removed recursively.
+    validate_name(name)?;
+    let scopes = match scope {
+        Some(scope) => vec![scope],
+        None => PluginScope::ALL.to_vec(),
+    };
+    for scope in scopes {
+        let dir = scope.dir(project_root);
+        if dir.join(name).is_dir() {
+            return Ok((scope, dir));
+        }
+    }
+    anyhow::bail!("Plugin '{name}' is not installed")
+}
+
+fn is_git_url(source: &str) -> bool {
+    ["https://", "http://", "ssh://", "git://", "git@"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+        || (source.ends_with(".git") && !Path::new(source).exists())
 }
 
 fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
     std::fs::create_dir_all(dst)?;
     for entry in std::fs::read_dir(src)?.flatten() {
+        if entry.file_name() == ".git" {
+            continue;
+        }
         let src_path = entry.path();
         let dst_path = dst.join(entry.file_name());
         if src_path.is_dir() {
@@ -116,3 +265,121 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_plugin(dir: &Path, name: &str) {
+        let root = dir.join(name);
+        std::fs::create_dir_all(root.join(".nyzhi-plugin")).unwrap();
+        std::fs::create_dir_all(root.join("skills/review")).unwrap();
+        std::fs::create_dir_all(root.join("agents")).unwrap();
+        std::fs::write(
+            root.join(".nyzhi-plugin/plugin.json"),
+            format!(
+                r#"{{"name": "{name}", "skills": "skills", "agents": ["agents"],
+                    "hooks": "hooks.json", "mcpServers": ".mcp.json"}}"#
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("skills/review/SKILL.md"),
+            "description: Review code\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("agents/auditor.md"),
+            "---\nname: auditor\n---\nAudit.",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("hooks.json"),
+            r#"{"hooks": [{"event": "after_edit", "command": "${NYZHI_PLUGIN_ROOT}/fmt.sh"}]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join(".mcp.json"),
+            r#"{"mcpServers": {"docs": {"command": "${NYZHI_PLUGIN_ROOT}/server"}}}"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn loads_namespaced_contributions() {
+        let project = tempfile::tempdir().unwrap();
+        let root = project.path();
+        write_plugin(&PluginScope::Project.dir(root), "acme");
+        write_plugin(&PluginScope::Local.dir(root), "acme");
+
+        let manager = PluginManager::load_all(root);
+        let acme: Vec<_> = manager
+            .plugins
+            .iter()
+            .filter(|(_, p)| p.manifest.name == "acme")
+            .collect();
+        assert_eq!(acme.len(), 1);
+        assert_eq!(acme[0].0, PluginScope::Local);
+        assert!(manager.conflicts.iter().any(|c| c.contains("shadowed")));
+
+        assert!(manager
+            .all_skills()
+            .iter()
+            .any(|s| s.name == "acme:review" && s.description.as_deref() == Some("Review code")));
+        assert_eq!(manager.agent_roles()["acme:auditor"].name, "acme:auditor");
+
+        let mut config = nyzhi_config::Config::default();
+        config.mcp.servers.insert(
+            "acme-docs".into(),
+            nyzhi_config::McpServerConfig::Http {
+                url: "http://localhost".into(),
+                headers: HashMap::new(),
+            },
+        );
+        let conflicts = manager.merge_into(&mut config);
+        assert!(conflicts.iter().any(|c| c.contains("acme-docs")));
+        let plugin_root = PluginScope::Local.dir(root).join("acme");
+        assert!(config
+            .agent
+            .hooks
+            .iter()
+            .any(|h| h.command == format!("{}/fmt.sh", plugin_root.display())));
+    }
+
+    #[test]
+    fn disabled_plugins_are_skipped() {
+        let project = tempfile::tempdir().unwrap();
+        let root = project.path();
+        write_plugin(&PluginScope::Project.dir(root), "acme");
+
+        PluginManager::set_enabled(root, "acme", None, false).unwrap();
+        assert!(!PluginManager::load_all(root)
+            .plugins
+            .iter()
+            .any(|(_, p)| p.manifest.name == "acme"));
+        let listed = PluginManager::list_installed(root);
+        assert!(listed.iter().any(|p| p.name == "acme" && !p.enabled));
+
+        PluginManager::set_enabled(root, "acme", None, true).unwrap();
+        assert!(PluginManager::load_all(root)
+            .plugins
+            .iter()
+            .any(|(_, p)| p.manifest.name == "acme"));
+
+        PluginManager::uninstall(root, "acme", Some(PluginScope::Project)).unwrap();
+        assert!(PluginManager::uninstall(root, "acme", None).is_err());
+    }
+
+    #[test]
+    fn names_that_are_not_plugin_names_are_rejected() {
+        let project = tempfile::tempdir().unwrap();
+        let root = project.path();
+        write_plugin(&PluginScope::Project.dir(root), "acme");
+
+        for name in ["..", "", ".", "acme/..", "../plugins"] {
+            assert!(PluginManager::uninstall(root, name, None).is_err());
+            assert!(PluginManager::set_enabled(root, name, None, false).is_err());
+        }
+        assert!(PluginScope::Project.dir(root).join("acme").is_dir());
+    }
+}
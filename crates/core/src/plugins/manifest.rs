@@ -3,6 +3,10 @@ use std::path::{Path, PathBuf};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+/// Placeholder for the plugin's install directory in manifest paths, hook
+/// commands and MCP server definitions.
+pub const PLUGIN_ROOT_VAR: &str = "${NYZHI_PLUGIN_ROOT}";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginManifest {
     pub name: String,
@@ -52,20 +56,23 @@ impl PluginManifest {
 
         let mut manifest: Self =
             serde_json::from_str(&content).context("Failed to parse plugin manifest")?;
+        validate_name(&manifest.name)?;
 
         let root_str = plugin_root.display().to_string();
-        if let Some(ref mut cmds) = manifest.commands {
-            *cmds = cmds.replace("${NYZHI_PLUGIN_ROOT}", &root_str);
-        }
-        if let Some(ref mut skills) = manifest.skills {
-            *skills = skills.replace("${NYZHI_PLUGIN_ROOT}", &root_str);
-        }
-        if let Some(ref mut hooks) = manifest.hooks {
-            *hooks = hooks.replace("${NYZHI_PLUGIN_ROOT}", &root_str);
+        for path in [
+            &mut manifest.commands,
+            &mut manifest.skills,
+            &mut manifest.hooks,
+            &mut manifest.mcp_servers,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = path.replace(PLUGIN_ROOT_VAR, &root_str);
         }
         if let Some(ref mut agents) = manifest.agents {
             for a in agents.iter_mut() {
-                *a = a.replace("${NYZHI_PLUGIN_ROOT}", &root_str);
+                *a = a.replace(PLUGIN_ROOT_VAR, &root_str);
             }
         }
 
@@ -78,6 +85,19 @@ impl PluginManifest {
     }
 }
 
+/// Plugin names become directory names and prefixes, so they are limited to
+/// ASCII letters, digits, `-` and `_`.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        anyhow::bail!("Invalid plugin name '{name}': use letters, digits, '-' and '_'");
+    }
+    Ok(())
+}
+
 /// Detect if a directory is a plugin (has .nyzhi-plugin/plugin.json).
 pub fn is_plugin_dir(dir: &Path) -> bool {
     dir.join(".nyzhi-plugin").join("plugin.json").exists()
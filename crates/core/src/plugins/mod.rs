@@ -2,7 +2,10 @@ pub mod loader;
 pub mod manager;
 pub mod manifest;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
 /// Plugin scopes, from most specific to least.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,17 +25,82 @@ impl std::fmt::Display for PluginScope {
     }
 }
 
+impl std::str::FromStr for PluginScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "local" => Ok(PluginScope::Local),
+            "project" => Ok(PluginScope::Project),
+            "user" | "global" => Ok(PluginScope::User),
+            other => {
+                anyhow::bail!("Unknown plugin scope '{other}' (expected user, project or local)")
+            }
+        }
+    }
+}
+
+impl PluginScope {
+    pub const ALL: [PluginScope; 3] = [PluginScope::Local, PluginScope::Project, PluginScope::User];
+
+    /// Directory holding this scope's plugins.
+    pub fn dir(&self, project_root: &Path) -> PathBuf {
+        match self {
+            PluginScope::Local => local_plugins_dir(project_root),
+            PluginScope::Project => project_plugins_dir(project_root),
+            PluginScope::User => user_plugins_dir(),
+        }
+    }
+}
+
+/// Prefix for names a plugin contributes: skills and agent roles become
+/// `plugin:name`. MCP servers become `plugin-name`, since the server name is
+/// part of tool names, which only allow `[A-Za-z0-9_-]`.
+pub fn namespaced(plugin: &str, name: &str) -> String {
+    format!("{plugin}:{name}")
+}
+
+pub fn namespaced_server(plugin: &str, server: &str) -> String {
+    format!("{plugin}-{server}")
+}
+
+/// Per-scope plugin state, kept in `plugins.toml` next to the plugins.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct PluginState {
+    #[serde(default)]
+    pub disabled: Vec<String>,
+}
+
+impl PluginState {
+    fn path(dir: &Path) -> PathBuf {
+        dir.join("plugins.toml")
+    }
+
+    pub(crate) fn load(dir: &Path) -> Self {
+        std::fs::read_to_string(Self::path(dir))
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(Self::path(dir), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
 fn user_plugins_dir() -> PathBuf {
-    dirs::data_dir()
+    dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
-        .join("nyzhi")
+        .join(".nyzhi")
         .join("plugins")
 }
 
-fn project_plugins_dir(root: &std::path::Path) -> PathBuf {
+fn project_plugins_dir(root: &Path) -> PathBuf {
     root.join(".nyzhi").join("plugins")
 }
 
-fn local_plugins_dir(root: &std::path::Path) -> PathBuf {
+fn local_plugins_dir(root: &Path) -> PathBuf {
     root.join(".nyzhi").join("plugins.local")
 }
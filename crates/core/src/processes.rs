@@ -0,0 +1,373 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
+
+use crate::sandbox::{isolate_process_group, Launcher, ProcessGroup};
+
+/// Largest chunk of new output returned by one read; older output is
+/// skipped and left in the log.
+const MAX_READ_BYTES: u64 = 32 * 1024;
+
+pub type ProcessManagerHandle = Arc<ProcessManager>;
+
+pub fn shared_manager() -> ProcessManagerHandle {
+    Arc::new(ProcessManager::new())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessStatus {
+    Running,
+    /// Exited on its own; `None` when it was ended by a signal.
+    Exited(Option<i32>),
+    Killed,
+}
+
+impl std::fmt::Display for ProcessStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessStatus::Running => write!(f, "running"),
+            ProcessStatus::Exited(Some(code)) => write!(f, "exited ({code})"),
+            ProcessStatus::Exited(None) => write!(f, "exited (signal)"),
+            ProcessStatus::Killed => write!(f, "killed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub id: usize,
+    pub command: String,
+    pub cwd: PathBuf,
+    /// Combined stdout and stderr of the process.
+    pub log_path: PathBuf,
+    pub started_at: DateTime<Utc>,
+    pub status: ProcessStatus,
+}
+
+/// Output written since the previous read.
+#[derive(Debug)]
+pub struct ProcessOutput {
+    pub text: String,
+    /// Bytes of new output not returned because there was too much.
+    pub skipped: u64,
+    pub status: ProcessStatus,
+}
+
+struct ManagedProcess {
+    id: usize,
+    command: String,
+    cwd: PathBuf,
+    log_path: PathBuf,
+    started_at: DateTime<Utc>,
+    status: Arc<Mutex<ProcessStatus>>,
+    stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+    /// Released once the process exits, since its id may then be reused.
+    group: Arc<Mutex<Option<ProcessGroup>>>,
+    /// How far into the log the agent has read.
+    read_offset: u64,
+}
+
+impl ManagedProcess {
+    fn info(&self) -> ProcessInfo {
+        ProcessInfo {
+            id: self.id,
+            command: self.command.clone(),
+            cwd: self.cwd.clone(),
+            log_path: self.log_path.clone(),
+            started_at: self.started_at,
+            status: *self.status.lock().unwrap(),
+        }
+    }
+}
+
+/// Shell commands left running in the background across turns, such as dev
+/// servers, watchers and long test runs. Output goes to a log file the agent
+/// reads incrementally. Every process runs in its own process group, which
+/// is killed with [`Self::kill`], [`Self::kill_all`] or when the manager is
+/// dropped, as long as the process itself is still running.
+pub struct ProcessManager {
+    processes: Mutex<Vec<ManagedProcess>>,
+    next_id: AtomicUsize,
+}
+
+impl Default for ProcessManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessManager {
+    pub fn new() -> Self {
+        Self {
+            processes: Mutex::new(Vec::new()),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    /// Start `command` through `launcher`, logging its output to a new file
+    /// in `log_dir`.
+    pub fn start(
+        &self,
+        command: &str,
+        launcher: &Launcher,
+        cwd: &Path,
+        log_dir: &Path,
+    ) -> Result<ProcessInfo> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let started_at = Utc::now();
+        std::fs::create_dir_all(log_dir)?;
+        let log_path = log_dir.join(format!("{}-{id}.log", started_at.format("%Y%m%dT%H%M%S")));
+        let log = std::fs::File::create(&log_path)
+            .with_context(|| format!("Failed to create {}", log_path.display()))?;
+
+        let mut shell = launcher.shell(command, cwd)?;
+        shell
+            .stdin(Stdio::piped())
+            .stdout(Stdio::from(log.try_clone()?))
+            .stderr(Stdio::from(log));
+        isolate_process_group(&mut shell);
+        let mut child = shell
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn command: {e}"))?;
+        let group = Arc::new(Mutex::new(Some(ProcessGroup::new(&child))));
+        let stdin = Arc::new(tokio::sync::Mutex::new(child.stdin.take()));
+
+        let status = Arc::new(Mutex::new(ProcessStatus::Running));
+        let exit_status = status.clone();
+        let exit_group = group.clone();
+        tokio::spawn(async move {
+            let code = child.wait().await.ok().and_then(|s| s.code());
+            if let Some(group) = exit_group.lock().unwrap().take() {
+                group.release();
+            }
+            let mut status = exit_status.lock().unwrap();
+            if *status == ProcessStatus::Running {
+                *status = ProcessStatus::Exited(code);
+            }
+        });
+
+        let process = ManagedProcess {
+            id,
+            command: command.to_string(),
+            cwd: cwd.to_path_buf(),
+            log_path,
+            started_at,
+            status,
+            stdin,
+            group,
+            read_offset: 0,
+        };
+        let info = process.info();
+        self.processes.lock().unwrap().push(process);
+        Ok(info)
+    }
+
+    pub fn list(&self) -> Vec<ProcessInfo> {
+        self.processes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(ManagedProcess::info)
+            .collect()
+    }
+
+    pub fn get(&self, id: usize) -> Option<ProcessInfo> {
+        self.processes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.id == id)
+            .map(ManagedProcess::info)
+    }
+
+    pub fn running_count(&self) -> usize {
+        self.list()
+            .iter()
+            .filter(|p| p.status == ProcessStatus::Running)
+            .count()
+    }
+
+    /// Output logged since the last call for this process. When more than
+    /// [`MAX_READ_BYTES`] is new, only the most recent part is returned.
+    pub fn read_new(&self, id: usize) -> Result<ProcessOutput> {
+        let mut processes = self.processes.lock().unwrap();
+        let process = processes
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| anyhow::anyhow!("No background process with id {id}"))?;
+        // Read the status first so output written before an exit is not
+        // missed.
+        let status = *process.status.lock().unwrap();
+
+        let mut file = std::fs::File::open(&process.log_path)
+            .with_context(|| format!("Failed to open {}", process.log_path.display()))?;
+        let end = file.metadata()?.len();
+        let offset = process.read_offset.min(end);
+        let start = offset.max(end.saturating_sub(MAX_READ_BYTES));
+        let skipped = start - offset;
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::with_capacity((end - start) as usize);
+        file.by_ref().take(end - start).read_to_end(&mut bytes)?;
+        process.read_offset = end;
+
+        Ok(ProcessOutput {
+            text: String::from_utf8_lossy(&bytes).into_owned(),
+            skipped,
+            status,
+        })
+    }
+
+    /// Write `text` to the process's stdin, closing it afterwards if `close`.
+    pub async fn send(&self, id: usize, text: &str, close: bool) -> Result<()> {
+        let (stdin, status) = {
+            let processes = self.processes.lock().unwrap();
+            let process = processes
+                .iter()
+                .find(|p| p.id == id)
+                .ok_or_else(|| anyhow::anyhow!("No background process with id {id}"))?;
+            let status = *process.status.lock().unwrap();
+            (process.stdin.clone(), status)
+        };
+        if status != ProcessStatus::Running {
+            anyhow::bail!("Background process {id} is not running ({status})");
+        }
+
+        let mut stdin = stdin.lock().await;
+        let pipe = stdin
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("stdin of background process {id} is closed"))?;
+        pipe.write_all(text.as_bytes()).await?;
+        pipe.flush().await?;
+        if close {
+            *stdin = None;
+        }
+        Ok(())
+    }
+
+    /// Kill the process and everything it started.
+    pub fn kill(&self, id: usize) -> Result<ProcessInfo> {
+        let mut processes = self.processes.lock().unwrap();
+        let process = processes
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| anyhow::anyhow!("No background process with id {id}"))?;
+        Self::kill_process(process);
+        Ok(process.info())
+    }
+
+    /// Kill every process still running; returns how many there were.
+    pub fn kill_all(&self) -> usize {
+        let mut processes = self.processes.lock().unwrap();
+        processes
+            .iter_mut()
+            .filter(|p| *p.status.lock().unwrap() == ProcessStatus::Running)
+            .map(Self::kill_process)
+            .count()
+    }
+
+    fn kill_process(process: &mut ManagedProcess) {
+        {
+            let mut status = process.status.lock().unwrap();
+            if *status == ProcessStatus::Running {
+                *status = ProcessStatus::Killed;
+            }
+        }
+        if let Some(mut group) = process.group.lock().unwrap().take() {
+            group.kill();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use nyzhi_config::SandboxLevel;
+
+    fn launcher(root: &Path) -> Launcher {
+        Launcher::new(
+            SandboxLevel::FullAccess,
+            crate::sandbox::SandboxConfig::default(),
+            root,
+        )
+    }
+
+    async fn wait_for(manager: &ProcessManager, id: usize, needle: &str) -> String {
+        let mut seen = String::new();
+        for _ in 0..100 {
+            seen.push_str(&manager.read_new(id).unwrap().text);
+            if seen.contains(needle) {
+                return seen;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("never saw {needle:?} in {seen:?}");
+    }
+
+    #[tokio::test]
+    async fn reads_only_new_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ProcessManager::new();
+        let info = manager
+            .start(
+                "echo one; read line; echo \"got $line\" >&2",
+                &launcher(dir.path()),
+                dir.path(),
+                &dir.path().join("logs"),
+            )
+            .unwrap();
+        assert!(info.log_path.starts_with(dir.path().join("logs")));
+
+        assert_eq!(wait_for(&manager, info.id, "one").await.trim(), "one");
+        manager.send(info.id, "two\n", true).await.unwrap();
+        assert_eq!(
+            wait_for(&manager, info.id, "got two").await.trim(),
+            "got two"
+        );
+
+        for _ in 0..100 {
+            if manager.get(info.id).unwrap().status != ProcessStatus::Running {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert_eq!(
+            manager.get(info.id).unwrap().status,
+            ProcessStatus::Exited(Some(0))
+        );
+        // Its group id may be reused now, so killing it later does nothing.
+        assert!(manager.processes.lock().unwrap()[0]
+            .group
+            .lock()
+            .unwrap()
+            .is_none());
+        assert!(manager.read_new(info.id).unwrap().text.is_empty());
+    }
+
+    #[tokio::test]
+    async fn kill_all_stops_running_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ProcessManager::new();
+        let info = manager
+            .start(
+                "echo ready; sleep 30",
+                &launcher(dir.path()),
+                dir.path(),
+                dir.path(),
+            )
+            .unwrap();
+        wait_for(&manager, info.id, "ready").await;
+
+        assert_eq!(manager.running_count(), 1);
+        assert_eq!(manager.kill_all(), 1);
+        assert_eq!(manager.get(info.id).unwrap().status, ProcessStatus::Killed);
+        assert!(manager.send(info.id, "x", false).await.is_err());
+        assert_eq!(manager.kill_all(), 0);
+    }
+}
@@ -65,7 +65,7 @@ pub fn plan_mode_instructions() -> &'static str {
 You are in **Plan Mode**. Analyze the codebase, design an approach, then persist a plan.
 
 ## Restrictions
-- Only read-only tools: read, grep, glob, fuzzy_find, semantic_search, think, ask_user, create_plan, web_search, web_fetch.
+- Only read-only tools: read, grep, glob, fuzzy_find, semantic_search, code_graph, think, ask_user, create_plan, web_search, web_fetch.
 - Mutating tools (write, edit, bash) are blocked.
 
 ## Workflow
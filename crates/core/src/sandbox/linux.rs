@@ -0,0 +1,406 @@
+//! In-process sandbox for Linux. Landlock limits the filesystem to an
+//! allow-list and a seccomp filter refuses every socket that is not a Unix
+//! socket. The ruleset and filter are built in the parent; the forked child
+//! only makes the syscalls that install them before exec, so nothing in the
+//! child allocates.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::SandboxPolicy;
+
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+/// ABI 2: renaming or linking a file into another directory.
+const ACCESS_FS_REFER: u64 = 1 << 13;
+/// ABI 3: truncating a file without opening it for writing.
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+const READ_ACCESS: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+const WRITE_ACCESS: u64 = READ_ACCESS
+    | ACCESS_FS_WRITE_FILE
+    | ACCESS_FS_REMOVE_DIR
+    | ACCESS_FS_REMOVE_FILE
+    | ACCESS_FS_MAKE_CHAR
+    | ACCESS_FS_MAKE_DIR
+    | ACCESS_FS_MAKE_REG
+    | ACCESS_FS_MAKE_SOCK
+    | ACCESS_FS_MAKE_FIFO
+    | ACCESS_FS_MAKE_BLOCK
+    | ACCESS_FS_MAKE_SYM
+    | ACCESS_FS_REFER
+    | ACCESS_FS_TRUNCATE;
+/// Rights that make sense on a single file; the kernel rejects the
+/// directory rights in a rule whose target is not a directory.
+const FILE_ACCESS: u64 =
+    ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE | ACCESS_FS_TRUNCATE;
+
+const CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+/// System locations commands need to run at all. Missing ones are skipped.
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/etc", "/opt", "/nix",
+    "/proc", "/sys", "/dev",
+];
+const SYSTEM_WRITE_PATHS: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/tty",
+    "/dev/pts",
+    "/dev/shm",
+];
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: RawFd,
+}
+
+/// Restrictions to install in a child process between fork and exec.
+pub(super) struct Restrictions {
+    ruleset: OwnedFd,
+    network_filter: Option<Vec<libc::sock_filter>>,
+}
+
+/// The Landlock ABI version the running kernel supports, or `None` when
+/// Landlock is missing or disabled.
+pub(super) fn landlock_abi() -> Option<i32> {
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    (abi >= 1).then_some(abi as i32)
+}
+
+/// Build the Landlock ruleset, and the network filter unless the policy
+/// allows network access.
+pub(super) fn prepare(policy: &SandboxPolicy, blocked: &[PathBuf]) -> Result<Restrictions> {
+    let abi = landlock_abi().context("Landlock is not available on this kernel")?;
+    let mut handled = WRITE_ACCESS;
+    if abi < 2 {
+        handled &= !ACCESS_FS_REFER;
+    }
+    if abi < 3 {
+        handled &= !ACCESS_FS_TRUNCATE;
+    }
+
+    let attr = RulesetAttr {
+        handled_access_fs: handled,
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0u32,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error()).context("creating Landlock ruleset");
+    }
+    let ruleset = unsafe { <OwnedFd as std::os::fd::FromRawFd>::from_raw_fd(fd as RawFd) };
+
+    let mut read_paths: Vec<PathBuf> = SYSTEM_READ_PATHS.iter().map(PathBuf::from).collect();
+    match &policy.read {
+        Some(paths) => read_paths.extend(paths.iter().cloned()),
+        None => read_paths.push(PathBuf::from("/")),
+    }
+    let mut write_paths: Vec<PathBuf> = SYSTEM_WRITE_PATHS.iter().map(PathBuf::from).collect();
+    write_paths.push(std::env::temp_dir());
+    write_paths.extend(policy.write.iter().cloned());
+
+    for (paths, access) in [(read_paths, READ_ACCESS), (write_paths, WRITE_ACCESS)] {
+        for path in paths {
+            let Ok(path) = path.canonicalize() else {
+                continue;
+            };
+            for granted in carve_out(&path, blocked) {
+                add_rule(&ruleset, &granted, access & handled)?;
+            }
+        }
+    }
+
+    let network_filter = if policy.network {
+        None
+    } else {
+        Some(network_filter().context(
+            "blocking network access needs a seccomp filter, which is not built for this architecture",
+        )?)
+    };
+
+    Ok(Restrictions {
+        ruleset,
+        network_filter,
+    })
+}
+
+impl Restrictions {
+    /// Runs in the forked child: only raw syscalls, no allocation.
+    pub(super) fn apply(&self) -> io::Result<()> {
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::syscall(
+                libc::SYS_landlock_restrict_self,
+                self.ruleset.as_raw_fd(),
+                0u32,
+            ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            if let Some(filter) = &self.network_filter {
+                let prog = libc::sock_fprog {
+                    len: filter.len() as libc::c_ushort,
+                    filter: filter.as_ptr() as *mut libc::sock_filter,
+                };
+                if libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &prog as *const libc::sock_fprog,
+                ) != 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn add_rule(ruleset: &OwnedFd, path: &Path, access: u64) -> Result<()> {
+    let Ok(meta) = std::fs::metadata(path) else {
+        return Ok(());
+    };
+    let access = if meta.is_dir() {
+        access
+    } else {
+        access & FILE_ACCESS
+    };
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+        .open(path)
+        .with_context(|| format!("opening {} for the sandbox", path.display()))?;
+    let attr = PathBeneathAttr {
+        allowed_access: access,
+        parent_fd: file.as_raw_fd(),
+    };
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0u32,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("adding sandbox rule for {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Landlock can only grant access, so a directory that contains a blocked
+/// path is replaced by its children, minus the blocked ones. Only the
+/// branches leading to a blocked path are expanded.
+fn carve_out(path: &Path, blocked: &[PathBuf]) -> Vec<PathBuf> {
+    if blocked.iter().any(|b| path.starts_with(b)) {
+        return vec![];
+    }
+    if !blocked.iter().any(|b| b.starts_with(path)) {
+        return vec![path.to_path_buf()];
+    }
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return vec![];
+    };
+    let mut granted = Vec::new();
+    for entry in entries.flatten() {
+        // Resolve symlinks so a link to a blocked directory is not granted.
+        let Ok(child) = entry.path().canonicalize() else {
+            continue;
+        };
+        if child.starts_with(path) && child != path {
+            granted.extend(carve_out(&child, blocked));
+        } else if !blocked
+            .iter()
+            .any(|b| child.starts_with(b) || b.starts_with(&child))
+        {
+            granted.push(child);
+        }
+    }
+    granted
+}
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// Seccomp program that lets `socket()` create Unix sockets only. io_uring
+/// is refused as well since it can open sockets without that syscall, and
+/// so are x32 syscall numbers, which would bypass the number checks.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn network_filter() -> Option<Vec<libc::sock_filter>> {
+    const LD_W_ABS: u16 = (libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16;
+    const JEQ: u16 = (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16;
+    const JGE: u16 = (libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K) as u16;
+    const RET: u16 = (libc::BPF_RET | libc::BPF_K) as u16;
+    // Offsets into struct seccomp_data; args[0] is read as its low 32 bits.
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+    const ARG0: u32 = 16;
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    let op = |code: u16, jt: u8, jf: u8, k: u32| libc::sock_filter { code, jt, jf, k };
+    Some(vec![
+        op(LD_W_ABS, 0, 0, ARCH),
+        op(JEQ, 1, 0, AUDIT_ARCH),
+        op(RET, 0, 0, libc::SECCOMP_RET_KILL_PROCESS),
+        op(LD_W_ABS, 0, 0, NR),
+        op(JGE, 5, 0, X32_SYSCALL_BIT),
+        op(JEQ, 4, 0, libc::SYS_io_uring_setup as u32),
+        op(JEQ, 0, 2, libc::SYS_socket as u32),
+        op(LD_W_ABS, 0, 0, ARG0),
+        op(JEQ, 0, 1, libc::AF_UNIX as u32),
+        op(RET, 0, 0, libc::SECCOMP_RET_ALLOW),
+        op(
+            RET,
+            0,
+            0,
+            libc::SECCOMP_RET_ERRNO | (libc::EACCES as u32 & libc::SECCOMP_RET_DATA),
+        ),
+    ])
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn network_filter() -> Option<Vec<libc::sock_filter>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carve_out_skips_blocked_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().canonicalize().unwrap();
+        for sub in [".ssh", ".config/gh", ".config/nvim", "src"] {
+            std::fs::create_dir_all(home.join(sub)).unwrap();
+        }
+        std::os::unix::fs::symlink(home.join(".ssh"), home.join("keys")).unwrap();
+        let blocked = vec![home.join(".ssh"), home.join(".config/gh")];
+
+        let mut granted = carve_out(&home, &blocked);
+        granted.sort();
+        assert_eq!(granted, vec![home.join(".config/nvim"), home.join("src")]);
+
+        assert!(carve_out(&home.join(".ssh"), &blocked).is_empty());
+        assert_eq!(
+            carve_out(&home.join("src"), &blocked),
+            vec![home.join("src")]
+        );
+    }
+
+    #[tokio::test]
+    async fn child_is_confined_to_allowed_paths() {
+        if landlock_abi().is_none() {
+            return;
+        }
+        let project = tempfile::tempdir().unwrap();
+        let outside = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let script = format!(
+            "echo ok > inside.txt && cat inside.txt; \
+             cat {outside} >/dev/null 2>&1 && echo leaked-read; \
+             echo x > {outside}.probe 2>/dev/null && echo leaked-write; \
+             python3 -c 'import socket; socket.socket()' 2>/dev/null && echo leaked-net; \
+             grep '^Seccomp:' /proc/self/status"
+        );
+        let sandboxed = super::super::wrap_command_sandboxed(
+            &script,
+            project.path(),
+            &super::super::SandboxConfig::default(),
+        )
+        .unwrap();
+        let output = sandboxed
+            .command()
+            .current_dir(project.path())
+            .output()
+            .await
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("ok\n"), "{stdout}");
+        assert!(!stdout.contains("leaked"), "{stdout}");
+        assert!(stdout.contains("Seccomp:\t2"), "{stdout}");
+        assert!(!Path::new(&format!("{outside}.probe")).exists());
+    }
+
+    #[tokio::test]
+    async fn read_only_launcher_blocks_project_writes() {
+        if landlock_abi().is_none() {
+            return;
+        }
+        // Outside the temp directory, which stays writable.
+        let project = tempfile::tempdir_in(env!("CARGO_MANIFEST_DIR")).unwrap();
+        std::fs::write(project.path().join("a.txt"), "hello").unwrap();
+        let launcher = super::super::Launcher::new(
+            nyzhi_config::SandboxLevel::ReadOnly,
+            Default::default(),
+            project.path(),
+        );
+        let output = launcher
+            .shell(
+                "cat a.txt; echo x > b.txt && echo leaked-write",
+                project.path(),
+            )
+            .unwrap()
+            .output()
+            .await
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout, "hello");
+        assert!(!project.path().join("b.txt").exists());
+    }
+
+    #[test]
+    fn network_filter_targets_are_in_range() {
+        let Some(filter) = network_filter() else {
+            return;
+        };
+        for (i, op) in filter.iter().enumerate() {
+            if op.code & 0x07 != libc::BPF_JMP as u16 {
+                continue;
+            }
+            assert!(i + 1 + (op.jt.max(op.jf) as usize) < filter.len());
+        }
+    }
+}
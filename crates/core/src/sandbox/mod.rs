@@ -0,0 +1,690 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use nyzhi_config::SandboxLevel;
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SandboxConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub allow_network: Vec<String>,
+    #[serde(default)]
+    pub allow_read: Vec<String>,
+    #[serde(default)]
+    pub allow_write: Vec<String>,
+    #[serde(default)]
+    pub block_dotfiles: bool,
+    #[serde(default)]
+    pub require: bool,
+}
+
+impl From<&nyzhi_config::SandboxSettings> for SandboxConfig {
+    fn from(settings: &nyzhi_config::SandboxSettings) -> Self {
+        Self {
+            enabled: settings.enabled,
+            allow_network: settings.allow_network.clone(),
+            allow_read: settings.allow_read.clone(),
+            allow_write: settings.allow_write.clone(),
+            block_dotfiles: settings.block_dotfiles,
+            require: settings.require,
+        }
+    }
+}
+
+/// What a sandboxed command may touch. System directories and the temp
+/// directory are always readable, the temp directory is always writable,
+/// and the `BLOCKED_DOTFILES` under the home directory are never readable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    /// Extra readable paths, or `None` to make the whole filesystem
+    /// readable apart from the blocked dotfiles.
+    pub read: Option<Vec<PathBuf>>,
+    /// Writable paths besides the temp directory.
+    pub write: Vec<PathBuf>,
+    pub network: bool,
+}
+
+impl SandboxPolicy {
+    /// The strict policy described by `[shell.sandbox]`: only the project
+    /// and the listed paths are visible, and the network is off unless
+    /// `allow_network` has entries.
+    pub fn from_config(project_root: &Path, config: &SandboxConfig) -> Self {
+        let mut read = vec![project_root.to_path_buf()];
+        read.extend(config.allow_read.iter().map(PathBuf::from));
+        let mut write = vec![project_root.to_path_buf()];
+        write.extend(config.allow_write.iter().map(PathBuf::from));
+        Self {
+            read: Some(read),
+            write,
+            network: !config.allow_network.is_empty(),
+        }
+    }
+
+    /// Policy for a session sandbox level, or `None` when commands run
+    /// unconfined.
+    ///
+    /// - `workspace-write`: reads anywhere, writes to the project, the
+    ///   package caches in [`cache_dirs`] and `allow_write`, network on.
+    ///   With `[shell.sandbox] enabled` the stricter
+    ///   [`SandboxPolicy::from_config`] applies instead, with the caches
+    ///   added.
+    /// - `read-only`: the same reads, no writes outside the temp directory
+    ///   and no network.
+    /// - `full-access`: no sandbox.
+    pub fn for_level(
+        level: SandboxLevel,
+        project_root: &Path,
+        config: &SandboxConfig,
+    ) -> Option<Self> {
+        let mut workspace = if config.enabled {
+            Self::from_config(project_root, config)
+        } else {
+            let mut write = vec![project_root.to_path_buf()];
+            write.extend(config.allow_write.iter().map(PathBuf::from));
+            Self {
+                read: None,
+                write,
+                network: true,
+            }
+        };
+        let caches = cache_dirs();
+        if let Some(read) = &mut workspace.read {
+            read.extend(caches.iter().cloned());
+        }
+        workspace.write.extend(caches);
+        match level {
+            SandboxLevel::FullAccess => None,
+            SandboxLevel::WorkspaceWrite => Some(workspace),
+            SandboxLevel::ReadOnly => Some(Self {
+                write: vec![],
+                network: false,
+                ..workspace
+            }),
+        }
+    }
+}
+
+/// Where package managers and build tools keep their caches, so builds and
+/// installs still work under `workspace-write`. Paths that do not exist are
+/// skipped by the backends.
+fn cache_dirs() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = [
+        "CARGO_HOME",
+        "RUSTUP_HOME",
+        "XDG_CACHE_HOME",
+        "npm_config_cache",
+        "GOMODCACHE",
+        "GRADLE_USER_HOME",
+    ]
+    .iter()
+    .filter_map(std::env::var_os)
+    .filter(|value| !value.is_empty())
+    .map(PathBuf::from)
+    .collect();
+    if let Some(home) = dirs::home_dir() {
+        paths.extend(
+            [
+                ".cargo",
+                ".rustup",
+                ".npm",
+                ".cache",
+                ".yarn",
+                ".pnpm-store",
+                ".bun",
+                ".gradle",
+                ".m2",
+                "go/pkg",
+            ]
+            .iter()
+            .map(|dir| home.join(dir)),
+        );
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Starts every shell command the agent or its hooks run: bash, verify
+/// checks, command hooks, formatters and `batch_apply`. Commands run under
+/// the policy for the session's sandbox level. When no backend can enforce
+/// it they run unconfined after a one-time warning, or fail to start with
+/// `[shell.sandbox] require`.
+#[derive(Debug, Clone)]
+pub struct Launcher {
+    level: SandboxLevel,
+    config: SandboxConfig,
+    project_root: PathBuf,
+}
+
+impl Launcher {
+    pub fn new(level: SandboxLevel, config: SandboxConfig, project_root: &Path) -> Self {
+        Self {
+            level,
+            config,
+            project_root: project_root.to_path_buf(),
+        }
+    }
+
+    pub fn level(&self) -> SandboxLevel {
+        self.level
+    }
+
+    pub fn policy(&self) -> Option<SandboxPolicy> {
+        SandboxPolicy::for_level(self.level, &self.project_root, &self.config)
+    }
+
+    /// `sh -c <cmd>` in `cwd`.
+    pub fn shell(&self, cmd: &str, cwd: &Path) -> Result<tokio::process::Command> {
+        let argv = vec!["/bin/sh".to_string(), "-c".to_string(), cmd.to_string()];
+        let mut command: tokio::process::Command = self.wrap(argv)?.std_command().into();
+        command.current_dir(cwd);
+        Ok(command)
+    }
+
+    /// A program run directly, without a shell.
+    pub fn program(
+        &self,
+        program: &str,
+        args: &[&str],
+        cwd: &Path,
+    ) -> Result<std::process::Command> {
+        let mut argv = vec![program.to_string()];
+        argv.extend(args.iter().map(|a| a.to_string()));
+        let mut command = self.wrap(argv)?.std_command();
+        command.current_dir(cwd);
+        Ok(command)
+    }
+
+    fn wrap(&self, argv: Vec<String>) -> Result<SandboxedCommand> {
+        match self.policy() {
+            Some(_) if !self.config.require && backend().is_none() => {
+                static WARNED: std::sync::Once = std::sync::Once::new();
+                WARNED.call_once(|| {
+                    tracing::warn!(
+                        "No sandbox backend is available; commands run unconfined despite the {} \
+                         level. Set [shell.sandbox] require = true to refuse them instead.",
+                        self.level
+                    );
+                });
+                Ok(SandboxedCommand::unconfined(argv))
+            }
+            Some(policy) => wrap_command(argv, &self.project_root, &policy).map_err(|e| {
+                anyhow::anyhow!(
+                    "{e:#}. Commands run under the {} sandbox; use full-access to run them unconfined.",
+                    self.level
+                )
+            }),
+            None => Ok(SandboxedCommand::unconfined(argv)),
+        }
+    }
+}
+
+pub struct SandboxedCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env_overrides: Vec<(String, String)>,
+    /// Landlock and seccomp rules the child installs on itself before exec.
+    #[cfg(target_os = "linux")]
+    restrictions: Option<std::sync::Arc<linux::Restrictions>>,
+}
+
+impl SandboxedCommand {
+    fn unconfined(mut argv: Vec<String>) -> Self {
+        let program = argv.remove(0);
+        Self {
+            program,
+            args: argv,
+            env_overrides: vec![],
+            #[cfg(target_os = "linux")]
+            restrictions: None,
+        }
+    }
+
+    /// The process to spawn. Use this rather than building a command from
+    /// `program` and `args`, which would leave out in-process restrictions.
+    pub fn command(&self) -> tokio::process::Command {
+        self.std_command().into()
+    }
+
+    pub fn std_command(&self) -> std::process::Command {
+        let mut command = std::process::Command::new(&self.program);
+        command.args(&self.args);
+        command.envs(self.env_overrides.iter().map(|(k, v)| (k, v)));
+        #[cfg(target_os = "linux")]
+        if let Some(restrictions) = self.restrictions.clone() {
+            use std::os::unix::process::CommandExt;
+            // SAFETY: `apply` only issues syscalls and does not allocate.
+            unsafe {
+                command.pre_exec(move || restrictions.apply());
+            }
+        }
+        command
+    }
+}
+
+/// Start the command in a process group of its own. Everything it spawns
+/// can then be stopped together with [`ProcessGroup`], and terminal signals
+/// such as Ctrl+C reach nyzhi instead of the command.
+pub fn isolate_process_group(command: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// The process group of a child started with [`isolate_process_group`].
+/// Dropping the handle kills the whole group, so a cancelled or abandoned
+/// command does not leave grandchildren running; call [`Self::release`]
+/// once the command has finished normally.
+pub struct ProcessGroup {
+    pgid: Option<u32>,
+}
+
+impl ProcessGroup {
+    pub fn new(child: &tokio::process::Child) -> Self {
+        Self { pgid: child.id() }
+    }
+
+    /// Kill every process in the group.
+    pub fn kill(&mut self) {
+        let Some(pgid) = self.pgid.take() else {
+            return;
+        };
+        #[cfg(unix)]
+        // SAFETY: killpg only sends a signal; a group that has already
+        // exited yields ESRCH, which is ignored.
+        unsafe {
+            libc::killpg(pgid as libc::pid_t, libc::SIGKILL);
+        }
+        #[cfg(not(unix))]
+        let _ = pgid;
+    }
+
+    /// Leave the group running, e.g. processes the command put in the
+    /// background on purpose.
+    pub fn release(mut self) {
+        self.pgid = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Which mechanism enforces sandboxed commands on this machine, or `None`
+/// when there is none.
+pub fn backend() -> Option<String> {
+    static BACKEND: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+    BACKEND.get_or_init(detect_backend).clone()
+}
+
+fn detect_backend() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(abi) = linux::landlock_abi() {
+            return Some(format!("Landlock ABI {abi} + seccomp"));
+        }
+        has_bwrap().then(|| "bwrap".to_string())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Some("sandbox-exec".to_string())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        None
+    }
+}
+
+const BLOCKED_DOTFILES: &[&str] = &[
+    ".ssh",
+    ".aws",
+    ".npmrc",
+    ".env",
+    ".netrc",
+    ".docker",
+    ".kube",
+    ".gnupg",
+    ".config/gh",
+    ".gitconfig",
+];
+
+/// Whether `cmd` contains a destructive command; see
+/// [`crate::tools::shell_parse::is_dangerous`].
+pub fn is_dangerous_command(cmd: &str) -> bool {
+    crate::tools::shell_parse::is_dangerous(cmd)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn blocked_dotfile_paths() -> Vec<PathBuf> {
+    let home = dirs::home_dir().unwrap_or_default();
+    let home = home.canonicalize().unwrap_or(home);
+    BLOCKED_DOTFILES.iter().map(|d| home.join(d)).collect()
+}
+
+pub fn check_dotfile_access(path: &str) -> bool {
+    let home = dirs::home_dir().unwrap_or_default();
+    for dotfile in BLOCKED_DOTFILES {
+        let blocked = home.join(dotfile);
+        if path.starts_with(&blocked.to_string_lossy().to_string()) {
+            return true;
+        }
+    }
+    false
+}
+
+pub fn scan_for_secrets(text: &str) -> Vec<String> {
+    let mut findings = Vec::new();
+    let patterns = [
+        ("AWS Access Key", r"AKIA[0-9A-Z]{16}"),
+        ("GitHub Token", r"gh[pousr]_[A-Za-z0-9_]{36,}"),
+        (
+            "Generic API Key",
+            r#"(?i)(api[_-]?key|apikey|secret[_-]?key)\s*[:=]\s*["'][A-Za-z0-9+/=]{20,}["']"#,
+        ),
+        ("Bearer Token", r"Bearer\s+[A-Za-z0-9\-._~+/]+=*"),
+        (
+            "Private Key Header",
+            r"-----BEGIN (RSA |EC |OPENSSH )?PRIVATE KEY-----",
+        ),
+    ];
+
+    for (name, pattern) in &patterns {
+        if let Ok(re) = regex::Regex::new(pattern) {
+            if re.is_match(text) {
+                findings.push(format!("Potential {name} detected in output"));
+            }
+        }
+    }
+    findings
+}
+
+pub fn wrap_command_sandboxed(
+    cmd: &str,
+    project_root: &Path,
+    config: &SandboxConfig,
+) -> Result<SandboxedCommand> {
+    let argv = vec!["/bin/sh".to_string(), "-c".to_string(), cmd.to_string()];
+    wrap_command(
+        argv,
+        project_root,
+        &SandboxPolicy::from_config(project_root, config),
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn wrap_command(
+    argv: Vec<String>,
+    project_root: &Path,
+    policy: &SandboxPolicy,
+) -> Result<SandboxedCommand> {
+    let profile = generate_seatbelt_profile(policy);
+    let profile_path = project_root.join(".nyzhi").join("sandbox.sb");
+    std::fs::create_dir_all(profile_path.parent().unwrap())?;
+    std::fs::write(&profile_path, &profile)?;
+
+    let mut args = vec!["-f".to_string(), profile_path.to_string_lossy().to_string()];
+    args.extend(argv);
+    Ok(SandboxedCommand {
+        program: "sandbox-exec".to_string(),
+        args,
+        env_overrides: vec![],
+    })
+}
+
+/// Prefers the in-process Landlock backend and falls back to `bwrap` on
+/// kernels without Landlock. Errors rather than running the command
+/// unconfined when neither is available.
+#[cfg(target_os = "linux")]
+fn wrap_command(
+    argv: Vec<String>,
+    _project_root: &Path,
+    policy: &SandboxPolicy,
+) -> Result<SandboxedCommand> {
+    if linux::landlock_abi().is_some() {
+        let restrictions = linux::prepare(policy, &blocked_dotfile_paths())?;
+        return Ok(SandboxedCommand {
+            restrictions: Some(std::sync::Arc::new(restrictions)),
+            ..SandboxedCommand::unconfined(argv)
+        });
+    }
+
+    if has_bwrap() {
+        Ok(SandboxedCommand {
+            program: "bwrap".to_string(),
+            args: bwrap_args(policy, argv),
+            env_overrides: vec![],
+            restrictions: None,
+        })
+    } else {
+        anyhow::bail!(
+            "sandboxing was requested but cannot be enforced: \
+             the kernel has no Landlock support and bwrap is not installed"
+        )
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn wrap_command(
+    _argv: Vec<String>,
+    _project_root: &Path,
+    _policy: &SandboxPolicy,
+) -> Result<SandboxedCommand> {
+    anyhow::bail!("sandboxing was requested but is not supported on this platform")
+}
+
+#[cfg(target_os = "linux")]
+fn has_bwrap() -> bool {
+    std::process::Command::new("which")
+        .arg("bwrap")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+fn bwrap_args(policy: &SandboxPolicy, argv: Vec<String>) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    let mut bind = |flag: &str, path: &Path| {
+        if path.exists() {
+            let path = path.to_string_lossy().to_string();
+            args.extend([flag.to_string(), path.clone(), path]);
+        }
+    };
+
+    match &policy.read {
+        None => bind("--ro-bind", Path::new("/")),
+        Some(paths) => {
+            for dir in ["/usr", "/bin", "/sbin", "/lib", "/lib64", "/etc", "/opt"] {
+                bind("--ro-bind", Path::new(dir));
+            }
+            for path in paths {
+                bind("--ro-bind", path);
+            }
+        }
+    }
+    for path in &policy.write {
+        bind("--bind", path);
+    }
+    bind("--bind", &std::env::temp_dir());
+    args.extend(["--proc", "/proc", "--dev", "/dev"].map(String::from));
+
+    // Later mounts shadow earlier ones, so the dotfiles are hidden last.
+    for blocked in blocked_dotfile_paths() {
+        if blocked.is_dir() {
+            args.extend(["--tmpfs".to_string(), blocked.to_string_lossy().to_string()]);
+        } else if blocked.exists() {
+            args.extend([
+                "--ro-bind".to_string(),
+                "/dev/null".to_string(),
+                blocked.to_string_lossy().to_string(),
+            ]);
+        }
+    }
+
+    if !policy.network {
+        args.push("--unshare-net".to_string());
+    }
+    args.push("--die-with-parent".to_string());
+    args.extend(argv);
+    args
+}
+
+#[cfg(target_os = "macos")]
+fn generate_seatbelt_profile(policy: &SandboxPolicy) -> String {
+    let tmp_dir = std::env::temp_dir().to_string_lossy().to_string();
+
+    let mut profile = String::from("(version 1)\n(deny default)\n");
+    profile.push_str("(allow process-exec)\n");
+    profile.push_str("(allow process-fork)\n");
+    profile.push_str("(allow sysctl-read)\n");
+    profile.push_str("(allow mach-lookup)\n");
+
+    profile.push_str(&format!("(allow file-read* (subpath \"{tmp_dir}\"))\n"));
+    profile.push_str(&format!("(allow file-write* (subpath \"{tmp_dir}\"))\n"));
+    profile.push_str("(allow file-write* (literal \"/dev/null\"))\n");
+
+    match &policy.read {
+        None => profile.push_str("(allow file-read*)\n"),
+        Some(paths) => {
+            profile.push_str("(allow file-read* (subpath \"/usr\"))\n");
+            profile.push_str("(allow file-read* (subpath \"/bin\"))\n");
+            profile.push_str("(allow file-read* (subpath \"/sbin\"))\n");
+            profile.push_str("(allow file-read* (subpath \"/Library\"))\n");
+            profile.push_str("(allow file-read* (subpath \"/System\"))\n");
+            profile.push_str("(allow file-read* (subpath \"/dev\"))\n");
+            profile.push_str("(allow file-read* (subpath \"/private/tmp\"))\n");
+            profile.push_str("(allow file-read* (subpath \"/private/var\"))\n");
+            for path in paths {
+                profile.push_str(&format!(
+                    "(allow file-read* (subpath \"{}\"))\n",
+                    path.display()
+                ));
+            }
+        }
+    }
+    for path in &policy.write {
+        profile.push_str(&format!(
+            "(allow file-read* file-write* (subpath \"{}\"))\n",
+            path.display()
+        ));
+    }
+
+    for blocked_path in blocked_dotfile_paths() {
+        profile.push_str(&format!(
+            "(deny file-read* (subpath \"{}\"))\n",
+            blocked_path.display()
+        ));
+    }
+
+    if policy.network {
+        profile.push_str("(allow network*)\n");
+    } else {
+        profile.push_str("(deny network*)\n");
+    }
+
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_map_to_policies() {
+        let root = Path::new("/work/project");
+        let config = SandboxConfig {
+            allow_write: vec!["/work/cache".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            SandboxPolicy::for_level(SandboxLevel::FullAccess, root, &config),
+            None
+        );
+
+        let workspace =
+            SandboxPolicy::for_level(SandboxLevel::WorkspaceWrite, root, &config).unwrap();
+        assert_eq!(workspace.read, None);
+        assert_eq!(
+            workspace.write[..2],
+            [PathBuf::from("/work/project"), PathBuf::from("/work/cache")]
+        );
+        assert_eq!(workspace.write[2..], cache_dirs()[..]);
+        assert!(workspace.network);
+
+        let read_only = SandboxPolicy::for_level(SandboxLevel::ReadOnly, root, &config).unwrap();
+        assert_eq!(read_only.read, None);
+        assert!(read_only.write.is_empty());
+        assert!(!read_only.network);
+    }
+
+    #[test]
+    fn enabled_config_narrows_workspace_write() {
+        let root = Path::new("/work/project");
+        let config = SandboxConfig {
+            enabled: true,
+            allow_read: vec!["/opt/sdk".into()],
+            ..Default::default()
+        };
+        let policy = SandboxPolicy::for_level(SandboxLevel::WorkspaceWrite, root, &config).unwrap();
+        let read = policy.read.unwrap();
+        assert_eq!(
+            read[..2],
+            [PathBuf::from("/work/project"), PathBuf::from("/opt/sdk")]
+        );
+        assert_eq!(read[2..], cache_dirs()[..]);
+        assert_eq!(policy.write[0], PathBuf::from("/work/project"));
+        assert_eq!(policy.write[1..], cache_dirs()[..]);
+        assert!(!policy.network);
+    }
+
+    #[test]
+    fn package_caches_are_writable() {
+        let Some(home) = dirs::home_dir() else {
+            return;
+        };
+        let caches = cache_dirs();
+        for dir in [".cargo", ".npm", ".cache"] {
+            assert!(caches.contains(&home.join(dir)), "{dir} missing");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn dropping_process_group_kills_grandchildren() {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+        let mut command = tokio::process::Command::new("/bin/sh");
+        command
+            .args(["-c", "sleep 30 & echo started; wait"])
+            .stdout(std::process::Stdio::piped());
+        isolate_process_group(&mut command);
+        let mut child = command.spawn().unwrap();
+        let group = ProcessGroup::new(&child);
+
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).await.unwrap();
+        assert_eq!(line.trim(), "started");
+
+        drop(group);
+        // The background `sleep` holds stdout open; EOF means it was killed.
+        let mut rest = Vec::new();
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            stdout.read_to_end(&mut rest),
+        )
+        .await
+        .expect("grandchild outlived its process group")
+        .unwrap();
+        let _ = child.wait().await;
+    }
+}
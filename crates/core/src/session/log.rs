@@ -0,0 +1,213 @@
+//! Append-only session logs: one JSON record per line. Replaying the records
+//! in order rebuilds the thread; a record cut short by a crash is skipped.
+
+use std::borrow::Cow;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use nyzhi_provider::Message;
+use serde::{Deserialize, Serialize};
+
+use super::SessionMeta;
+use crate::conversation::{Branch, Node, Thread};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum Record<'a> {
+    Meta {
+        id: Cow<'a, str>,
+        title: Cow<'a, str>,
+        created_at: DateTime<Utc>,
+        provider: Cow<'a, str>,
+        model: Cow<'a, str>,
+    },
+    Node {
+        id: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent: Option<usize>,
+        message: Cow<'a, Message>,
+    },
+    State {
+        branches: Cow<'a, [Branch]>,
+        active: usize,
+        updated_at: DateTime<Utc>,
+    },
+}
+
+impl<'a> Record<'a> {
+    pub(super) fn meta(meta: &'a SessionMeta) -> Self {
+        Record::Meta {
+            id: Cow::Borrowed(&meta.id),
+            title: Cow::Borrowed(&meta.title),
+            created_at: meta.created_at,
+            provider: Cow::Borrowed(&meta.provider),
+            model: Cow::Borrowed(&meta.model),
+        }
+    }
+
+    pub(super) fn node(id: usize, node: &'a Node) -> Self {
+        Record::Node {
+            id,
+            parent: node.parent,
+            message: Cow::Borrowed(&node.message),
+        }
+    }
+
+    pub(super) fn state(thread: &'a Thread, updated_at: DateTime<Utc>) -> Self {
+        Record::State {
+            branches: Cow::Borrowed(thread.branch_heads()),
+            active: thread.active_branch(),
+            updated_at,
+        }
+    }
+}
+
+/// Every record needed to rebuild `thread` from scratch.
+pub(super) fn snapshot<'a>(thread: &'a Thread, meta: &'a SessionMeta) -> Vec<Record<'a>> {
+    let mut records = vec![Record::meta(meta)];
+    records.extend(
+        thread
+            .nodes()
+            .iter()
+            .enumerate()
+            .map(|(id, node)| Record::node(id, node)),
+    );
+    records.push(Record::state(thread, meta.updated_at));
+    records
+}
+
+pub(super) struct Replayed {
+    pub thread: Thread,
+    pub meta: SessionMeta,
+    /// False when some records could not be applied; the log should be
+    /// rewritten rather than appended to.
+    pub clean: bool,
+}
+
+pub(super) fn replay(path: &Path) -> Result<Replayed> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let mut meta: Option<SessionMeta> = None;
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut branches = Vec::new();
+    let mut active = 0;
+    let mut updated_at = None;
+    let mut clean = true;
+
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let record: Record = match serde_json::from_str(line) {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::warn!("Skipping unreadable record in {}: {e}", path.display());
+                clean = false;
+                continue;
+            }
+        };
+        match record {
+            Record::Meta {
+                id,
+                title,
+                created_at,
+                provider,
+                model,
+            } => {
+                meta = Some(SessionMeta {
+                    id: id.into_owned(),
+                    title: title.into_owned(),
+                    created_at,
+                    updated_at: created_at,
+                    message_count: 0,
+                    provider: provider.into_owned(),
+                    model: model.into_owned(),
+                });
+            }
+            Record::Node {
+                id,
+                parent,
+                message,
+            } => {
+                let node = Node {
+                    parent,
+                    message: message.into_owned(),
+                };
+                if id < nodes.len() {
+                    nodes[id] = node;
+                } else if id == nodes.len() {
+                    nodes.push(node);
+                } else {
+                    clean = false;
+                }
+            }
+            Record::State {
+                branches: b,
+                active: a,
+                updated_at: u,
+            } => {
+                branches = b.into_owned();
+                active = a;
+                updated_at = Some(u);
+            }
+        }
+    }
+
+    let mut meta = meta.with_context(|| format!("{} has no session metadata", path.display()))?;
+    let thread = Thread::from_parts(meta.id.clone(), meta.created_at, nodes, branches, active);
+    meta.message_count = thread.message_count();
+    if let Some(u) = updated_at {
+        meta.updated_at = u;
+    }
+    Ok(Replayed {
+        thread,
+        meta,
+        clean,
+    })
+}
+
+fn encode(records: &[Record]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut buf, record)?;
+        buf.push(b'\n');
+    }
+    Ok(buf)
+}
+
+/// Append records with a single write. If an earlier write was cut short, the
+/// partial line is terminated first so it cannot swallow the new records.
+pub(super) fn append(path: &Path, records: &[Record]) -> Result<()> {
+    let mut buf = encode(records)?;
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    if file.metadata()?.len() > 0 {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            buf.insert(0, b'\n');
+        }
+    }
+    file.write_all(&buf)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Replace the log atomically: write a temp file, sync it, then rename it
+/// over the old log.
+pub(super) fn rewrite(path: &Path, records: &[Record]) -> Result<()> {
+    let buf = encode(records)?;
+    let tmp = path.with_extension("jsonl.tmp");
+    {
+        let mut file = std::fs::File::create(&tmp)
+            .with_context(|| format!("Failed to create {}", tmp.display()))?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
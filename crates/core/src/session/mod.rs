@@ -1,10 +1,15 @@
+mod log;
+pub mod store;
+
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::conversation::Thread;
+pub use store::SessionStore;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionMeta {
@@ -17,10 +22,12 @@ pub struct SessionMeta {
     pub model: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct SessionFile {
-    meta: SessionMeta,
-    thread: Thread,
+/// A session found by `search_sessions`, with the matching message text when
+/// the match came from the conversation rather than the id or title.
+#[derive(Debug, Clone)]
+pub struct SessionMatch {
+    pub meta: SessionMeta,
+    pub snippet: Option<String>,
 }
 
 fn sessions_dir() -> Result<PathBuf> {
@@ -30,85 +37,48 @@ fn sessions_dir() -> Result<PathBuf> {
     Ok(sessions)
 }
 
-fn session_path(id: &str) -> Result<PathBuf> {
-    Ok(sessions_dir()?.join(format!("{id}.json")))
+/// Directory for files that belong to session `id`, such as the logs of its
+/// background processes. Deleted along with the session.
+pub fn session_data_dir(id: &str) -> Result<PathBuf> {
+    validate_id(id)?;
+    let dir = sessions_dir()?.join(id);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Rejects ids that would name something other than one entry of the
+/// sessions directory.
+pub(crate) fn validate_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        anyhow::bail!("Invalid session id: {id:?}");
+    }
+    Ok(())
+}
+
+/// The store for this user's sessions, opened (and migrated) on first use.
+pub fn store() -> Result<&'static SessionStore> {
+    static STORE: OnceLock<SessionStore> = OnceLock::new();
+    if let Some(store) = STORE.get() {
+        return Ok(store);
+    }
+    let store = SessionStore::open_dir(&sessions_dir()?)?;
+    Ok(STORE.get_or_init(|| store))
 }
 
 pub fn save_session(thread: &Thread, provider: &str, model: &str) -> Result<SessionMeta> {
-    let title = thread
-        .messages()
-        .iter()
-        .find(|m| m.role == nyzhi_provider::Role::User)
-        .map(|m| {
-            let text = m.content.as_text();
-            if text.len() > 80 {
-                format!("{}...", &text[..77])
-            } else {
-                text.to_string()
-            }
-        })
-        .unwrap_or_else(|| "untitled".to_string());
-
-    let meta = SessionMeta {
-        id: thread.id.clone(),
-        title,
-        created_at: thread.created_at,
-        updated_at: Utc::now(),
-        message_count: thread.message_count(),
-        provider: provider.to_string(),
-        model: model.to_string(),
-    };
-
-    let file = SessionFile {
-        meta: meta.clone(),
-        thread: thread.clone(),
-    };
-
-    let path = session_path(&meta.id)?;
-    let json = serde_json::to_string(&file)?;
-    std::fs::write(path, json)?;
-
-    Ok(meta)
+    store()?.save(thread, provider, model)
 }
 
 pub fn load_session(id: &str) -> Result<(Thread, SessionMeta)> {
-    let path = session_path(id)?;
-    let json = std::fs::read_to_string(path)?;
-    let file: SessionFile = serde_json::from_str(&json)?;
-    Ok((file.thread, file.meta))
+    store()?.load(id)
 }
 
 pub fn list_sessions() -> Result<Vec<SessionMeta>> {
-    let dir = sessions_dir()?;
-    let mut sessions = Vec::new();
-
-    for entry in std::fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("json") {
-            continue;
-        }
-        let json = match std::fs::read_to_string(&path) {
-            Ok(j) => j,
-            Err(_) => continue,
-        };
-        let file: SessionFile = match serde_json::from_str(&json) {
-            Ok(f) => f,
-            Err(_) => continue,
-        };
-        sessions.push(file.meta);
-    }
-
-    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-    Ok(sessions)
+    store()?.list()
 }
 
 pub fn delete_session(id: &str) -> Result<()> {
-    let path = session_path(id)?;
-    if path.exists() {
-        std::fs::remove_file(path)?;
-    }
-    Ok(())
+    store()?.delete(id)
 }
 
 pub fn latest_session() -> Result<Option<SessionMeta>> {
@@ -117,20 +87,31 @@ pub fn latest_session() -> Result<Option<SessionMeta>> {
 }
 
 pub fn rename_session(id: &str, new_title: &str) -> Result<()> {
-    let path = session_path(id)?;
-    let json = std::fs::read_to_string(&path)?;
-    let mut file: SessionFile = serde_json::from_str(&json)?;
-    file.meta.title = new_title.to_string();
-    let json = serde_json::to_string(&file)?;
-    std::fs::write(path, json)?;
-    Ok(())
+    store()?.rename(id, new_title)
+}
+
+/// Copy the first `at` messages of a session's active branch (all of them by
+/// default) into a new session.
+pub fn fork_session(id: &str, at: Option<usize>) -> Result<SessionMeta> {
+    let (thread, meta) = load_session(id)?;
+    let count = thread.message_count();
+    let at = at.unwrap_or(count);
+    if at > count {
+        anyhow::bail!("Session {id} has {count} messages; cannot fork at {at}");
+    }
+    let forked = thread.fork_into_new(at);
+    let mut forked_meta = save_session(&forked, &meta.provider, &meta.model)?;
+    forked_meta.title = format!("{} (fork)", meta.title);
+    rename_session(&forked_meta.id, &forked_meta.title)?;
+    Ok(forked_meta)
 }
 
+/// Sessions whose id starts with `query` or whose title contains it.
 pub fn find_sessions(query: &str) -> Result<Vec<SessionMeta>> {
-    let query_lower = query.to_lowercase();
-    let sessions = list_sessions()?;
-    Ok(sessions
-        .into_iter()
-        .filter(|s| s.id.starts_with(query) || s.title.to_lowercase().contains(&query_lower))
-        .collect())
+    store()?.find(query)
+}
+
+/// Like `find_sessions`, but also searches the text of every message.
+pub fn search_sessions(query: &str) -> Result<Vec<SessionMatch>> {
+    store()?.search(query)
 }
@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use nyzhi_provider::{ContentPart, Message, MessageContent, Role};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+
+use super::log::{self, Record};
+use super::{SessionMatch, SessionMeta};
+use crate::conversation::{Branch, Thread};
+
+/// Bump when the index schema changes; the index is then rebuilt from the logs.
+const INDEX_VERSION: i64 = 1;
+
+/// Sessions stored as append-only logs (`<id>.jsonl`) with a SQLite index of
+/// their metadata and a full-text index of their messages (`index.db`). The
+/// logs are the source of truth; the index can be rebuilt from them.
+pub struct SessionStore {
+    dir: PathBuf,
+    conn: Mutex<Connection>,
+    /// What each log already holds, so saves only append what is new.
+    saved: Mutex<HashMap<String, SavedState>>,
+}
+
+struct SavedState {
+    generation: u64,
+    nodes: usize,
+    branches: Vec<Branch>,
+    active: usize,
+    title: String,
+    provider: String,
+    model: String,
+}
+
+impl SavedState {
+    fn of(thread: &Thread, meta: &SessionMeta) -> Self {
+        SavedState {
+            generation: thread.generation(),
+            nodes: thread.nodes().len(),
+            branches: thread.branch_heads().to_vec(),
+            active: thread.active_branch(),
+            title: meta.title.clone(),
+            provider: meta.provider.clone(),
+            model: meta.model.clone(),
+        }
+    }
+}
+
+/// Session files written before the log format.
+#[derive(Deserialize)]
+struct LegacySessionFile {
+    meta: SessionMeta,
+    thread: Thread,
+}
+
+impl SessionStore {
+    pub fn open_dir(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let conn = Connection::open(dir.join("index.db"))?;
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        let rebuild = version != INDEX_VERSION;
+        if rebuild {
+            conn.execute_batch(
+                "DROP TABLE IF EXISTS sessions;
+                DROP TABLE IF EXISTS message_fts;",
+            )?;
+        }
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                message_count INTEGER NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_sessions_updated ON sessions(updated_at);
+            CREATE VIRTUAL TABLE IF NOT EXISTS message_fts USING fts5(
+                session_id UNINDEXED,
+                content
+            );",
+        )?;
+
+        let store = SessionStore {
+            dir: dir.to_path_buf(),
+            conn: Mutex::new(conn),
+            saved: Mutex::new(HashMap::new()),
+        };
+        if rebuild {
+            store.rebuild_index()?;
+            store
+                .conn
+                .lock()
+                .unwrap()
+                .execute_batch(&format!("PRAGMA user_version = {INDEX_VERSION}"))?;
+        }
+        store.migrate_legacy();
+        Ok(store)
+    }
+
+    /// Every path built from a session id goes through here, so an id can
+    /// never name a file outside the sessions directory.
+    fn log_path(&self, id: &str) -> Result<PathBuf> {
+        super::validate_id(id)?;
+        Ok(self.dir.join(format!("{id}.jsonl")))
+    }
+
+    pub fn save(&self, thread: &Thread, provider: &str, model: &str) -> Result<SessionMeta> {
+        let existing = self.get(&thread.id)?;
+        let title = match existing {
+            Some(ref m) if m.title != "untitled" => m.title.clone(),
+            _ => derive_title(thread),
+        };
+        let meta = SessionMeta {
+            id: thread.id.clone(),
+            title,
+            created_at: thread.created_at,
+            updated_at: Utc::now(),
+            message_count: thread.message_count(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+        };
+
+        let path = self.log_path(&meta.id)?;
+        let mut saved = self.saved.lock().unwrap();
+        let appendable = saved.get(&meta.id).filter(|s| {
+            s.generation == thread.generation() && s.nodes <= thread.nodes().len() && path.exists()
+        });
+
+        let new_nodes = match appendable {
+            Some(state) => {
+                let mut records = Vec::new();
+                if state.title != meta.title
+                    || state.provider != meta.provider
+                    || state.model != meta.model
+                {
+                    records.push(Record::meta(&meta));
+                }
+                let new_nodes = state.nodes;
+                records.extend(
+                    thread.nodes()[new_nodes..]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, node)| Record::node(new_nodes + i, node)),
+                );
+                if records.is_empty()
+                    && state.branches == thread.branch_heads()
+                    && state.active == thread.active_branch()
+                {
+                    return Ok(existing.unwrap_or(meta));
+                }
+                records.push(Record::state(thread, meta.updated_at));
+                log::append(&path, &records)?;
+                new_nodes
+            }
+            None => {
+                log::rewrite(&path, &log::snapshot(thread, &meta))?;
+                0
+            }
+        };
+
+        self.index(thread, &meta, new_nodes)?;
+        saved.insert(meta.id.clone(), SavedState::of(thread, &meta));
+        Ok(meta)
+    }
+
+    pub fn load(&self, id: &str) -> Result<(Thread, SessionMeta)> {
+        let replayed = log::replay(&self.log_path(id)?)?;
+        let mut saved = self.saved.lock().unwrap();
+        if replayed.clean {
+            saved.insert(
+                id.to_string(),
+                SavedState::of(&replayed.thread, &replayed.meta),
+            );
+        } else {
+            saved.remove(id);
+        }
+        Ok((replayed.thread, replayed.meta))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<SessionMeta>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT id, title, created_at, updated_at, message_count, provider, model
+                 FROM sessions WHERE id = ?1",
+                params![id],
+                meta_from_row,
+            )
+            .optional()?)
+    }
+
+    /// All sessions, most recently updated first.
+    pub fn list(&self) -> Result<Vec<SessionMeta>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, title, created_at, updated_at, message_count, provider, model
+             FROM sessions ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt
+            .query_map([], meta_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Sessions whose id starts with `query` or whose title contains it.
+    pub fn find(&self, query: &str) -> Result<Vec<SessionMeta>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, title, created_at, updated_at, message_count, provider, model
+             FROM sessions
+             WHERE substr(id, 1, length(?1)) = ?1 OR instr(lower(title), lower(?1)) > 0
+             ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt
+            .query_map(params![query], meta_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Id and title matches first, then sessions whose messages match, with
+    /// a snippet of the best matching message.
+    pub fn search(&self, query: &str) -> Result<Vec<SessionMatch>> {
+        let mut matches: Vec<SessionMatch> = self
+            .find(query)?
+            .into_iter()
+            .map(|meta| SessionMatch {
+                meta,
+                snippet: None,
+            })
+            .collect();
+
+        let Some(fts_query) = fts_query(query) else {
+            return Ok(matches);
+        };
+        let hits: Vec<(String, String)> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT session_id, snippet(message_fts, 1, '[', ']', '…', 12)
+                 FROM message_fts WHERE message_fts MATCH ?1
+                 ORDER BY rank LIMIT 500",
+            )?;
+            let rows = stmt
+                .query_map(params![fts_query], |r| Ok((r.get(0)?, r.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        for (id, snippet) in hits {
+            if let Some(m) = matches.iter_mut().find(|m| m.meta.id == id) {
+                m.snippet.get_or_insert(snippet);
+                continue;
+            }
+            if let Some(meta) = self.get(&id)? {
+                matches.push(SessionMatch {
+                    meta,
+                    snippet: Some(snippet),
+                });
+            }
+        }
+        Ok(matches)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        let path = self.log_path(id)?;
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let data_dir = self.dir.join(id);
+        if data_dir.is_dir() {
+            std::fs::remove_dir_all(data_dir)?;
+        }
+        self.saved.lock().unwrap().remove(id);
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
+        conn.execute("DELETE FROM message_fts WHERE session_id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn rename(&self, id: &str, title: &str) -> Result<()> {
+        let mut meta = self
+            .get(id)?
+            .with_context(|| format!("No session with id {id}"))?;
+        meta.title = title.to_string();
+        log::append(&self.log_path(id)?, &[Record::meta(&meta)])?;
+        if let Some(state) = self.saved.lock().unwrap().get_mut(id) {
+            state.title = meta.title.clone();
+        }
+        self.conn.lock().unwrap().execute(
+            "UPDATE sessions SET title = ?2 WHERE id = ?1",
+            params![id, title],
+        )?;
+        Ok(())
+    }
+
+    /// Upsert the session row and index messages from node `from` on.
+    fn index(&self, thread: &Thread, meta: &SessionMeta, from: usize) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO sessions (id, title, created_at, updated_at, message_count, provider, model)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(id) DO UPDATE SET
+                title = excluded.title,
+                updated_at = excluded.updated_at,
+                message_count = excluded.message_count,
+                provider = excluded.provider,
+                model = excluded.model",
+            params![
+                meta.id,
+                meta.title,
+                meta.created_at.timestamp_millis(),
+                meta.updated_at.timestamp_millis(),
+                meta.message_count as i64,
+                meta.provider,
+                meta.model,
+            ],
+        )?;
+        if from == 0 {
+            tx.execute(
+                "DELETE FROM message_fts WHERE session_id = ?1",
+                params![meta.id],
+            )?;
+        }
+        {
+            let mut stmt =
+                tx.prepare("INSERT INTO message_fts (session_id, content) VALUES (?1, ?2)")?;
+            for node in &thread.nodes()[from..] {
+                let text = searchable_text(&node.message);
+                if !text.is_empty() {
+                    stmt.execute(params![meta.id, text])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn rebuild_index(&self) -> Result<()> {
+        for entry in std::fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            match log::replay(&path) {
+                Ok(replayed) => self.index(&replayed.thread, &replayed.meta, 0)?,
+                Err(e) => tracing::warn!("Skipping session log {}: {e}", path.display()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert `<id>.json` files from before the log format, moving the
+    /// originals to `legacy/` once converted.
+    fn migrate_legacy(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Err(e) = self.migrate_file(&path) {
+                tracing::warn!("Failed to migrate session {}: {e}", path.display());
+            }
+        }
+    }
+
+    fn migrate_file(&self, path: &Path) -> Result<()> {
+        let json = std::fs::read_to_string(path)?;
+        let file: LegacySessionFile = serde_json::from_str(&json)?;
+        let mut meta = file.meta;
+        meta.message_count = file.thread.message_count();
+        log::rewrite(
+            &self.log_path(&meta.id)?,
+            &log::snapshot(&file.thread, &meta),
+        )?;
+        self.index(&file.thread, &meta, 0)?;
+
+        let legacy_dir = self.dir.join("legacy");
+        std::fs::create_dir_all(&legacy_dir)?;
+        std::fs::rename(path, legacy_dir.join(path.file_name().unwrap_or_default()))?;
+        Ok(())
+    }
+}
+
+fn derive_title(thread: &Thread) -> String {
+    thread
+        .messages()
+        .iter()
+        .find(|m| m.role == Role::User)
+        .map(|m| {
+            let text = m.content.as_text();
+            if text.chars().count() > 80 {
+                format!("{}...", text.chars().take(77).collect::<String>())
+            } else {
+                text.to_string()
+            }
+        })
+        .unwrap_or_else(|| "untitled".to_string())
+}
+
+fn meta_from_row(row: &rusqlite::Row) -> rusqlite::Result<SessionMeta> {
+    let millis = |i| -> rusqlite::Result<DateTime<Utc>> {
+        Ok(DateTime::from_timestamp_millis(row.get(i)?).unwrap_or_default())
+    };
+    Ok(SessionMeta {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        created_at: millis(2)?,
+        updated_at: millis(3)?,
+        message_count: row.get::<_, i64>(4)? as usize,
+        provider: row.get(5)?,
+        model: row.get(6)?,
+    })
+}
+
+/// Text worth searching: prose from users and the assistant, not tool
+/// output or images.
+fn searchable_text(message: &Message) -> String {
+    match &message.content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Quote each word so user input cannot be read as FTS5 syntax; all words
+/// must match.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(role: Role, text: &str) -> Message {
+        Message {
+            role,
+            content: MessageContent::Text(text.into()),
+        }
+    }
+
+    fn log_lines(store: &SessionStore, id: &str) -> usize {
+        std::fs::read_to_string(store.log_path(id).unwrap())
+            .unwrap()
+            .lines()
+            .count()
+    }
+
+    #[test]
+    fn saves_append_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::open_dir(dir.path()).unwrap();
+        let mut thread = Thread::new();
+        thread.push_message(text(Role::User, "fix the parser"));
+        thread.push_message(text(Role::Assistant, "done"));
+        store.save(&thread, "openai", "gpt").unwrap();
+        // meta + 2 nodes + state
+        assert_eq!(log_lines(&store, &thread.id), 4);
+
+        thread.push_message(text(Role::User, "now the lexer"));
+        store.save(&thread, "openai", "gpt").unwrap();
+        // + 1 node + state
+        assert_eq!(log_lines(&store, &thread.id), 6);
+        // Nothing new: nothing written.
+        store.save(&thread, "openai", "gpt").unwrap();
+        assert_eq!(log_lines(&store, &thread.id), 6);
+
+        store.rename(&thread.id, "Parser work").unwrap();
+        let reopened = SessionStore::open_dir(dir.path()).unwrap();
+        let (loaded, meta) = reopened.load(&thread.id).unwrap();
+        assert_eq!(loaded.message_count(), 3);
+        assert_eq!(meta.title, "Parser work");
+        assert_eq!(reopened.list().unwrap()[0].title, "Parser work");
+    }
+
+    #[test]
+    fn rewrites_after_compaction_and_survives_torn_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::open_dir(dir.path()).unwrap();
+        let mut thread = Thread::new();
+        for i in 0..6 {
+            thread.push_message(text(Role::User, &format!("q{i}")));
+            thread.push_message(text(Role::Assistant, &format!("a{i}")));
+        }
+        store.save(&thread, "p", "m").unwrap();
+        thread.compact("summary", 2);
+        store.save(&thread, "p", "m").unwrap();
+        let (loaded, _) = store.load(&thread.id).unwrap();
+        assert_eq!(loaded.message_count(), thread.message_count());
+
+        // A crash mid-append leaves a partial line at the end.
+        let path = store.log_path(&thread.id).unwrap();
+        let mut content = std::fs::read_to_string(&path).unwrap();
+        content.push_str("{\"type\":\"node\",\"id\":");
+        std::fs::write(&path, content).unwrap();
+        let (mut loaded, _) = store.load(&thread.id).unwrap();
+        assert_eq!(loaded.message_count(), thread.message_count());
+
+        loaded.push_message(text(Role::User, "after crash"));
+        store.save(&loaded, "p", "m").unwrap();
+        let (reloaded, _) = store.load(&thread.id).unwrap();
+        assert_eq!(
+            reloaded.messages().last().unwrap().content.as_text(),
+            "after crash"
+        );
+    }
+
+    #[test]
+    fn migrates_json_sessions_and_searches_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = r#"{
+            "meta": {"id": "abc123", "title": "old one",
+                     "created_at": "2025-01-01T00:00:00Z", "updated_at": "2025-01-02T00:00:00Z",
+                     "message_count": 2, "provider": "openai", "model": "gpt"},
+            "thread": {"id": "abc123", "created_at": "2025-01-01T00:00:00Z",
+                       "messages": [{"role": "user", "content": "why does the borrow checker complain"},
+                                    {"role": "assistant", "content": "because of lifetimes"}]}
+        }"#;
+        std::fs::write(dir.path().join("abc123.json"), legacy).unwrap();
+
+        let store = SessionStore::open_dir(dir.path()).unwrap();
+        assert!(dir.path().join("legacy/abc123.json").exists());
+        let (thread, meta) = store.load("abc123").unwrap();
+        assert_eq!(thread.message_count(), 2);
+        assert_eq!(meta.title, "old one");
+
+        assert!(store.find("lifetimes").unwrap().is_empty());
+        let hits = store.search("lifetimes").unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].meta.id, "abc123");
+        assert!(hits[0].snippet.as_deref().unwrap().contains("[lifetimes]"));
+        assert_eq!(store.search("abc").unwrap()[0].snippet, None);
+        assert!(store.search("\"unbalanced").unwrap().is_empty());
+
+        // The index can be rebuilt from the logs alone.
+        drop(store);
+        std::fs::remove_file(dir.path().join("index.db")).unwrap();
+        let store = SessionStore::open_dir(dir.path()).unwrap();
+        assert_eq!(store.search("borrow checker").unwrap().len(), 1);
+
+        store.delete("abc123").unwrap();
+        assert!(store.list().unwrap().is_empty());
+        assert!(store.search("lifetimes").unwrap().is_empty());
+    }
+
+    #[test]
+    fn long_non_ascii_titles_are_cut_between_characters() {
+        let mut thread = Thread::new();
+        thread.push_message(text(Role::User, &"é".repeat(100)));
+        assert_eq!(derive_title(&thread), format!("{}...", "é".repeat(77)));
+
+        let mut thread = Thread::new();
+        thread.push_message(text(Role::User, &"日本".repeat(40)));
+        assert_eq!(derive_title(&thread), "日本".repeat(40));
+    }
+
+    #[test]
+    fn ids_outside_the_sessions_dir_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = dir.path().join("sessions");
+        std::fs::create_dir_all(dir.path().join("keep")).unwrap();
+        let store = SessionStore::open_dir(&sessions).unwrap();
+
+        std::fs::write(dir.path().join("keep.jsonl"), "").unwrap();
+        for id in ["", "..", "../keep", ".", "a/b"] {
+            assert!(store.delete(id).is_err(), "{id:?}");
+            assert!(store.load(id).is_err(), "{id:?}");
+        }
+        assert!(dir.path().join("keep.jsonl").exists());
+        assert!(dir.path().join("keep").is_dir());
+        assert!(sessions.is_dir());
+    }
+}
@@ -37,10 +37,8 @@ pub fn analyze_session(thread: &Thread) -> SessionAnalytics {
     };
 
     let mut consecutive_user_msgs = 0;
-    let mut turn_idx = 0;
-
-    for msg in messages {
-        turn_idx += 1;
+    for (i, msg) in messages.iter().enumerate() {
+        let turn_idx = i + 1;
         match msg.role {
             nyzhi_provider::Role::User => {
                 consecutive_user_msgs += 1;
@@ -63,11 +63,13 @@ fn scan_skills_dir(dir: &Path) -> Vec<Skill> {
     skills
 }
 
-/// Load skills from `.nyzhi/skills/` and `.claude/skills/`.
+/// Load skills from `.nyzhi/skills/` and `.claude/skills/`, plus the
+/// namespaced skills of enabled plugins.
 /// `.nyzhi/skills/` takes priority on name collisions.
 pub fn load_skills(project_root: &Path) -> Result<Vec<Skill>> {
     let mut skills = scan_skills_dir(&skills_dir(project_root));
-    let fallback = scan_skills_dir(&project_root.join(".claude").join("skills"));
+    let mut fallback = scan_skills_dir(&project_root.join(".claude").join("skills"));
+    fallback.extend(crate::plugins::manager::PluginManager::load_all(project_root).all_skills());
 
     for skill in fallback {
         if !skills.iter().any(|s| s.name == skill.name) {
@@ -81,7 +83,7 @@ pub fn load_skills(project_root: &Path) -> Result<Vec<Skill>> {
 
 /// Extract a one-line description from a skill's content.
 /// Checks for `description:` frontmatter, then falls back to the first non-heading line.
-fn extract_description(content: &str) -> Option<String> {
+pub(crate) fn extract_description(content: &str) -> Option<String> {
     for line in content.lines() {
         let trimmed = line.trim();
         if let Some(desc) = trimmed.strip_prefix("description:") {
@@ -135,6 +137,20 @@ pub fn format_skills_full(skills: &[Skill]) -> String {
     out
 }
 
+pub fn build_skill_template(name: &str, description: &str, patterns: &[String]) -> String {
+    let mut content = format!("# Skill: {name}\n\n");
+    content.push_str(&format!("## Description\n{description}\n\n"));
+    content.push_str("## Patterns\n\n");
+    for pattern in patterns {
+        content.push_str(&format!("- {pattern}\n"));
+    }
+    content.push_str("\n## When to Apply\n\n");
+    content.push_str("- [Describe conditions]\n\n");
+    content.push_str("## Examples\n\n");
+    content.push_str("```\n[Add examples]\n```\n");
+    content
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,17 +191,3 @@ mod tests {
         assert!(skills.is_empty());
     }
 }
-
-pub fn build_skill_template(name: &str, description: &str, patterns: &[String]) -> String {
-    let mut content = format!("# Skill: {name}\n\n");
-    content.push_str(&format!("## Description\n{description}\n\n"));
-    content.push_str("## Patterns\n\n");
-    for pattern in patterns {
-        content.push_str(&format!("- {pattern}\n"));
-    }
-    content.push_str("\n## When to Apply\n\n");
-    content.push_str("- [Describe conditions]\n\n");
-    content.push_str("## Examples\n\n");
-    content.push_str("```\n[Add examples]\n```\n");
-    content
-}
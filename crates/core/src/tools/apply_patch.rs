@@ -274,7 +274,7 @@ fn parse_unified_diff(patch: &str) -> Result<Vec<FileHunks>> {
     let mut current_hunk: Option<Hunk> = None;
 
     for line in patch.lines() {
-        if line.starts_with("+++ ") {
+        if let Some(target) = line.strip_prefix("+++ ") {
             if let Some(hunk) = current_hunk.take() {
                 if let Some(f) = current_file.as_mut() {
                     f.hunks.push(hunk);
@@ -285,7 +285,7 @@ fn parse_unified_diff(patch: &str) -> Result<Vec<FileHunks>> {
                     result.push(f);
                 }
             }
-            let target = line[4..].trim();
+            let target = target.trim();
             let target = target.strip_prefix("b/").unwrap_or(target);
             current_file = Some(FileHunks {
                 target_file: target.to_string(),
@@ -4,11 +4,13 @@ use serde_json::{json, Value};
 use std::process::Stdio;
 use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
 
 use super::permission::ToolPermission;
 use super::{Tool, ToolContext, ToolResult};
 use crate::agent::AgentEvent;
+use crate::persistent_shell::{PersistentShell, ShellOutcome};
+use crate::sandbox::{is_dangerous_command, isolate_process_group, ProcessGroup};
+use nyzhi_config::SandboxLevel;
 
 const MAX_OUTPUT_BYTES: usize = 100 * 1024;
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
@@ -24,8 +26,12 @@ impl Tool for BashTool {
 
     fn description(&self) -> &str {
         "Run a shell command and return stdout, stderr, and exit code. \
-         Commands are executed in the working directory. \
-         Use `timeout` parameter to set a timeout in seconds (default 30, max 120)."
+         Commands are executed in the working directory under the session's sandbox: \
+         read-only allows no writes outside the temp directory and no network, \
+         workspace-write limits writes to the project. \
+         Use `timeout` parameter to set a timeout in seconds (default 30, max 120). \
+         When the persistent shell is enabled, `cd`, exported variables and sourced \
+         scripts carry over between calls."
     }
 
     fn parameters_schema(&self) -> Value {
@@ -61,18 +67,34 @@ impl Tool for BashTool {
             .unwrap_or(DEFAULT_TIMEOUT_SECS)
             .min(MAX_TIMEOUT_SECS);
 
-        let spawn_result = Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .current_dir(&ctx.cwd)
+        let launcher = ctx.launcher();
+        if launcher.level() != SandboxLevel::FullAccess && is_dangerous_command(command) {
+            return Ok(ToolResult {
+                output: format!(
+                    "Command refused by the {} sandbox: it runs a destructive command or could not be parsed",
+                    launcher.level()
+                ),
+                title: format!("bash (blocked): {}", truncate_title(command)),
+                metadata: json!({ "exit_code": -1, "denied": true, "reason": "dangerous_command" }),
+            });
+        }
+
+        if let Some(shell) = &ctx.shell {
+            return run_persistent(shell, command, timeout_secs, &launcher, ctx).await;
+        }
+
+        let mut shell = launcher.shell(command, &ctx.cwd)?;
+        shell
+            .stdin(Stdio::null())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn();
+            .stderr(Stdio::piped());
+        isolate_process_group(&mut shell);
 
-        let mut child = match spawn_result {
+        let mut child = match shell.spawn() {
             Ok(c) => c,
             Err(e) => return Err(anyhow::anyhow!("Failed to spawn command: {e}")),
         };
+        let mut group = ProcessGroup::new(&child);
 
         let stdout_pipe = child.stdout.take().unwrap();
         let stderr_pipe = child.stderr.take().unwrap();
@@ -84,6 +106,7 @@ impl Tool for BashTool {
         let mut stdout_done = false;
         let mut stderr_done = false;
         let mut timed_out = false;
+        let mut cancelled = false;
 
         let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout_secs);
 
@@ -122,12 +145,33 @@ impl Tool for BashTool {
                 }
                 _ = tokio::time::sleep_until(deadline) => {
                     timed_out = true;
-                    let _ = child.kill().await;
+                    break;
+                }
+                _ = ctx.cancel.cancelled() => {
+                    cancelled = true;
                     break;
                 }
             }
         }
 
+        if timed_out || cancelled {
+            group.kill();
+            let _ = child.kill().await;
+        }
+
+        if cancelled {
+            truncate_output(&mut accumulated);
+            let mut result = ToolResult::cancelled(
+                "bash",
+                format!("{accumulated}\n\n(command cancelled by user)")
+                    .trim_start()
+                    .to_string(),
+            );
+            result.title = format!("bash (cancelled): {}", truncate_title(command));
+            result.metadata["exit_code"] = json!(-1);
+            return Ok(result);
+        }
+
         if timed_out {
             return Ok(ToolResult {
                 output: if accumulated.is_empty() {
@@ -143,6 +187,7 @@ impl Tool for BashTool {
 
         let status = child.wait().await;
         let exit_code = status.ok().and_then(|s| s.code()).unwrap_or(-1);
+        group.release();
 
         if accumulated.is_empty() {
             accumulated.push_str("(no output)");
@@ -158,6 +203,91 @@ impl Tool for BashTool {
     }
 }
 
+/// Run `command` in the agent's persistent shell. The result's metadata
+/// carries the shell's working directory afterwards as `cwd`.
+async fn run_persistent(
+    shell: &PersistentShell,
+    command: &str,
+    timeout_secs: u64,
+    launcher: &crate::sandbox::Launcher,
+    ctx: &ToolContext,
+) -> Result<ToolResult> {
+    let mut on_output = |text: &str| {
+        for line in text.lines() {
+            emit_delta(ctx, line);
+        }
+    };
+    let run = shell
+        .run(
+            command,
+            launcher,
+            &ctx.cwd,
+            Duration::from_secs(timeout_secs),
+            &ctx.cancel,
+            &mut on_output,
+        )
+        .await?;
+
+    let mut output = run.output.trim_end_matches('\n').to_string();
+    truncate_output(&mut output);
+    let mut notes = Vec::new();
+    match run.outcome {
+        ShellOutcome::Cancelled => notes.push("(command cancelled by user)".to_string()),
+        ShellOutcome::TimedOut => {
+            notes.push(format!("(command timed out after {timeout_secs}s)"))
+        }
+        ShellOutcome::Completed if run.exit_code.is_none() => {
+            notes.push("(the shell exited)".to_string())
+        }
+        ShellOutcome::Completed => {}
+    }
+    if run.reset {
+        notes.push(
+            "(the persistent shell was reset; the next command starts a new one \
+             in the last known directory, without earlier exports)"
+                .to_string(),
+        );
+    }
+    for note in notes {
+        if !output.is_empty() {
+            output.push_str("\n\n");
+        }
+        output.push_str(&note);
+    }
+
+    let exit_code = run.exit_code.unwrap_or(-1);
+    let mut metadata = json!({ "exit_code": exit_code, "persistent": true });
+    if let Some(cwd) = &run.cwd {
+        metadata["cwd"] = json!(cwd);
+    }
+    let result = match run.outcome {
+        ShellOutcome::Cancelled => {
+            let mut result = ToolResult::cancelled("bash", output);
+            result.title = format!("bash (cancelled): {}", truncate_title(command));
+            result.metadata["exit_code"] = json!(-1);
+            result
+        }
+        ShellOutcome::TimedOut => {
+            metadata["timeout"] = json!(true);
+            ToolResult {
+                output,
+                title: format!("bash (timeout): {}", truncate_title(command)),
+                metadata,
+            }
+        }
+        ShellOutcome::Completed => ToolResult {
+            output: if output.is_empty() {
+                "(no output)".to_string()
+            } else {
+                output
+            },
+            title: format!("bash: {}", truncate_title(command)),
+            metadata,
+        },
+    };
+    Ok(result)
+}
+
 fn emit_delta(ctx: &ToolContext, line: &str) {
     if let Some(tx) = &ctx.event_tx {
         let _ = tx.send(AgentEvent::ToolOutputDelta {
@@ -169,15 +299,39 @@ fn emit_delta(ctx: &ToolContext, line: &str) {
 
 fn truncate_output(s: &mut String) {
     if s.len() > MAX_OUTPUT_BYTES {
-        s.truncate(MAX_OUTPUT_BYTES);
+        s.truncate(floor_char_boundary(s, MAX_OUTPUT_BYTES));
         s.push_str("\n... (output truncated)");
     }
 }
 
 fn truncate_title(cmd: &str) -> String {
     if cmd.len() > 60 {
-        format!("{}...", &cmd[..57])
+        format!("{}...", &cmd[..floor_char_boundary(cmd, 57)])
     } else {
         cmd.to_string()
     }
 }
+
+/// The largest index `<= index` that does not split a character.
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncation_keeps_multi_byte_characters_whole() {
+        let title = truncate_title(&format!("echo {}", "é".repeat(40)));
+        assert!(title.ends_with("é..."));
+        assert!(title.len() <= 60);
+
+        let mut output = format!("x{}", "é".repeat(MAX_OUTPUT_BYTES));
+        truncate_output(&mut output);
+        assert!(output.ends_with("é\n... (output truncated)"));
+    }
+}
@@ -63,16 +63,15 @@ impl Tool for BatchApplyTool {
         }
 
         let total = files.len();
+        let launcher = ctx.launcher();
         let futs = files.iter().map(|file| {
             let cmd = command.replace("{file}", file);
-            let cwd = ctx.cwd.clone();
+            let command = launcher.shell(&cmd, &ctx.cwd);
             async move {
-                let output = tokio::process::Command::new("sh")
-                    .arg("-c")
-                    .arg(&cmd)
-                    .current_dir(&cwd)
-                    .output()
-                    .await;
+                let output = match command {
+                    Ok(mut command) => command.output().await,
+                    Err(e) => return format!("[error] {file}: {e:#}"),
+                };
                 match output {
                     Ok(out) => {
                         let stdout = String::from_utf8_lossy(&out.stdout);
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{Tool, ToolContext, ToolResult};
+use crate::tools::permission::ToolPermission;
+
+use nyzhi_index::CodebaseIndex;
+use std::sync::Arc;
+
+const DEFAULT_MAX_RESULTS: usize = 50;
+
+pub struct CodeGraphTool {
+    index: Arc<CodebaseIndex>,
+}
+
+impl CodeGraphTool {
+    pub fn new(index: Arc<CodebaseIndex>) -> Self {
+        Self { index }
+    }
+}
+
+#[async_trait]
+impl Tool for CodeGraphTool {
+    fn name(&self) -> &str {
+        "code_graph"
+    }
+
+    fn description(&self) -> &str {
+        "Query the cross-reference index built from the project's syntax trees. \
+         'definitions' finds where a symbol is defined, 'callers' lists call sites of a \
+         function, 'callees' lists what a function calls, and 'dependents' lists files that \
+         import a file or use names only it defines. Use it for impact questions like \
+         'what breaks if I change ToolContext' instead of grepping. Names are matched \
+         textually, so same-named methods on different types are not told apart."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "enum": ["definitions", "callers", "callees", "dependents"],
+                    "description": "Which relation to look up"
+                },
+                "target": {
+                    "type": "string",
+                    "description": "Symbol name (e.g. 'run_turn' or 'Store::search') or, for 'dependents', a project-relative file path"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "description": "Maximum results to return (default: 50)",
+                    "default": 50
+                }
+            },
+            "required": ["query", "target"]
+        })
+    }
+
+    fn permission(&self) -> ToolPermission {
+        ToolPermission::ReadOnly
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: query"))?;
+        let target = args
+            .get("target")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: target"))?;
+        let max_results = args
+            .get("max_results")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_MAX_RESULTS as u64) as usize;
+
+        let lines: Vec<String> = match query {
+            "definitions" => self
+                .index
+                .definitions(target)?
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            "callers" => self
+                .index
+                .callers_of(target)?
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            "callees" => self
+                .index
+                .callees_of(target)?
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            "dependents" => {
+                let rel = relative_path(target, ctx);
+                self.index.dependents_of(&rel)?
+            }
+            other => anyhow::bail!(
+                "Unknown query '{other}' (expected definitions, callers, callees or dependents)"
+            ),
+        };
+
+        let count = lines.len();
+        let output = if lines.is_empty() {
+            let hint = if self.index.is_ready() {
+                "Languages without a tree-sitter grammar are not covered; try grep."
+            } else {
+                "The index is still building; try again in a moment or use grep."
+            };
+            format!("No {query} found for '{target}'. {hint}")
+        } else {
+            let mut out = lines
+                .into_iter()
+                .take(max_results)
+                .collect::<Vec<_>>()
+                .join("\n");
+            if count > max_results {
+                out.push_str(&format!("\n... ({} more)", count - max_results));
+            }
+            out
+        };
+
+        Ok(ToolResult {
+            output,
+            title: format!("code_graph: {query} {target}"),
+            metadata: json!({ "query": query, "result_count": count }),
+        })
+    }
+}
+
+/// Index paths are relative to the project root; accept absolute paths too.
+fn relative_path(target: &str, ctx: &ToolContext) -> String {
+    let path = std::path::Path::new(target);
+    path.strip_prefix(&ctx.project_root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .trim_start_matches("./")
+        .to_string()
+}
@@ -70,7 +70,7 @@ impl Tool for FuzzyFindTool {
             })
             .collect();
 
-        scored.sort_by(|a, b| b.1.cmp(&a.1));
+        scored.sort_by_key(|s| std::cmp::Reverse(s.1));
         scored.truncate(max_results);
 
         let count = scored.len();
@@ -1,10 +1,23 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use super::{Tool, ToolContext, ToolResult};
+use crate::lsp::{LspClient, LspLocation, LspPoolHandle, LspPosition};
 
-pub struct LspDiagnosticsTool;
+pub struct LspDiagnosticsTool {
+    pool: LspPoolHandle,
+}
+
+impl LspDiagnosticsTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
 
 #[async_trait]
 impl Tool for LspDiagnosticsTool {
@@ -13,19 +26,29 @@ impl Tool for LspDiagnosticsTool {
     }
 
     fn description(&self) -> &str {
-        "Detect available LSP language servers and list diagnostics capabilities. \
-         Shows which servers are installed for the current project."
+        "Get compiler/linter diagnostics for a file from its language server. \
+         Without a file, lists which LSP servers are installed and running for the project."
     }
 
     fn parameters_schema(&self) -> Value {
         json!({
             "type": "object",
-            "properties": {}
+            "properties": {
+                "file": {
+                    "type": "string",
+                    "description": "File to fetch diagnostics for (absolute or relative to cwd)"
+                }
+            }
         })
     }
 
-    async fn execute(&self, _args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        if let Some(file) = args.get("file").and_then(|v| v.as_str()) {
+            return self.file_diagnostics(&ctx.cwd.join(file), ctx).await;
+        }
+
         let servers = crate::lsp::detect_available_servers();
+        let running = self.pool.running().await;
 
         let output = if servers.is_empty() {
             "No LSP servers detected.\n\nInstall one of:\n  \
@@ -37,7 +60,18 @@ impl Tool for LspDiagnosticsTool {
         } else {
             let lines: Vec<String> = servers
                 .iter()
-                .map(|(lang, cmd)| format!("  {lang}: {cmd}"))
+                .map(|(lang, cmd)| {
+                    let roots: Vec<String> = running
+                        .iter()
+                        .filter(|(l, _)| l == lang)
+                        .map(|(_, root)| root.display().to_string())
+                        .collect();
+                    if roots.is_empty() {
+                        format!("  {lang}: {cmd}")
+                    } else {
+                        format!("  {lang}: {cmd} (running in {})", roots.join(", "))
+                    }
+                })
                 .collect();
             format!("Available LSP servers:\n{}", lines.join("\n"))
         };
@@ -50,6 +84,63 @@ impl Tool for LspDiagnosticsTool {
     }
 }
 
+impl LspDiagnosticsTool {
+    async fn file_diagnostics(&self, file: &Path, ctx: &ToolContext) -> Result<ToolResult> {
+        let display = file.strip_prefix(&ctx.project_root).unwrap_or(file);
+        let Some(client) = self.pool.client_for(file, &ctx.project_root).await? else {
+            return Ok(ToolResult {
+                output: format!("No LSP server installed for {}", display.display()),
+                title: "lsp_diagnostics".to_string(),
+                metadata: json!({"file": display}),
+            });
+        };
+
+        let diagnostics = client.diagnostics(file, Duration::from_secs(10)).await?;
+        let content = tokio::fs::read_to_string(file).await.unwrap_or_default();
+        let source: Vec<&str> = content.lines().collect();
+
+        let output = if diagnostics.is_empty() {
+            format!("No diagnostics for {}", display.display())
+        } else {
+            let lines: Vec<String> = diagnostics
+                .iter()
+                .map(|d| {
+                    let line = source
+                        .get(d.range.start.line as usize)
+                        .copied()
+                        .unwrap_or("");
+                    let col = crate::lsp::char_column(line, d.range.start.character) + 1;
+                    let severity = match d.severity {
+                        Some(1) => "error",
+                        Some(2) => "warning",
+                        Some(3) => "info",
+                        Some(4) => "hint",
+                        _ => "diagnostic",
+                    };
+                    let source = d
+                        .source
+                        .as_deref()
+                        .map(|s| format!(" [{s}]"))
+                        .unwrap_or_default();
+                    format!(
+                        "{}:{}:{col}: {severity}{source}: {}",
+                        display.display(),
+                        d.range.start.line + 1,
+                        d.message
+                    )
+                })
+                .collect();
+            lines.join("\n")
+        };
+
+        Ok(ToolResult {
+            output,
+            title: format!("lsp_diagnostics({})", display.display()),
+            metadata: json!({"file": display, "count": diagnostics.len()}),
+        })
+    }
+}
+
 pub struct AstSearchTool;
 
 #[async_trait]
@@ -133,7 +224,131 @@ impl Tool for AstSearchTool {
     }
 }
 
-pub struct LspGotoDefinitionTool;
+/// A symbol position from tool arguments, resolved against the tool context.
+pub(super) struct SymbolPosition {
+    pub(super) file: PathBuf,
+    pub(super) line: u64,
+    pub(super) column: u64,
+    pub(super) word: String,
+    pub(super) lsp: LspPosition,
+}
+
+fn position_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "file": { "type": "string", "description": "Path to the file (absolute or relative to cwd)." },
+            "line": { "type": "integer", "description": "1-based line number." },
+            "column": { "type": "integer", "description": "1-based column number." }
+        },
+        "required": ["file", "line", "column"]
+    })
+}
+
+pub(super) async fn resolve_position(args: &Value, ctx: &ToolContext) -> Result<SymbolPosition> {
+    let file = args
+        .get("file")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing: file"))?;
+    let line = args
+        .get("line")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(1)
+        .max(1);
+    let column = args
+        .get("column")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(1)
+        .max(1);
+    let file = ctx.cwd.join(file);
+
+    let content = tokio::fs::read_to_string(&file).await?;
+    let target_line = content.lines().nth((line - 1) as usize).unwrap_or("");
+    let word = extract_word_at(target_line, (column - 1) as usize);
+    let lsp = LspPosition {
+        line: (line - 1) as u32,
+        character: crate::lsp::utf16_column(target_line, (column - 1) as usize),
+    };
+
+    Ok(SymbolPosition {
+        file,
+        line,
+        column,
+        word,
+        lsp,
+    })
+}
+
+/// Look up the pooled server for a file. Errors are logged and reported as
+/// `None` together with a note so the caller can fall back to ripgrep.
+pub(super) async fn pooled_client(
+    pool: &LspPoolHandle,
+    file: &Path,
+    ctx: &ToolContext,
+) -> (Option<Arc<LspClient>>, Option<String>) {
+    match pool.client_for(file, &ctx.project_root).await {
+        Ok(client) => (client, None),
+        Err(e) => {
+            tracing::warn!("LSP unavailable for {}: {e:#}", file.display());
+            (None, Some(format!("LSP unavailable ({e}); ")))
+        }
+    }
+}
+
+/// Render locations as `path:line:col: preview`, relative to the project root.
+async fn format_locations(locations: &[LspLocation], project_root: &Path, limit: usize) -> String {
+    let mut files: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let mut lines = Vec::new();
+    for loc in locations.iter().take(limit) {
+        let Some(path) = loc.path() else {
+            lines.push(format!("{}:{}", loc.uri, loc.range.start.line + 1));
+            continue;
+        };
+        if !files.contains_key(&path) {
+            let text = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+            files.insert(path.clone(), text.lines().map(String::from).collect());
+        }
+        let source_line = files[&path]
+            .get(loc.range.start.line as usize)
+            .map(String::as_str)
+            .unwrap_or("");
+        let col = crate::lsp::char_column(source_line, loc.range.start.character) + 1;
+        let display = path.strip_prefix(project_root).unwrap_or(&path);
+        lines.push(format!(
+            "{}:{}:{col}: {}",
+            display.display(),
+            loc.range.start.line + 1,
+            source_line.trim()
+        ));
+    }
+    if locations.len() > limit {
+        lines.push(format!("... ({} total)", locations.len()));
+    }
+    lines.join("\n")
+}
+
+fn no_symbol_result(pos: &SymbolPosition, title: &str) -> ToolResult {
+    ToolResult {
+        output: format!(
+            "No symbol found at {}:{}:{}",
+            pos.file.display(),
+            pos.line,
+            pos.column
+        ),
+        title: title.to_string(),
+        metadata: json!({}),
+    }
+}
+
+pub struct LspGotoDefinitionTool {
+    pool: LspPoolHandle,
+}
+
+impl LspGotoDefinitionTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
 
 #[async_trait]
 impl Tool for LspGotoDefinitionTool {
@@ -141,80 +356,60 @@ impl Tool for LspGotoDefinitionTool {
         "lsp_goto_definition"
     }
     fn description(&self) -> &str {
-        "Find the definition of a symbol at a given file location. Returns the file and line \
-         where the symbol is defined."
+        "Find the definition of a symbol at a given file location using the project's language \
+         server. Returns the file and line where the symbol is defined."
     }
     fn parameters_schema(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "file": { "type": "string", "description": "Absolute path to the file." },
-                "line": { "type": "integer", "description": "1-based line number." },
-                "column": { "type": "integer", "description": "1-based column number." }
-            },
-            "required": ["file", "line", "column"]
-        })
+        position_schema()
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
-        let file = args
-            .get("file")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing: file"))?;
-        let line = args.get("line").and_then(|v| v.as_u64()).unwrap_or(1);
-        let col = args.get("column").and_then(|v| v.as_u64()).unwrap_or(1);
-
-        let content = tokio::fs::read_to_string(file).await?;
-        let target_line = content.lines().nth((line - 1) as usize).unwrap_or("");
-        let word = extract_word_at(target_line, (col - 1) as usize);
-
-        if word.is_empty() {
-            return Ok(ToolResult {
-                output: format!("No symbol found at {file}:{line}:{col}"),
-                title: "lsp_goto_definition".to_string(),
-                metadata: json!({}),
-            });
-        }
-
-        let rg = tokio::process::Command::new("rg")
-            .args([
-                "--line-number",
-                "--no-heading",
-                "-e",
-                &format!(
-                    r"(fn|struct|enum|trait|type|const|static|class|interface|def)\s+{word}\b"
-                ),
-            ])
-            .arg(
-                std::path::Path::new(file)
-                    .parent()
-                    .unwrap_or(std::path::Path::new(".")),
-            )
-            .output()
-            .await;
-
-        let output = match rg {
-            Ok(out) => {
-                let stdout = String::from_utf8_lossy(&out.stdout);
-                if stdout.is_empty() {
-                    format!("Definition of '{word}' not found via structural search")
-                } else {
-                    let lines: Vec<&str> = stdout.lines().take(10).collect();
-                    format!("Definition(s) of '{word}':\n{}", lines.join("\n"))
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let pos = resolve_position(&args, ctx).await?;
+        let (client, note) = pooled_client(&self.pool, &pos.file, ctx).await;
+
+        if let Some(client) = client {
+            match client.definition(&pos.file, &pos.lsp).await {
+                Ok(locations) => {
+                    let output = if locations.is_empty() {
+                        format!("No definition found for '{}'", pos.word)
+                    } else {
+                        format!(
+                            "Definition(s) of '{}':\n{}",
+                            pos.word,
+                            format_locations(&locations, &ctx.project_root, 10).await
+                        )
+                    };
+                    return Ok(ToolResult {
+                        output,
+                        title: format!("goto_definition({})", pos.word),
+                        metadata: json!({"symbol": pos.word, "backend": "lsp", "count": locations.len()}),
+                    });
                 }
+                Err(e) => tracing::warn!("LSP definition failed: {e:#}"),
             }
-            Err(_) => "ripgrep not available".to_string(),
-        };
+        }
 
+        if pos.word.is_empty() {
+            return Ok(no_symbol_result(&pos, "lsp_goto_definition"));
+        }
+        let output = fallback_definition(&pos.word, &pos.file).await;
         Ok(ToolResult {
-            output,
-            title: format!("goto_definition({word})"),
-            metadata: json!({"symbol": word}),
+            output: format!("{}{output}", note.unwrap_or_default()),
+            title: format!("goto_definition({})", pos.word),
+            metadata: json!({"symbol": pos.word, "backend": "ripgrep"}),
         })
     }
 }
 
-pub struct LspFindReferencesTool;
+pub struct LspFindReferencesTool {
+    pool: LspPoolHandle,
+}
+
+impl LspFindReferencesTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
 
 #[async_trait]
 impl Tool for LspFindReferencesTool {
@@ -222,81 +417,61 @@ impl Tool for LspFindReferencesTool {
         "lsp_find_references"
     }
     fn description(&self) -> &str {
-        "Find all references to a symbol at a given file location."
+        "Find all references to a symbol at a given file location using the project's \
+         language server."
     }
     fn parameters_schema(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "file": { "type": "string" },
-                "line": { "type": "integer" },
-                "column": { "type": "integer" }
-            },
-            "required": ["file", "line", "column"]
-        })
+        position_schema()
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
-        let file = args
-            .get("file")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing: file"))?;
-        let line = args.get("line").and_then(|v| v.as_u64()).unwrap_or(1);
-        let col = args.get("column").and_then(|v| v.as_u64()).unwrap_or(1);
-
-        let content = tokio::fs::read_to_string(file).await?;
-        let target_line = content.lines().nth((line - 1) as usize).unwrap_or("");
-        let word = extract_word_at(target_line, (col - 1) as usize);
-
-        if word.is_empty() {
-            return Ok(ToolResult {
-                output: format!("No symbol found at {file}:{line}:{col}"),
-                title: "lsp_find_references".to_string(),
-                metadata: json!({}),
-            });
-        }
-
-        let rg = tokio::process::Command::new("rg")
-            .args(["--line-number", "--no-heading", "-w", &word])
-            .arg(
-                std::path::Path::new(file)
-                    .parent()
-                    .unwrap_or(std::path::Path::new(".")),
-            )
-            .output()
-            .await;
-
-        let output = match rg {
-            Ok(out) => {
-                let stdout = String::from_utf8_lossy(&out.stdout);
-                let count = stdout.lines().count();
-                let lines: Vec<&str> = stdout.lines().take(30).collect();
-                let truncated = if count > 30 {
-                    format!("\n... ({count} total references)")
-                } else {
-                    String::new()
-                };
-                if lines.is_empty() {
-                    format!("No references to '{word}' found")
-                } else {
-                    format!(
-                        "References to '{word}' ({count} found):\n{}{truncated}",
-                        lines.join("\n")
-                    )
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let pos = resolve_position(&args, ctx).await?;
+        let (client, note) = pooled_client(&self.pool, &pos.file, ctx).await;
+
+        if let Some(client) = client {
+            match client.references(&pos.file, &pos.lsp, true).await {
+                Ok(locations) => {
+                    let count = locations.len();
+                    let output = if locations.is_empty() {
+                        format!("No references to '{}' found", pos.word)
+                    } else {
+                        format!(
+                            "References to '{}' ({count} found):\n{}",
+                            pos.word,
+                            format_locations(&locations, &ctx.project_root, 30).await
+                        )
+                    };
+                    return Ok(ToolResult {
+                        output,
+                        title: format!("find_references({})", pos.word),
+                        metadata: json!({"symbol": pos.word, "backend": "lsp", "count": count}),
+                    });
                 }
+                Err(e) => tracing::warn!("LSP references failed: {e:#}"),
             }
-            Err(_) => "ripgrep not available".to_string(),
-        };
+        }
 
+        if pos.word.is_empty() {
+            return Ok(no_symbol_result(&pos, "lsp_find_references"));
+        }
+        let output = fallback_references(&pos.word, &pos.file).await;
         Ok(ToolResult {
-            output,
-            title: format!("find_references({word})"),
-            metadata: json!({"symbol": word}),
+            output: format!("{}{output}", note.unwrap_or_default()),
+            title: format!("find_references({})", pos.word),
+            metadata: json!({"symbol": pos.word, "backend": "ripgrep"}),
         })
     }
 }
 
-pub struct LspHoverTool;
+pub struct LspHoverTool {
+    pool: LspPoolHandle,
+}
+
+impl LspHoverTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
 
 #[async_trait]
 impl Tool for LspHoverTool {
@@ -304,77 +479,131 @@ impl Tool for LspHoverTool {
         "lsp_hover"
     }
     fn description(&self) -> &str {
-        "Get type information and documentation for a symbol at a given file location."
+        "Get type information and documentation for a symbol at a given file location using \
+         the project's language server."
     }
     fn parameters_schema(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "file": { "type": "string" },
-                "line": { "type": "integer" },
-                "column": { "type": "integer" }
-            },
-            "required": ["file", "line", "column"]
-        })
+        position_schema()
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
-        let file = args
-            .get("file")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing: file"))?;
-        let line = args.get("line").and_then(|v| v.as_u64()).unwrap_or(1);
-        let col = args.get("column").and_then(|v| v.as_u64()).unwrap_or(1);
-
-        let content = tokio::fs::read_to_string(file).await?;
-        let target_line = content.lines().nth((line - 1) as usize).unwrap_or("");
-        let word = extract_word_at(target_line, (col - 1) as usize);
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let pos = resolve_position(&args, ctx).await?;
+        let (client, note) = pooled_client(&self.pool, &pos.file, ctx).await;
+
+        if let Some(client) = client {
+            match client.hover(&pos.file, &pos.lsp).await {
+                Ok(hover) => {
+                    let output = match hover {
+                        Some(text) => text,
+                        None => format!("No type information found for '{}'", pos.word),
+                    };
+                    return Ok(ToolResult {
+                        output,
+                        title: format!("hover({})", pos.word),
+                        metadata: json!({"symbol": pos.word, "backend": "lsp"}),
+                    });
+                }
+                Err(e) => tracing::warn!("LSP hover failed: {e:#}"),
+            }
+        }
 
-        if word.is_empty() {
-            return Ok(ToolResult {
-                output: format!("No symbol found at {file}:{line}:{col}"),
-                title: "lsp_hover".to_string(),
-                metadata: json!({}),
-            });
+        if pos.word.is_empty() {
+            return Ok(no_symbol_result(&pos, "lsp_hover"));
         }
+        let output = fallback_hover(&pos.word, &pos.file).await;
+        Ok(ToolResult {
+            output: format!("{}{output}", note.unwrap_or_default()),
+            title: format!("hover({})", pos.word),
+            metadata: json!({"symbol": pos.word, "backend": "ripgrep"}),
+        })
+    }
+}
 
-        let rg = tokio::process::Command::new("rg")
-            .args([
-                "--line-number",
-                "--no-heading",
-                "-B",
-                "3",
-                "-e",
-                &format!(
-                    r"(pub\s+)?(fn|struct|enum|trait|type|const|class|interface|def)\s+{word}\b"
-                ),
-            ])
-            .arg(
-                std::path::Path::new(file)
-                    .parent()
-                    .unwrap_or(std::path::Path::new(".")),
-            )
-            .output()
-            .await;
+fn search_dir(file: &Path) -> &Path {
+    file.parent().unwrap_or(Path::new("."))
+}
 
-        let output = match rg {
-            Ok(out) => {
-                let stdout = String::from_utf8_lossy(&out.stdout);
-                if stdout.is_empty() {
-                    format!("No type information found for '{word}'")
-                } else {
-                    let lines: Vec<&str> = stdout.lines().take(20).collect();
-                    format!("Type info for '{word}':\n{}", lines.join("\n"))
-                }
+async fn fallback_definition(word: &str, file: &Path) -> String {
+    let rg = tokio::process::Command::new("rg")
+        .args([
+            "--line-number",
+            "--no-heading",
+            "-e",
+            &format!(r"(fn|struct|enum|trait|type|const|static|class|interface|def)\s+{word}\b"),
+        ])
+        .arg(search_dir(file))
+        .output()
+        .await;
+
+    match rg {
+        Ok(out) => {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            if stdout.is_empty() {
+                format!("Definition of '{word}' not found via structural search")
+            } else {
+                let lines: Vec<&str> = stdout.lines().take(10).collect();
+                format!("Definition(s) of '{word}':\n{}", lines.join("\n"))
             }
-            Err(_) => "ripgrep not available".to_string(),
-        };
+        }
+        Err(_) => "ripgrep not available".to_string(),
+    }
+}
 
-        Ok(ToolResult {
-            output,
-            title: format!("hover({word})"),
-            metadata: json!({"symbol": word}),
-        })
+async fn fallback_references(word: &str, file: &Path) -> String {
+    let rg = tokio::process::Command::new("rg")
+        .args(["--line-number", "--no-heading", "-w", word])
+        .arg(search_dir(file))
+        .output()
+        .await;
+
+    match rg {
+        Ok(out) => {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let count = stdout.lines().count();
+            let lines: Vec<&str> = stdout.lines().take(30).collect();
+            let truncated = if count > 30 {
+                format!("\n... ({count} total references)")
+            } else {
+                String::new()
+            };
+            if lines.is_empty() {
+                format!("No references to '{word}' found")
+            } else {
+                format!(
+                    "References to '{word}' ({count} found):\n{}{truncated}",
+                    lines.join("\n")
+                )
+            }
+        }
+        Err(_) => "ripgrep not available".to_string(),
+    }
+}
+
+async fn fallback_hover(word: &str, file: &Path) -> String {
+    let rg = tokio::process::Command::new("rg")
+        .args([
+            "--line-number",
+            "--no-heading",
+            "-B",
+            "3",
+            "-e",
+            &format!(r"(pub\s+)?(fn|struct|enum|trait|type|const|class|interface|def)\s+{word}\b"),
+        ])
+        .arg(search_dir(file))
+        .output()
+        .await;
+
+    match rg {
+        Ok(out) => {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            if stdout.is_empty() {
+                format!("No type information found for '{word}'")
+            } else {
+                let lines: Vec<&str> = stdout.lines().take(20).collect();
+                format!("Type info for '{word}':\n{}", lines.join("\n"))
+            }
+        }
+        Err(_) => "ripgrep not available".to_string(),
     }
 }
 
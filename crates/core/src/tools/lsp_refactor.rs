@@ -0,0 +1,830 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::change_tracker::FileChange;
+use super::diff::{truncate_diff, unified_diff};
+use super::lsp::{pooled_client, resolve_position};
+use super::permission::{normalize_path, ToolPermission};
+use super::{Prepared, Tool, ToolContext, ToolResult};
+use crate::lsp::{EditOperation, LspClient, LspPoolHandle, LspPosition, LspRange};
+
+/// Argument through which `prepare` hands the server's edits to `execute`,
+/// so the call applies exactly the edits whose paths were approved. Like
+/// every name starting with [`super::PREPARED_ARG_PREFIX`], it is stripped
+/// from the model's arguments.
+const PREPARED_EDITS: &str = "_workspace_edits";
+
+/// Argument through which `prepare` hands a code action's command to
+/// `execute`, which runs it only once the call is approved.
+const PREPARED_COMMAND: &str = "_command";
+
+/// What a prepared call applies: the edits, then the command, if any.
+#[derive(Default)]
+struct PreparedChange {
+    edits: Vec<Value>,
+    command: Option<Value>,
+}
+
+/// Result of applying one file's worth of a workspace edit.
+struct AppliedChange {
+    path: PathBuf,
+    original: Option<String>,
+    new_content: Option<String>,
+}
+
+/// The operations of server-provided `WorkspaceEdit`s, in order, with `.`
+/// and `..` resolved in every path. Fails if any path leaves the project.
+fn workspace_edit_ops(edits: &[Value], project_root: &Path) -> Result<Vec<EditOperation>> {
+    let root = normalize_path(project_root);
+    let mut ops = Vec::new();
+    for edit in edits {
+        for mut op in crate::lsp::parse_workspace_edit(edit)? {
+            for path in op_paths_mut(&mut op) {
+                *path = normalize_path(path);
+                if !path.starts_with(&root) {
+                    anyhow::bail!(
+                        "Refusing to edit {} outside the project root {}",
+                        path.display(),
+                        root.display()
+                    );
+                }
+            }
+            ops.push(op);
+        }
+    }
+    Ok(ops)
+}
+
+/// Apply server-provided `WorkspaceEdit`s as one transaction and record
+/// every touched file in the change tracker, so `/undo` reverts them like an
+/// `edit`. Nothing is written unless every edit applies; edits outside the
+/// project root are rejected.
+async fn apply_workspace_edits(
+    edits: &[Value],
+    ctx: &ToolContext,
+    tool_name: &str,
+) -> Result<Vec<AppliedChange>> {
+    let ops = workspace_edit_ops(edits, &ctx.project_root)?;
+
+    // Virtual view of the files: path -> current content (None = absent).
+    let mut current: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut originals: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut order: Vec<PathBuf> = Vec::new();
+
+    async fn load(
+        path: &Path,
+        current: &mut HashMap<PathBuf, Option<String>>,
+        originals: &mut HashMap<PathBuf, Option<String>>,
+        order: &mut Vec<PathBuf>,
+    ) -> Option<String> {
+        if let Some(content) = current.get(path) {
+            return content.clone();
+        }
+        let content = tokio::fs::read_to_string(path).await.ok();
+        current.insert(path.to_path_buf(), content.clone());
+        originals.insert(path.to_path_buf(), content.clone());
+        order.push(path.to_path_buf());
+        content
+    }
+
+    for op in &ops {
+        match op {
+            EditOperation::Edit { path, edits } => {
+                let text = load(path, &mut current, &mut originals, &mut order)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("Cannot read {}", path.display()))?;
+                let updated = crate::lsp::apply_text_edits(&text, edits)?;
+                current.insert(path.clone(), Some(updated));
+            }
+            EditOperation::Create { path, overwrite } => {
+                let existing = load(path, &mut current, &mut originals, &mut order).await;
+                if existing.is_none() || *overwrite {
+                    current.insert(path.clone(), Some(String::new()));
+                }
+            }
+            EditOperation::Rename {
+                from,
+                to,
+                overwrite,
+            } => {
+                let content = load(from, &mut current, &mut originals, &mut order)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("Cannot read {}", from.display()))?;
+                let target = load(to, &mut current, &mut originals, &mut order).await;
+                if target.is_some() && !*overwrite {
+                    anyhow::bail!("Rename target {} already exists", to.display());
+                }
+                current.insert(from.clone(), None);
+                current.insert(to.clone(), Some(content));
+            }
+            EditOperation::Delete { path } => {
+                load(path, &mut current, &mut originals, &mut order).await;
+                current.insert(path.clone(), None);
+            }
+        }
+    }
+
+    let mut applied: Vec<AppliedChange> = Vec::new();
+    for path in order {
+        let original = originals.remove(&path).flatten();
+        let new_content = current.remove(&path).flatten();
+        if original == new_content {
+            continue;
+        }
+
+        let written = match &new_content {
+            Some(content) => {
+                async {
+                    if let Some(parent) = path.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    tokio::fs::write(&path, content).await
+                }
+                .await
+            }
+            None => tokio::fs::remove_file(&path).await,
+        };
+        if let Err(e) = written {
+            rollback(&applied).await;
+            anyhow::bail!(
+                "Failed to update {}: {e}; changes rolled back",
+                path.display()
+            );
+        }
+        applied.push(AppliedChange {
+            path,
+            original,
+            new_content,
+        });
+    }
+
+    let mut tracker = ctx.change_tracker.lock().await;
+    for change in &applied {
+        tracker.record(FileChange {
+            path: change.path.clone(),
+            original: change.original.clone(),
+            new_content: change.new_content.clone().unwrap_or_default(),
+            tool_name: tool_name.to_string(),
+            timestamp: chrono::Utc::now(),
+        });
+    }
+
+    Ok(applied)
+}
+
+/// Attach `change` to `args` along with every path its edits touch.
+fn prepared(args: &Value, change: PreparedChange, project_root: &Path) -> Result<Prepared> {
+    let PreparedChange { edits, command } = change;
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for mut op in workspace_edit_ops(&edits, project_root)? {
+        paths.extend(op_paths_mut(&mut op).into_iter().map(|p| p.clone()));
+    }
+    paths.sort();
+    paths.dedup();
+
+    let mut args = args.clone();
+    if let Some(fields) = args.as_object_mut() {
+        fields.insert(PREPARED_EDITS.into(), Value::Array(edits));
+        if let Some(command) = command {
+            fields.insert(PREPARED_COMMAND.into(), command);
+        }
+    }
+    Ok(Prepared::Run { args, paths })
+}
+
+/// The change a call carries from `prepare`, fetching it from the server
+/// when it was not prepared; or the result when there is nothing to apply.
+async fn change_for(
+    tool: &dyn Tool,
+    args: &Value,
+    ctx: &ToolContext,
+) -> Result<std::result::Result<PreparedChange, ToolResult>> {
+    let prepared_args;
+    let args = match args.get(PREPARED_EDITS) {
+        Some(_) => args,
+        None => match tool.prepare(args, ctx).await? {
+            Some(Prepared::Run { args, .. }) => {
+                prepared_args = args;
+                &prepared_args
+            }
+            Some(Prepared::Done(result)) => return Ok(Err(result)),
+            None => return Ok(Ok(PreparedChange::default())),
+        },
+    };
+    Ok(Ok(PreparedChange {
+        edits: args
+            .get(PREPARED_EDITS)
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default(),
+        command: args.get(PREPARED_COMMAND).cloned(),
+    }))
+}
+
+fn op_paths_mut(op: &mut EditOperation) -> Vec<&mut PathBuf> {
+    match op {
+        EditOperation::Edit { path, .. }
+        | EditOperation::Create { path, .. }
+        | EditOperation::Delete { path } => vec![path],
+        EditOperation::Rename { from, to, .. } => vec![from, to],
+    }
+}
+
+async fn rollback(applied: &[AppliedChange]) {
+    for change in applied.iter().rev() {
+        match &change.original {
+            Some(content) => {
+                let _ = tokio::fs::write(&change.path, content).await;
+            }
+            None => {
+                let _ = tokio::fs::remove_file(&change.path).await;
+            }
+        }
+    }
+}
+
+/// Summarize applied changes with a short diff per file.
+fn describe_changes(changes: &[AppliedChange], project_root: &Path) -> String {
+    let mut out = Vec::new();
+    for change in changes {
+        let rel = change
+            .path
+            .strip_prefix(project_root)
+            .unwrap_or(&change.path);
+        let rel = rel.display().to_string();
+        match (&change.original, &change.new_content) {
+            (None, Some(_)) => out.push(format!("created {rel}")),
+            (Some(_), None) => out.push(format!("deleted {rel}")),
+            (Some(old), Some(new)) => {
+                let diff = truncate_diff(&unified_diff(&rel, old, new, 1), 20);
+                out.push(format!("modified {rel}\n{diff}"));
+            }
+            (None, None) => {}
+        }
+    }
+    out.join("\n")
+}
+
+/// The pooled server for `file`, or a tool result explaining why there is none.
+/// Refactorings have no ripgrep fallback.
+async fn require_client(
+    pool: &LspPoolHandle,
+    file: &Path,
+    ctx: &ToolContext,
+    title: &str,
+) -> std::result::Result<Arc<LspClient>, ToolResult> {
+    match pooled_client(pool, file, ctx).await {
+        (Some(client), _) => Ok(client),
+        (None, note) => Err(ToolResult {
+            output: format!(
+                "{}No LSP server available for {}",
+                note.unwrap_or_default(),
+                file.display()
+            ),
+            title: title.to_string(),
+            metadata: json!({ "error": "no_server" }),
+        }),
+    }
+}
+
+pub struct LspRenameTool {
+    pool: LspPoolHandle,
+}
+
+impl LspRenameTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Tool for LspRenameTool {
+    fn name(&self) -> &str {
+        "lsp_rename"
+    }
+
+    fn description(&self) -> &str {
+        "Rename the symbol at a file location across the whole workspace using the language \
+         server. Updates every usage, including macros and re-exports, in one step. \
+         Prefer this over repeated edits when renaming."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "file": { "type": "string", "description": "Path to the file (absolute or relative to cwd)." },
+                "line": { "type": "integer", "description": "1-based line number." },
+                "column": { "type": "integer", "description": "1-based column number." },
+                "new_name": { "type": "string", "description": "The new name for the symbol." }
+            },
+            "required": ["file", "line", "column", "new_name"]
+        })
+    }
+
+    fn permission(&self) -> ToolPermission {
+        ToolPermission::NeedsApproval
+    }
+
+    /// Ask the server for the rename so every file it touches is checked.
+    async fn prepare(&self, args: &Value, ctx: &ToolContext) -> Result<Option<Prepared>> {
+        let new_name = rename_target(args)?;
+        let pos = resolve_position(args, ctx).await?;
+        let title = format!("rename({} -> {new_name})", pos.word);
+
+        let client = match require_client(&self.pool, &pos.file, ctx, &title).await {
+            Ok(client) => client,
+            Err(result) => return Ok(Some(Prepared::Done(result))),
+        };
+
+        let Some(edit) = client.rename(&pos.file, &pos.lsp, new_name).await? else {
+            return Ok(Some(Prepared::Done(ToolResult {
+                output: format!(
+                    "Nothing to rename at {}:{}:{}",
+                    pos.file.display(),
+                    pos.line,
+                    pos.column
+                ),
+                title,
+                metadata: json!({ "applied": false }),
+            })));
+        };
+        let change = PreparedChange {
+            edits: vec![edit],
+            command: None,
+        };
+        prepared(args, change, &ctx.project_root).map(Some)
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let new_name = rename_target(&args)?;
+        let pos = resolve_position(&args, ctx).await?;
+        let title = format!("rename({} -> {new_name})", pos.word);
+        let edits = match change_for(self, &args, ctx).await? {
+            Ok(change) => change.edits,
+            Err(result) => return Ok(result),
+        };
+
+        let changes = apply_workspace_edits(&edits, ctx, "lsp_rename").await?;
+        Ok(ToolResult {
+            output: format!(
+                "Renamed '{}' to '{new_name}' in {} file(s):\n{}",
+                pos.word,
+                changes.len(),
+                describe_changes(&changes, &ctx.project_root)
+            ),
+            title,
+            metadata: json!({ "applied": true, "files": changes.len() }),
+        })
+    }
+}
+
+fn rename_target(args: &Value) -> Result<&str> {
+    args.get("new_name")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Missing required parameter: new_name"))
+}
+
+/// Range arguments for code actions: a start position plus an optional end.
+async fn resolve_range(args: &Value, ctx: &ToolContext) -> Result<(PathBuf, LspRange)> {
+    let pos = resolve_position(args, ctx).await?;
+    let end = match args.get("end_line").and_then(|v| v.as_u64()) {
+        Some(end_line) => {
+            let end_line = end_line.max(1);
+            let end_col = args
+                .get("end_column")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1)
+                .max(1);
+            let content = tokio::fs::read_to_string(&pos.file).await?;
+            let line_text = content.lines().nth((end_line - 1) as usize).unwrap_or("");
+            LspPosition {
+                line: (end_line - 1) as u32,
+                character: crate::lsp::utf16_column(line_text, (end_col - 1) as usize),
+            }
+        }
+        None => pos.lsp.clone(),
+    };
+    Ok((
+        pos.file,
+        LspRange {
+            start: pos.lsp,
+            end,
+        },
+    ))
+}
+
+fn range_schema(extra: Value) -> Value {
+    let mut schema = json!({
+        "type": "object",
+        "properties": {
+            "file": { "type": "string", "description": "Path to the file (absolute or relative to cwd)." },
+            "line": { "type": "integer", "description": "1-based start line." },
+            "column": { "type": "integer", "description": "1-based start column." },
+            "end_line": { "type": "integer", "description": "1-based end line (defaults to the start)." },
+            "end_column": { "type": "integer", "description": "1-based end column." },
+            "kind": {
+                "type": "string",
+                "description": "Only return actions of this kind, e.g. quickfix, refactor.extract, source.organizeImports"
+            }
+        },
+        "required": ["file", "line", "column"]
+    });
+    if let (Some(props), Value::Object(extra)) = (schema["properties"].as_object_mut(), extra) {
+        props.extend(extra);
+    }
+    schema
+}
+
+fn action_title(action: &Value) -> &str {
+    action
+        .get("title")
+        .and_then(|t| t.as_str())
+        .unwrap_or("(untitled)")
+}
+
+async fn fetch_actions(
+    client: &LspClient,
+    args: &Value,
+    ctx: &ToolContext,
+) -> Result<(PathBuf, Vec<Value>)> {
+    let (file, range) = resolve_range(args, ctx).await?;
+    let only: Option<Vec<String>> = args
+        .get("kind")
+        .and_then(|v| v.as_str())
+        .map(|k| vec![k.to_string()]);
+    let actions = client.code_actions(&file, &range, only.as_deref()).await?;
+    Ok((file, actions))
+}
+
+pub struct LspCodeActionsTool {
+    pool: LspPoolHandle,
+}
+
+impl LspCodeActionsTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Tool for LspCodeActionsTool {
+    fn name(&self) -> &str {
+        "lsp_code_actions"
+    }
+
+    fn description(&self) -> &str {
+        "List the language server's code actions (quick fixes, refactors, import fixes) for a \
+         file location or range. Apply one with lsp_apply_code_action."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        range_schema(json!({}))
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let file = ctx.cwd.join(
+            args.get("file")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Missing: file"))?,
+        );
+        let client = match require_client(&self.pool, &file, ctx, "lsp_code_actions").await {
+            Ok(client) => client,
+            Err(result) => return Ok(result),
+        };
+
+        let (_, actions) = fetch_actions(&client, &args, ctx).await?;
+        let output = if actions.is_empty() {
+            "No code actions available at this location".to_string()
+        } else {
+            let lines: Vec<String> = actions
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    let kind = a
+                        .get("kind")
+                        .and_then(|k| k.as_str())
+                        .map(|k| format!(" [{k}]"))
+                        .unwrap_or_default();
+                    format!("{}. {}{kind}", i + 1, action_title(a))
+                })
+                .collect();
+            format!("Code actions:\n{}", lines.join("\n"))
+        };
+
+        Ok(ToolResult {
+            output,
+            title: "lsp_code_actions".to_string(),
+            metadata: json!({
+                "actions": actions.iter().map(action_title).collect::<Vec<_>>(),
+            }),
+        })
+    }
+}
+
+pub struct LspApplyCodeActionTool {
+    pool: LspPoolHandle,
+}
+
+impl LspApplyCodeActionTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Tool for LspApplyCodeActionTool {
+    fn name(&self) -> &str {
+        "lsp_apply_code_action"
+    }
+
+    fn description(&self) -> &str {
+        "Apply a language server code action by title at a file location or range. \
+         Use lsp_code_actions first to see the available titles."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        let mut schema = range_schema(json!({
+            "title": {
+                "type": "string",
+                "description": "Exact title of the action to apply (as listed by lsp_code_actions)"
+            }
+        }));
+        schema["required"] = json!(["file", "line", "column", "title"]);
+        schema
+    }
+
+    fn permission(&self) -> ToolPermission {
+        ToolPermission::NeedsApproval
+    }
+
+    /// Resolve the action so every file its edit touches is checked. Its
+    /// command, if any, is only run by `execute`, after approval.
+    async fn prepare(&self, args: &Value, ctx: &ToolContext) -> Result<Option<Prepared>> {
+        let wanted = action_wanted(args)?;
+        let file = ctx.cwd.join(
+            args.get("file")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Missing: file"))?,
+        );
+        let title = format!("code_action({wanted})");
+        let client = match require_client(&self.pool, &file, ctx, &title).await {
+            Ok(client) => client,
+            Err(result) => return Ok(Some(Prepared::Done(result))),
+        };
+
+        let (_, actions) = fetch_actions(&client, args, ctx).await?;
+        let Some(action) = actions.iter().find(|a| action_title(a) == wanted) else {
+            let available: Vec<&str> = actions.iter().map(action_title).collect();
+            return Ok(Some(Prepared::Done(ToolResult {
+                output: format!(
+                    "No code action titled '{wanted}'. Available: {}",
+                    if available.is_empty() {
+                        "(none)".to_string()
+                    } else {
+                        available.join(", ")
+                    }
+                ),
+                title,
+                metadata: json!({ "applied": false }),
+            })));
+        };
+
+        let action = client.resolve_code_action(action).await?;
+        let change = PreparedChange {
+            edits: action.get("edit").cloned().into_iter().collect(),
+            // A bare Command has a string `command`; a CodeAction nests one.
+            command: match action.get("command") {
+                Some(Value::String(_)) => Some(action.clone()),
+                Some(cmd @ Value::Object(_)) => Some(cmd.clone()),
+                _ => None,
+            },
+        };
+        prepared(args, change, &ctx.project_root).map(Some)
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let wanted = action_wanted(&args)?;
+        let title = format!("code_action({wanted})");
+        let change = match change_for(self, &args, ctx).await? {
+            Ok(change) => change,
+            Err(result) => return Ok(result),
+        };
+
+        let mut changes =
+            apply_workspace_edits(&change.edits, ctx, "lsp_apply_code_action").await?;
+        if let Some(command) = change.command {
+            let file = ctx.cwd.join(
+                args.get("file")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("Missing: file"))?,
+            );
+            let client = match require_client(&self.pool, &file, ctx, &title).await {
+                Ok(client) => client,
+                Err(result) => return Ok(result),
+            };
+            // Edits the server requests while running the command are held
+            // to the project root like any other.
+            let edits = client.execute_command(&command).await?;
+            changes.extend(apply_workspace_edits(&edits, ctx, "lsp_apply_code_action").await?);
+        }
+
+        let output = if changes.is_empty() {
+            format!("Applied '{wanted}' (no file changes)")
+        } else {
+            format!(
+                "Applied '{wanted}' to {} file(s):\n{}",
+                changes.len(),
+                describe_changes(&changes, &ctx.project_root)
+            )
+        };
+        Ok(ToolResult {
+            output,
+            title,
+            metadata: json!({ "applied": true, "files": changes.len() }),
+        })
+    }
+}
+
+fn action_wanted(args: &Value) -> Result<&str> {
+    args.get("title")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required parameter: title"))
+}
+
+pub struct LspWorkspaceSymbolTool {
+    pool: LspPoolHandle,
+}
+
+impl LspWorkspaceSymbolTool {
+    pub fn new(pool: LspPoolHandle) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Tool for LspWorkspaceSymbolTool {
+    fn name(&self) -> &str {
+        "lsp_workspace_symbol"
+    }
+
+    fn description(&self) -> &str {
+        "Search the workspace for symbols (types, functions, constants) by name using the \
+         language server. Needs a file of the target language to pick the server."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Symbol name or fuzzy fragment." },
+                "file": {
+                    "type": "string",
+                    "description": "Any file in the project of the language to search (selects the server)."
+                }
+            },
+            "required": ["query", "file"]
+        })
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: query"))?;
+        let file = ctx.cwd.join(
+            args.get("file")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Missing: file"))?,
+        );
+        let title = format!("workspace_symbol({query})");
+        let client = match require_client(&self.pool, &file, ctx, &title).await {
+            Ok(client) => client,
+            Err(result) => return Ok(result),
+        };
+
+        client.sync_document(&file).await?;
+        let symbols = client.workspace_symbols(query).await?;
+        let output = if symbols.is_empty() {
+            format!("No symbols matching '{query}'")
+        } else {
+            let lines: Vec<String> = symbols
+                .iter()
+                .take(50)
+                .map(|s| {
+                    let path = s
+                        .location
+                        .path()
+                        .unwrap_or_else(|| PathBuf::from(&s.location.uri));
+                    let rel = path.strip_prefix(&ctx.project_root).unwrap_or(&path);
+                    let container = s
+                        .container_name
+                        .as_deref()
+                        .map(|c| format!(" in {c}"))
+                        .unwrap_or_default();
+                    format!(
+                        "{} {}{container} -- {}:{}",
+                        crate::lsp::symbol_kind_name(s.kind),
+                        s.name,
+                        rel.display(),
+                        s.location.range.start.line + 1
+                    )
+                })
+                .collect();
+            let more = if symbols.len() > 50 {
+                format!("\n... ({} total)", symbols.len())
+            } else {
+                String::new()
+            };
+            format!("{}{more}", lines.join("\n"))
+        };
+
+        Ok(ToolResult {
+            output,
+            title,
+            metadata: json!({ "count": symbols.len() }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_edit(uri: &str, text: &str) -> Value {
+        let range =
+            json!({"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 0}});
+        json!({ "changes": { uri: [{"range": range, "newText": text}] } })
+    }
+
+    #[test]
+    fn edits_are_merged_in_order_with_normalized_paths() {
+        let ops = workspace_edit_ops(
+            &[
+                text_edit("file:///proj/src/./a.rs", "x"),
+                text_edit("file:///proj/tests/../src/b.rs", "y"),
+            ],
+            Path::new("/proj"),
+        )
+        .unwrap();
+        let paths: Vec<_> = ops
+            .iter()
+            .map(|op| match op {
+                EditOperation::Edit { path, .. } => path.clone(),
+                _ => panic!("expected text edits"),
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [Path::new("/proj/src/a.rs"), Path::new("/proj/src/b.rs")]
+        );
+    }
+
+    #[test]
+    fn prepared_calls_carry_the_edits_and_every_path() {
+        let edits = vec![
+            text_edit("file:///proj/src/a.rs", "x"),
+            json!({ "documentChanges": [
+                {"kind": "rename", "oldUri": "file:///proj/src/b.rs", "newUri": "file:///proj/src/c.rs"},
+            ]}),
+        ];
+        let change = PreparedChange {
+            edits,
+            command: Some(json!({"title": "Organize", "command": "organize"})),
+        };
+        let Prepared::Run { args, paths } =
+            prepared(&json!({"file": "src/a.rs"}), change, Path::new("/proj")).unwrap()
+        else {
+            panic!("expected a call to run");
+        };
+        assert_eq!(
+            paths,
+            ["/proj/src/a.rs", "/proj/src/b.rs", "/proj/src/c.rs"].map(PathBuf::from)
+        );
+        assert_eq!(args[PREPARED_EDITS].as_array().unwrap().len(), 2);
+        assert_eq!(args[PREPARED_COMMAND]["command"], "organize");
+        assert_eq!(args["file"], "src/a.rs");
+    }
+
+    #[test]
+    fn paths_escaping_the_project_reject_every_edit() {
+        let result = workspace_edit_ops(
+            &[
+                text_edit("file:///proj/src/a.rs", "x"),
+                text_edit("file:///proj/src/../../etc/passwd", "y"),
+            ],
+            Path::new("/proj"),
+        );
+        assert!(result.is_err());
+        assert!(workspace_edit_ops(
+            &[text_edit("file:///proj-other/a.rs", "x")],
+            Path::new("/proj")
+        )
+        .is_err());
+    }
+}
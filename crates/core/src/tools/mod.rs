@@ -5,6 +5,7 @@ pub mod batch;
 pub mod browser;
 pub mod change_tracker;
 pub mod close_agent;
+pub mod code_graph;
 pub mod diff;
 pub mod edit;
 pub mod filesystem;
@@ -15,14 +16,17 @@ pub mod grep;
 pub mod instrument;
 pub mod load_skill;
 pub mod lsp;
+pub mod lsp_refactor;
 pub mod memory;
 pub mod notepad;
 pub mod permission;
 pub mod pr;
+pub mod process;
 pub mod read;
 pub mod resume_agent;
 pub mod semantic_search;
 pub mod send_input;
+pub mod shell_parse;
 pub mod spawn_agent;
 pub mod tail_file;
 pub mod task;
@@ -45,6 +49,7 @@ use async_trait::async_trait;
 use permission::ToolPermission;
 use serde_json::Value;
 use tokio::sync::broadcast;
+pub use tokio_util::sync::CancellationToken;
 
 pub type IndexHandle = Arc<nyzhi_index::CodebaseIndex>;
 
@@ -56,9 +61,34 @@ pub trait Tool: Send + Sync {
     fn permission(&self) -> ToolPermission {
         ToolPermission::ReadOnly
     }
+    /// Work out what a call will change before it is approved, for tools
+    /// whose targets are not all in their arguments.
+    async fn prepare(&self, _args: &Value, _ctx: &ToolContext) -> Result<Option<Prepared>> {
+        Ok(None)
+    }
     async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult>;
 }
 
+/// Arguments whose names start with this are added by [`Tool::prepare`] for
+/// `execute`; a model-supplied one is removed before `prepare` runs.
+pub const PREPARED_ARG_PREFIX: &str = "_";
+
+/// Drop every prepared-only argument from a model's tool call.
+pub fn strip_prepared_args(args: &mut Value) {
+    if let Some(fields) = args.as_object_mut() {
+        fields.retain(|key, _| !key.starts_with(PREPARED_ARG_PREFIX));
+    }
+}
+
+/// What [`Tool::prepare`] found.
+pub enum Prepared {
+    /// Run the call with `args`, which carry the prepared changes, once
+    /// every path in `paths` passes the permission checks.
+    Run { args: Value, paths: Vec<PathBuf> },
+    /// Nothing to change; this is the result.
+    Done(ToolResult),
+}
+
 #[derive(Clone)]
 pub struct ToolContext {
     pub session_id: String,
@@ -82,10 +112,34 @@ pub struct ToolContext {
     pub index: Option<IndexHandle>,
     /// Sandbox enforcement level for tool execution.
     pub sandbox_level: nyzhi_config::SandboxLevel,
+    /// `[shell.sandbox]` settings that refine the level for shell commands.
+    pub sandbox_config: crate::sandbox::SandboxConfig,
+    /// Approval rules remembered this session, shared with sub-agents.
+    pub approvals: Option<permission::ApprovalMemoryHandle>,
     /// Runtime model overrides per agent role (session-scoped).
     pub subagent_model_overrides: Option<crate::agent_roles::SubagentModelOverrides>,
     /// Shared context for subagent briefings.
     pub shared_context: Option<std::sync::Arc<tokio::sync::Mutex<crate::context_briefing::SharedContext>>>,
+    /// Background processes started with `process_start`, shared with
+    /// sub-agents.
+    pub processes: Option<crate::processes::ProcessManagerHandle>,
+    /// This agent's persistent shell for `bash` when `[shell] persistent`
+    /// is on; sub-agents get a shell of their own.
+    pub shell: Option<crate::persistent_shell::PersistentShellHandle>,
+    /// Cancelled when the user stops the turn. Long-running tools stop early
+    /// and return what they have; sub-agents get a child token.
+    pub cancel: CancellationToken,
+}
+
+impl ToolContext {
+    /// Launcher for shell commands under this context's sandbox level.
+    pub fn launcher(&self) -> crate::sandbox::Launcher {
+        crate::sandbox::Launcher::new(
+            self.sandbox_level,
+            self.sandbox_config.clone(),
+            &self.project_root,
+        )
+    }
 }
 
 pub struct ToolResult {
@@ -94,40 +148,122 @@ pub struct ToolResult {
     pub metadata: Value,
 }
 
+/// How long a cancelled tool may keep running to wind down and report
+/// partial output before its call is dropped.
+const CANCEL_GRACE: std::time::Duration = std::time::Duration::from_secs(3);
+
+impl ToolResult {
+    pub fn cancelled(tool_name: &str, output: impl Into<String>) -> Self {
+        Self {
+            output: output.into(),
+            title: format!("{tool_name} (cancelled)"),
+            metadata: serde_json::json!({ "cancelled": true }),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.metadata.get("cancelled").and_then(Value::as_bool) == Some(true)
+    }
+}
+
 pub struct ToolRegistry {
-    tools: HashMap<String, Box<dyn Tool>>,
+    /// Behind a lock so MCP tools can be swapped when a server's tool list
+    /// changes mid-session.
+    tools: std::sync::RwLock<HashMap<String, Arc<dyn Tool>>>,
     /// Tools that are indexed but not sent as full definitions in ChatRequest.
     /// The agent can discover them via tool_search and they expand on first use.
-    deferred: std::collections::HashSet<String>,
+    deferred: std::sync::RwLock<std::collections::HashSet<String>>,
     /// Session-level cache of deferred tools that have been expanded (used at least once).
     /// Uses interior mutability so expansion works through `&self`.
     expanded: std::sync::RwLock<std::collections::HashSet<String>>,
+    /// The index `tool_search` reads, rebuilt when deferred tools change.
+    search_index: Option<tool_search::DeferredToolIndex>,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
-            tools: HashMap::new(),
-            deferred: std::collections::HashSet::new(),
+            tools: std::sync::RwLock::new(HashMap::new()),
+            deferred: std::sync::RwLock::new(std::collections::HashSet::new()),
             expanded: std::sync::RwLock::new(std::collections::HashSet::new()),
+            search_index: None,
         }
     }
 
+    fn tools(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Arc<dyn Tool>>> {
+        self.tools.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn deferred(&self) -> std::sync::RwLockReadGuard<'_, std::collections::HashSet<String>> {
+        self.deferred.read().unwrap_or_else(|e| e.into_inner())
+    }
+
     pub fn register(&mut self, tool: Box<dyn Tool>) {
-        self.tools.insert(tool.name().to_string(), tool);
+        self.tools
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(tool.name().to_string(), tool.into());
+    }
+
+    /// Replace every tool whose name starts with `prefix` by `tools`, e.g.
+    /// all `mcp__github__` tools after the server announced new ones. The
+    /// new tools are deferred if the ones they replace were, and the
+    /// `tool_search` index is rebuilt.
+    pub fn replace_prefixed(&self, prefix: &str, tools: Vec<Box<dyn Tool>>) {
+        // One lock at a time, so readers taking them in any order can't
+        // deadlock against this.
+        let defer = self.deferred().iter().any(|name| name.starts_with(prefix));
+        let names: Vec<String> = tools.iter().map(|t| t.name().to_string()).collect();
+        {
+            let mut map = self.tools.write().unwrap_or_else(|e| e.into_inner());
+            map.retain(|name, _| !name.starts_with(prefix));
+            for tool in tools {
+                map.insert(tool.name().to_string(), tool.into());
+            }
+        }
+        {
+            let mut deferred = self.deferred.write().unwrap_or_else(|e| e.into_inner());
+            deferred.retain(|name| !name.starts_with(prefix));
+            if defer {
+                deferred.extend(names);
+            }
+        }
+        self.expanded
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|name| !name.starts_with(prefix));
+        self.refresh_search_index();
+    }
+
+    /// Share `index` with `tool_search`; see [`Self::refresh_search_index`].
+    pub fn set_search_index(&mut self, index: tool_search::DeferredToolIndex) {
+        self.search_index = Some(index);
+    }
+
+    /// Rebuild the `tool_search` index from the current deferred tools.
+    pub fn refresh_search_index(&self) {
+        if let Some(index) = &self.search_index {
+            *index.write().unwrap_or_else(|e| e.into_inner()) = self.deferred_index();
+        }
     }
 
     /// Register a tool as deferred (index-only, not sent in ChatRequest until used).
     pub fn register_deferred(&mut self, tool: Box<dyn Tool>) {
         let name = tool.name().to_string();
-        self.tools.insert(name.clone(), tool);
-        self.deferred.insert(name);
+        self.tools
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.clone(), tool.into());
+        self.deferred
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name);
     }
 
     /// Mark a deferred tool as expanded (it will now be included in definitions).
     /// Takes `&self` thanks to interior mutability.
     pub fn expand_deferred(&self, name: &str) {
-        if self.deferred.contains(name) {
+        if self.deferred().contains(name) {
             if let Ok(mut expanded) = self.expanded.write() {
                 expanded.insert(name.to_string());
             }
@@ -136,7 +272,7 @@ impl ToolRegistry {
 
     /// Check if a tool is deferred and not yet expanded.
     pub fn is_deferred(&self, name: &str) -> bool {
-        self.deferred.contains(name)
+        self.deferred().contains(name)
             && !self
                 .expanded
                 .read()
@@ -144,17 +280,18 @@ impl ToolRegistry {
                 .unwrap_or(false)
     }
 
-    pub fn get(&self, name: &str) -> Option<&dyn Tool> {
-        self.tools.get(name).map(|t| t.as_ref())
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools().get(name).cloned()
     }
 
     /// Return definitions for all non-deferred tools plus any expanded deferred tools.
     pub fn definitions(&self) -> Vec<nyzhi_provider::ToolDefinition> {
+        let deferred = self.deferred();
         let expanded = self.expanded.read().unwrap_or_else(|e| e.into_inner());
         let mut defs: Vec<_> = self
-            .tools
+            .tools()
             .values()
-            .filter(|t| !self.deferred.contains(t.name()) || expanded.contains(t.name()))
+            .filter(|t| !deferred.contains(t.name()) || expanded.contains(t.name()))
             .map(|t| nyzhi_provider::ToolDefinition {
                 name: t.name().to_string(),
                 description: t.description().to_string(),
@@ -167,12 +304,13 @@ impl ToolRegistry {
 
     /// Return definitions for read-only tools only (plan mode).
     pub fn definitions_read_only(&self) -> Vec<nyzhi_provider::ToolDefinition> {
+        let deferred = self.deferred();
         let expanded = self.expanded.read().unwrap_or_else(|e| e.into_inner());
         let mut defs: Vec<_> = self
-            .tools
+            .tools()
             .values()
             .filter(|t| t.permission() == permission::ToolPermission::ReadOnly)
-            .filter(|t| !self.deferred.contains(t.name()) || expanded.contains(t.name()))
+            .filter(|t| !deferred.contains(t.name()) || expanded.contains(t.name()))
             .map(|t| nyzhi_provider::ToolDefinition {
                 name: t.name().to_string(),
                 description: t.description().to_string(),
@@ -189,17 +327,18 @@ impl ToolRegistry {
             .read()
             .map(|e| e.len())
             .unwrap_or(0);
-        self.deferred.len().saturating_sub(expanded_count)
+        self.deferred().len().saturating_sub(expanded_count)
     }
 
     /// Build the deferred tool index for tool_search.
     pub fn deferred_index(&self) -> Vec<tool_search::DeferredToolEntry> {
         let expanded = self.expanded.read().unwrap_or_else(|e| e.into_inner());
-        self.deferred
+        let tools = self.tools();
+        self.deferred()
             .iter()
             .filter(|name| !expanded.contains(name.as_str()))
             .filter_map(|name| {
-                self.tools
+                tools
                     .get(name)
                     .map(|t| tool_search::DeferredToolEntry {
                         name: t.name().to_string(),
@@ -218,7 +357,26 @@ impl ToolRegistry {
         let tool = self
             .get(name)
             .ok_or_else(|| anyhow::anyhow!("Unknown tool: {name}"))?;
-        tool.execute(args, ctx).await
+        if ctx.cancel.is_cancelled() {
+            return Ok(ToolResult::cancelled(name, "Not run: the turn was cancelled"));
+        }
+
+        // Tools that watch the token return partial output themselves; the
+        // grace period lets them clean up before the call is abandoned.
+        let run = tool.execute(args, ctx);
+        tokio::pin!(run);
+        tokio::select! {
+            result = &mut run => result,
+            _ = ctx.cancel.cancelled() => {
+                match tokio::time::timeout(CANCEL_GRACE, &mut run).await {
+                    Ok(result) => result,
+                    Err(_) => Ok(ToolResult::cancelled(
+                        name,
+                        "Cancelled by user before completing",
+                    )),
+                }
+            }
+        }
     }
 }
 
@@ -238,7 +396,7 @@ impl ToolRegistry {
         allowed: Option<&[String]>,
         disallowed: Option<&[String]>,
     ) -> Vec<String> {
-        let mut names: Vec<String> = self.tools.keys().cloned().collect();
+        let mut names: Vec<String> = self.tools().keys().cloned().collect();
 
         if let Some(allow_list) = allowed {
             let allow_set: std::collections::HashSet<&str> =
@@ -256,7 +414,7 @@ impl ToolRegistry {
     }
 
     pub fn names(&self) -> Vec<String> {
-        self.tools.keys().cloned().collect()
+        self.tools().keys().cloned().collect()
     }
 
     /// Return tool definitions filtered to only the given tool names.
@@ -267,7 +425,7 @@ impl ToolRegistry {
         let allow_set: std::collections::HashSet<&str> =
             allowed_names.iter().map(|s| s.as_str()).collect();
         let mut defs: Vec<_> = self
-            .tools
+            .tools()
             .values()
             .filter(|t| allow_set.contains(t.name()))
             .map(|t| nyzhi_provider::ToolDefinition {
@@ -309,7 +467,9 @@ pub fn default_registry(codebase_index: Option<IndexHandle>) -> RegistryBundle {
     let todo_store = todo::shared_store();
     let deferred_index = tool_search::shared_deferred_index();
     let instrument_store = instrument::shared_store();
+    let lsp_pool = crate::lsp::shared_pool();
     let mut registry = ToolRegistry::new();
+    registry.set_search_index(deferred_index.clone());
 
     // Core tools
     registry.register(Box::new(bash::BashTool));
@@ -347,16 +507,27 @@ pub fn default_registry(codebase_index: Option<IndexHandle>) -> RegistryBundle {
     registry.register(Box::new(verify::VerifyTool));
     registry.register(Box::new(notepad::NotepadWriteTool));
     registry.register(Box::new(notepad::NotepadReadTool));
-    registry.register(Box::new(lsp::LspDiagnosticsTool));
+    registry.register(Box::new(lsp::LspDiagnosticsTool::new(lsp_pool.clone())));
     registry.register(Box::new(lsp::AstSearchTool));
-    registry.register(Box::new(lsp::LspGotoDefinitionTool));
-    registry.register(Box::new(lsp::LspFindReferencesTool));
-    registry.register(Box::new(lsp::LspHoverTool));
+    registry.register(Box::new(lsp::LspGotoDefinitionTool::new(lsp_pool.clone())));
+    registry.register(Box::new(lsp::LspFindReferencesTool::new(lsp_pool.clone())));
+    registry.register(Box::new(lsp::LspHoverTool::new(lsp_pool.clone())));
+    registry.register(Box::new(lsp_refactor::LspRenameTool::new(lsp_pool.clone())));
+    registry.register(Box::new(lsp_refactor::LspCodeActionsTool::new(lsp_pool.clone())));
+    registry.register(Box::new(lsp_refactor::LspApplyCodeActionTool::new(lsp_pool.clone())));
+    registry.register(Box::new(lsp_refactor::LspWorkspaceSymbolTool::new(lsp_pool)));
 
     // Web
     registry.register(Box::new(web::WebFetchTool));
     registry.register(Box::new(web::WebSearchTool));
 
+    // Background processes
+    registry.register(Box::new(process::ProcessStartTool));
+    registry.register(Box::new(process::ProcessOutputTool));
+    registry.register(Box::new(process::ProcessSendTool));
+    registry.register(Box::new(process::ProcessKillTool));
+    registry.register(Box::new(process::ProcessListTool));
+
     // Misc
     registry.register(Box::new(tail_file::TailFileTool));
     registry.register(Box::new(load_skill::LoadSkillTool));
@@ -379,7 +550,8 @@ pub fn default_registry(codebase_index: Option<IndexHandle>) -> RegistryBundle {
 
     // Phase 1.1: Semantic search & fuzzy find
     if let Some(idx) = codebase_index {
-        registry.register(Box::new(semantic_search::SemanticSearchTool::new(idx)));
+        registry.register(Box::new(semantic_search::SemanticSearchTool::new(idx.clone())));
+        registry.register(Box::new(code_graph::CodeGraphTool::new(idx)));
     }
     registry.register(Box::new(fuzzy_find::FuzzyFindTool));
 
@@ -421,3 +593,66 @@ pub fn default_registry(codebase_index: Option<IndexHandle>) -> RegistryBundle {
         deferred_index,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str);
+
+    #[async_trait]
+    impl Tool for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            "test tool"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({ "type": "object" })
+        }
+
+        async fn execute(&self, _args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn model_supplied_prepared_args_are_dropped() {
+        let mut args = serde_json::json!({
+            "file": "src/a.rs",
+            "_workspace_edits": [{ "changes": {} }],
+            "_command": { "command": "rm" },
+        });
+        strip_prepared_args(&mut args);
+        assert_eq!(args, serde_json::json!({ "file": "src/a.rs" }));
+    }
+
+    #[test]
+    fn replacing_deferred_tools_keeps_them_deferred() {
+        let index = tool_search::shared_deferred_index();
+        let mut registry = ToolRegistry::new();
+        registry.set_search_index(index.clone());
+        registry.register(Box::new(Named("read")));
+        registry.register_deferred(Box::new(Named("mcp__db__query")));
+        registry.register_deferred(Box::new(Named("mcp__db__drop")));
+        registry.expand_deferred("mcp__db__query");
+
+        registry.replace_prefixed(
+            "mcp__db__",
+            vec![Box::new(Named("mcp__db__query")), Box::new(Named("mcp__db__list"))],
+        );
+
+        assert!(registry.get("mcp__db__drop").is_none());
+        assert!(registry.is_deferred("mcp__db__query"));
+        assert!(registry.is_deferred("mcp__db__list"));
+        assert!(!registry.is_deferred("read"));
+        let mut indexed: Vec<_> = index.read().unwrap().iter().map(|e| e.name.clone()).collect();
+        indexed.sort();
+        assert_eq!(indexed, ["mcp__db__list", "mcp__db__query"]);
+        let sent: Vec<_> = registry.definitions().into_iter().map(|d| d.name).collect();
+        assert_eq!(sent, ["read"]);
+    }
+}
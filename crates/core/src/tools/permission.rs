@@ -1,3 +1,9 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ToolPermission {
     ReadOnly,
@@ -5,16 +11,21 @@ pub enum ToolPermission {
 }
 
 /// Returns true if the tool or path is explicitly denied by the trust config.
+/// `deny_tools` entries may be rules such as `bash: git push *`, which deny a
+/// command line if any of its commands matches.
 pub fn check_deny(
     tool_name: &str,
     target_path: Option<&str>,
+    project_root: &Path,
     trust: &nyzhi_config::TrustConfig,
 ) -> bool {
-    if trust
-        .deny_tools
-        .iter()
-        .any(|d| d.eq_ignore_ascii_case(tool_name) || d == "*")
-    {
+    if rules_match(
+        &parse_rules(&trust.deny_tools),
+        tool_name,
+        target_path,
+        project_root,
+        false,
+    ) {
         return true;
     }
 
@@ -31,94 +42,612 @@ pub fn check_deny(
     false
 }
 
-/// Check whether a tool should be auto-approved under the current trust config.
-/// Returns Some(true) for auto-approve, Some(false) for always-ask, None for default behavior.
+/// Check the explicit `always_ask` and `auto_approve` lists, whose entries
+/// are tool names or rules such as `bash: cargo test *`.
+/// Returns Some(false) for always-ask, Some(true) for auto-approve, None to
+/// defer to the trust mode. A command line is auto-approved only if every
+/// command in it is.
 pub fn check_auto_approve(
     tool_name: &str,
-    permission: ToolPermission,
+    target_path: Option<&str>,
+    project_root: &Path,
     trust: &nyzhi_config::TrustConfig,
 ) -> Option<bool> {
-    if trust
-        .always_ask
-        .iter()
-        .any(|t| t.eq_ignore_ascii_case(tool_name))
-    {
+    if rules_match(
+        &parse_rules(&trust.always_ask),
+        tool_name,
+        target_path,
+        project_root,
+        false,
+    ) {
         return Some(false);
     }
-    if trust
-        .auto_approve
-        .iter()
-        .any(|t| t.eq_ignore_ascii_case(tool_name))
-    {
+    if rules_match(
+        &parse_rules(&trust.auto_approve),
+        tool_name,
+        target_path,
+        project_root,
+        true,
+    ) {
         return Some(true);
     }
+    None
+}
+
+/// Parse config entries into rules, skipping invalid ones.
+pub fn parse_rules(entries: &[String]) -> Vec<PermissionRule> {
+    entries
+        .iter()
+        .filter_map(|entry| PermissionRule::parse(entry))
+        .collect()
+}
+
+/// Whether `rules` cover a call. A `bash` command line is split into its
+/// simple commands: with `every` each of them must be matched (approvals),
+/// otherwise one is enough (denials). A command or path matched by a `!`
+/// rule is never covered.
+pub fn rules_match(
+    rules: &[PermissionRule],
+    tool_name: &str,
+    target: Option<&str>,
+    project_root: &Path,
+    every: bool,
+) -> bool {
+    let rules: Vec<&PermissionRule> = rules.iter().filter(|r| r.applies_to(tool_name)).collect();
+    if rules.is_empty() {
+        return false;
+    }
+    let covered = |subject: Option<&str>| {
+        rules
+            .iter()
+            .any(|r| !r.negated && r.matches_subject(tool_name, subject))
+            && !rules
+                .iter()
+                .any(|r| r.negated && r.matches_subject(tool_name, subject))
+    };
+
+    if !is_shell_tool(tool_name) {
+        let Some(target) = target else {
+            return covered(None);
+        };
+        // A path that leaves the project is never approved by a rule, but
+        // can still be denied by one written against its absolute form.
+        return match relative_target(target, project_root) {
+            Some(path) => covered(Some(&path)),
+            None => {
+                let absolute = normalize_path(&project_root.join(target));
+                !every && covered(Some(&absolute.to_string_lossy()))
+            }
+        };
+    }
+    let Some(command) = target else {
+        return covered(None);
+    };
+    match crate::tools::shell_parse::parse(command) {
+        Some(commands) if !commands.is_empty() => {
+            if every && commands.iter().any(|c| c.sets_sensitive_env()) {
+                return false;
+            }
+            let mut texts = commands.iter().map(|c| c.text());
+            if every {
+                texts.all(|t| covered(Some(&t)))
+            } else {
+                texts.any(|t| covered(Some(&t)))
+            }
+        }
+        // Unparseable lines are never approved by a rule but can still be
+        // denied by one that matches the raw text.
+        _ => !every && covered(Some(command.trim())),
+    }
+}
+
+/// A permission rule: a tool name, optionally narrowed by a glob over the
+/// call's target. For `bash` and `process_start` the glob is matched
+/// against each command in the line (arguments and redirections, without
+/// `NAME=value` prefixes; a command that sets `PATH`, `LD_*` or a shell
+/// startup variable is never approved by a rule), for file tools against the path relative to the
+/// project root after resolving `.` and `..`. A pattern
+/// starting with `!` excludes what it matches from the other rules.
+///
+/// `bash: cargo test *` matches `cargo test` with any arguments,
+/// `bash: !rm -rf *` carves out recursive deletes, `edit: src/**` matches
+/// any edit under `src/`, and `web_fetch` every call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionRule {
+    pub tool: String,
+    pub pattern: Option<String>,
+    pub negated: bool,
+}
+
+impl PermissionRule {
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (tool, pattern) = match rule.split_once(':') {
+            Some((tool, pattern)) => (tool.trim(), Some(pattern.trim())),
+            None => (rule.trim(), None),
+        };
+        let (pattern, negated) = match pattern.map(|p| p.strip_prefix('!')) {
+            Some(Some(rest)) => (Some(rest.trim_start()), true),
+            _ => (pattern, false),
+        };
+        if tool.is_empty() || pattern.is_some_and(|p| p.is_empty()) {
+            return None;
+        }
+        if let Some(pattern) = pattern {
+            glob::Pattern::new(pattern).ok()?;
+        }
+        Some(Self {
+            tool: tool.to_string(),
+            pattern: pattern.map(String::from),
+            negated,
+        })
+    }
+
+    pub fn applies_to(&self, tool_name: &str) -> bool {
+        self.tool == "*" || self.tool.eq_ignore_ascii_case(tool_name)
+    }
+
+    /// Match one command (for `bash`) or project-relative path, ignoring
+    /// negation.
+    fn matches_subject(&self, tool_name: &str, subject: Option<&str>) -> bool {
+        let Some(pattern) = &self.pattern else {
+            return true;
+        };
+        let Some(subject) = subject else {
+            return false;
+        };
+        let Ok(glob) = glob::Pattern::new(pattern) else {
+            return false;
+        };
+
+        if is_shell_tool(tool_name) {
+            return glob.matches(subject)
+                || pattern.strip_suffix(" *").is_some_and(|prefix| {
+                    glob::Pattern::new(prefix).is_ok_and(|g| g.matches(subject))
+                });
+        }
+
+        glob.matches_with(
+            subject,
+            glob::MatchOptions {
+                require_literal_separator: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// The rules offered when approving a call beyond this once: each
+    /// command with its subcommand for `bash`, the containing directory for
+    /// file tools, the whole tool otherwise. Empty for command lines that
+    /// cannot be parsed and paths outside the project.
+    pub fn suggest(tool_name: &str, target: Option<&str>, project_root: &Path) -> Vec<Self> {
+        let rule = |pattern: Option<String>| Self {
+            tool: tool_name.to_string(),
+            pattern,
+            negated: false,
+        };
+        let Some(target) = target else {
+            return vec![rule(None)];
+        };
+        if !is_shell_tool(tool_name) {
+            let Some(path) = relative_target(target, project_root) else {
+                return Vec::new();
+            };
+            let pattern = match path.rsplit_once('/') {
+                Some((dir, _)) => format!("{}/**", glob::Pattern::escape(dir)),
+                None => glob::Pattern::escape(&path),
+            };
+            return vec![rule(Some(pattern))];
+        }
+
+        let mut rules: Vec<Self> = Vec::new();
+        for command in crate::tools::shell_parse::parse(target).unwrap_or_default() {
+            let mut words = command.argv.iter();
+            let Some(program) = words.next() else {
+                continue;
+            };
+            let mut prefix = glob::Pattern::escape(program);
+            if let Some(sub) = words.next().filter(|w| {
+                !w.starts_with('-')
+                    && w.chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            }) {
+                prefix.push(' ');
+                prefix.push_str(sub);
+            }
+            let suggested = rule(Some(format!("{prefix} *")));
+            if !rules.contains(&suggested) {
+                rules.push(suggested);
+            }
+        }
+        rules
+    }
+}
+
+impl std::fmt::Display for PermissionRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bang = if self.negated { "!" } else { "" };
+        match &self.pattern {
+            Some(pattern) => write!(f, "{}: {bang}{pattern}", self.tool),
+            None => write!(f, "{}", self.tool),
+        }
+    }
+}
+
+/// Tools whose target is a shell command line rather than a path.
+fn is_shell_tool(tool_name: &str) -> bool {
+    matches!(tool_name, "bash" | "process_start")
+}
+
+/// The target relative to the project root, with `.` and `..` resolved;
+/// `None` when it resolves to somewhere outside the project.
+fn relative_target(target: &str, project_root: &Path) -> Option<String> {
+    let root = normalize_path(project_root);
+    let path = normalize_path(&root.join(target));
+    let relative = path.strip_prefix(&root).ok()?;
+    Some(relative.to_string_lossy().to_string())
+}
+
+/// Resolve `.` and `..` in the path text without touching the filesystem.
+/// `..` never climbs above the root.
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// How long an approval holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalScope {
+    #[default]
+    Once,
+    Session,
+    /// Saved under the user's data directory, keyed by the project path.
+    Project,
+    /// Saved to `approvals.toml` in the global config directory.
+    Always,
+}
+
+impl std::fmt::Display for ApprovalScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalScope::Once => write!(f, "once"),
+            ApprovalScope::Session => write!(f, "session"),
+            ApprovalScope::Project => write!(f, "project"),
+            ApprovalScope::Always => write!(f, "always"),
+        }
+    }
+}
+
+/// Answer to an approval request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApprovalResponse {
+    pub approved: bool,
+    pub scope: ApprovalScope,
+    /// Rule to remember instead of the suggested ones, e.g. `bash: cargo *`.
+    pub rule: Option<String>,
+}
+
+impl ApprovalResponse {
+    pub fn once(approved: bool) -> Self {
+        Self {
+            approved,
+            ..Default::default()
+        }
+    }
 
-    match trust.mode {
-        nyzhi_config::TrustMode::Full => Some(true),
-        nyzhi_config::TrustMode::AutoEdit => match permission {
-            ToolPermission::ReadOnly => Some(true),
-            ToolPermission::NeedsApproval => {
-                let write_tools = [
-                    "write",
-                    "edit",
-                    "multi_edit",
-                    "apply_patch",
-                    "delete_file",
-                    "move_file",
-                    "copy_file",
-                    "create_dir",
-                ];
-                if write_tools.contains(&tool_name) {
-                    Some(true)
-                } else {
-                    None
+    pub fn remember(scope: ApprovalScope) -> Self {
+        Self {
+            approved: true,
+            scope,
+            rule: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ApprovalsFile {
+    #[serde(default)]
+    rules: Vec<String>,
+}
+
+pub type ApprovalMemoryHandle = Arc<Mutex<ApprovalMemory>>;
+
+/// Approval memory for a session, loaded with the rules saved for the
+/// project and globally when `remember_approvals` is on.
+pub fn shared_memory(project_root: &Path, remember_approvals: bool) -> ApprovalMemoryHandle {
+    let mut memory = ApprovalMemory::default();
+    if remember_approvals {
+        for path in [
+            project_approvals_path(project_root),
+            global_approvals_path(),
+        ] {
+            for rule in read_approvals(&path).rules {
+                if let Some(rule) = PermissionRule::parse(&rule) {
+                    memory.rules.push(rule);
                 }
             }
-        },
-        nyzhi_config::TrustMode::Limited => match permission {
-            ToolPermission::ReadOnly => Some(true),
-            ToolPermission::NeedsApproval => None,
-        },
-        nyzhi_config::TrustMode::Off => None,
-    }
-}
-
-const DANGEROUS_COMMANDS: &[&str] = &[
-    "rm -rf /",
-    "rm -rf /*",
-    "sudo rm",
-    "mkfs.",
-    "dd if=",
-    ":(){:|:&};:",
-    "curl | bash",
-    "curl | sh",
-    "wget | bash",
-    "wget | sh",
-    "> /dev/sd",
-    "chmod 777 /",
-];
-
-/// Returns true if a bash command contains dangerous patterns
-/// that should always require explicit approval.
-pub fn is_dangerous_bash(command: &str) -> bool {
-    let lower = command.to_lowercase();
-    DANGEROUS_COMMANDS
-        .iter()
-        .any(|pat| lower.contains(&pat.to_lowercase()))
+        }
+    }
+    Arc::new(Mutex::new(memory))
+}
+
+/// Kept outside the checkout so a cloned repository cannot pre-approve
+/// rules for itself.
+pub fn project_approvals_path(project_root: &Path) -> PathBuf {
+    nyzhi_config::Config::data_dir()
+        .join("projects")
+        .join(crate::memory::project_hash(project_root))
+        .join("approvals.toml")
+}
+
+pub fn global_approvals_path() -> PathBuf {
+    nyzhi_config::Config::config_dir().join("approvals.toml")
+}
+
+fn read_approvals(path: &Path) -> ApprovalsFile {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
 }
 
-/// Session-level approval memory.
-#[derive(Default)]
+/// Rules approved during this session, including those loaded from disk.
+#[derive(Debug, Default)]
 pub struct ApprovalMemory {
-    approved: std::collections::HashSet<String>,
+    rules: Vec<PermissionRule>,
 }
 
 impl ApprovalMemory {
-    pub fn remember(&mut self, tool_name: &str, pattern: &str) {
-        self.approved.insert(format!("{tool_name}:{pattern}"));
+    pub fn allows(&self, tool_name: &str, target: Option<&str>, project_root: &Path) -> bool {
+        rules_match(&self.rules, tool_name, target, project_root, true)
+    }
+
+    pub fn rules(&self) -> &[PermissionRule] {
+        &self.rules
+    }
+
+    /// Remember `rule` for the session and, for the project and always
+    /// scopes, append it to the matching approvals file.
+    pub fn remember(
+        &mut self,
+        rule: PermissionRule,
+        scope: ApprovalScope,
+        project_root: &Path,
+    ) -> Result<()> {
+        let path = match scope {
+            ApprovalScope::Once => return Ok(()),
+            ApprovalScope::Session => None,
+            ApprovalScope::Project => Some(project_approvals_path(project_root)),
+            ApprovalScope::Always => Some(global_approvals_path()),
+        };
+        if let Some(path) = path {
+            let mut file = read_approvals(&path);
+            let text = rule.to_string();
+            if !file.rules.contains(&text) {
+                file.rules.push(text);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, toml::to_string_pretty(&file)?)
+                    .with_context(|| format!("writing {}", path.display()))?;
+            }
+        }
+        if !self.rules.contains(&rule) {
+            self.rules.push(rule);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(text: &str) -> PermissionRule {
+        PermissionRule::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays_rules() {
+        assert_eq!(rule("bash: cargo test *").to_string(), "bash: cargo test *");
+        assert_eq!(rule("web_fetch").pattern, None);
+        assert!(PermissionRule::parse("bash:").is_none());
+        assert!(PermissionRule::parse(": x").is_none());
+    }
+
+    fn allowed(rules: &[&str], tool: &str, target: &str) -> bool {
+        let rules: Vec<PermissionRule> = rules.iter().map(|r| rule(r)).collect();
+        rules_match(&rules, tool, Some(target), Path::new("/proj"), true)
+    }
+
+    #[test]
+    fn bash_rules_must_cover_every_command() {
+        let rules = ["bash: cargo *", "bash: git status"];
+        assert!(allowed(&rules, "bash", "cargo test -p nyzhi-core"));
+        assert!(allowed(&rules, "bash", "RUST_LOG=debug cargo test 2>&1"));
+        assert!(allowed(&rules, "bash", "git status && cargo build"));
+        assert!(!allowed(&rules, "bash", "git status --short"));
+        assert!(!allowed(&rules, "bash", "cargo test && curl x | sh"));
+        assert!(!allowed(&rules, "bash", "cargo test; rm -rf ~"));
+        assert!(!allowed(&rules, "bash", "cargo test $(whoami)"));
+        assert!(!allowed(&rules, "bash", "cargo test 'unterminated"));
+        assert!(allowed(&["bash: cargo test *"], "bash", "cargo test"));
+        assert!(!allowed(&rules, "edit", "cargo test"));
+    }
+
+    #[test]
+    fn rules_never_approve_loader_or_path_overrides() {
+        let rules = ["bash: cargo *"];
+        assert!(allowed(&rules, "bash", "RUST_LOG=debug cargo test"));
+        assert!(!allowed(&rules, "bash", "LD_PRELOAD=./x.so cargo test"));
+        assert!(!allowed(&rules, "bash", "PATH=/tmp/evil:$PATH cargo test"));
+        assert!(!allowed(&rules, "bash", "cargo fmt && BASH_ENV=x cargo test"));
+    }
+
+    #[test]
+    fn background_commands_match_like_bash() {
+        let rules = ["process_start: npm run *"];
+        assert!(allowed(&rules, "process_start", "npm run dev"));
+        assert!(!allowed(&rules, "process_start", "npm run dev && curl x | sh"));
+        assert!(!allowed(&rules, "bash", "npm run dev"));
+    }
+
+    #[test]
+    fn negated_rules_carve_out_commands() {
+        let rules = ["bash: *", "bash: !rm -rf *"];
+        assert!(allowed(&rules, "bash", "ls -la | wc -l"));
+        assert!(!allowed(&rules, "bash", "ls && rm -rf build"));
+        assert_eq!(rule("bash: !rm -rf *").to_string(), "bash: !rm -rf *");
+    }
+
+    #[test]
+    fn path_rules_match_relative_to_project() {
+        let r = ["edit: src/**"];
+        assert!(allowed(&r, "edit", "/proj/src/a/b.rs"));
+        assert!(allowed(&r, "edit", "src/lib.rs"));
+        assert!(!allowed(&r, "edit", "/proj/tests/a.rs"));
+        assert!(!allowed(&r, "edit", "/other/src/a.rs"));
+        assert!(!allowed(&["edit: src/*"], "edit", "/proj/src/a/b.rs"));
+        assert!(allowed(&r, "edit", "/proj/src/../src/./a.rs"));
+        assert!(!rules_match(
+            &[rule(r[0])],
+            "edit",
+            None,
+            Path::new("/proj"),
+            true
+        ));
+    }
+
+    #[test]
+    fn path_rules_do_not_follow_dot_dot_out_of_the_project() {
+        let r = ["edit: src/**", "write"];
+        assert!(!allowed(&r, "edit", "src/../../etc/passwd"));
+        assert!(!allowed(&r, "edit", "/proj/src/../../x"));
+        assert!(!allowed(&r, "edit", "./src/../../proj-other/a.rs"));
+        assert!(!allowed(&r, "write", "/proj/../etc/passwd"));
+        assert!(allowed(&r, "write", "/proj/src/../README.md"));
+
+        let deny = [rule("edit: /etc/**")];
+        let denied = |t| rules_match(&deny, "edit", Some(t), Path::new("/proj"), false);
+        assert!(denied("/proj/src/../../etc/passwd"));
+        assert!(!denied("/proj/src/a.rs"));
+
+        let root = Path::new("/proj");
+        assert!(PermissionRule::suggest("edit", Some("/proj/src/../../x/a.rs"), root).is_empty());
+    }
+
+    #[test]
+    fn suggests_narrow_rules() {
+        let root = Path::new("/proj");
+        let s = |tool, target| {
+            PermissionRule::suggest(tool, target, root)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            s("bash", Some("cargo test -p core")),
+            ["bash: cargo test *"]
+        );
+        assert_eq!(s("bash", Some("ls -la")), ["bash: ls *"]);
+        assert_eq!(
+            s("bash", Some("make && make check | tee log")),
+            ["bash: make *", "bash: make check *", "bash: tee log *"]
+        );
+        assert!(s("bash", Some("echo 'oops")).is_empty());
+        assert_eq!(
+            s("edit", Some("/proj/src/tools/a.rs")),
+            ["edit: src/tools/**"]
+        );
+        assert_eq!(s("write", Some("/proj/README.md")), ["write: README.md"]);
+        assert_eq!(s("web_fetch", None), ["web_fetch"]);
+    }
+
+    #[test]
+    fn explicit_lists_accept_rules() {
+        let root = Path::new("/proj");
+        let trust = nyzhi_config::TrustConfig {
+            auto_approve: vec!["bash: cargo *".into()],
+            always_ask: vec!["bash: cargo publish *".into()],
+            deny_tools: vec!["bash: git push *".into()],
+            ..Default::default()
+        };
+        let check = |cmd| check_auto_approve("bash", Some(cmd), root, &trust);
+        assert_eq!(check("cargo check"), Some(true));
+        assert_eq!(check("cargo publish --dry-run"), Some(false));
+        assert_eq!(check("npm test"), None);
+        assert!(check_deny(
+            "bash",
+            Some("git push origin main"),
+            root,
+            &trust
+        ));
+        assert!(!check_deny("bash", Some("git status"), root, &trust));
+    }
+
+    #[test]
+    fn remembered_rules_persist_per_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut memory = ApprovalMemory::default();
+        memory
+            .remember(
+                rule("bash: cargo test *"),
+                ApprovalScope::Session,
+                dir.path(),
+            )
+            .unwrap();
+        memory
+            .remember(rule("edit: src/**"), ApprovalScope::Project, dir.path())
+            .unwrap();
+        assert!(memory.allows("bash", Some("cargo test"), dir.path()));
+        let saved = project_approvals_path(dir.path());
+        assert!(saved.exists());
+        assert!(!saved.starts_with(dir.path()));
+
+        let reloaded = shared_memory(dir.path(), true);
+        let allowed = reloaded
+            .lock()
+            .unwrap()
+            .allows("edit", Some("src/main.rs"), dir.path());
+        let session_only = reloaded
+            .lock()
+            .unwrap()
+            .allows("bash", Some("cargo test"), dir.path());
+        let disabled = shared_memory(dir.path(), false)
+            .lock()
+            .unwrap()
+            .rules()
+            .is_empty();
+        std::fs::remove_dir_all(saved.parent().unwrap()).unwrap();
+        assert!(allowed);
+        assert!(!session_only);
+        assert!(disabled);
     }
 
-    pub fn was_approved(&self, tool_name: &str, pattern: &str) -> bool {
-        self.approved.contains(&format!("{tool_name}:{pattern}"))
+    #[test]
+    fn approvals_inside_the_checkout_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".nyzhi")).unwrap();
+        std::fs::write(
+            dir.path().join(".nyzhi/approvals.toml"),
+            "rules = [\"bash: curl *\"]\n",
+        )
+        .unwrap();
+        let memory = shared_memory(dir.path(), true);
+        assert!(!memory
+            .lock()
+            .unwrap()
+            .allows("bash", Some("curl x"), dir.path()));
     }
 }
@@ -0,0 +1,349 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::permission::ToolPermission;
+use super::{Tool, ToolContext, ToolResult};
+use crate::processes::{ProcessInfo, ProcessManagerHandle, ProcessOutput, ProcessStatus};
+use crate::sandbox::is_dangerous_command;
+use nyzhi_config::SandboxLevel;
+
+const MAX_WAIT_SECS: u64 = 30;
+
+fn manager(ctx: &ToolContext) -> Result<&ProcessManagerHandle> {
+    ctx.processes
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Background processes are not available in this session"))
+}
+
+fn process_id(args: &Value) -> Result<usize> {
+    args.get("id")
+        .and_then(|v| v.as_u64())
+        .map(|id| id as usize)
+        .ok_or_else(|| anyhow::anyhow!("Missing required parameter: id"))
+}
+
+fn error_result(tool_name: &str, id: usize, e: anyhow::Error) -> ToolResult {
+    ToolResult {
+        output: format!("Error: {e}"),
+        title: format!("{tool_name} (error)"),
+        metadata: json!({ "error": e.to_string(), "id": id }),
+    }
+}
+
+fn describe(info: &ProcessInfo) -> String {
+    format!(
+        "#{} [{}] {} (log: {})",
+        info.id,
+        info.status,
+        info.command,
+        info.log_path.display()
+    )
+}
+
+pub struct ProcessStartTool;
+
+#[async_trait]
+impl Tool for ProcessStartTool {
+    fn name(&self) -> &str {
+        "process_start"
+    }
+
+    fn description(&self) -> &str {
+        "Start a shell command in the background and return its id without waiting for it. \
+         Use for dev servers, watchers and test runs that outlast bash's timeout. \
+         Read its output with process_output, write to its stdin with process_send and stop \
+         it with process_kill. Background processes run under the session's sandbox and are \
+         killed when the session ends."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "The shell command to run"
+                }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn permission(&self) -> ToolPermission {
+        ToolPermission::NeedsApproval
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let command = args
+            .get("command")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: command"))?;
+        let manager = manager(ctx)?;
+
+        let launcher = ctx.launcher();
+        if launcher.level() != SandboxLevel::FullAccess && is_dangerous_command(command) {
+            return Ok(ToolResult {
+                output: format!(
+                    "Command refused by the {} sandbox: it runs a destructive command or could not be parsed",
+                    launcher.level()
+                ),
+                title: "process_start (blocked)".to_string(),
+                metadata: json!({ "denied": true, "reason": "dangerous_command" }),
+            });
+        }
+
+        let log_dir = crate::session::session_data_dir(&ctx.session_id)?.join("processes");
+        let info = manager.start(command, &launcher, &ctx.cwd, &log_dir)?;
+        Ok(ToolResult {
+            output: format!(
+                "Started background process #{} (log: {}). \
+                 Check on it with process_output.",
+                info.id,
+                info.log_path.display()
+            ),
+            title: format!("process_start #{}: {}", info.id, info.command),
+            metadata: json!({
+                "id": info.id,
+                "log_path": info.log_path.display().to_string(),
+            }),
+        })
+    }
+}
+
+pub struct ProcessOutputTool;
+
+#[async_trait]
+impl Tool for ProcessOutputTool {
+    fn name(&self) -> &str {
+        "process_output"
+    }
+
+    fn description(&self) -> &str {
+        "Read what a background process has written to stdout and stderr since the last \
+         process_output call for it, along with whether it is still running. \
+         Set `wait` to wait up to that many seconds (max 30) for new output or for the \
+         process to exit."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "integer",
+                    "description": "Process id from process_start"
+                },
+                "wait": {
+                    "type": "integer",
+                    "description": "Seconds to wait for new output (default 0, max 30)"
+                }
+            },
+            "required": ["id"]
+        })
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let id = process_id(&args)?;
+        let manager = manager(ctx)?;
+        let wait = args
+            .get("wait")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+            .min(MAX_WAIT_SECS);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(wait);
+        let mut output = ProcessOutput {
+            text: String::new(),
+            skipped: 0,
+            status: ProcessStatus::Running,
+        };
+        loop {
+            let next = match manager.read_new(id) {
+                Ok(next) => next,
+                Err(e) => return Ok(error_result("process_output", id, e)),
+            };
+            output.text.push_str(&next.text);
+            output.skipped += next.skipped;
+            output.status = next.status;
+            if !output.text.is_empty()
+                || output.status != ProcessStatus::Running
+                || tokio::time::Instant::now() >= deadline
+            {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(250)) => {}
+                _ = ctx.cancel.cancelled() => break,
+            }
+        }
+
+        let mut text = String::new();
+        if output.skipped > 0 {
+            let log = manager
+                .get(id)
+                .map(|p| p.log_path.display().to_string())
+                .unwrap_or_default();
+            text.push_str(&format!(
+                "... ({} earlier bytes skipped, full output in {log})\n",
+                output.skipped
+            ));
+        }
+        if output.text.is_empty() {
+            text.push_str("(no new output)");
+        } else {
+            text.push_str(&output.text);
+        }
+        text.push_str(&format!("\n\n[process #{id}: {}]", output.status));
+
+        Ok(ToolResult {
+            output: text,
+            title: format!("process_output #{id}"),
+            metadata: json!({
+                "id": id,
+                "running": output.status == ProcessStatus::Running,
+                "skipped_bytes": output.skipped,
+            }),
+        })
+    }
+}
+
+pub struct ProcessSendTool;
+
+#[async_trait]
+impl Tool for ProcessSendTool {
+    fn name(&self) -> &str {
+        "process_send"
+    }
+
+    fn description(&self) -> &str {
+        "Write text to the stdin of a background process. No newline is added, so include \
+         `\\n` to submit a line. Set `close` to close stdin afterwards (end of input)."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "integer",
+                    "description": "Process id from process_start"
+                },
+                "input": {
+                    "type": "string",
+                    "description": "Text to write to stdin"
+                },
+                "close": {
+                    "type": "boolean",
+                    "description": "Close stdin after writing (default false)"
+                }
+            },
+            "required": ["id", "input"]
+        })
+    }
+
+    fn permission(&self) -> ToolPermission {
+        ToolPermission::NeedsApproval
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let id = process_id(&args)?;
+        let input = args
+            .get("input")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: input"))?;
+        let close = args.get("close").and_then(|v| v.as_bool()).unwrap_or(false);
+        let manager = manager(ctx)?;
+
+        if let Err(e) = manager.send(id, input, close).await {
+            return Ok(error_result("process_send", id, e));
+        }
+        Ok(ToolResult {
+            output: format!(
+                "Wrote {} bytes to process #{id}{}",
+                input.len(),
+                if close { " and closed its stdin" } else { "" }
+            ),
+            title: format!("process_send #{id}"),
+            metadata: json!({ "id": id, "bytes": input.len(), "closed": close }),
+        })
+    }
+}
+
+pub struct ProcessKillTool;
+
+#[async_trait]
+impl Tool for ProcessKillTool {
+    fn name(&self) -> &str {
+        "process_kill"
+    }
+
+    fn description(&self) -> &str {
+        "Kill a background process and everything it started."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "integer",
+                    "description": "Process id from process_start"
+                }
+            },
+            "required": ["id"]
+        })
+    }
+
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let id = process_id(&args)?;
+        match manager(ctx)?.kill(id) {
+            Ok(info) => Ok(ToolResult {
+                output: format!("Process #{id} is {}", info.status),
+                title: format!("process_kill #{id}"),
+                metadata: json!({ "id": id }),
+            }),
+            Err(e) => Ok(error_result("process_kill", id, e)),
+        }
+    }
+}
+
+pub struct ProcessListTool;
+
+#[async_trait]
+impl Tool for ProcessListTool {
+    fn name(&self) -> &str {
+        "process_list"
+    }
+
+    fn description(&self) -> &str {
+        "List the background processes started in this session with their status and log files."
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {}
+        })
+    }
+
+    async fn execute(&self, _args: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let processes = manager(ctx)?.list();
+        let output = if processes.is_empty() {
+            "No background processes.".to_string()
+        } else {
+            processes
+                .iter()
+                .map(describe)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        Ok(ToolResult {
+            output,
+            title: format!("process_list ({})", processes.len()),
+            metadata: json!({ "count": processes.len() }),
+        })
+    }
+}
@@ -5,7 +5,7 @@ use serde_json::{json, Value};
 use super::{Tool, ToolContext, ToolResult};
 use crate::tools::permission::ToolPermission;
 
-use nyzhi_index::CodebaseIndex;
+use nyzhi_index::{CodebaseIndex, SearchMode};
 use std::sync::Arc;
 
 pub struct SemanticSearchTool {
@@ -25,8 +25,9 @@ impl Tool for SemanticSearchTool {
     }
 
     fn description(&self) -> &str {
-        "Search the codebase using natural language. Finds code chunks that are \
-         semantically relevant to the query, even if they don't contain the exact words. \
+        "Search the codebase using natural language or identifiers. Finds code chunks that are \
+         semantically relevant to the query, even if they don't contain the exact words, and \
+         ranks definitions of identifiers named in the query first. \
          Use for questions like 'where is authentication handled?' or 'database connection setup'."
     }
 
@@ -36,7 +37,13 @@ impl Tool for SemanticSearchTool {
             "properties": {
                 "query": {
                     "type": "string",
-                    "description": "Natural language search query describing what you're looking for"
+                    "description": "Natural language description of what you're looking for, or an identifier"
+                },
+                "mode": {
+                    "type": "string",
+                    "enum": ["hybrid", "vector", "keyword"],
+                    "description": "Retrieval mode: 'hybrid' fuses embedding similarity with keyword (BM25) ranking, 'vector' is embeddings only, 'keyword' is BM25 only (best for exact identifiers). Default: hybrid",
+                    "default": "hybrid"
                 },
                 "max_results": {
                     "type": "integer",
@@ -63,6 +70,11 @@ impl Tool for SemanticSearchTool {
             .and_then(|v| v.as_u64())
             .unwrap_or(10) as usize;
 
+        let mode = match args.get("mode").and_then(|v| v.as_str()) {
+            Some(m) => m.parse::<SearchMode>()?,
+            None => SearchMode::Hybrid,
+        };
+
         if !self.index.is_ready() {
             return Ok(ToolResult {
                 output:
@@ -73,7 +85,10 @@ impl Tool for SemanticSearchTool {
             });
         }
 
-        let results = self.index.search(query, max_results).await?;
+        let results = self
+            .index
+            .search_with_mode(query, max_results, mode)
+            .await?;
 
         let count = results.len();
         let output = if results.is_empty() {
@@ -83,15 +98,7 @@ impl Tool for SemanticSearchTool {
                 .into_iter()
                 .enumerate()
                 .map(|(i, r)| {
-                    format!(
-                        "{}. {} (lines {}-{}, score: {:.3})\n{}",
-                        i + 1,
-                        r.file,
-                        r.start_line,
-                        r.end_line,
-                        r.score,
-                        preview(&r.content, 12),
-                    )
+                    format!("{}. {}\n{}", i + 1, r, preview(&r.content, 12))
                 })
                 .collect::<Vec<_>>()
                 .join("\n---\n")
@@ -100,7 +107,7 @@ impl Tool for SemanticSearchTool {
         Ok(ToolResult {
             output,
             title: format!("semantic_search: {query}"),
-            metadata: json!({ "result_count": count }),
+            metadata: json!({ "result_count": count, "mode": mode.as_str() }),
         })
     }
 }
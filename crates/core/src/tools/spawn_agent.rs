@@ -116,6 +116,7 @@ impl Tool for SpawnAgentTool {
             auto_context: false,
             auto_context_chunks: 0,
             subagent_model: None,
+            fallbacks: Vec::new(),
         };
 
         apply_role(&mut agent_config, &role);
@@ -106,6 +106,7 @@ impl Tool for TaskTool {
             auto_context: false,
             auto_context_chunks: 0,
             subagent_model: None,
+            fallbacks: Vec::new(),
         };
 
         let mut child_thread = Thread::new();
@@ -135,6 +136,8 @@ impl Tool for TaskTool {
                             elapsed_ms,
                         },
                         AgentEvent::TurnComplete => break,
+                        // The sub-task's shell is its own.
+                        AgentEvent::CwdChanged(_) => continue,
                         other => other,
                     };
                     let _ = parent.send(forwarded);
@@ -162,8 +165,13 @@ impl Tool for TaskTool {
             todo_store: ctx.todo_store.clone(),
             index: ctx.index.clone(),
             sandbox_level: ctx.sandbox_level,
+            sandbox_config: ctx.sandbox_config.clone(),
+            approvals: ctx.approvals.clone(),
             subagent_model_overrides: ctx.subagent_model_overrides.clone(),
             shared_context: ctx.shared_context.clone(),
+            processes: ctx.processes.clone(),
+            shell: ctx.shell.as_ref().map(|s| s.fork()),
+            cancel: ctx.cancel.child_token(),
         };
 
         let mut session_usage = SessionUsage::default();
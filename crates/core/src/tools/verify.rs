@@ -53,7 +53,7 @@ impl Tool for VerifyTool {
             });
         }
 
-        let report = verify::run_all_checks(&checks, &ctx.cwd).await;
+        let report = verify::run_all_checks(&checks, &ctx.cwd, &ctx.launcher()).await;
         let passed = report.all_passed();
 
         Ok(ToolResult {
@@ -39,7 +39,7 @@ impl Tool for WebFetchTool {
         ToolPermission::NeedsApproval
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
         let url = args
             .get("url")
             .and_then(|v| v.as_str())
@@ -55,7 +55,15 @@ impl Tool for WebFetchTool {
             .user_agent("nyzhi/1.0")
             .build()?;
 
-        let resp = client.get(url).send().await?;
+        let resp = tokio::select! {
+            resp = client.get(url).send() => resp?,
+            _ = ctx.cancel.cancelled() => {
+                return Ok(ToolResult::cancelled(
+                    "web_fetch",
+                    format!("Fetching {url} was cancelled"),
+                ));
+            }
+        };
         let status = resp.status();
         let content_type = resp
             .headers()
@@ -72,7 +80,15 @@ impl Tool for WebFetchTool {
             });
         }
 
-        let body = resp.text().await?;
+        let body = tokio::select! {
+            body = resp.text() => body?,
+            _ = ctx.cancel.cancelled() => {
+                return Ok(ToolResult::cancelled(
+                    "web_fetch",
+                    format!("Fetching {url} was cancelled"),
+                ));
+            }
+        };
         let text = if content_type.contains("html") {
             html_to_text(&body)
         } else {
@@ -132,7 +148,7 @@ impl Tool for WebSearchTool {
         ToolPermission::NeedsApproval
     }
 
-    async fn execute(&self, args: Value, _ctx: &ToolContext) -> Result<ToolResult> {
+    async fn execute(&self, args: Value, ctx: &ToolContext) -> Result<ToolResult> {
         let query = args
             .get("query")
             .and_then(|v| v.as_str())
@@ -154,8 +170,15 @@ impl Tool for WebSearchTool {
             urlencoding::encode(query)
         );
 
-        let resp = client.get(&url).send().await?;
-        let body = resp.text().await?;
+        let body = tokio::select! {
+            body = async { client.get(&url).send().await?.text().await } => body?,
+            _ = ctx.cancel.cancelled() => {
+                return Ok(ToolResult::cancelled(
+                    "web_search",
+                    format!("Searching for {query} was cancelled"),
+                ));
+            }
+        };
 
         let results = parse_ddg_results(&body, num_results);
 
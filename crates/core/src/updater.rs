@@ -149,7 +149,7 @@ fn prune_old_backups() {
         })
         .collect();
 
-    backups.sort_by(|a, b| b.1.cmp(&a.1));
+    backups.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     for (path, _) in backups.into_iter().skip(MAX_BACKUPS) {
         let _ = std::fs::remove_file(path);
@@ -186,7 +186,7 @@ pub fn list_backups() -> Vec<PathBuf> {
                 .map(|t| (e.path(), t))
         })
         .collect();
-    backups.sort_by(|a, b| b.1.cmp(&a.1));
+    backups.sort_by_key(|b| std::cmp::Reverse(b.1));
     backups.into_iter().map(|(p, _)| p).collect()
 }
 
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::time::{Duration, SystemTime};
 
+use crate::sandbox::Launcher;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CheckKind {
@@ -151,19 +153,17 @@ pub fn detect_checks(project_root: &Path) -> Vec<VerifyCheck> {
     checks
 }
 
-pub async fn run_check(check: &VerifyCheck, cwd: &Path) -> Evidence {
+pub async fn run_check(check: &VerifyCheck, cwd: &Path, launcher: &Launcher) -> Evidence {
     let start = std::time::Instant::now();
     let ts = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
 
-    let result = tokio::process::Command::new("sh")
-        .arg("-c")
-        .arg(&check.command)
-        .current_dir(cwd)
-        .output()
-        .await;
+    let result = match launcher.shell(&check.command, cwd) {
+        Ok(mut command) => command.output().await.map_err(anyhow::Error::from),
+        Err(e) => Err(e),
+    };
 
     let elapsed = start.elapsed().as_millis() as u64;
 
@@ -189,10 +189,14 @@ pub async fn run_check(check: &VerifyCheck, cwd: &Path) -> Evidence {
     }
 }
 
-pub async fn run_all_checks(checks: &[VerifyCheck], cwd: &Path) -> VerifyReport {
+pub async fn run_all_checks(
+    checks: &[VerifyCheck],
+    cwd: &Path,
+    launcher: &Launcher,
+) -> VerifyReport {
     let mut evidence = vec![];
     for check in checks {
-        evidence.push(run_check(check, cwd).await);
+        evidence.push(run_check(check, cwd, launcher).await);
     }
     VerifyReport { checks: evidence }
 }
@@ -150,7 +150,7 @@ pub async fn record_audio(output_dir: &Path, duration_secs: u32) -> Result<std::
             .await;
 
         match status {
-            Ok(s) if s.success() => return Ok(output_path),
+            Ok(s) if s.success() => Ok(output_path),
             _ => anyhow::bail!(
                 "No audio recording tool found. Install alsa-utils: sudo apt install alsa-utils"
             ),
@@ -267,8 +267,8 @@ fn extract_paths_frontmatter(content: &str) -> Option<Vec<String>> {
             continue;
         }
         if in_paths {
-            if trimmed.starts_with("- ") {
-                let pat = trimmed[2..].trim().trim_matches('"').trim_matches('\'');
+            if let Some(rest) = trimmed.strip_prefix("- ") {
+                let pat = rest.trim().trim_matches('"').trim_matches('\'');
                 if !pat.is_empty() {
                     patterns.push(pat.to_string());
                 }
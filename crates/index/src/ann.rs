@@ -0,0 +1,619 @@
+//! Approximate nearest-neighbour search over chunk embeddings.
+//!
+//! A hierarchical navigable small world (HNSW) graph over L2-normalized
+//! vectors, so the inner product is the cosine similarity. Nodes are keyed by
+//! the chunk row id in SQLite and can be inserted and removed one at a time;
+//! removals are tombstoned and the graph is compacted once too many pile up.
+//! The whole structure is persisted to a single file next to the index DB.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// Max links per node on upper layers; layer 0 allows twice as many.
+const M: usize = 16;
+const M0: usize = 2 * M;
+const EF_CONSTRUCTION: usize = 100;
+/// Lower bound on the search beam; raised to `k` for larger requests.
+const EF_SEARCH: usize = 64;
+/// Compact once this fraction of nodes are tombstones.
+const MAX_DELETED_RATIO: f32 = 0.25;
+
+const MAGIC: &[u8; 8] = b"NYZHNSW1";
+const NO_ENTRY: u32 = u32::MAX;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Scored {
+    sim: f32,
+    slot: u32,
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sim
+            .total_cmp(&other.sim)
+            .then_with(|| other.slot.cmp(&self.slot))
+    }
+}
+
+struct Node {
+    id: i64,
+    deleted: bool,
+    /// Neighbour slots per layer; `links.len() - 1` is the node's level.
+    links: Vec<Vec<u32>>,
+}
+
+pub struct Hnsw {
+    dims: usize,
+    /// Normalized vectors, `dims` floats per slot.
+    vectors: Vec<f32>,
+    nodes: Vec<Node>,
+    slots: HashMap<i64, u32>,
+    entry: u32,
+    deleted: usize,
+    rng: u64,
+}
+
+impl Default for Hnsw {
+    fn default() -> Self {
+        Self {
+            dims: 0,
+            vectors: Vec::new(),
+            nodes: Vec::new(),
+            slots: HashMap::new(),
+            entry: NO_ENTRY,
+            deleted: 0,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+}
+
+impl Hnsw {
+    /// Number of live (non-deleted) vectors.
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.deleted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    pub fn contains(&self, id: i64) -> bool {
+        self.slots.contains_key(&id)
+    }
+
+    /// Ids of all live vectors.
+    pub fn ids(&self) -> impl Iterator<Item = i64> + '_ {
+        self.slots.keys().copied()
+    }
+
+    pub fn insert(&mut self, id: i64, vector: &[f32]) -> Result<()> {
+        if vector.is_empty() {
+            bail!("empty vector");
+        }
+        if self.slots.contains_key(&id) {
+            self.remove(id);
+        }
+        if self.nodes.is_empty() {
+            self.dims = vector.len();
+        } else if vector.len() != self.dims {
+            bail!(
+                "vector has {} dimensions, index has {}",
+                vector.len(),
+                self.dims
+            );
+        }
+
+        let slot = self.nodes.len() as u32;
+        let level = self.random_level();
+        self.vectors.extend(normalized(vector));
+        self.nodes.push(Node {
+            id,
+            deleted: false,
+            links: vec![Vec::new(); level + 1],
+        });
+        self.slots.insert(id, slot);
+
+        if self.entry == NO_ENTRY {
+            self.entry = slot;
+            return Ok(());
+        }
+
+        let query = self.vector(slot).to_vec();
+        let top = self.level(self.entry);
+        let mut entry_points = vec![self.entry];
+        for layer in (level + 1..=top).rev() {
+            let nearest = self.search_layer(&query, &entry_points, 1, layer);
+            entry_points = vec![nearest[0].slot];
+        }
+
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(&query, &entry_points, EF_CONSTRUCTION, layer);
+            let live: Vec<Scored> = candidates
+                .iter()
+                .copied()
+                .filter(|c| !self.nodes[c.slot as usize].deleted)
+                .collect();
+            let neighbours = self.select_neighbours(&live, M);
+            self.nodes[slot as usize].links[layer] = neighbours.clone();
+
+            let max_links = if layer == 0 { M0 } else { M };
+            for &neighbour in &neighbours {
+                let links = &mut self.nodes[neighbour as usize].links[layer];
+                links.push(slot);
+                if links.len() > max_links {
+                    self.shrink_links(neighbour, layer, max_links);
+                }
+            }
+            entry_points = candidates.iter().map(|c| c.slot).collect();
+        }
+
+        // A tombstoned entry point still routes searches, so it is kept.
+        if level > top {
+            self.entry = slot;
+        }
+        Ok(())
+    }
+
+    /// Tombstone `id`. Returns whether it was present.
+    pub fn remove(&mut self, id: i64) -> bool {
+        let Some(slot) = self.slots.remove(&id) else {
+            return false;
+        };
+        self.nodes[slot as usize].deleted = true;
+        self.deleted += 1;
+
+        if self.slots.is_empty() {
+            *self = Self {
+                rng: self.rng,
+                ..Self::default()
+            };
+        } else if self.nodes.len() > 64
+            && self.deleted as f32 > self.nodes.len() as f32 * MAX_DELETED_RATIO
+        {
+            self.compact();
+        }
+        true
+    }
+
+    /// Top `k` live vectors by cosine similarity, best first.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(i64, f32)> {
+        if self.entry == NO_ENTRY || query.len() != self.dims || k == 0 {
+            return vec![];
+        }
+        let query = normalized(query);
+        let mut entry_points = vec![self.entry];
+        for layer in (1..=self.level(self.entry)).rev() {
+            let nearest = self.search_layer(&query, &entry_points, 1, layer);
+            entry_points = vec![nearest[0].slot];
+        }
+
+        // Tombstones occupy beam slots, so widen it while any are around.
+        let ef = EF_SEARCH.max(k) + if self.deleted > 0 { k } else { 0 };
+        self.search_layer(&query, &entry_points, ef, 0)
+            .into_iter()
+            .filter(|c| !self.nodes[c.slot as usize].deleted)
+            .take(k)
+            .map(|c| (self.nodes[c.slot as usize].id, c.sim))
+            .collect()
+    }
+
+    /// Top `k` by scanning every live vector.
+    pub fn search_exact(&self, query: &[f32], k: usize) -> Vec<(i64, f32)> {
+        if query.len() != self.dims || k == 0 {
+            return vec![];
+        }
+        let query = normalized(query);
+        let mut best: BinaryHeap<Reverse<Scored>> = BinaryHeap::with_capacity(k + 1);
+        for (slot, node) in self.nodes.iter().enumerate() {
+            if node.deleted {
+                continue;
+            }
+            let scored = Scored {
+                sim: dot(&query, self.vector(slot as u32)),
+                slot: slot as u32,
+            };
+            best.push(Reverse(scored));
+            if best.len() > k {
+                best.pop();
+            }
+        }
+        let mut out: Vec<Scored> = best.into_iter().map(|Reverse(s)| s).collect();
+        out.sort_by(|a, b| b.cmp(a));
+        out.into_iter()
+            .map(|s| (self.nodes[s.slot as usize].id, s.sim))
+            .collect()
+    }
+
+    /// Mean recall@k of the graph search against the exact scan, using up to
+    /// `samples` stored vectors as queries.
+    pub fn recall(&self, samples: usize, k: usize) -> Option<f32> {
+        let live: Vec<u32> = self.slots.values().copied().collect();
+        if live.is_empty() || samples == 0 || k == 0 {
+            return None;
+        }
+        let stride = (live.len() / samples).max(1);
+        let mut total = 0.0f32;
+        let mut count = 0usize;
+        for &slot in live.iter().step_by(stride).take(samples) {
+            let query = self.vector(slot).to_vec();
+            let exact: HashSet<i64> = self
+                .search_exact(&query, k)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            let found = self
+                .search(&query, k)
+                .into_iter()
+                .filter(|(id, _)| exact.contains(id))
+                .count();
+            total += found as f32 / exact.len().max(1) as f32;
+            count += 1;
+        }
+        Some(total / count as f32)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut w = BufWriter::new(std::fs::File::create(&tmp)?);
+            w.write_all(MAGIC)?;
+            w.write_all(&(self.dims as u32).to_le_bytes())?;
+            w.write_all(&(self.nodes.len() as u32).to_le_bytes())?;
+            w.write_all(&self.entry.to_le_bytes())?;
+            w.write_all(&self.rng.to_le_bytes())?;
+            for (slot, node) in self.nodes.iter().enumerate() {
+                w.write_all(&node.id.to_le_bytes())?;
+                w.write_all(&[node.deleted as u8, node.links.len() as u8])?;
+                for f in self.vector(slot as u32) {
+                    w.write_all(&f.to_le_bytes())?;
+                }
+                for links in &node.links {
+                    w.write_all(&(links.len() as u32).to_le_bytes())?;
+                    for l in links {
+                        w.write_all(&l.to_le_bytes())?;
+                    }
+                }
+            }
+            w.flush()?;
+        }
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file =
+            std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let mut r = BufReader::new(file);
+
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("not an ANN index file");
+        }
+        let dims = read_u32(&mut r)? as usize;
+        let count = read_u32(&mut r)? as usize;
+        let entry = read_u32(&mut r)?;
+        let rng = read_u64(&mut r)?;
+
+        let mut index = Self {
+            dims,
+            entry,
+            rng,
+            vectors: Vec::with_capacity(dims * count),
+            nodes: Vec::with_capacity(count),
+            ..Self::default()
+        };
+        for slot in 0..count {
+            let id = read_u64(&mut r)? as i64;
+            let mut flags = [0u8; 2];
+            r.read_exact(&mut flags)?;
+            for _ in 0..dims {
+                index.vectors.push(f32::from_le_bytes(read_array(&mut r)?));
+            }
+            let mut links = Vec::with_capacity(flags[1] as usize);
+            for _ in 0..flags[1] {
+                let n = read_u32(&mut r)? as usize;
+                let mut layer = Vec::with_capacity(n);
+                for _ in 0..n {
+                    let l = read_u32(&mut r)?;
+                    if l as usize >= count {
+                        bail!("corrupt ANN index: link out of range");
+                    }
+                    layer.push(l);
+                }
+                links.push(layer);
+            }
+            if links.is_empty() {
+                bail!("corrupt ANN index: node without layers");
+            }
+            let deleted = flags[0] != 0;
+            if deleted {
+                index.deleted += 1;
+            } else {
+                index.slots.insert(id, slot as u32);
+            }
+            index.nodes.push(Node { id, deleted, links });
+        }
+        if (entry == NO_ENTRY) != index.nodes.is_empty()
+            || (entry != NO_ENTRY && entry as usize >= count)
+        {
+            bail!("corrupt ANN index: bad entry point");
+        }
+        Ok(index)
+    }
+
+    /// Rebuild the graph from live nodes only.
+    fn compact(&mut self) {
+        let live: Vec<(i64, Vec<f32>)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.deleted)
+            .map(|(slot, n)| (n.id, self.vector(slot as u32).to_vec()))
+            .collect();
+        *self = Self {
+            rng: self.rng,
+            ..Self::default()
+        };
+        for (id, vector) in live {
+            // Vectors came out of this index, so dimensions always match.
+            let _ = self.insert(id, &vector);
+        }
+    }
+
+    fn vector(&self, slot: u32) -> &[f32] {
+        let start = slot as usize * self.dims;
+        &self.vectors[start..start + self.dims]
+    }
+
+    fn level(&self, slot: u32) -> usize {
+        self.nodes[slot as usize].links.len() - 1
+    }
+
+    fn random_level(&mut self) -> usize {
+        // xorshift64*: deterministic, so rebuilt graphs are reproducible.
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        let uniform = ((bits >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+        let ml = 1.0 / (M as f64).ln();
+        ((-uniform.ln() * ml) as usize).min(16)
+    }
+
+    /// Beam search on one layer; returns up to `ef` nodes, best first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[u32],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Scored> {
+        let mut visited: HashSet<u32> = entry_points.iter().copied().collect();
+        let mut candidates: BinaryHeap<Scored> = BinaryHeap::new();
+        let mut results: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+
+        for &slot in entry_points {
+            let scored = Scored {
+                sim: dot(query, self.vector(slot)),
+                slot,
+            };
+            candidates.push(scored);
+            results.push(Reverse(scored));
+            if results.len() > ef {
+                results.pop();
+            }
+        }
+
+        while let Some(current) = candidates.pop() {
+            let worst = results.peek().map(|Reverse(s)| s.sim).unwrap_or(f32::MIN);
+            if current.sim < worst && results.len() >= ef {
+                break;
+            }
+            let Some(links) = self.nodes[current.slot as usize].links.get(layer) else {
+                continue;
+            };
+            for &next in links {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let scored = Scored {
+                    sim: dot(query, self.vector(next)),
+                    slot: next,
+                };
+                let worst = results.peek().map(|Reverse(s)| s.sim).unwrap_or(f32::MIN);
+                if results.len() < ef || scored.sim > worst {
+                    candidates.push(scored);
+                    results.push(Reverse(scored));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut out: Vec<Scored> = results.into_iter().map(|Reverse(s)| s).collect();
+        out.sort_by(|a, b| b.cmp(a));
+        out
+    }
+
+    /// Neighbour selection heuristic from the HNSW paper: prefer candidates
+    /// that are closer to the base than to any already selected neighbour,
+    /// which keeps links spread out. `candidates` must be sorted best first.
+    fn select_neighbours(&self, candidates: &[Scored], m: usize) -> Vec<u32> {
+        let mut selected: Vec<u32> = Vec::with_capacity(m);
+        let mut skipped: Vec<u32> = Vec::new();
+        for c in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let diverse = selected
+                .iter()
+                .all(|&s| dot(self.vector(c.slot), self.vector(s)) < c.sim);
+            if diverse {
+                selected.push(c.slot);
+            } else {
+                skipped.push(c.slot);
+            }
+        }
+        for s in skipped {
+            if selected.len() >= m {
+                break;
+            }
+            selected.push(s);
+        }
+        selected
+    }
+
+    fn shrink_links(&mut self, slot: u32, layer: usize, max_links: usize) {
+        let base = self.vector(slot).to_vec();
+        let mut scored: Vec<Scored> = self.nodes[slot as usize].links[layer]
+            .iter()
+            .map(|&l| Scored {
+                sim: dot(&base, self.vector(l)),
+                slot: l,
+            })
+            .collect();
+        scored.sort_by(|a, b| b.cmp(a));
+        let kept = self.select_neighbours(&scored, max_links);
+        self.nodes[slot as usize].links[layer] = kept;
+    }
+}
+
+fn normalized(v: &[f32]) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm < 1e-10 {
+        v.to_vec()
+    } else {
+        v.iter().map(|x| x / norm).collect()
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_array(r)?))
+}
+
+fn read_u64(r: &mut impl Read) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_array(r)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_vectors(n: usize, dims: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut state = seed;
+        (0..n)
+            .map(|_| {
+                (0..dims)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((state >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn similarity_is_cosine() {
+        let mut index = Hnsw::default();
+        index.insert(1, &[1.0, 2.0, 3.0]).unwrap();
+        index.insert(2, &[-2.0, 1.0, 0.0]).unwrap();
+        let hits = index.search_exact(&[2.0, 4.0, 6.0], 2);
+        assert_eq!(hits[0].0, 1);
+        assert!((hits[0].1 - 1.0).abs() < 1e-5);
+        assert!(hits[1].1.abs() < 1e-5);
+    }
+
+    #[test]
+    fn graph_search_recall_is_high() {
+        let mut index = Hnsw::default();
+        for (i, v) in random_vectors(1000, 32, 7).iter().enumerate() {
+            index.insert(i as i64, v).unwrap();
+        }
+        let recall = index.recall(100, 10).unwrap();
+        assert!(recall > 0.9, "recall@10 = {recall}");
+    }
+
+    #[test]
+    fn removed_ids_are_never_returned() {
+        let vectors = random_vectors(300, 16, 3);
+        let mut index = Hnsw::default();
+        for (i, v) in vectors.iter().enumerate() {
+            index.insert(i as i64, v).unwrap();
+        }
+        for id in 0..100 {
+            assert!(index.remove(id));
+        }
+        assert_eq!(index.len(), 200);
+        let hits = index.search(&vectors[5], 20);
+        assert_eq!(hits.len(), 20);
+        assert!(hits.iter().all(|(id, _)| *id >= 100));
+        assert_eq!(index.search_exact(&vectors[150], 1)[0].0, 150);
+    }
+
+    #[test]
+    fn reinserting_an_id_replaces_its_vector() {
+        let mut index = Hnsw::default();
+        index.insert(1, &[1.0, 0.0]).unwrap();
+        index.insert(2, &[0.0, 1.0]).unwrap();
+        index.insert(1, &[0.0, -1.0]).unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.search(&[1.0, 0.0], 1)[0].0, 2);
+        assert!(index.insert(3, &[1.0, 0.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let vectors = random_vectors(200, 8, 11);
+        let mut index = Hnsw::default();
+        for (i, v) in vectors.iter().enumerate() {
+            index.insert(i as i64 * 3, v).unwrap();
+        }
+        index.remove(3);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.hnsw");
+        index.save(&path).unwrap();
+        let loaded = Hnsw::load(&path).unwrap();
+
+        assert_eq!(loaded.len(), index.len());
+        assert!(!loaded.contains(3));
+        assert_eq!(
+            loaded.search(&vectors[10], 5),
+            index.search(&vectors[10], 5)
+        );
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(Hnsw::load(&path).is_err());
+    }
+}
@@ -1,10 +1,13 @@
 use std::path::Path;
 
-const MAX_CHUNK_LINES: usize = 80;
+pub(crate) const MAX_CHUNK_LINES: usize = 80;
 const MIN_CHUNK_LINES: usize = 5;
 const TARGET_CHUNK_LINES: usize = 60;
 const OVERLAP_LINES: usize = 5;
 
+/// Bumped whenever chunk boundaries change so existing indexes get rebuilt.
+pub const CHUNKER_VERSION: &str = "2";
+
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub file: String,
@@ -12,6 +15,19 @@ pub struct Chunk {
     pub end_line: usize,
     pub text: String,
     pub kind: ChunkKind,
+    /// Enclosing definitions, outermost first, e.g. `impl Store > fn search`.
+    pub symbol: Option<String>,
+}
+
+impl Chunk {
+    /// Text handed to the embedder: the chunk prefixed with where it lives,
+    /// so a method body still matches queries about its type.
+    pub fn embedding_text(&self) -> String {
+        match &self.symbol {
+            Some(symbol) => format!("{} > {}\n{}", self.file, symbol, self.text),
+            None => self.text.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -33,6 +49,13 @@ pub fn chunk_file(rel_path: &str, content: &str) -> Vec<Chunk> {
         return vec![];
     }
 
+    #[cfg(feature = "syntax")]
+    if let Some(chunks) = crate::syntax::chunk(rel_path, lang, content) {
+        if !chunks.is_empty() {
+            return chunks;
+        }
+    }
+
     let boundaries = find_boundaries(&lines, lang);
     if boundaries.is_empty() {
         return sliding_window_chunks(rel_path, &lines);
@@ -42,7 +65,7 @@ pub fn chunk_file(rel_path: &str, content: &str) -> Vec<Chunk> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum Language {
+pub(crate) enum Language {
     Rust,
     Python,
     JavaScript,
@@ -50,11 +73,16 @@ enum Language {
     Go,
     Java,
     C,
+    Cpp,
     Ruby,
+    Kotlin,
+    CSharp,
+    Swift,
+    Php,
     Unknown,
 }
 
-fn detect_language(path: &str) -> Language {
+pub(crate) fn detect_language(path: &str) -> Language {
     let ext = Path::new(path)
         .extension()
         .and_then(|e| e.to_str())
@@ -67,8 +95,13 @@ fn detect_language(path: &str) -> Language {
         "ts" | "tsx" | "mts" | "cts" => Language::TypeScript,
         "go" => Language::Go,
         "java" => Language::Java,
-        "c" | "h" | "cpp" | "hpp" | "cc" | "cxx" => Language::C,
+        "c" | "h" => Language::C,
+        "cpp" | "hpp" | "cc" | "cxx" | "hh" | "hxx" => Language::Cpp,
         "rb" => Language::Ruby,
+        "kt" | "kts" => Language::Kotlin,
+        "cs" => Language::CSharp,
+        "swift" => Language::Swift,
+        "php" => Language::Php,
         _ => Language::Unknown,
     }
 }
@@ -92,8 +125,12 @@ fn find_boundaries(lines: &[&str], lang: Language) -> Vec<Boundary> {
             Language::Python => detect_python_boundary(trimmed),
             Language::JavaScript | Language::TypeScript => detect_js_boundary(trimmed),
             Language::Go => detect_go_boundary(trimmed),
-            Language::Java => detect_java_boundary(trimmed),
-            Language::C => detect_c_boundary(trimmed, i, lines),
+            Language::Java
+            | Language::Kotlin
+            | Language::CSharp
+            | Language::Swift
+            | Language::Php => detect_java_boundary(trimmed),
+            Language::C | Language::Cpp => detect_c_boundary(trimmed, i, lines),
             Language::Ruby => detect_ruby_boundary(trimmed),
             Language::Unknown => None,
         };
@@ -263,6 +300,7 @@ fn structural_chunks(file: &str, lines: &[&str], boundaries: &[Boundary]) -> Vec
             end_line: end,
             text,
             kind: boundary.kind.clone(),
+            symbol: None,
         });
     }
 
@@ -274,11 +312,23 @@ fn structural_chunks(file: &str, lines: &[&str], boundaries: &[Boundary]) -> Vec
 }
 
 fn sliding_window_chunks(file: &str, lines: &[&str]) -> Vec<Chunk> {
+    window_chunks(file, lines, 0, lines.len(), ChunkKind::Block, None)
+}
+
+/// Split rows `from..to` into overlapping windows of `TARGET_CHUNK_LINES`.
+pub(crate) fn window_chunks(
+    file: &str,
+    lines: &[&str],
+    from: usize,
+    to: usize,
+    kind: ChunkKind,
+    symbol: Option<String>,
+) -> Vec<Chunk> {
     let mut chunks = Vec::new();
-    let mut pos = 0;
+    let mut pos = from;
 
-    while pos < lines.len() {
-        let end = (pos + TARGET_CHUNK_LINES).min(lines.len());
+    while pos < to {
+        let end = (pos + TARGET_CHUNK_LINES).min(to);
         let text = lines[pos..end].join("\n");
 
         if !text.trim().is_empty() {
@@ -287,11 +337,12 @@ fn sliding_window_chunks(file: &str, lines: &[&str]) -> Vec<Chunk> {
                 start_line: pos + 1,
                 end_line: end,
                 text,
-                kind: ChunkKind::Block,
+                kind: kind.clone(),
+                symbol: symbol.clone(),
             });
         }
 
-        if end >= lines.len() {
+        if end >= to {
             break;
         }
         pos = end.saturating_sub(OVERLAP_LINES);
@@ -329,4 +380,102 @@ mod tests {
         assert!(!chunks.is_empty());
         assert!(chunks.iter().all(|c| c.kind == ChunkKind::Block));
     }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn oversized_impl_is_split_into_methods() {
+        let body = (0..40)
+            .map(|i| format!("        let x{i} = {i};"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let code = format!(
+            "use std::io;\n\nimpl Store {{\n    /// Search.\n    pub fn search(&self) {{\n{body}\n    }}\n\n    fn small(&self) {{}}\n\n    fn other(&self) {{\n{body}\n    }}\n}}\n"
+        );
+        let chunks = chunk_file("store.rs", &code);
+
+        let search = chunks
+            .iter()
+            .find(|c| c.symbol.as_deref() == Some("impl Store > fn search"))
+            .expect("search chunk");
+        assert_eq!(search.kind, ChunkKind::Function);
+        assert_eq!(search.start_line, 4, "doc comment belongs to the method");
+        assert!(search.text.starts_with("    /// Search."));
+        assert!(chunks
+            .iter()
+            .any(|c| c.symbol.as_deref() == Some("impl Store > fn other")));
+        assert!(chunks
+            .iter()
+            .any(|c| c.symbol.as_deref() == Some("impl Store") && c.text.contains("fn small")));
+    }
+
+    #[cfg(feature = "lang-typescript")]
+    #[test]
+    fn exported_arrow_function_is_a_definition() {
+        let code =
+            "import x from 'y';\n\nexport const helper = (a: number) => {\n  return a + 1;\n};\n";
+        let chunks = chunk_file("util.ts", code);
+        let helper = chunks
+            .iter()
+            .find(|c| c.symbol.as_deref() == Some("const helper"))
+            .expect("helper chunk");
+        assert_eq!(helper.kind, ChunkKind::Function);
+        assert_eq!((helper.start_line, helper.end_line), (3, 5));
+    }
+
+    #[cfg(all(
+        feature = "lang-kotlin",
+        feature = "lang-c-sharp",
+        feature = "lang-swift",
+        feature = "lang-php"
+    ))]
+    #[test]
+    fn newly_supported_languages_are_parsed() {
+        let cases = [
+            (
+                "Repo.kt",
+                "class Repo {\n    fun load(): Int {\n        return 1\n    }\n}\n",
+                "class Repo",
+            ),
+            (
+                "Repo.cs",
+                "public class Repo {\n    public int Load() {\n        return 1;\n    }\n}\n",
+                "class Repo",
+            ),
+            (
+                "Repo.swift",
+                "struct Repo {\n    func load() -> Int {\n        return 1\n    }\n}\n",
+                "struct Repo",
+            ),
+            (
+                "Repo.php",
+                "<?php\nclass Repo {\n    public function load() {\n        return 1;\n    }\n}\n",
+                "class Repo",
+            ),
+        ];
+        for (path, code, symbol) in cases {
+            let chunks = chunk_file(path, code);
+            assert!(
+                chunks
+                    .iter()
+                    .any(|c| c.kind == ChunkKind::Class && c.symbol.as_deref() == Some(symbol)),
+                "{path}: {chunks:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn embedding_text_includes_symbol_path() {
+        let chunk = Chunk {
+            file: "src/store.rs".into(),
+            start_line: 1,
+            end_line: 3,
+            text: "fn search() {}".into(),
+            kind: ChunkKind::Function,
+            symbol: Some("impl Store > fn search".into()),
+        };
+        assert_eq!(
+            chunk.embedding_text(),
+            "src/store.rs > impl Store > fn search\nfn search() {}"
+        );
+    }
 }
@@ -1,13 +1,21 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 #[async_trait]
 pub trait Embedder: Send + Sync {
     async fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+    /// Vector size, or 0 while it has not been detected yet.
     fn dimensions(&self) -> usize;
     fn model_id(&self) -> &str;
+
+    /// Vector size, embedding a probe text if the model has not reported
+    /// one yet (local endpoints and model files only know it at runtime).
+    async fn detect_dimensions(&self) -> Result<usize> {
+        Ok(self.dimensions())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -38,7 +46,11 @@ const PERPLEXITY: EmbeddingProviderDef = EmbeddingProviderDef {
     dims: 1024,
 };
 
-/// Auto-selection priority: Voyage > OpenAI > Perplexity > TF-IDF
+const OLLAMA_URL: &str = "http://localhost:11434/v1";
+const OLLAMA_MODEL: &str = "nomic-embed-text";
+const LLAMACPP_URL: &str = "http://127.0.0.1:8080/v1";
+
+/// Auto-selection priority: local model > Voyage > OpenAI > Perplexity > TF-IDF
 const AUTO_PRIORITY: &[(&str, &EmbeddingProviderDef)] = &[
     ("voyage", &VOYAGE),
     ("openai", &OPENAI),
@@ -49,16 +61,76 @@ const AUTO_PRIORITY: &[(&str, &EmbeddingProviderDef)] = &[
 // Factory: create the right embedder from config + available keys
 // ---------------------------------------------------------------------------
 
+/// Offline embedding sources. Either may be empty.
+#[derive(Debug, Clone, Default)]
+pub struct LocalEmbedding {
+    /// OpenAI-compatible base URL serving `/embeddings`, e.g. an Ollama or
+    /// llama.cpp server on localhost.
+    pub url: String,
+    /// ONNX model file run on CPU; `tokenizer.json` must sit next to it.
+    pub model_path: String,
+}
+
+impl LocalEmbedding {
+    fn is_configured(&self) -> bool {
+        !self.url.is_empty() || !self.model_path.is_empty()
+    }
+}
+
 /// `api_keys` maps provider id ("openai", "voyage", "perplexity") -> api key.
 pub fn create_embedder(
     mode: &str,
     model_override: &str,
     api_keys: &HashMap<String, String>,
+) -> Arc<dyn Embedder> {
+    create_embedder_with_local(mode, model_override, api_keys, &LocalEmbedding::default())
+}
+
+/// Like [`create_embedder`], with an offline model or endpoint available.
+/// "local" and "auto" prefer it over hosted APIs when configured.
+pub fn create_embedder_with_local(
+    mode: &str,
+    model_override: &str,
+    api_keys: &HashMap<String, String>,
+    local: &LocalEmbedding,
 ) -> Arc<dyn Embedder> {
     let mode = mode.trim().to_ascii_lowercase();
 
     match mode.as_str() {
-        "tfidf" | "local" => return Arc::new(TfIdfEmbedder::new()),
+        "tfidf" => return Arc::new(TfIdfEmbedder::new()),
+        "local" => {
+            if let Some(embedder) = make_local_embedder(local, model_override) {
+                return embedder;
+            }
+            return Arc::new(TfIdfEmbedder::new());
+        }
+        "onnx" => {
+            if local.model_path.is_empty() {
+                tracing::warn!("onnx embedding requested but index.embedding_model_path is empty; using TF-IDF");
+                return Arc::new(TfIdfEmbedder::new());
+            }
+            let local = LocalEmbedding {
+                url: String::new(),
+                model_path: local.model_path.clone(),
+            };
+            if let Some(embedder) = make_local_embedder(&local, model_override) {
+                return embedder;
+            }
+            return Arc::new(TfIdfEmbedder::new());
+        }
+        "ollama" | "llamacpp" | "endpoint" => {
+            let default_url = match mode.as_str() {
+                "ollama" => OLLAMA_URL,
+                "llamacpp" => LLAMACPP_URL,
+                _ => "",
+            };
+            let url = if local.url.is_empty() { default_url } else { &local.url };
+            if !url.is_empty() {
+                return Arc::new(make_endpoint_embedder(url, model_override, &mode));
+            }
+            tracing::warn!("endpoint embedding requested but index.embedding_url is empty; using TF-IDF");
+            return Arc::new(TfIdfEmbedder::new());
+        }
         "voyage" => {
             if let Some(key) = api_keys.get("voyage") {
                 return Arc::new(make_api_embedder(key, &VOYAGE, model_override));
@@ -83,6 +155,12 @@ pub fn create_embedder(
         }
     }
 
+    if local.is_configured() {
+        if let Some(embedder) = make_local_embedder(local, model_override) {
+            return embedder;
+        }
+    }
+
     for (provider_id, def) in AUTO_PRIORITY {
         if let Some(key) = api_keys.get(*provider_id) {
             return Arc::new(make_api_embedder(key, def, model_override));
@@ -98,12 +176,42 @@ fn make_api_embedder(key: &str, def: &EmbeddingProviderDef, model_override: &str
     } else {
         model_override.to_string()
     };
-    ApiEmbedder {
-        api_key: key.to_string(),
-        model,
-        base_url: def.base_url.to_string(),
-        dims: def.dims,
+    ApiEmbedder::new(key.to_string(), model, def.base_url.to_string(), def.dims)
+}
+
+/// An OpenAI-compatible server without auth whose vector size is learned
+/// from its first response.
+fn make_endpoint_embedder(url: &str, model_override: &str, mode: &str) -> ApiEmbedder {
+    let model = match (model_override, mode) {
+        ("", "ollama") => OLLAMA_MODEL,
+        ("", _) => "default",
+        (model, _) => model,
+    };
+    ApiEmbedder::new(
+        String::new(),
+        model.to_string(),
+        url.trim_end_matches('/').to_string(),
+        0,
+    )
+}
+
+/// The configured model file takes precedence over the endpoint.
+fn make_local_embedder(local: &LocalEmbedding, model_override: &str) -> Option<Arc<dyn Embedder>> {
+    if !local.model_path.is_empty() {
+        #[cfg(feature = "onnx")]
+        match crate::onnx::OnnxEmbedder::new(std::path::Path::new(&local.model_path)) {
+            Ok(embedder) => return Some(Arc::new(embedder)),
+            Err(e) => tracing::warn!("Cannot use local embedding model: {e:#}"),
+        }
+        #[cfg(not(feature = "onnx"))]
+        tracing::warn!(
+            "index.embedding_model_path is set but this build lacks the `onnx` feature"
+        );
     }
+    if !local.url.is_empty() {
+        return Some(Arc::new(make_endpoint_embedder(&local.url, model_override, "endpoint")));
+    }
+    None
 }
 
 // ---------------------------------------------------------------------------
@@ -114,22 +222,55 @@ const MAX_RETRIES: u32 = 3;
 const BATCH_SIZE: usize = 100;
 
 pub struct ApiEmbedder {
+    /// Empty for local servers, which get no `Authorization` header.
     api_key: String,
     model: String,
     base_url: String,
-    dims: usize,
+    /// Expected vector size, replaced by what the server actually returns
+    /// (e.g. a model override with a different size). 0 = not known yet.
+    dims: AtomicUsize,
+    /// Whether `dims` came from a response rather than the provider table.
+    dims_detected: std::sync::atomic::AtomicBool,
 }
 
 impl ApiEmbedder {
+    /// `dims` is the expected vector size; pass 0 to detect it from the
+    /// first response.
     pub fn new(api_key: String, model: String, base_url: String, dims: usize) -> Self {
         Self {
             api_key,
             model,
             base_url,
-            dims,
+            dims: AtomicUsize::new(dims),
+            dims_detected: std::sync::atomic::AtomicBool::new(false),
         }
     }
 
+    /// Record the vector size reported by the server; it must not change
+    /// between responses, or vectors in the index would be incomparable.
+    fn observe_dims(&self, embeddings: &[Vec<f32>]) -> Result<()> {
+        let Some(len) = embeddings.first().map(Vec::len) else {
+            return Ok(());
+        };
+        if embeddings.iter().any(|e| e.len() != len) {
+            anyhow::bail!("Embedding response has vectors of different sizes");
+        }
+        if self.dims_detected.swap(true, Ordering::SeqCst) {
+            let known = self.dims.load(Ordering::SeqCst);
+            if known != len {
+                anyhow::bail!(
+                    "Embedding size changed from {known} to {len}; was the model swapped? Rebuild the index"
+                );
+            }
+        } else {
+            let expected = self.dims.swap(len, Ordering::SeqCst);
+            if expected != 0 && expected != len {
+                tracing::debug!("{} returns {len}-dim vectors (expected {expected})", self.model);
+            }
+        }
+        Ok(())
+    }
+
     async fn embed_batch(&self, client: &reqwest::Client, batch: &[&str]) -> Result<Vec<Vec<f32>>> {
         let body = serde_json::json!({
             "model": self.model,
@@ -139,13 +280,13 @@ impl ApiEmbedder {
         let mut last_err = String::new();
 
         for attempt in 0..MAX_RETRIES {
-            let resp = client
+            let mut req = client
                 .post(format!("{}/embeddings", self.base_url))
-                .header("Authorization", format!("Bearer {}", self.api_key))
-                .header("Content-Type", "application/json")
-                .json(&body)
-                .send()
-                .await;
+                .header("Content-Type", "application/json");
+            if !self.api_key.is_empty() {
+                req = req.header("Authorization", format!("Bearer {}", self.api_key));
+            }
+            let resp = req.json(&body).send().await;
 
             let resp = match resp {
                 Ok(r) => r,
@@ -193,7 +334,7 @@ impl ApiEmbedder {
             last_err = truncate_error(&format!("{status_code}: {body_text}"));
 
             // 4xx (except 429) -> no retry
-            if status_code >= 400 && status_code < 500 && status_code != 429 {
+            if (400..500).contains(&status_code) && status_code != 429 {
                 anyhow::bail!("Embedding API error {last_err}");
             }
 
@@ -232,6 +373,7 @@ impl Embedder for ApiEmbedder {
         for batch in texts.chunks(BATCH_SIZE) {
             let batch_vec: Vec<&str> = batch.to_vec();
             let embeddings = self.embed_batch(&client, &batch_vec).await?;
+            self.observe_dims(&embeddings)?;
             all_embeddings.extend(embeddings);
         }
 
@@ -239,12 +381,21 @@ impl Embedder for ApiEmbedder {
     }
 
     fn dimensions(&self) -> usize {
-        self.dims
+        self.dims.load(Ordering::SeqCst)
     }
 
     fn model_id(&self) -> &str {
         &self.model
     }
+
+    async fn detect_dimensions(&self) -> Result<usize> {
+        if !self.dims_detected.load(Ordering::SeqCst) {
+            self.embed(&["fn main() {}"])
+                .await
+                .with_context(|| format!("cannot reach embedding endpoint {}", self.base_url))?;
+        }
+        Ok(self.dimensions())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -503,6 +654,97 @@ mod tests {
         assert_eq!(e.model_id(), "text-embedding-3-large");
     }
 
+    #[test]
+    fn create_embedder_local_endpoints() {
+        let none = LocalEmbedding::default();
+        let e = create_embedder_with_local("ollama", "", &HashMap::new(), &none);
+        assert_eq!(e.model_id(), "nomic-embed-text");
+        assert_eq!(e.dimensions(), 0);
+        let e = create_embedder_with_local("local", "", &HashMap::new(), &none);
+        assert_eq!(e.model_id(), "tfidf-hash-384");
+
+        let mut keys = HashMap::new();
+        keys.insert("openai".to_string(), "sk-test".to_string());
+        let local = LocalEmbedding {
+            url: "http://localhost:8080/v1".into(),
+            model_path: String::new(),
+        };
+        let e = create_embedder_with_local("auto", "bge-small", &keys, &local);
+        assert_eq!(e.model_id(), "bge-small");
+        assert_eq!(e.dimensions(), 0);
+    }
+
+    /// Serve `/embeddings` with `dims`-sized vectors, recording whether any
+    /// request carried an Authorization header.
+    async fn stub_server(dims: usize) -> (String, Arc<std::sync::atomic::AtomicBool>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let saw_auth = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = saw_auth.clone();
+        tokio::spawn(async move {
+            while let Ok((mut sock, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let body = loop {
+                    let n = sock.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let len: usize = head
+                            .lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                            .unwrap_or(0);
+                        if head.to_ascii_lowercase().contains("authorization:") {
+                            flag.store(true, Ordering::SeqCst);
+                        }
+                        if body.len() >= len {
+                            break body.to_string();
+                        }
+                    }
+                };
+                let req: serde_json::Value = serde_json::from_str(&body).unwrap();
+                let n = req["input"].as_array().unwrap().len();
+                let data: Vec<_> = (0..n)
+                    .map(|i| serde_json::json!({ "index": i, "embedding": vec![0.5f32; dims] }))
+                    .collect();
+                let resp = serde_json::json!({ "data": data }).to_string();
+                let http = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    resp.len(),
+                    resp
+                );
+                sock.write_all(http.as_bytes()).await.unwrap();
+            }
+        });
+        (url, saw_auth)
+    }
+
+    #[tokio::test]
+    async fn endpoint_dimensions_are_detected() {
+        let (url, saw_auth) = stub_server(7).await;
+        let local = LocalEmbedding {
+            url,
+            model_path: String::new(),
+        };
+        let e = create_embedder_with_local("endpoint", "", &HashMap::new(), &local);
+        assert_eq!(e.dimensions(), 0);
+        assert_eq!(e.detect_dimensions().await.unwrap(), 7);
+        let vectors = e.embed(&["a", "b"]).await.unwrap();
+        assert_eq!(vectors.len(), 2);
+        assert!(vectors.iter().all(|v| v.len() == 7));
+        assert!(!saw_auth.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn endpoint_rejects_size_change() {
+        let (url, _) = stub_server(5).await;
+        let e = ApiEmbedder::new(String::new(), "m".into(), url, 0);
+        e.observe_dims(&[vec![0.0; 3]]).unwrap();
+        assert!(e.embed(&["a"]).await.is_err());
+    }
+
     fn cosine(a: &[f32], b: &[f32]) -> f32 {
         let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
         let na: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -0,0 +1,126 @@
+//! Keyword side of retrieval: identifier-aware tokenization and BM25.
+//!
+//! Identifiers are indexed whole and split into their camelCase/snake_case
+//! parts, so `wrap_command_sandboxed` matches the exact query as well as
+//! "command sandbox"-style queries that mention its pieces.
+
+use std::collections::HashMap;
+
+/// BM25 term-frequency saturation.
+const K1: f32 = 1.2;
+/// BM25 document-length normalization.
+const B: f32 = 0.75;
+const MAX_TOKEN_LEN: usize = 64;
+
+/// Term frequencies for a chunk (or query). Each identifier contributes the
+/// whole lowercased word plus its parts.
+pub fn term_frequencies(text: &str) -> HashMap<String, u32> {
+    let mut tf = HashMap::new();
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        for term in identifier_terms(word) {
+            *tf.entry(term).or_insert(0) += 1;
+        }
+    }
+    tf
+}
+
+/// Distinct query terms, in first-seen order.
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    query
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .flat_map(identifier_terms)
+        .filter(|t| seen.insert(t.clone()))
+        .collect()
+}
+
+fn identifier_terms(word: &str) -> Vec<String> {
+    let word = word.trim_matches('_');
+    if word.len() < 2 || word.len() > MAX_TOKEN_LEN {
+        return vec![];
+    }
+    let whole = word.to_lowercase();
+    let parts = split_identifier(word);
+    let mut terms = Vec::with_capacity(parts.len() + 1);
+    if parts.len() > 1 {
+        terms.extend(parts.into_iter().filter(|p| p.len() >= 2 && *p != whole));
+    }
+    terms.push(whole);
+    terms
+}
+
+/// Split an identifier on `_` and camelCase boundaries, keeping acronyms
+/// together: `parseHTTPResponse_v2` -> `parse`, `http`, `response`, `v2`.
+pub fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for piece in word.split('_').filter(|p| !p.is_empty()) {
+        let chars: Vec<char> = piece.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (prev, cur) = (chars[i - 1], chars[i]);
+            let next_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            let boundary = ((prev.is_lowercase() || prev.is_ascii_digit()) && cur.is_uppercase())
+                || (prev.is_uppercase() && cur.is_uppercase() && next_lower);
+            if boundary {
+                parts.push(chars[start..i].iter().collect::<String>().to_lowercase());
+                start = i;
+            }
+        }
+        parts.push(chars[start..].iter().collect::<String>().to_lowercase());
+    }
+    parts
+}
+
+/// Inverse document frequency (the non-negative BM25+ variant).
+pub fn idf(total_docs: usize, doc_freq: usize) -> f32 {
+    let n = total_docs as f32;
+    let df = doc_freq as f32;
+    (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+}
+
+/// BM25 contribution of one term to one document.
+pub fn bm25(idf: f32, tf: u32, doc_len: usize, avg_doc_len: f32) -> f32 {
+    let tf = tf as f32;
+    let norm = 1.0 - B + B * doc_len as f32 / avg_doc_len.max(1.0);
+    idf * tf * (K1 + 1.0) / (tf + K1 * norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_snake_and_camel_case() {
+        assert_eq!(
+            split_identifier("wrap_command_sandboxed"),
+            vec!["wrap", "command", "sandboxed"]
+        );
+        assert_eq!(split_identifier("CodebaseIndex"), vec!["codebase", "index"]);
+        assert_eq!(
+            split_identifier("parseHTTPResponse"),
+            vec!["parse", "http", "response"]
+        );
+        assert_eq!(split_identifier("utf16Column"), vec!["utf16", "column"]);
+    }
+
+    #[test]
+    fn identifiers_index_whole_word_and_parts() {
+        let tf = term_frequencies("fn wrap_command_sandboxed(cmd: &str) { wrap(cmd) }");
+        assert_eq!(tf["wrap_command_sandboxed"], 1);
+        assert_eq!(tf["wrap"], 2);
+        assert_eq!(tf["sandboxed"], 1);
+        assert_eq!(tf["cmd"], 2);
+        assert_eq!(tf["fn"], 1);
+        assert_eq!(
+            query_terms("CodebaseIndex codebase"),
+            vec!["codebase", "index", "codebaseindex"]
+        );
+    }
+
+    #[test]
+    fn bm25_prefers_rare_terms_and_short_docs() {
+        assert!(idf(1000, 1) > idf(1000, 500));
+        assert!(bm25(1.0, 2, 10, 50.0) > bm25(1.0, 2, 200, 50.0));
+        assert!(bm25(1.0, 5, 50, 50.0) > bm25(1.0, 1, 50, 50.0));
+    }
+}
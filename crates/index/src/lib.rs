@@ -1,18 +1,27 @@
+pub mod ann;
 pub mod chunker;
 pub mod embedder;
+pub mod keyword;
+#[cfg(feature = "onnx")]
+pub mod onnx;
 pub mod search;
 pub mod store;
+pub mod symbols;
+#[cfg(feature = "syntax")]
+mod syntax;
 pub mod watcher;
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
 use anyhow::Result;
 
-pub use search::SearchResult;
+pub use search::{SearchMode, SearchResult};
 pub use store::IndexStats;
+pub use symbols::{CallSite, SymbolLocation};
 
 #[derive(Debug, Clone)]
 pub struct IndexProgress {
@@ -39,6 +48,10 @@ impl Default for IndexProgress {
 pub struct IndexOptions {
     pub embedding_mode: String,
     pub embedding_model: String,
+    /// OpenAI-compatible endpoint for local embeddings (Ollama, llama.cpp).
+    pub embedding_url: String,
+    /// ONNX model file for offline embeddings (`onnx` feature).
+    pub embedding_model_path: String,
     pub exclude: Vec<String>,
     pub api_keys: HashMap<String, String>,
 }
@@ -49,6 +62,9 @@ pub struct CodebaseIndex {
     project_root: PathBuf,
     exclude: Vec<String>,
     progress: Arc<Mutex<IndexProgress>>,
+    /// Serializes full builds and watcher updates.
+    update_lock: Mutex<()>,
+    watch_task: std::sync::Mutex<Option<JoinHandle<()>>>,
 }
 
 impl CodebaseIndex {
@@ -72,10 +88,14 @@ impl CodebaseIndex {
     ) -> Result<Self> {
         let store = store::Store::open(project_root)?;
 
-        let embedder = embedder::create_embedder(
+        let embedder = embedder::create_embedder_with_local(
             &options.embedding_mode,
             &options.embedding_model,
             &options.api_keys,
+            &embedder::LocalEmbedding {
+                url: options.embedding_url,
+                model_path: options.embedding_model_path,
+            },
         );
 
         Ok(Self {
@@ -84,6 +104,8 @@ impl CodebaseIndex {
             project_root: project_root.to_path_buf(),
             exclude: options.exclude,
             progress: Arc::new(Mutex::new(IndexProgress::default())),
+            update_lock: Mutex::new(()),
+            watch_task: std::sync::Mutex::new(None),
         })
     }
 
@@ -99,6 +121,7 @@ impl CodebaseIndex {
     }
 
     pub async fn build(&self) -> Result<IndexStats> {
+        let _guard = self.update_lock.lock().await;
         {
             let mut p = self.progress.lock().await;
             p.phase = "walking";
@@ -108,19 +131,47 @@ impl CodebaseIndex {
             p.errors.clear();
         }
 
-        // Model change detection: purge if embedding model changed
-        let current_model = self.embedder.model_id().to_string();
-        if let Ok(Some(stored_model)) = self.store.get_meta("embedding_model") {
-            if stored_model != current_model {
-                tracing::debug!(
-                    "Embedding model changed ({} -> {}); purging index",
-                    stored_model,
-                    current_model
-                );
-                self.store.purge_all_chunks()?;
+        let dims = match self.embedder.detect_dimensions().await {
+            Ok(dims) => dims,
+            Err(e) => {
+                let mut p = self.progress.lock().await;
+                p.phase = "complete";
+                p.complete = true;
+                p.errors.push(("embedder".to_string(), format!("{e:#}")));
+                return Err(e);
             }
+        };
+
+        // Model change detection: purge if the embedding model or its vector
+        // size changed (a local server can swap models under the same name)
+        let current_model = self.embedder.model_id().to_string();
+        let current_dims = dims.to_string();
+        let stored_model = self.store.get_meta("embedding_model").ok().flatten();
+        let stored_dims = self.store.get_meta("embedding_dims").ok().flatten();
+        let model_changed = stored_model.as_ref().is_some_and(|m| *m != current_model);
+        let dims_changed = stored_dims.as_ref().is_some_and(|d| *d != current_dims)
+            || self.store.vector_dims().is_some_and(|d| d != dims);
+        if model_changed || dims_changed {
+            tracing::debug!(
+                "Embedding model changed ({}/{} -> {}/{}); purging index",
+                stored_model.as_deref().unwrap_or("?"),
+                stored_dims.as_deref().unwrap_or("?"),
+                current_model,
+                current_dims
+            );
+            self.store.purge_all_chunks()?;
         }
         self.store.set_meta("embedding_model", &current_model)?;
+        self.store.set_meta("embedding_dims", &current_dims)?;
+
+        // Chunker change detection: purge if chunk boundaries changed
+        let stored_chunker = self.store.get_meta("chunker_version").ok().flatten();
+        if stored_chunker.as_deref() != Some(chunker::CHUNKER_VERSION) {
+            tracing::debug!("Chunker changed; purging index");
+            self.store.purge_all_chunks()?;
+            self.store
+                .set_meta("chunker_version", chunker::CHUNKER_VERSION)?;
+        }
 
         let existing = self.store.file_hashes()?;
         let walk = watcher::walk_project(&self.project_root, &self.exclude)?;
@@ -133,13 +184,23 @@ impl CodebaseIndex {
 
         self.store.remove_deleted(&walk)?;
 
-        let dims = self.embedder.dimensions();
+        // Symbol extraction is cheap next to embedding, so a new extractor
+        // re-scans unchanged files without re-embedding them.
+        let refresh_symbols = self.store.get_meta("symbols_version").ok().flatten().as_deref()
+            != Some(symbols::SYMBOLS_VERSION);
+
         let mut indexed = 0usize;
 
         for entry in &walk {
             let rel = entry.rel_path.clone();
             if let Some(old_hash) = existing.get(&rel) {
                 if *old_hash == entry.hash {
+                    if refresh_symbols {
+                        if let Ok(content) = std::fs::read_to_string(&entry.abs_path) {
+                            self.store
+                                .replace_symbols(&rel, &symbols::extract(&rel, &content))?;
+                        }
+                    }
                     indexed += 1;
                     let mut p = self.progress.lock().await;
                     p.indexed = indexed;
@@ -158,19 +219,24 @@ impl CodebaseIndex {
             let chunks = chunker::chunk_file(&rel, &content);
             if chunks.is_empty() {
                 self.store.upsert_file(&rel, &entry.hash, 0)?;
+                self.store
+                    .replace_symbols(&rel, &symbols::extract(&rel, &content))?;
                 indexed += 1;
                 let mut p = self.progress.lock().await;
                 p.indexed = indexed;
                 continue;
             }
 
-            let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+            let texts: Vec<String> = chunks.iter().map(|c| c.embedding_text()).collect();
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
             match self.embedder.embed(&texts).await {
                 Ok(embeddings) => {
                     let model_id = self.embedder.model_id();
                     self.store.upsert_file(&rel, &entry.hash, chunks.len())?;
                     self.store
                         .replace_chunks(&rel, &chunks, &embeddings, model_id, dims)?;
+                    self.store
+                        .replace_symbols(&rel, &symbols::extract(&rel, &content))?;
                 }
                 Err(e) => {
                     let msg = e.to_string();
@@ -186,7 +252,9 @@ impl CodebaseIndex {
             p.indexed = indexed;
         }
 
-        self.store.load_vectors()?;
+        self.store.flush()?;
+        self.store
+            .set_meta("symbols_version", symbols::SYMBOLS_VERSION)?;
 
         {
             let mut p = self.progress.lock().await;
@@ -199,6 +267,7 @@ impl CodebaseIndex {
     }
 
     pub async fn update_file(&self, rel_path: &str) -> Result<()> {
+        let _guard = self.update_lock.lock().await;
         let abs = self.project_root.join(rel_path);
         let content = std::fs::read_to_string(&abs)?;
         let hash = watcher::hash_content(content.as_bytes());
@@ -209,45 +278,239 @@ impl CodebaseIndex {
             }
         }
 
-        let chunks = chunker::chunk_file(rel_path, &content);
-        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
-        let embeddings = self.embedder.embed(&texts).await?;
-        let dims = self.embedder.dimensions();
+        self.index_content(rel_path, &hash, &content).await?;
+        self.store.flush()?;
+
+        Ok(())
+    }
+
+    /// Bring the given project-relative paths up to date: changed files are
+    /// re-chunked and re-embedded, unchanged ones are skipped, and files that
+    /// are gone (or no longer indexable) are dropped.
+    pub async fn sync_files(&self, rel_paths: &[String]) -> Result<()> {
+        let _guard = self.update_lock.lock().await;
+        {
+            let mut p = self.progress.lock().await;
+            p.phase = "updating";
+            p.indexed = 0;
+            p.total = rel_paths.len();
+            p.complete = false;
+            p.errors.clear();
+        }
+
+        let mut indexed = 0usize;
+        for rel in rel_paths {
+            let result = match watcher::file_entry_for(&self.project_root, rel) {
+                Some(entry) => self.sync_entry(&entry).await,
+                None => self.store.remove_path(rel),
+            };
+            indexed += 1;
+            let mut p = self.progress.lock().await;
+            p.indexed = indexed;
+            if let Err(e) = result {
+                tracing::debug!("Index update failed for {}: {}", rel, e);
+                p.errors.push((rel.clone(), e.to_string()));
+            }
+        }
+
+        self.store.flush()?;
+
+        let mut p = self.progress.lock().await;
+        p.phase = "complete";
+        p.complete = true;
+        Ok(())
+    }
+
+    async fn sync_entry(&self, entry: &watcher::FileEntry) -> Result<()> {
+        if self.store.file_hash(&entry.rel_path)?.as_deref() == Some(entry.hash.as_str()) {
+            return Ok(());
+        }
+        let content = match std::fs::read_to_string(&entry.abs_path) {
+            Ok(c) => c,
+            Err(_) => return self.store.remove_path(&entry.rel_path),
+        };
+        self.index_content(&entry.rel_path, &entry.hash, &content)
+            .await
+    }
+
+    async fn index_content(&self, rel_path: &str, hash: &str, content: &str) -> Result<()> {
+        let chunks = chunker::chunk_file(rel_path, content);
+        let texts: Vec<String> = chunks.iter().map(|c| c.embedding_text()).collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = if texts.is_empty() {
+            vec![]
+        } else {
+            self.embedder.embed(&texts).await?
+        };
+        let dims = self.embedder.detect_dimensions().await?;
         let model_id = self.embedder.model_id();
 
-        self.store.upsert_file(rel_path, &hash, chunks.len())?;
+        self.store.upsert_file(rel_path, hash, chunks.len())?;
         self.store
             .replace_chunks(rel_path, &chunks, &embeddings, model_id, dims)?;
-        self.store.load_vectors()?;
+        self.store
+            .replace_symbols(rel_path, &symbols::extract(rel_path, content))?;
+        Ok(())
+    }
+
+    /// Watch the project tree in the background and re-index files as they
+    /// change on disk (IDE edits, `git checkout`, formatters). Progress is
+    /// reported through [`IndexProgress`] like a regular build. Calling this
+    /// again while already watching is a no-op.
+    pub fn start_watching(self: &Arc<Self>) -> Result<()> {
+        let mut task = self.watch_task.lock().unwrap();
+        if task.is_some() {
+            return Ok(());
+        }
 
+        let mut project_watcher = watcher::ProjectWatcher::new(&self.project_root, &self.exclude)?;
+        let index = Arc::downgrade(self);
+        *task = Some(tokio::spawn(async move {
+            while let Some(batch) = project_watcher.next_batch().await {
+                let Some(index) = index.upgrade() else {
+                    break;
+                };
+                tracing::debug!("Index watcher: {} changed paths", batch.len());
+                if let Err(e) = index.sync_files(&batch).await {
+                    tracing::debug!("Index watcher update failed: {e}");
+                }
+            }
+        }));
         Ok(())
     }
 
-    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
-        let query_embedding = self.embedder.embed(&[query]).await?;
-        if query_embedding.is_empty() {
-            return Ok(vec![]);
+    pub fn stop_watching(&self) {
+        if let Some(task) = self.watch_task.lock().unwrap().take() {
+            task.abort();
         }
-        self.store.search(&query_embedding[0], query, limit)
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watch_task.lock().unwrap().is_some()
+    }
+
+    /// Hybrid search: embedding similarity fused with BM25 keyword ranking.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        self.search_with_mode(query, limit, SearchMode::Hybrid).await
+    }
+
+    pub async fn search_with_mode(
+        &self,
+        query: &str,
+        limit: usize,
+        mode: SearchMode,
+    ) -> Result<Vec<SearchResult>> {
+        let query_vec = match mode {
+            SearchMode::Keyword => None,
+            SearchMode::Vector => {
+                let Some(v) = self.embedder.embed(&[query]).await?.into_iter().next() else {
+                    return Ok(vec![]);
+                };
+                Some(v)
+            }
+            // Keyword results are still useful when the embedder is down.
+            SearchMode::Hybrid => match self.embedder.embed(&[query]).await {
+                Ok(v) => v.into_iter().next(),
+                Err(e) => {
+                    tracing::debug!("Query embedding failed, keyword search only: {e}");
+                    None
+                }
+            },
+        };
+        self.store.search(query_vec.as_deref(), query, limit, mode)
+    }
+
+    /// Where `name` is defined. `Type::method` narrows to definitions whose
+    /// symbol path mentions `Type`.
+    pub fn definitions(&self, name: &str) -> Result<Vec<SymbolLocation>> {
+        self.store.definitions(name)
+    }
+
+    /// Every call to `name`, with the calling definition.
+    pub fn callers_of(&self, name: &str) -> Result<Vec<CallSite>> {
+        self.store.callers_of(name)
+    }
+
+    /// Every call made from inside definitions named `name`.
+    pub fn callees_of(&self, name: &str) -> Result<Vec<CallSite>> {
+        self.store.callees_of(name)
+    }
+
+    /// Project files that import `rel_path` or use names only it defines.
+    pub fn dependents_of(&self, rel_path: &str) -> Result<Vec<String>> {
+        self.store.dependents_of(rel_path)
     }
 
     pub async fn auto_context(&self, query: &str, limit: usize) -> Result<String> {
-        let results = self.search(query, limit).await?;
+        let mut results = self.mentioned_definitions(query);
+        for r in self.search(query, limit).await? {
+            let duplicate = results
+                .iter()
+                .any(|d| d.file == r.file && d.start_line == r.start_line);
+            if !duplicate {
+                results.push(r);
+            }
+        }
         if results.is_empty() {
             return Ok(String::new());
         }
 
         let mut xml = String::from("<codebase_context>\n");
         for r in &results {
+            let symbol = r
+                .symbol
+                .as_deref()
+                .map(|s| format!(" symbol=\"{}\"", s.replace('"', "'")))
+                .unwrap_or_default();
             xml.push_str(&format!(
-                "<chunk file=\"{}\" lines=\"{}-{}\" score=\"{:.2}\">\n{}\n</chunk>\n",
-                r.file, r.start_line, r.end_line, r.score, r.content
+                "<chunk file=\"{}\" lines=\"{}-{}\"{} score=\"{:.2}\">\n{}\n</chunk>\n",
+                r.file, r.start_line, r.end_line, symbol, r.score, r.content
             ));
         }
         xml.push_str("</codebase_context>");
         Ok(xml)
     }
 
+    /// Definition chunks for code symbols named in a prompt (`ToolContext`,
+    /// `run_turn`), ahead of what search finds. Names defined in many places
+    /// are skipped as too ambiguous to be worth the context.
+    fn mentioned_definitions(&self, prompt: &str) -> Vec<SearchResult> {
+        const MAX_SYMBOLS: usize = 3;
+        const MAX_DEFINITIONS_PER_SYMBOL: usize = 2;
+
+        let mut results: Vec<SearchResult> = Vec::new();
+        for name in symbols::mentioned_symbols(prompt).iter().take(MAX_SYMBOLS) {
+            let Ok(defs) = self.store.definitions(name) else {
+                continue;
+            };
+            if defs.len() > MAX_DEFINITIONS_PER_SYMBOL {
+                continue;
+            }
+            for def in defs {
+                let Ok(Some((start_line, end_line, content))) =
+                    self.store.chunk_at(&def.file, def.start_line)
+                else {
+                    continue;
+                };
+                if results
+                    .iter()
+                    .any(|r| r.file == def.file && r.start_line == start_line)
+                {
+                    continue;
+                }
+                results.push(SearchResult {
+                    file: def.file,
+                    start_line,
+                    end_line,
+                    symbol: Some(def.path),
+                    score: 1.0,
+                    content,
+                });
+            }
+        }
+        results
+    }
+
     pub fn stats(&self) -> Result<IndexStats> {
         self.store.stats()
     }
@@ -256,7 +519,29 @@ impl CodebaseIndex {
         self.progress.lock().await.clone()
     }
 
+    /// "hnsw" once the index is large enough for approximate search,
+    /// otherwise "exact".
+    pub fn search_backend(&self) -> &'static str {
+        if self.store.uses_ann() {
+            "hnsw"
+        } else {
+            "exact"
+        }
+    }
+
+    /// Mean recall@k of the ANN graph against an exact scan over up to
+    /// `samples` stored vectors used as queries.
+    pub fn ann_recall(&self, samples: usize, k: usize) -> Option<f32> {
+        self.store.ann_recall(samples, k)
+    }
+
     pub fn is_ready(&self) -> bool {
         self.store.vector_count() > 0
     }
 }
+
+impl Drop for CodebaseIndex {
+    fn drop(&mut self) {
+        self.stop_watching();
+    }
+}
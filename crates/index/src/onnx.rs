@@ -0,0 +1,272 @@
+//! Offline embeddings from an ONNX sentence-embedding model run on CPU.
+//!
+//! Expects a BERT-style export (`input_ids`, `attention_mask` and optionally
+//! `token_type_ids`) with `tokenizer.json` in the same directory, e.g. the
+//! ONNX builds of bge-small, all-MiniLM or jina-embeddings-v2-base-code.
+//! Token embeddings are mean-pooled over the attention mask and normalized.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+use tract_onnx::prelude::*;
+
+use crate::embedder::Embedder;
+
+/// Longest input the common code-embedding exports accept.
+const MAX_TOKENS: usize = 512;
+const BATCH_SIZE: usize = 16;
+
+type Plan = TypedRunnableModel<TypedModel>;
+
+#[derive(Clone, Copy)]
+enum Input {
+    Ids,
+    Mask,
+    TypeIds,
+}
+
+struct Loaded {
+    plan: Plan,
+    tokenizer: Tokenizer,
+    inputs: Vec<Input>,
+}
+
+struct Model {
+    model_path: PathBuf,
+    tokenizer_path: PathBuf,
+    /// Loaded on first use: optimizing a model takes a few seconds and
+    /// should not block opening the index.
+    loaded: OnceLock<std::result::Result<Loaded, String>>,
+}
+
+pub struct OnnxEmbedder {
+    model: Arc<Model>,
+    model_id: String,
+    dims: AtomicUsize,
+}
+
+impl OnnxEmbedder {
+    pub fn new(model_path: &Path) -> Result<Self> {
+        anyhow::ensure!(
+            model_path.is_file(),
+            "embedding model {} not found",
+            model_path.display()
+        );
+        let tokenizer_path = model_path.with_file_name("tokenizer.json");
+        anyhow::ensure!(
+            tokenizer_path.is_file(),
+            "{} not found next to the embedding model",
+            tokenizer_path.display()
+        );
+        let name = model_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .or_else(|| model_path.file_stem())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self {
+            model: Arc::new(Model {
+                model_path: model_path.to_path_buf(),
+                tokenizer_path,
+                loaded: OnceLock::new(),
+            }),
+            model_id: format!("onnx:{name}"),
+            dims: AtomicUsize::new(0),
+        })
+    }
+}
+
+#[async_trait]
+impl Embedder for OnnxEmbedder {
+    async fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let model = self.model.clone();
+        let texts: Vec<String> = texts.iter().map(|t| t.to_string()).collect();
+        let embeddings = tokio::task::spawn_blocking(move || {
+            let loaded = model.get()?;
+            let mut out = Vec::with_capacity(texts.len());
+            for batch in texts.chunks(BATCH_SIZE) {
+                out.extend(loaded.embed_batch(batch)?);
+            }
+            Ok::<_, anyhow::Error>(out)
+        })
+        .await??;
+        if let Some(first) = embeddings.first() {
+            self.dims.store(first.len(), Ordering::SeqCst);
+        }
+        Ok(embeddings)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dims.load(Ordering::SeqCst)
+    }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    async fn detect_dimensions(&self) -> Result<usize> {
+        if self.dimensions() == 0 {
+            self.embed(&["fn main() {}"]).await?;
+        }
+        Ok(self.dimensions())
+    }
+}
+
+impl Model {
+    fn get(&self) -> Result<&Loaded> {
+        self.loaded
+            .get_or_init(|| self.load().map_err(|e| format!("{e:#}")))
+            .as_ref()
+            .map_err(|e| anyhow::anyhow!("{e}"))
+    }
+
+    fn load(&self) -> Result<Loaded> {
+        let mut tokenizer = Tokenizer::from_file(&self.tokenizer_path)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("reading {}", self.tokenizer_path.display()))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+
+        let mut model = tract_onnx::onnx()
+            .model_for_path(&self.model_path)
+            .with_context(|| format!("loading {}", self.model_path.display()))?;
+        let batch = model.sym("batch");
+        let seq = model.sym("seq");
+
+        let mut inputs = Vec::new();
+        for (i, outlet) in model.input_outlets()?.to_vec().into_iter().enumerate() {
+            let name = model.node(outlet.node).name.as_str();
+            let input = match name {
+                n if n.contains("mask") => Input::Mask,
+                n if n.contains("type") => Input::TypeIds,
+                n if n.contains("input_ids") || n == "ids" => Input::Ids,
+                other => anyhow::bail!("unsupported model input '{other}'"),
+            };
+            inputs.push(input);
+            model = model.with_input_fact(
+                i,
+                InferenceFact::dt_shape(i64::datum_type(), tvec![batch.to_dim(), seq.to_dim()]),
+            )?;
+        }
+        anyhow::ensure!(
+            inputs.iter().any(|i| matches!(i, Input::Ids)),
+            "model has no input_ids input"
+        );
+
+        let plan = model.into_optimized()?.into_runnable()?;
+        Ok(Loaded {
+            plan,
+            tokenizer,
+            inputs,
+        })
+    }
+}
+
+impl Loaded {
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        let rows = encodings.len();
+        let cols = encodings.first().map_or(0, |e| e.get_ids().len());
+
+        let tensor = |pick: fn(&tokenizers::Encoding) -> &[u32]| -> Result<TValue> {
+            let data: Vec<i64> = encodings
+                .iter()
+                .flat_map(|e| pick(e).iter().map(|&v| v as i64))
+                .collect();
+            Ok(tract_ndarray::Array2::from_shape_vec((rows, cols), data)?
+                .into_tensor()
+                .into())
+        };
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| match input {
+                Input::Ids => tensor(|e| e.get_ids()),
+                Input::Mask => tensor(|e| e.get_attention_mask()),
+                Input::TypeIds => tensor(|e| e.get_type_ids()),
+            })
+            .collect::<Result<TVec<_>>>()?;
+
+        let outputs = self.plan.run(inputs)?;
+        let output = outputs[0].to_array_view::<f32>()?;
+        let masks: Vec<&[u32]> = encodings.iter().map(|e| e.get_attention_mask()).collect();
+        Ok(pool(output, &masks))
+    }
+}
+
+/// One normalized vector per row: mean over unmasked tokens for per-token
+/// outputs (`[batch, seq, dims]`), or the row as-is for pooled outputs.
+fn pool(output: tract_ndarray::ArrayViewD<f32>, masks: &[&[u32]]) -> Vec<Vec<f32>> {
+    let mut vectors = Vec::with_capacity(masks.len());
+    for (row, mask) in masks.iter().enumerate() {
+        let row_view = output.index_axis(tract_ndarray::Axis(0), row);
+        let mut v = if row_view.ndim() == 2 {
+            let dims = row_view.shape()[1];
+            let mut sum = vec![0.0f32; dims];
+            let mut count = 0.0f32;
+            for (token, &m) in row_view.outer_iter().zip(mask.iter()) {
+                if m == 0 {
+                    continue;
+                }
+                for (s, x) in sum.iter_mut().zip(token.iter()) {
+                    *s += x;
+                }
+                count += 1.0;
+            }
+            sum.iter().map(|s| s / count.max(1.0)).collect::<Vec<f32>>()
+        } else {
+            row_view.iter().copied().collect()
+        };
+        let norm: f32 = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 1e-10 {
+            v.iter_mut().for_each(|x| *x /= norm);
+        }
+        vectors.push(v);
+    }
+    vectors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_pool_skips_padding() {
+        // batch 2, seq 3, dims 2; second row has one padding token.
+        let data = vec![
+            1.0, 0.0, 3.0, 0.0, 2.0, 0.0, //
+            0.0, 1.0, 0.0, 3.0, 100.0, 100.0,
+        ];
+        let output = tract_ndarray::ArrayD::from_shape_vec(vec![2, 3, 2], data).unwrap();
+        let masks: [&[u32]; 2] = [&[1, 1, 1], &[1, 1, 0]];
+        let vectors = pool(output.view(), &masks);
+        assert_eq!(vectors[0], vec![1.0, 0.0]);
+        assert_eq!(vectors[1], vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn missing_model_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(OnnxEmbedder::new(&dir.path().join("model.onnx")).is_err());
+        std::fs::write(dir.path().join("model.onnx"), b"").unwrap();
+        let err = OnnxEmbedder::new(&dir.path().join("model.onnx"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("tokenizer.json"));
+    }
+}
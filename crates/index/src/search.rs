@@ -1,18 +1,102 @@
+use std::collections::HashMap;
+
+/// Rank constant for reciprocal rank fusion; 60 is the value from the
+/// original RRF paper and works well without tuning.
+const RRF_K: f32 = 60.0;
+
+/// Which retrievers a search runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// Embedding similarity only.
+    Vector,
+    /// BM25 over chunk text and identifiers only; needs no embedding call.
+    Keyword,
+    /// Both, fused with reciprocal rank fusion.
+    #[default]
+    Hybrid,
+}
+
+impl SearchMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchMode::Vector => "vector",
+            SearchMode::Keyword => "keyword",
+            SearchMode::Hybrid => "hybrid",
+        }
+    }
+}
+
+impl std::str::FromStr for SearchMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vector" | "semantic" => Ok(SearchMode::Vector),
+            "keyword" | "bm25" => Ok(SearchMode::Keyword),
+            "hybrid" => Ok(SearchMode::Hybrid),
+            other => {
+                anyhow::bail!("unknown search mode '{other}' (expected vector, keyword or hybrid)")
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub file: String,
     pub start_line: usize,
     pub end_line: usize,
+    /// Enclosing definitions of the chunk, e.g. `impl Store > fn search`.
+    pub symbol: Option<String>,
     pub score: f32,
     pub content: String,
 }
 
 impl std::fmt::Display for SearchResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " [{symbol}]")?;
+        }
         write!(
             f,
-            "{} (lines {}-{}, score: {:.3})",
-            self.file, self.start_line, self.end_line, self.score
+            " (lines {}-{}, score: {:.3})",
+            self.start_line, self.end_line, self.score
         )
     }
 }
+
+/// Reciprocal rank fusion of ranked id lists: each list contributes
+/// `1 / (RRF_K + rank)` per id. Returns ids best first.
+pub fn reciprocal_rank_fusion(rankings: &[&[i64]]) -> Vec<(i64, f32)> {
+    let mut scores: HashMap<i64, f32> = HashMap::new();
+    for ranking in rankings {
+        for (rank, id) in ranking.iter().enumerate() {
+            *scores.entry(*id).or_insert(0.0) += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+    let mut fused: Vec<(i64, f32)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rrf_rewards_agreement() {
+        let vector = [1, 2, 3];
+        let keyword = [3, 4, 1];
+        let fused = reciprocal_rank_fusion(&[&vector, &keyword]);
+        let order: Vec<i64> = fused.iter().map(|(id, _)| *id).collect();
+        assert_eq!(order, vec![1, 3, 2, 4]);
+    }
+
+    #[test]
+    fn mode_parses() {
+        assert_eq!("hybrid".parse::<SearchMode>().unwrap(), SearchMode::Hybrid);
+        assert_eq!("BM25".parse::<SearchMode>().unwrap(), SearchMode::Keyword);
+        assert!("fuzzy".parse::<SearchMode>().is_err());
+    }
+}
@@ -1,14 +1,24 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::RwLock;
 
 use anyhow::Result;
 use rusqlite::{params, Connection};
 use sha2::Digest;
 
+use crate::ann::Hnsw;
 use crate::chunker::Chunk;
+use crate::keyword;
+use crate::search::{reciprocal_rank_fusion, SearchMode};
+use crate::symbols::{self, CallSite, FileSymbols, SymbolLocation};
 use crate::watcher::FileEntry;
 
+/// Below this many vectors an exact scan is fast enough and has perfect recall.
+const EXACT_SCAN_MAX: usize = 10_000;
+/// Bump when keyword tokenization changes so postings are rebuilt on open.
+const KEYWORD_INDEX_VERSION: &str = "1";
+
 #[derive(Debug, Clone, Default)]
 pub struct IndexStats {
     pub file_count: usize,
@@ -19,24 +29,29 @@ pub struct IndexStats {
 
 pub struct Store {
     conn: std::sync::Mutex<Connection>,
-    vectors: RwLock<VectorCache>,
+    /// Embeddings keyed by chunk id; chunk text and location stay in SQLite.
+    ann: RwLock<Hnsw>,
+    /// Set while `ann` has changes that are not yet in `ann_path`.
+    ann_dirty: AtomicBool,
+    ann_path: PathBuf,
     db_path: PathBuf,
 }
 
-#[derive(Default)]
-struct VectorCache {
-    ids: Vec<i64>,
-    embeddings: Vec<Vec<f32>>,
-    file_paths: Vec<String>,
-    start_lines: Vec<usize>,
-    end_lines: Vec<usize>,
-    chunk_texts: Vec<String>,
+struct ChunkRow {
+    file_path: String,
+    start_line: usize,
+    end_line: usize,
+    chunk_text: String,
+    symbol: Option<String>,
 }
 
 impl Store {
     pub fn open(project_root: &Path) -> Result<Self> {
-        let db_dir = index_dir(project_root);
-        std::fs::create_dir_all(&db_dir)?;
+        Self::open_dir(&index_dir(project_root))
+    }
+
+    fn open_dir(db_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(db_dir)?;
         let db_path = db_dir.join("index.db");
 
         let conn = Connection::open(&db_path)?;
@@ -60,18 +75,58 @@ impl Store {
                 chunk_text TEXT NOT NULL,
                 embedding BLOB,
                 model_id TEXT,
-                dims INTEGER NOT NULL DEFAULT 0
+                dims INTEGER NOT NULL DEFAULT 0,
+                symbol TEXT,
+                token_count INTEGER NOT NULL DEFAULT 0
             );
             CREATE INDEX IF NOT EXISTS idx_chunks_file ON chunks(file_path);
+            CREATE TABLE IF NOT EXISTS postings (
+                term TEXT NOT NULL,
+                chunk_id INTEGER NOT NULL REFERENCES chunks(id) ON DELETE CASCADE,
+                tf INTEGER NOT NULL,
+                PRIMARY KEY (term, chunk_id)
+            ) WITHOUT ROWID;
+            CREATE INDEX IF NOT EXISTS idx_postings_chunk ON postings(chunk_id);
+            CREATE TABLE IF NOT EXISTS symbols (
+                file_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                path TEXT NOT NULL,
+                start_line INTEGER NOT NULL,
+                end_line INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_symbols_name ON symbols(name);
+            CREATE INDEX IF NOT EXISTS idx_symbols_file ON symbols(file_path);
+            CREATE TABLE IF NOT EXISTS refs (
+                file_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                line INTEGER NOT NULL,
+                is_call INTEGER NOT NULL,
+                caller TEXT,
+                caller_path TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_refs_name ON refs(name);
+            CREATE INDEX IF NOT EXISTS idx_refs_caller ON refs(caller) WHERE is_call = 1;
+            CREATE INDEX IF NOT EXISTS idx_refs_file ON refs(file_path);
+            CREATE TABLE IF NOT EXISTS imports (
+                file_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE,
+                target TEXT NOT NULL,
+                line INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_imports_file ON imports(file_path);
             CREATE TABLE IF NOT EXISTS meta (
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL
             );",
         )?;
 
+        migrate(&conn)?;
+
         let store = Self {
             conn: std::sync::Mutex::new(conn),
-            vectors: RwLock::new(VectorCache::default()),
+            ann: RwLock::new(Hnsw::default()),
+            ann_dirty: AtomicBool::new(false),
+            ann_path: db_dir.join("index.hnsw"),
             db_path,
         };
 
@@ -123,22 +178,30 @@ impl Store {
         dims: usize,
     ) -> Result<()> {
         let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        let old_ids = chunk_ids(&conn, "file_path = ?1", params![file_path])?;
         conn.execute(
             "DELETE FROM chunks WHERE file_path = ?1",
             params![file_path],
         )?;
 
+        let mut ann = self.ann.write().unwrap();
+        self.mark_dirty(&conn)?;
+        for id in old_ids {
+            ann.remove(id);
+        }
+
         let mut stmt = conn.prepare(
-            "INSERT INTO chunks (file_path, start_line, end_line, content_hash, chunk_text, embedding, model_id, dims)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            "INSERT INTO chunks (file_path, start_line, end_line, content_hash, chunk_text, embedding, model_id, dims, symbol)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         )?;
 
         for (i, chunk) in chunks.iter().enumerate() {
             let content_hash = hex::encode(sha2::Sha256::digest(chunk.text.as_bytes()));
 
-            let embedding_blob: Option<Vec<u8>> = embeddings
-                .get(i)
-                .map(|emb| emb.iter().flat_map(|f| f.to_le_bytes()).collect());
+            let embedding = embeddings.get(i);
+            let embedding_blob: Option<Vec<u8>> =
+                embedding.map(|emb| emb.iter().flat_map(|f| f.to_le_bytes()).collect());
 
             stmt.execute(params![
                 file_path,
@@ -149,12 +212,219 @@ impl Store {
                 embedding_blob,
                 model_id,
                 dims as i64,
+                chunk.symbol,
             ])?;
+            let id = conn.last_insert_rowid();
+            index_terms(&conn, id, &chunk.text, chunk.symbol.as_deref())?;
+
+            if let Some(emb) = embedding.filter(|e| e.len() == dims && dims > 0) {
+                if let Err(e) = ann.insert(id, emb) {
+                    tracing::debug!("Skipping vector for {}: {}", file_path, e);
+                }
+            }
+        }
+        drop(stmt);
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Replace the definitions, references and imports recorded for a file.
+    /// The file must already have a row (see [`Store::upsert_file`]).
+    pub fn replace_symbols(&self, file_path: &str, symbols: &FileSymbols) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        for table in ["symbols", "refs", "imports"] {
+            conn.execute(
+                &format!("DELETE FROM {table} WHERE file_path = ?1"),
+                params![file_path],
+            )?;
         }
 
+        let mut stmt = conn.prepare(
+            "INSERT INTO symbols (file_path, name, kind, path, start_line, end_line)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for d in &symbols.definitions {
+            stmt.execute(params![
+                file_path,
+                d.name,
+                d.kind,
+                d.path,
+                d.start_line as i64,
+                d.end_line as i64
+            ])?;
+        }
+        let mut stmt = conn.prepare(
+            "INSERT INTO refs (file_path, name, line, is_call, caller, caller_path)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for r in &symbols.references {
+            stmt.execute(params![
+                file_path,
+                r.name,
+                r.line as i64,
+                r.call,
+                r.caller,
+                r.caller_path
+            ])?;
+        }
+        let mut stmt =
+            conn.prepare("INSERT INTO imports (file_path, target, line) VALUES (?1, ?2, ?3)")?;
+        for i in &symbols.imports {
+            stmt.execute(params![file_path, i.target, i.line as i64])?;
+        }
+        drop(stmt);
+        tx.commit()?;
         Ok(())
     }
 
+    /// Definitions named `name`. A qualifier (`Store::search`) narrows the
+    /// match to definitions whose symbol path mentions it.
+    pub fn definitions(&self, name: &str) -> Result<Vec<SymbolLocation>> {
+        let (qualifier, name) = symbols::split_qualified(name);
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT file_path, name, kind, path, start_line, end_line FROM symbols
+             WHERE name = ?1 ORDER BY file_path, start_line",
+        )?;
+        let found = stmt
+            .query_map(params![name], |row| {
+                Ok(SymbolLocation {
+                    file: row.get(0)?,
+                    name: row.get(1)?,
+                    kind: row.get(2)?,
+                    path: row.get(3)?,
+                    start_line: row.get::<_, i64>(4)? as usize,
+                    end_line: row.get::<_, i64>(5)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let Some(qualifier) = qualifier else {
+            return Ok(found);
+        };
+        let qualifier = symbols::split_qualified(qualifier).1;
+        let narrowed: Vec<SymbolLocation> = found
+            .iter()
+            .filter(|d| d.path.contains(qualifier))
+            .cloned()
+            .collect();
+        Ok(if narrowed.is_empty() { found } else { narrowed })
+    }
+
+    /// Call sites of `name` (qualifiers are ignored).
+    pub fn callers_of(&self, name: &str) -> Result<Vec<CallSite>> {
+        let (_, name) = symbols::split_qualified(name);
+        self.call_sites("name = ?1", name)
+    }
+
+    /// Calls made from inside definitions named `name`.
+    pub fn callees_of(&self, name: &str) -> Result<Vec<CallSite>> {
+        let (_, name) = symbols::split_qualified(name);
+        self.call_sites("caller = ?1", name)
+    }
+
+    fn call_sites(&self, filter: &str, name: &str) -> Result<Vec<CallSite>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT file_path, line, caller_path, name FROM refs
+             WHERE is_call = 1 AND {filter} ORDER BY file_path, line"
+        ))?;
+        let sites = stmt
+            .query_map(params![name], |row| {
+                Ok(CallSite {
+                    file: row.get(0)?,
+                    line: row.get::<_, i64>(1)? as usize,
+                    caller: row.get(2)?,
+                    callee: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(sites)
+    }
+
+    /// Files that import `file` or use a name only `file` defines, i.e. the
+    /// files likely affected by changing it.
+    pub fn dependents_of(&self, file: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut dependents: std::collections::BTreeSet<String> = conn
+            .prepare(
+                "SELECT DISTINCT r.file_path FROM refs r
+                 WHERE r.file_path != ?1 AND r.name IN (
+                     SELECT s.name FROM symbols s WHERE s.file_path = ?1
+                     AND NOT EXISTS (
+                         SELECT 1 FROM symbols o WHERE o.name = s.name AND o.file_path != ?1
+                     )
+                 )",
+            )?
+            .query_map(params![file], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let module = Path::new(file)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let needle = match module.as_str() {
+            "mod" | "index" | "__init__" => Path::new(file)
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            _ => module,
+        };
+        let mut stmt = conn.prepare(
+            "SELECT file_path, target FROM imports
+             WHERE file_path != ?1 AND instr(target, ?2) > 0",
+        )?;
+        let imports = stmt.query_map(params![file, needle], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in imports {
+            let (importer, target) = row?;
+            if symbols::import_matches(&importer, &target, file) {
+                dependents.insert(importer);
+            }
+        }
+        Ok(dependents.into_iter().collect())
+    }
+
+    /// The smallest chunk covering `line` of `file`: `(start, end, text)`.
+    pub fn chunk_at(&self, file: &str, line: usize) -> Result<Option<(usize, usize, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT start_line, end_line, chunk_text FROM chunks
+             WHERE file_path = ?1 AND start_line <= ?2 AND end_line >= ?2
+             ORDER BY end_line - start_line LIMIT 1",
+        )?;
+        match stmt.query_row(params![file, line as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as usize,
+                row.get::<_, i64>(1)? as usize,
+                row.get::<_, String>(2)?,
+            ))
+        }) {
+            Ok(chunk) => Ok(Some(chunk)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Drop a file, or every file under a directory, from the index.
+    pub fn remove_path(&self, rel_path: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let dir_prefix = format!("{}/", rel_path.trim_end_matches('/'));
+        let ids = chunk_ids(
+            &conn,
+            "file_path = ?1 OR substr(file_path, 1, length(?2)) = ?2",
+            params![rel_path, dir_prefix],
+        )?;
+        conn.execute(
+            "DELETE FROM files WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2",
+            params![rel_path, dir_prefix],
+        )?;
+        self.forget_ids(&conn, ids)
+    }
+
     pub fn remove_deleted(&self, current_files: &[FileEntry]) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare("SELECT path FROM files")?;
@@ -166,44 +436,54 @@ impl Store {
         let current_set: std::collections::HashSet<&str> =
             current_files.iter().map(|e| e.rel_path.as_str()).collect();
 
+        let mut ids = Vec::new();
         for path in &stored {
             if !current_set.contains(path.as_str()) {
+                ids.extend(chunk_ids(&conn, "file_path = ?1", params![path])?);
                 conn.execute("DELETE FROM files WHERE path = ?1", params![path])?;
             }
         }
-        Ok(())
+        self.forget_ids(&conn, ids)
     }
 
+    /// Load the ANN graph from disk, rebuilding it from the embeddings in
+    /// the DB when the file is missing or was not written after the last
+    /// change (e.g. the process died mid-build).
     pub fn load_vectors(&self) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, start_line, end_line, chunk_text, embedding, dims
-             FROM chunks WHERE embedding IS NOT NULL",
+        let expected: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM chunks
+             WHERE embedding IS NOT NULL AND dims > 0 AND length(embedding) = dims * 4",
+            [],
+            |r| r.get(0),
         )?;
+        let synced = meta_value(&conn, "ann_synced")?.as_deref() == Some("1");
+        if synced {
+            match Hnsw::load(&self.ann_path) {
+                Ok(ann) if ann.len() as i64 == expected => {
+                    *self.ann.write().unwrap() = ann;
+                    self.ann_dirty.store(false, Ordering::SeqCst);
+                    return Ok(());
+                }
+                Ok(_) => tracing::debug!("ANN index out of sync with DB; rebuilding"),
+                Err(e) => tracing::debug!("ANN index unreadable ({e}); rebuilding"),
+            }
+        }
 
-        let mut cache = VectorCache::default();
-
+        let mut stmt = conn.prepare(
+            "SELECT id, embedding, dims FROM chunks WHERE embedding IS NOT NULL ORDER BY id",
+        )?;
         let rows = stmt.query_map([], |row| {
-            let id: i64 = row.get(0)?;
-            let file_path: String = row.get(1)?;
-            let start_line: i64 = row.get(2)?;
-            let end_line: i64 = row.get(3)?;
-            let chunk_text: String = row.get(4)?;
-            let blob: Vec<u8> = row.get(5)?;
-            let dims: i64 = row.get(6)?;
             Ok((
-                id,
-                file_path,
-                start_line as usize,
-                end_line as usize,
-                chunk_text,
-                blob,
-                dims as usize,
+                row.get::<_, i64>(0)?,
+                row.get::<_, Vec<u8>>(1)?,
+                row.get::<_, i64>(2)? as usize,
             ))
         })?;
 
+        let mut ann = Hnsw::default();
         for row in rows {
-            let (id, file_path, start, end_line, text, blob, dims) = row?;
+            let (id, blob, dims) = row?;
             if dims == 0 || blob.len() != dims * 4 {
                 continue;
             }
@@ -211,73 +491,103 @@ impl Store {
                 .chunks_exact(4)
                 .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
                 .collect();
-
-            cache.ids.push(id);
-            cache.embeddings.push(embedding);
-            cache.file_paths.push(file_path);
-            cache.start_lines.push(start);
-            cache.end_lines.push(end_line);
-            cache.chunk_texts.push(text);
+            if let Err(e) = ann.insert(id, &embedding) {
+                tracing::debug!("Skipping vector for chunk {}: {}", id, e);
+            }
         }
+        drop(stmt);
 
-        *self.vectors.write().unwrap() = cache;
-        Ok(())
+        *self.ann.write().unwrap() = ann;
+        self.mark_dirty(&conn)?;
+        drop(conn);
+        self.flush()
+    }
+
+    /// Persist pending ANN changes next to the DB.
+    pub fn flush(&self) -> Result<()> {
+        if !self.ann_dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let saved = self.ann.read().unwrap().save(&self.ann_path);
+        if let Err(e) = saved {
+            self.ann_dirty.store(true, Ordering::SeqCst);
+            return Err(e);
+        }
+        self.set_meta("ann_synced", "1")
     }
 
+    /// Search stored chunks. `query_vec` feeds the vector ranking and may
+    /// be `None` (keyword mode, or the embedder is unavailable); `query_text`
+    /// feeds BM25. Hybrid mode fuses both rankings with reciprocal rank
+    /// fusion, and in keyword and hybrid mode chunks that define an
+    /// identifier named in the query are ranked ahead of the rest.
     pub fn search(
         &self,
-        query_vec: &[f32],
+        query_vec: Option<&[f32]>,
         query_text: &str,
         limit: usize,
+        mode: SearchMode,
     ) -> Result<Vec<crate::SearchResult>> {
-        let cache = self.vectors.read().unwrap();
-        if cache.embeddings.is_empty() {
+        // Over-fetch so fusion and the per-file cap have room to work.
+        let candidates = (limit * 10).max(50);
+
+        let vector_hits = match query_vec {
+            Some(query_vec) if mode != SearchMode::Keyword => {
+                let ann = self.ann.read().unwrap();
+                let hits = if ann.len() <= EXACT_SCAN_MAX {
+                    ann.search_exact(query_vec, candidates)
+                } else {
+                    ann.search(query_vec, candidates)
+                };
+                hits.into_iter().filter(|(_, sim)| *sim > 0.05).collect()
+            }
+            _ => Vec::new(),
+        };
+        let keyword_hits = if mode == SearchMode::Vector {
+            Vec::new()
+        } else {
+            self.keyword_search(query_text, candidates)?
+        };
+
+        let ranked: Vec<(i64, f32)> = match mode {
+            SearchMode::Vector => vector_hits,
+            SearchMode::Keyword => keyword_hits,
+            SearchMode::Hybrid => {
+                let vector_ids: Vec<i64> = vector_hits.iter().map(|(id, _)| *id).collect();
+                let keyword_ids: Vec<i64> = keyword_hits.iter().map(|(id, _)| *id).collect();
+                reciprocal_rank_fusion(&[&vector_ids, &keyword_ids])
+            }
+        };
+        if ranked.is_empty() {
             return Ok(vec![]);
         }
+        let rows = self.chunk_rows(ranked.iter().map(|(id, _)| *id))?;
 
-        let query_tokens: Vec<String> = query_text
-            .split(|c: char| !c.is_alphanumeric() && c != '_')
-            .filter(|s| s.len() >= 2)
-            .map(|s| s.to_lowercase())
-            .collect();
-
-        let mut scored: Vec<(usize, f32)> = cache
-            .embeddings
+        let mut scored: Vec<(&ChunkRow, f32)> = ranked
             .iter()
-            .enumerate()
-            .map(|(i, emb)| {
-                let mut score = cosine_similarity(query_vec, emb);
-
-                if !query_tokens.is_empty() {
-                    let text_lower = cache.chunk_texts[i].to_lowercase();
-                    let hits = query_tokens
-                        .iter()
-                        .filter(|t| text_lower.contains(t.as_str()))
-                        .count();
-                    let keyword_boost = (hits as f32 / query_tokens.len() as f32) * 0.15;
-                    score += keyword_boost;
-                }
-
-                (i, score)
-            })
-            .filter(|(_, s)| *s > 0.05)
+            .filter_map(|(id, score)| Some((rows.get(id)?, *score)))
             .collect();
-
-        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        if mode != SearchMode::Vector {
+            let identifiers: Vec<&str> = query_text
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter(|w| w.len() >= 3)
+                .collect();
+            // Stable sort: definitions first, fused order otherwise kept.
+            scored.sort_by_key(|(row, _)| !defines_any(row.symbol.as_deref(), &identifiers));
+        }
         scored.truncate(limit * 3);
 
         let mut results = Vec::new();
-        let mut seen_files: HashMap<String, usize> = HashMap::new();
+        let mut seen_files: HashMap<&str, usize> = HashMap::new();
 
-        for (idx, score) in scored {
-            let file = &cache.file_paths[idx];
-            let count = seen_files.entry(file.clone()).or_insert(0);
+        for (row, score) in scored {
+            let count = seen_files.entry(row.file_path.as_str()).or_insert(0);
             if *count >= 3 {
                 continue;
             }
             *count += 1;
 
-            let content = &cache.chunk_texts[idx];
+            let content = &row.chunk_text;
             let preview = if content.lines().count() > 15 {
                 let lines: Vec<&str> = content.lines().take(15).collect();
                 format!(
@@ -290,9 +600,10 @@ impl Store {
             };
 
             results.push(crate::SearchResult {
-                file: file.clone(),
-                start_line: cache.start_lines[idx],
-                end_line: cache.end_lines[idx],
+                file: row.file_path.clone(),
+                start_line: row.start_line,
+                end_line: row.end_line,
+                symbol: row.symbol.clone(),
                 score,
                 content: preview,
             });
@@ -305,14 +616,118 @@ impl Store {
         Ok(results)
     }
 
-    pub fn get_meta(&self, key: &str) -> Result<Option<String>> {
+    /// BM25 ranking of chunks against the query terms, best first.
+    fn keyword_search(&self, query: &str, limit: usize) -> Result<Vec<(i64, f32)>> {
+        let terms = keyword::query_terms(query);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT value FROM meta WHERE key = ?1")?;
-        match stmt.query_row(params![key], |row| row.get::<_, String>(0)) {
-            Ok(v) => Ok(Some(v)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e.into()),
+        let (total_docs, avg_doc_len): (i64, f64) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(AVG(token_count), 0) FROM chunks",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        if total_docs == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT p.chunk_id, p.tf, c.token_count FROM postings p
+             JOIN chunks c ON c.id = p.chunk_id WHERE p.term = ?1",
+        )?;
+        let mut scores: HashMap<i64, f32> = HashMap::new();
+        for term in &terms {
+            let postings = stmt
+                .query_map(params![term], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)? as u32,
+                        row.get::<_, i64>(2)? as usize,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let idf = keyword::idf(total_docs as usize, postings.len());
+            for (id, tf, doc_len) in postings {
+                *scores.entry(id).or_insert(0.0) +=
+                    keyword::bm25(idf, tf, doc_len, avg_doc_len as f32);
+            }
+        }
+
+        let mut ranked: Vec<(i64, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+        Ok(ranked)
+    }
+
+    /// Mean recall@k of the ANN graph against an exact scan, sampling up to
+    /// `samples` stored vectors as queries. `None` for an empty index.
+    pub fn ann_recall(&self, samples: usize, k: usize) -> Option<f32> {
+        self.ann.read().unwrap().recall(samples, k)
+    }
+
+    /// Whether searches currently go through the ANN graph rather than an
+    /// exact scan.
+    pub fn uses_ann(&self) -> bool {
+        self.ann.read().unwrap().len() > EXACT_SCAN_MAX
+    }
+
+    fn chunk_rows(&self, ids: impl Iterator<Item = i64>) -> Result<HashMap<i64, ChunkRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT file_path, start_line, end_line, chunk_text, symbol FROM chunks WHERE id = ?1",
+        )?;
+        let mut rows = HashMap::new();
+        for id in ids {
+            let row = stmt.query_row(params![id], |row| {
+                Ok(ChunkRow {
+                    file_path: row.get(0)?,
+                    start_line: row.get::<_, i64>(1)? as usize,
+                    end_line: row.get::<_, i64>(2)? as usize,
+                    chunk_text: row.get(3)?,
+                    symbol: row.get(4)?,
+                })
+            });
+            match row {
+                Ok(r) => {
+                    rows.insert(id, r);
+                }
+                Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Drop chunk ids from the ANN graph after their rows were deleted.
+    fn forget_ids(&self, conn: &Connection, ids: Vec<i64>) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut ann = self.ann.write().unwrap();
+        self.mark_dirty(conn)?;
+        for id in ids {
+            ann.remove(id);
         }
+        Ok(())
+    }
+
+    /// Record that the on-disk ANN file no longer matches the DB, so a crash
+    /// before the next `flush` forces a rebuild on open.
+    fn mark_dirty(&self, conn: &Connection) -> Result<()> {
+        if !self.ann_dirty.swap(true, Ordering::SeqCst) {
+            conn.execute(
+                "INSERT INTO meta (key, value) VALUES ('ann_synced', '0')
+                 ON CONFLICT(key) DO UPDATE SET value = '0'",
+                [],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        meta_value(&conn, key)
     }
 
     pub fn set_meta(&self, key: &str, value: &str) -> Result<()> {
@@ -328,12 +743,20 @@ impl Store {
     pub fn purge_all_chunks(&self) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute_batch("DELETE FROM chunks; DELETE FROM files;")?;
-        *self.vectors.write().unwrap() = VectorCache::default();
+        let mut ann = self.ann.write().unwrap();
+        self.mark_dirty(&conn)?;
+        *ann = Hnsw::default();
         Ok(())
     }
 
     pub fn vector_count(&self) -> usize {
-        self.vectors.read().unwrap().embeddings.len()
+        self.ann.read().unwrap().len()
+    }
+
+    /// Size of the stored vectors, `None` while there are none.
+    pub fn vector_dims(&self) -> Option<usize> {
+        let ann = self.ann.read().unwrap();
+        (!ann.is_empty()).then(|| ann.dims())
     }
 
     pub fn stats(&self) -> Result<IndexStats> {
@@ -359,6 +782,104 @@ impl Store {
     }
 }
 
+/// Bring databases created by older versions up to the current schema.
+fn migrate(conn: &Connection) -> Result<()> {
+    let has_symbol = conn
+        .prepare("SELECT 1 FROM pragma_table_info('chunks') WHERE name = 'symbol'")?
+        .exists([])?;
+    if !has_symbol {
+        conn.execute_batch("ALTER TABLE chunks ADD COLUMN symbol TEXT;")?;
+    }
+    let has_token_count = conn
+        .prepare("SELECT 1 FROM pragma_table_info('chunks') WHERE name = 'token_count'")?
+        .exists([])?;
+    if !has_token_count {
+        conn.execute_batch(
+            "ALTER TABLE chunks ADD COLUMN token_count INTEGER NOT NULL DEFAULT 0;",
+        )?;
+    }
+    if meta_value(conn, "keyword_index")?.as_deref() != Some(KEYWORD_INDEX_VERSION) {
+        rebuild_postings(conn)?;
+    }
+    Ok(())
+}
+
+/// Re-tokenize every stored chunk into the postings table.
+fn rebuild_postings(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    conn.execute("DELETE FROM postings", [])?;
+    let chunks = conn
+        .prepare("SELECT id, chunk_text, symbol FROM chunks")?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, text, symbol) in chunks {
+        index_terms(conn, id, &text, symbol.as_deref())?;
+    }
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES ('keyword_index', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = ?1",
+        params![KEYWORD_INDEX_VERSION],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Write the postings and token count for one chunk.
+fn index_terms(conn: &Connection, chunk_id: i64, text: &str, symbol: Option<&str>) -> Result<()> {
+    let mut tf = keyword::term_frequencies(text);
+    if let Some(symbol) = symbol {
+        for (term, n) in keyword::term_frequencies(symbol) {
+            *tf.entry(term).or_insert(0) += n;
+        }
+    }
+    let mut stmt =
+        conn.prepare_cached("INSERT INTO postings (term, chunk_id, tf) VALUES (?1, ?2, ?3)")?;
+    for (term, n) in &tf {
+        stmt.execute(params![term, chunk_id, *n as i64])?;
+    }
+    let token_count: u32 = tf.values().sum();
+    conn.execute(
+        "UPDATE chunks SET token_count = ?1 WHERE id = ?2",
+        params![token_count as i64, chunk_id],
+    )?;
+    Ok(())
+}
+
+/// Whether a symbol path such as `impl Store > fn search` ends in a
+/// definition of one of `identifiers`.
+fn defines_any(symbol: Option<&str>, identifiers: &[&str]) -> bool {
+    let Some(name) = symbol
+        .and_then(|s| s.rsplit(" > ").next())
+        .and_then(|last| last.split_whitespace().last())
+    else {
+        return false;
+    };
+    identifiers.contains(&name)
+}
+
+fn meta_value(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT value FROM meta WHERE key = ?1")?;
+    match stmt.query_row(params![key], |row| row.get::<_, String>(0)) {
+        Ok(v) => Ok(Some(v)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn chunk_ids(conn: &Connection, filter: &str, args: impl rusqlite::Params) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(&format!("SELECT id FROM chunks WHERE {filter}"))?;
+    let ids = stmt
+        .query_map(args, |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(ids)
+}
+
 fn index_dir(project_root: &Path) -> PathBuf {
     let hash = hex::encode(&sha2::Sha256::digest(project_root.to_string_lossy().as_bytes())[..8]);
     dirs::data_dir()
@@ -368,42 +889,174 @@ fn index_dir(project_root: &Path) -> PathBuf {
         .join(hash)
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    if a.len() != b.len() {
-        return 0.0;
-    }
-    let mut dot = 0.0f32;
-    let mut norm_a = 0.0f32;
-    let mut norm_b = 0.0f32;
-    for i in 0..a.len() {
-        dot += a[i] * b[i];
-        norm_a += a[i] * a[i];
-        norm_b += b[i] * b[i];
-    }
-    let denom = norm_a.sqrt() * norm_b.sqrt();
-    if denom < 1e-10 {
-        0.0
-    } else {
-        dot / denom
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::embedder::{Embedder, TfIdfEmbedder};
 
-    #[test]
-    fn cosine_identical_vectors() {
-        let a = vec![1.0, 2.0, 3.0];
-        let sim = cosine_similarity(&a, &a);
-        assert!((sim - 1.0).abs() < 1e-5);
+    fn chunk(text: &str, symbol: &str) -> Chunk {
+        Chunk {
+            file: String::new(),
+            kind: crate::chunker::ChunkKind::Function,
+            text: text.to_string(),
+            start_line: 1,
+            end_line: text.lines().count(),
+            symbol: Some(symbol.to_string()),
+        }
+    }
+
+    async fn store_with(files: &[(&str, Vec<Chunk>)]) -> (tempfile::TempDir, Store) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open_dir(dir.path()).unwrap();
+        let embedder = TfIdfEmbedder::new();
+        for (path, chunks) in files {
+            let texts: Vec<String> = chunks.iter().map(|c| c.embedding_text()).collect();
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            let embeddings = embedder.embed(&texts).await.unwrap();
+            store.upsert_file(path, "hash", chunks.len()).unwrap();
+            store
+                .replace_chunks(
+                    path,
+                    chunks,
+                    &embeddings,
+                    embedder.model_id(),
+                    embedder.dimensions(),
+                )
+                .unwrap();
+        }
+        (dir, store)
+    }
+
+    #[tokio::test]
+    async fn exact_identifier_ranks_its_definition_first() {
+        let (_dir, store) = store_with(&[
+            (
+                "src/sandbox.rs",
+                vec![chunk(
+                    "pub fn wrap_command_sandboxed(cmd: &str) -> String {\n    format!(\"bwrap -- {cmd}\")\n}",
+                    "fn wrap_command_sandboxed",
+                )],
+            ),
+            (
+                "src/bash.rs",
+                vec![chunk(
+                    "fn run(cmd: &str) {\n    // wrap the command in the sandbox if sandboxed\n    let wrapped = wrap_command_sandboxed(cmd);\n    spawn(wrapped);\n}",
+                    "fn run",
+                )],
+            ),
+            (
+                "src/other.rs",
+                vec![chunk(
+                    "fn command_line() -> Vec<String> {\n    std::env::args().collect()\n}",
+                    "fn command_line",
+                )],
+            ),
+        ])
+        .await;
+
+        let embedder = TfIdfEmbedder::new();
+        let query = "wrap_command_sandboxed";
+        let query_vec = embedder.embed(&[query]).await.unwrap().remove(0);
+        for mode in [SearchMode::Keyword, SearchMode::Hybrid] {
+            let results = store.search(Some(&query_vec), query, 5, mode).unwrap();
+            assert_eq!(results[0].file, "src/sandbox.rs", "{mode:?}");
+            assert_eq!(results[1].file, "src/bash.rs", "{mode:?}");
+        }
+
+        let results = store
+            .search(None, "command line", 5, SearchMode::Keyword)
+            .unwrap();
+        assert_eq!(results[0].file, "src/other.rs");
     }
 
+    #[cfg(feature = "lang-rust")]
     #[test]
-    fn cosine_orthogonal_vectors() {
-        let a = vec![1.0, 0.0, 0.0];
-        let b = vec![0.0, 1.0, 0.0];
-        let sim = cosine_similarity(&a, &b);
-        assert!(sim.abs() < 1e-5);
+    fn symbol_graph_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open_dir(dir.path()).unwrap();
+        let files = [
+            (
+                "src/tools/context.rs",
+                "pub struct ToolContext {\n    pub cwd: String,\n}\n\nimpl ToolContext {\n    pub fn new() -> Self {\n        Self { cwd: String::new() }\n    }\n}\n",
+            ),
+            (
+                "src/tools/bash.rs",
+                "use super::context::ToolContext;\n\nfn run(ctx: &ToolContext) {\n    spawn(&ctx.cwd);\n}\n",
+            ),
+            (
+                "src/agent.rs",
+                "fn turn() {\n    let ctx = crate::tools::context::ToolContext::new();\n    run(&ctx);\n}\n",
+            ),
+            ("src/other.rs", "fn unrelated() {\n    spawn(\"x\");\n}\n"),
+        ];
+        for (path, src) in files {
+            store.upsert_file(path, "h", 0).unwrap();
+            store
+                .replace_symbols(path, &crate::symbols::extract(path, src))
+                .unwrap();
+        }
+
+        let defs = store.definitions("ToolContext").unwrap();
+        assert_eq!(defs.len(), 2);
+        assert!(defs.iter().all(|d| d.file == "src/tools/context.rs"));
+        let defs = store.definitions("ToolContext::new").unwrap();
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].path, "impl ToolContext > fn new");
+
+        let callers = store.callers_of("run").unwrap();
+        assert_eq!(callers.len(), 1);
+        assert_eq!(callers[0].file, "src/agent.rs");
+        assert_eq!(callers[0].caller.as_deref(), Some("fn turn"));
+
+        let callees: Vec<String> = store
+            .callees_of("turn")
+            .unwrap()
+            .into_iter()
+            .map(|c| c.callee)
+            .collect();
+        assert_eq!(callees, vec!["new", "run"]);
+
+        assert_eq!(
+            store.dependents_of("src/tools/context.rs").unwrap(),
+            vec!["src/agent.rs", "src/tools/bash.rs"]
+        );
+
+        store.remove_path("src/tools/bash.rs").unwrap();
+        assert!(store
+            .callers_of("spawn")
+            .unwrap()
+            .iter()
+            .all(|c| c.file != "src/tools/bash.rs"));
+        assert_eq!(
+            store.dependents_of("src/tools/context.rs").unwrap(),
+            vec!["src/agent.rs"]
+        );
+    }
+
+    #[tokio::test]
+    async fn postings_follow_chunk_replacement() {
+        let (_dir, store) = store_with(&[(
+            "a.rs",
+            vec![chunk(
+                "fn alpha_beta() {}\nfn x() {}\nfn y() {}",
+                "fn alpha_beta",
+            )],
+        )])
+        .await;
+        assert_eq!(store.keyword_search("alphaBeta", 10).unwrap().len(), 1);
+
+        store
+            .replace_chunks("a.rs", &[chunk("fn gamma() {}", "fn gamma")], &[], "", 0)
+            .unwrap();
+        assert!(store.keyword_search("alpha_beta", 10).unwrap().is_empty());
+        assert_eq!(store.keyword_search("gamma", 10).unwrap().len(), 1);
+
+        store.remove_path("a.rs").unwrap();
+        assert!(store.keyword_search("gamma", 10).unwrap().is_empty());
+        let conn = store.conn.lock().unwrap();
+        let postings: i64 = conn
+            .query_row("SELECT COUNT(*) FROM postings", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(postings, 0);
     }
 }
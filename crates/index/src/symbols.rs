@@ -0,0 +1,392 @@
+//! Cross-reference data extracted per file: definitions, references (with
+//! call edges) and imports. Stored next to the chunks so agents can ask
+//! "who calls this" or "what depends on this file" without grepping.
+//!
+//! Extraction needs a tree-sitter grammar; files without one contribute no
+//! symbols. Names are matched textually, so overloads and same-named methods
+//! on different types are not told apart.
+
+use std::path::Path;
+
+/// Bumped whenever extraction changes so unchanged files are re-scanned.
+pub const SYMBOLS_VERSION: &str = "1";
+
+/// A named definition (function, type, impl block, module, ...).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolDefinition {
+    /// Bare name, e.g. `search` for `impl Store > fn search`.
+    pub name: String,
+    /// Definition keyword, e.g. `fn`, `struct`, `class`.
+    pub kind: String,
+    /// Enclosing definitions including this one, e.g. `impl Store > fn search`.
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// A use of a name. Calls record the innermost enclosing function as the
+/// caller, which is what the call graph is built from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolReference {
+    pub name: String,
+    pub line: usize,
+    pub call: bool,
+    /// Bare name of the enclosing definition, `None` at file level.
+    pub caller: Option<String>,
+    /// Symbol path of the enclosing definition.
+    pub caller_path: Option<String>,
+}
+
+/// An import, `use`, `#include` or `require` target as written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolImport {
+    pub target: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FileSymbols {
+    pub definitions: Vec<SymbolDefinition>,
+    pub references: Vec<SymbolReference>,
+    pub imports: Vec<SymbolImport>,
+}
+
+/// Where a symbol is defined.
+#[derive(Debug, Clone)]
+pub struct SymbolLocation {
+    pub file: String,
+    pub name: String,
+    pub kind: String,
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl std::fmt::Display for SymbolLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}-{} [{}]",
+            self.file, self.start_line, self.end_line, self.path
+        )
+    }
+}
+
+/// One call edge: `caller` calls `callee` at `file:line`.
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    pub file: String,
+    pub line: usize,
+    /// Symbol path of the calling definition, `None` for file-level code.
+    pub caller: Option<String>,
+    pub callee: String,
+}
+
+impl std::fmt::Display for CallSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} {} -> {}",
+            self.file,
+            self.line,
+            self.caller.as_deref().unwrap_or("<file>"),
+            self.callee
+        )
+    }
+}
+
+/// Extract symbols from a file, or nothing when its language has no grammar.
+pub fn extract(rel_path: &str, content: &str) -> FileSymbols {
+    #[cfg(feature = "syntax")]
+    {
+        let lang = crate::chunker::detect_language(rel_path);
+        if let Some(symbols) = crate::syntax::symbols(rel_path, lang, content) {
+            return symbols;
+        }
+    }
+    #[cfg(not(feature = "syntax"))]
+    let _ = (rel_path, content);
+    FileSymbols::default()
+}
+
+/// The bare name a query refers to: `Store::search` and `store.search` both
+/// look up `search`, with the qualifier returned separately.
+pub fn split_qualified(name: &str) -> (Option<&str>, &str) {
+    let name = name.trim();
+    match name
+        .rfind("::")
+        .map(|i| (i, 2))
+        .or_else(|| name.rfind('.').map(|i| (i, 1)))
+    {
+        Some((i, len)) if i > 0 && i + len < name.len() => (Some(&name[..i]), &name[i + len..]),
+        _ => (None, name),
+    }
+}
+
+/// Heuristic: does the import `target`, written in `importer`, refer to the
+/// project file `file`? Resolves relative JS-style paths exactly; otherwise
+/// matches the file's module path (`tools/permission.rs` ~ `tools::permission`,
+/// `tools.permission`, `tools/permission.h`), or just its module name for
+/// imports between files in the same directory (`super::permission`,
+/// `from . import permission`).
+pub fn import_matches(importer: &str, target: &str, file: &str) -> bool {
+    let file_module = strip_module_suffix(file);
+    if target.starts_with("./") || target.starts_with("../") {
+        let base = Path::new(importer).parent().unwrap_or(Path::new(""));
+        let resolved = normalize(&base.join(target).to_string_lossy());
+        return strip_module_suffix(&resolved) == file_module;
+    }
+
+    let mut modules = file_module
+        .rsplit('/')
+        .filter(|s| !matches!(*s, "src" | "lib" | "app" | "pkg"));
+    let Some(module) = modules.next() else {
+        return false;
+    };
+    let parent = modules.next();
+    let segments: Vec<&str> = target
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .filter(|s| !s.is_empty())
+        .collect();
+    let Some(pos) = segments.iter().position(|s| *s == module) else {
+        return false;
+    };
+
+    let same_dir = Path::new(importer).parent() == Path::new(file).parent();
+    match parent {
+        _ if same_dir => true,
+        Some(parent) => pos > 0 && segments[pos - 1] == parent,
+        None => true,
+    }
+}
+
+/// `a/b/mod.rs`, `a/b/index.ts`, `a/b/__init__.py` and `a/b.rs` all name
+/// module `a/b`.
+fn strip_module_suffix(path: &str) -> String {
+    let path = path.trim_start_matches("./");
+    let stem = match path.rfind('.') {
+        Some(dot) if !path[dot..].contains('/') => &path[..dot],
+        _ => path,
+    };
+    for index in ["/mod", "/index", "/__init__"] {
+        if let Some(dir) = stem.strip_suffix(index) {
+            return dir.to_string();
+        }
+    }
+    stem.to_string()
+}
+
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    parts.join("/")
+}
+
+/// Identifiers in a prompt that look like code symbols: `snake_case`,
+/// `CamelCase`, `Type::method` or anything in backticks. Plain words are
+/// skipped so ordinary prose does not trigger lookups.
+pub fn mentioned_symbols(prompt: &str) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    let mut push = |name: &str| {
+        let name = name.trim_matches(|c: char| !(c.is_alphanumeric() || c == '_'));
+        if name.len() >= 3 && !found.iter().any(|f| f == name) {
+            found.push(name.to_string());
+        }
+    };
+
+    for (i, quoted) in prompt.split('`').enumerate() {
+        if i % 2 == 1 && !quoted.contains(char::is_whitespace) {
+            push(split_qualified(quoted.trim_end_matches("()")).1);
+        }
+    }
+    for word in prompt.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':')) {
+        let name = split_qualified(word).1;
+        let inner_upper = name.chars().skip(1).any(|c| c.is_uppercase())
+            && name.chars().any(|c| c.is_lowercase());
+        let snake = name.trim_matches('_').contains('_');
+        if inner_upper || snake {
+            push(name);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_resolve_to_files() {
+        let file = "crates/core/src/tools/permission.rs";
+        assert!(import_matches(
+            "crates/core/src/agent/mod.rs",
+            "crate::tools::permission::ToolPermission",
+            file
+        ));
+        assert!(import_matches(
+            "crates/core/src/tools/bash.rs",
+            "super::permission::ToolPermission",
+            file
+        ));
+        assert!(!import_matches(
+            "crates/core/src/agent/mod.rs",
+            "crate::hooks::permission",
+            file
+        ));
+
+        assert!(import_matches(
+            "web/src/app.ts",
+            "./util/format",
+            "web/src/util/format.ts"
+        ));
+        assert!(import_matches(
+            "web/src/a/b.ts",
+            "../util",
+            "web/src/util/index.ts"
+        ));
+        assert!(!import_matches(
+            "web/src/app.ts",
+            "./format",
+            "web/src/util/format.ts"
+        ));
+
+        assert!(import_matches(
+            "pkg/main.py",
+            "pkg.models.user",
+            "pkg/models/user.py"
+        ));
+        assert!(import_matches(
+            "src/main.c",
+            "util/strings.h",
+            "src/util/strings.h"
+        ));
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn rust_definitions_calls_and_imports() {
+        let src = r#"use crate::tools::permission::ToolPermission;
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn search(&self, q: &str) -> Vec<Hit> {
+        let hits = self.scan(q);
+        rank(hits)
+    }
+
+    fn scan(&self, q: &str) -> Vec<Hit> {
+        println!("{q}");
+        Vec::new()
+    }
+}
+"#;
+        let symbols = extract("src/store.rs", src);
+        let names: Vec<(&str, &str, &str)> = symbols
+            .definitions
+            .iter()
+            .map(|d| (d.name.as_str(), d.kind.as_str(), d.path.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Store", "struct", "struct Store"),
+                ("Store", "impl", "impl Store"),
+                ("search", "fn", "impl Store > fn search"),
+                ("scan", "fn", "impl Store > fn scan"),
+            ]
+        );
+        assert_eq!(symbols.definitions[2].start_line, 8);
+        assert_eq!(symbols.definitions[2].end_line, 11);
+
+        let calls: Vec<(&str, Option<&str>)> = symbols
+            .references
+            .iter()
+            .filter(|r| r.call)
+            .map(|r| (r.name.as_str(), r.caller.as_deref()))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                ("scan", Some("search")),
+                ("rank", Some("search")),
+                ("println", Some("scan")),
+                ("new", Some("scan")),
+            ]
+        );
+        assert!(symbols
+            .references
+            .iter()
+            .any(|r| !r.call && r.name == "Hit" && r.caller.as_deref() == Some("search")));
+        assert!(!symbols
+            .references
+            .iter()
+            .any(|r| !r.call && r.name == "search"));
+        assert_eq!(
+            symbols.imports,
+            vec![SymbolImport {
+                target: "crate::tools::permission::ToolPermission".into(),
+                line: 1
+            }]
+        );
+    }
+
+    #[cfg(feature = "lang-python")]
+    #[test]
+    fn python_imports_and_method_calls() {
+        let src = "from pkg.models import User\nimport os\n\nclass Repo:\n    def load(self, uid):\n        row = self.db.fetch(uid)\n        return User(row)\n";
+        let symbols = extract("pkg/repo.py", src);
+        let targets: Vec<&str> = symbols.imports.iter().map(|i| i.target.as_str()).collect();
+        assert_eq!(targets, vec!["pkg.models", "os"]);
+        let calls: Vec<(&str, Option<&str>)> = symbols
+            .references
+            .iter()
+            .filter(|r| r.call)
+            .map(|r| (r.name.as_str(), r.caller_path.as_deref()))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                ("fetch", Some("class Repo > def load")),
+                ("User", Some("class Repo > def load")),
+            ]
+        );
+    }
+
+    #[cfg(feature = "lang-typescript")]
+    #[test]
+    fn typescript_relative_imports() {
+        let src = "import { format } from './util/format';\nexport function show(x: number) {\n  return format(x);\n}\n";
+        let symbols = extract("web/src/app.ts", src);
+        assert_eq!(symbols.imports[0].target, "./util/format");
+        assert!(symbols
+            .references
+            .iter()
+            .any(|r| r.call && r.name == "format" && r.caller.as_deref() == Some("show")));
+    }
+
+    #[test]
+    fn qualified_names_split() {
+        assert_eq!(split_qualified("Store::search"), (Some("Store"), "search"));
+        assert_eq!(split_qualified("store.search"), (Some("store"), "search"));
+        assert_eq!(split_qualified("search"), (None, "search"));
+    }
+
+    #[test]
+    fn prompt_mentions() {
+        assert_eq!(
+            mentioned_symbols("What breaks if I change ToolContext or `run_turn`? Also see Store::open_dir and the index."),
+            vec!["run_turn", "ToolContext", "open_dir"]
+        );
+    }
+}
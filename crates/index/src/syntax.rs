@@ -0,0 +1,744 @@
+//! Syntax-aware chunking backed by tree-sitter.
+//!
+//! Chunks are aligned to definitions (functions, impl blocks, classes,
+//! methods, ...) and carry the path of enclosing definitions, e.g.
+//! `impl Store > fn search`. Each grammar sits behind its own cargo feature;
+//! languages without a compiled grammar return `None` so the caller can fall
+//! back to the line-based chunker. The same grammars feed symbol extraction
+//! for the cross-reference tables (see [`crate::symbols`]).
+
+use tree_sitter::{Node, Parser};
+
+use crate::chunker::{window_chunks, Chunk, ChunkKind, Language, MAX_CHUNK_LINES};
+use crate::symbols::{FileSymbols, SymbolDefinition, SymbolImport, SymbolReference};
+
+const PATH_SEPARATOR: &str = " > ";
+
+struct Definition {
+    node: &'static str,
+    /// Label prefix. Empty means "use the node's first keyword token", which
+    /// covers grammars that share one node kind for class/struct/enum/etc.
+    keyword: &'static str,
+    kind: ChunkKind,
+}
+
+const fn def(node: &'static str, keyword: &'static str, kind: ChunkKind) -> Definition {
+    Definition {
+        node,
+        keyword,
+        kind,
+    }
+}
+
+/// Nodes that wrap a definition without being one themselves. The chunk spans
+/// the wrapper so decorators, `export` and template headers stay attached.
+const WRAPPERS: &[&str] = &[
+    "export_statement",
+    "decorated_definition",
+    "template_declaration",
+    "expression_statement",
+    "ambient_declaration",
+];
+
+/// Definitions whose grammar has no `name` field; the keyword alone labels them.
+const NAMELESS: &[&str] = &[
+    "init_declaration",
+    "deinit_declaration",
+    "secondary_constructor",
+];
+
+struct Grammar {
+    language: tree_sitter::Language,
+    definitions: &'static [Definition],
+}
+
+#[cfg(feature = "lang-rust")]
+const RUST: &[Definition] = &[
+    def("function_item", "fn", ChunkKind::Function),
+    def("function_signature_item", "fn", ChunkKind::Function),
+    def("impl_item", "impl", ChunkKind::Class),
+    def("struct_item", "struct", ChunkKind::Class),
+    def("enum_item", "enum", ChunkKind::Class),
+    def("union_item", "union", ChunkKind::Class),
+    def("trait_item", "trait", ChunkKind::Class),
+    def("mod_item", "mod", ChunkKind::Module),
+    def("macro_definition", "macro_rules!", ChunkKind::Function),
+];
+
+#[cfg(feature = "lang-python")]
+const PYTHON: &[Definition] = &[
+    def("function_definition", "def", ChunkKind::Function),
+    def("class_definition", "class", ChunkKind::Class),
+];
+
+#[cfg(any(feature = "lang-javascript", feature = "lang-typescript"))]
+const JAVASCRIPT: &[Definition] = &[
+    def("function_declaration", "function", ChunkKind::Function),
+    def(
+        "generator_function_declaration",
+        "function",
+        ChunkKind::Function,
+    ),
+    def("lexical_declaration", "", ChunkKind::Function),
+    def("variable_declaration", "var", ChunkKind::Function),
+    def("class_declaration", "class", ChunkKind::Class),
+    def("method_definition", "fn", ChunkKind::Function),
+    def("abstract_class_declaration", "class", ChunkKind::Class),
+    def("interface_declaration", "interface", ChunkKind::Class),
+    def("enum_declaration", "enum", ChunkKind::Class),
+    def("type_alias_declaration", "type", ChunkKind::Class),
+    def("internal_module", "namespace", ChunkKind::Module),
+    def("module", "module", ChunkKind::Module),
+    def("function_signature", "function", ChunkKind::Function),
+    def("method_signature", "fn", ChunkKind::Function),
+    def("abstract_method_signature", "fn", ChunkKind::Function),
+];
+
+#[cfg(feature = "lang-go")]
+const GO: &[Definition] = &[
+    def("function_declaration", "func", ChunkKind::Function),
+    def("method_declaration", "func", ChunkKind::Function),
+    def("type_declaration", "type", ChunkKind::Class),
+];
+
+#[cfg(feature = "lang-java")]
+const JAVA: &[Definition] = &[
+    def("class_declaration", "class", ChunkKind::Class),
+    def("interface_declaration", "interface", ChunkKind::Class),
+    def("enum_declaration", "enum", ChunkKind::Class),
+    def("record_declaration", "record", ChunkKind::Class),
+    def(
+        "annotation_type_declaration",
+        "@interface",
+        ChunkKind::Class,
+    ),
+    def("method_declaration", "fn", ChunkKind::Function),
+    def(
+        "constructor_declaration",
+        "constructor",
+        ChunkKind::Function,
+    ),
+];
+
+#[cfg(any(feature = "lang-c", feature = "lang-cpp"))]
+const C: &[Definition] = &[
+    def("function_definition", "fn", ChunkKind::Function),
+    def("struct_specifier", "struct", ChunkKind::Class),
+    def("union_specifier", "union", ChunkKind::Class),
+    def("enum_specifier", "enum", ChunkKind::Class),
+    def("type_definition", "typedef", ChunkKind::Class),
+    def("class_specifier", "class", ChunkKind::Class),
+    def("namespace_definition", "namespace", ChunkKind::Module),
+];
+
+#[cfg(feature = "lang-ruby")]
+const RUBY: &[Definition] = &[
+    def("method", "def", ChunkKind::Function),
+    def("singleton_method", "def self.", ChunkKind::Function),
+    def("class", "class", ChunkKind::Class),
+    def("module", "module", ChunkKind::Module),
+];
+
+#[cfg(feature = "lang-kotlin")]
+const KOTLIN: &[Definition] = &[
+    def("class_declaration", "", ChunkKind::Class),
+    def("object_declaration", "object", ChunkKind::Class),
+    def("function_declaration", "fun", ChunkKind::Function),
+    def("secondary_constructor", "constructor", ChunkKind::Function),
+];
+
+#[cfg(feature = "lang-c-sharp")]
+const CSHARP: &[Definition] = &[
+    def("namespace_declaration", "namespace", ChunkKind::Module),
+    def("class_declaration", "class", ChunkKind::Class),
+    def("struct_declaration", "struct", ChunkKind::Class),
+    def("interface_declaration", "interface", ChunkKind::Class),
+    def("enum_declaration", "enum", ChunkKind::Class),
+    def("record_declaration", "record", ChunkKind::Class),
+    def("method_declaration", "fn", ChunkKind::Function),
+    def(
+        "constructor_declaration",
+        "constructor",
+        ChunkKind::Function,
+    ),
+    def("property_declaration", "property", ChunkKind::Function),
+];
+
+#[cfg(feature = "lang-swift")]
+const SWIFT: &[Definition] = &[
+    def("class_declaration", "", ChunkKind::Class),
+    def("protocol_declaration", "protocol", ChunkKind::Class),
+    def("function_declaration", "func", ChunkKind::Function),
+    def("protocol_function_declaration", "func", ChunkKind::Function),
+    def("init_declaration", "init", ChunkKind::Function),
+    def("deinit_declaration", "deinit", ChunkKind::Function),
+];
+
+#[cfg(feature = "lang-php")]
+const PHP: &[Definition] = &[
+    def("function_definition", "function", ChunkKind::Function),
+    def("method_declaration", "function", ChunkKind::Function),
+    def("class_declaration", "class", ChunkKind::Class),
+    def("interface_declaration", "interface", ChunkKind::Class),
+    def("trait_declaration", "trait", ChunkKind::Class),
+    def("enum_declaration", "enum", ChunkKind::Class),
+    def("namespace_definition", "namespace", ChunkKind::Module),
+];
+
+#[cfg_attr(not(feature = "lang-typescript"), allow(unused_variables))]
+fn grammar(lang: Language, rel_path: &str) -> Option<Grammar> {
+    let (language, definitions): (tree_sitter::Language, &'static [Definition]) = match lang {
+        #[cfg(feature = "lang-rust")]
+        Language::Rust => (tree_sitter_rust::LANGUAGE.into(), RUST),
+        #[cfg(feature = "lang-python")]
+        Language::Python => (tree_sitter_python::LANGUAGE.into(), PYTHON),
+        #[cfg(feature = "lang-javascript")]
+        Language::JavaScript => (tree_sitter_javascript::LANGUAGE.into(), JAVASCRIPT),
+        #[cfg(feature = "lang-typescript")]
+        Language::TypeScript => {
+            let language = if rel_path.ends_with(".tsx") {
+                tree_sitter_typescript::LANGUAGE_TSX
+            } else {
+                tree_sitter_typescript::LANGUAGE_TYPESCRIPT
+            };
+            (language.into(), JAVASCRIPT)
+        }
+        #[cfg(feature = "lang-go")]
+        Language::Go => (tree_sitter_go::LANGUAGE.into(), GO),
+        #[cfg(feature = "lang-java")]
+        Language::Java => (tree_sitter_java::LANGUAGE.into(), JAVA),
+        #[cfg(feature = "lang-c")]
+        Language::C => (tree_sitter_c::LANGUAGE.into(), C),
+        #[cfg(feature = "lang-cpp")]
+        Language::Cpp => (tree_sitter_cpp::LANGUAGE.into(), C),
+        #[cfg(feature = "lang-ruby")]
+        Language::Ruby => (tree_sitter_ruby::LANGUAGE.into(), RUBY),
+        #[cfg(feature = "lang-kotlin")]
+        Language::Kotlin => (tree_sitter_kotlin_ng::LANGUAGE.into(), KOTLIN),
+        #[cfg(feature = "lang-c-sharp")]
+        Language::CSharp => (tree_sitter_c_sharp::LANGUAGE.into(), CSHARP),
+        #[cfg(feature = "lang-swift")]
+        Language::Swift => (tree_sitter_swift::LANGUAGE.into(), SWIFT),
+        #[cfg(feature = "lang-php")]
+        Language::Php => (tree_sitter_php::LANGUAGE_PHP.into(), PHP),
+        _ => return None,
+    };
+    Some(Grammar {
+        language,
+        definitions,
+    })
+}
+
+/// Chunk `content` along its syntax tree. Returns `None` when no grammar is
+/// compiled in for `lang` or the parser gives up.
+pub(crate) fn chunk(rel_path: &str, lang: Language, content: &str) -> Option<Vec<Chunk>> {
+    let grammar = grammar(lang, rel_path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&grammar.language).ok()?;
+    let tree = parser.parse(content, None)?;
+
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.is_empty() {
+        return Some(vec![]);
+    }
+
+    let mut chunker = TreeChunker {
+        file: rel_path,
+        src: content,
+        lines: &lines,
+        definitions: grammar.definitions,
+        chunks: Vec::new(),
+    };
+    let root = tree.root_node();
+    chunker.chunk_children(root, 0, lines.len() - 1, &[]);
+    Some(chunker.chunks)
+}
+
+struct TreeChunker<'a> {
+    file: &'a str,
+    src: &'a str,
+    lines: &'a [&'a str],
+    definitions: &'static [Definition],
+    chunks: Vec<Chunk>,
+}
+
+impl<'a> TreeChunker<'a> {
+    /// Walk the named children of `parent`, emitting one chunk per definition
+    /// and grouping everything in between (imports, fields, small helpers)
+    /// into block chunks labelled with the enclosing path. Covers the rows
+    /// `first..=last` exactly once, apart from window overlap.
+    fn chunk_children(&mut self, parent: Node, first: usize, last: usize, path: &[String]) {
+        let mut cursor = first;
+        let mut walker = parent.walk();
+        let children: Vec<Node> = parent.named_children(&mut walker).collect();
+
+        for (i, child) in children.iter().enumerate() {
+            let Some((inner, definition, label)) = self.definition(*child) else {
+                continue;
+            };
+
+            let mut start = child.start_position().row.max(cursor);
+            let end = last_row(*child).min(last);
+            if end < start {
+                continue;
+            }
+            // Attach doc comments, attributes and decorators directly above.
+            for prev in children[..i].iter().rev() {
+                if !is_leading_trivia(prev.kind()) || last_row(*prev) + 1 < start {
+                    break;
+                }
+                if prev.start_position().row < cursor {
+                    break;
+                }
+                start = prev.start_position().row;
+            }
+
+            if end == child.start_position().row {
+                // One-liners (fields, forward declarations, `fn x() {}`) are
+                // not worth a chunk of their own; they stay in the block.
+                continue;
+            }
+            let size = end - start + 1;
+
+            self.flush_block(cursor, start, path);
+            cursor = end + 1;
+
+            let mut child_path = path.to_vec();
+            child_path.push(label);
+
+            if size <= MAX_CHUNK_LINES {
+                self.push(start, end, definition.kind.clone(), &child_path);
+            } else if matches!(definition.kind, ChunkKind::Class | ChunkKind::Module) {
+                match body_of(inner) {
+                    Some(body) => self.chunk_children(body, start, end, &child_path),
+                    None => self.push_windows(start, end, definition.kind.clone(), &child_path),
+                }
+            } else {
+                self.push_windows(start, end, definition.kind.clone(), &child_path);
+            }
+        }
+
+        self.flush_block(cursor, last + 1, path);
+    }
+
+    /// Resolve `node` (or the definition it wraps) to a definition and label.
+    fn definition<'t>(&self, node: Node<'t>) -> Option<(Node<'t>, &'static Definition, String)> {
+        if let Some(found) = self.lookup(node) {
+            return Some(found);
+        }
+        if !WRAPPERS.contains(&node.kind()) {
+            return None;
+        }
+        let mut walker = node.walk();
+        let inner = node
+            .named_children(&mut walker)
+            .find_map(|child| self.lookup(child));
+        inner
+    }
+
+    fn lookup<'t>(&self, node: Node<'t>) -> Option<(Node<'t>, &'static Definition, String)> {
+        let definition = self.definitions.iter().find(|d| d.node == node.kind())?;
+        let keyword = if definition.keyword.is_empty() {
+            first_keyword(node)?
+        } else {
+            definition.keyword
+        };
+        let label = match definition_name(node, self.src) {
+            Some(name) if keyword.ends_with('.') => format!("{keyword}{name}"),
+            Some(name) => format!("{keyword} {name}"),
+            None if NAMELESS.contains(&node.kind()) => keyword.to_string(),
+            None => return None,
+        };
+        Some((node, definition, label))
+    }
+
+    /// Emit rows `from..to` (exclusive) as block chunks, skipping stretches
+    /// that hold nothing but blank lines and closing braces.
+    fn flush_block(&mut self, from: usize, to: usize, path: &[String]) {
+        if from >= to {
+            return;
+        }
+        let has_content = self.lines[from..to]
+            .iter()
+            .any(|l| l.chars().any(|c| c.is_alphanumeric()));
+        if has_content {
+            self.push_windows(from, to - 1, ChunkKind::Block, path);
+        }
+    }
+
+    fn push(&mut self, start: usize, end: usize, kind: ChunkKind, path: &[String]) {
+        self.chunks.push(Chunk {
+            file: self.file.to_string(),
+            start_line: start + 1,
+            end_line: end + 1,
+            text: self.lines[start..=end].join("\n"),
+            kind,
+            symbol: symbol_path(path),
+        });
+    }
+
+    fn push_windows(&mut self, start: usize, end: usize, kind: ChunkKind, path: &[String]) {
+        if end - start < MAX_CHUNK_LINES {
+            self.push(start, end, kind, path);
+            return;
+        }
+        self.chunks.extend(window_chunks(
+            self.file,
+            self.lines,
+            start,
+            end + 1,
+            kind,
+            symbol_path(path),
+        ));
+    }
+}
+
+fn symbol_path(path: &[String]) -> Option<String> {
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.join(PATH_SEPARATOR))
+    }
+}
+
+/// Last row that actually holds text of `node`. Nodes that swallow their
+/// trailing newline end at column 0 of the following row.
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+fn is_leading_trivia(kind: &str) -> bool {
+    kind.contains("comment")
+        || matches!(
+            kind,
+            "attribute_item" | "attribute_list" | "attribute" | "decorator" | "annotation"
+        )
+}
+
+fn text<'s>(node: Node, src: &'s str) -> &'s str {
+    &src[node.byte_range()]
+}
+
+/// First anonymous keyword token of `node`, e.g. `struct` in a Swift
+/// `class_declaration` or `const` in a JS `lexical_declaration`.
+fn first_keyword(node: Node) -> Option<&'static str> {
+    let mut walker = node.walk();
+    let keyword = node
+        .children(&mut walker)
+        .find(|c| !c.is_named() && c.kind().chars().all(|ch| ch.is_ascii_alphabetic()))
+        .map(|c| c.kind());
+    keyword
+}
+
+fn definition_name(node: Node, src: &str) -> Option<String> {
+    match node.kind() {
+        "impl_item" => {
+            let ty = text(node.child_by_field_name("type")?, src);
+            return Some(match node.child_by_field_name("trait") {
+                Some(tr) => format!("{} for {ty}", text(tr, src)),
+                None => ty.to_string(),
+            });
+        }
+        "method_declaration" if node.child_by_field_name("receiver").is_some() => {
+            // Go: func (s *Store) Search
+            let receiver = text(node.child_by_field_name("receiver")?, src);
+            let name = text(node.child_by_field_name("name")?, src);
+            return Some(format!("{receiver} {name}"));
+        }
+        "type_declaration" => {
+            let mut walker = node.walk();
+            let spec = node
+                .named_children(&mut walker)
+                .find(|c| c.kind() == "type_spec" || c.kind() == "type_alias")?;
+            return Some(text(spec.child_by_field_name("name")?, src).to_string());
+        }
+        "lexical_declaration" | "variable_declaration" => {
+            // Only `const f = () => ...` style bindings count as definitions.
+            let mut walker = node.walk();
+            let declarator = node
+                .named_children(&mut walker)
+                .find(|c| c.kind() == "variable_declarator")?;
+            let value = declarator.child_by_field_name("value")?;
+            if !matches!(
+                value.kind(),
+                "arrow_function" | "function_expression" | "function" | "class"
+            ) {
+                return None;
+            }
+            return Some(text(declarator.child_by_field_name("name")?, src).to_string());
+        }
+        _ => {}
+    }
+
+    if let Some(name) = node.child_by_field_name("name") {
+        return Some(text(name, src).to_string());
+    }
+
+    // C/C++ functions and typedefs name themselves through nested declarators.
+    let mut current = node.child_by_field_name("declarator")?;
+    loop {
+        match current.child_by_field_name("declarator") {
+            Some(next) => current = next,
+            None => return Some(text(current, src).to_string()),
+        }
+    }
+}
+
+/// The node holding a container's members, e.g. `declaration_list` for a
+/// Rust `impl` or `class_body` for a Kotlin class.
+fn body_of(node: Node) -> Option<Node> {
+    if let Some(body) = node.child_by_field_name("body") {
+        return Some(body);
+    }
+    let mut walker = node.walk();
+    let body = node.named_children(&mut walker).find(|c| {
+        let kind = c.kind();
+        kind.ends_with("body") || kind.ends_with("declaration_list")
+    });
+    body
+}
+
+// ---------------------------------------------------------------------------
+// Symbol extraction
+// ---------------------------------------------------------------------------
+
+/// Leaf nodes that name something across the supported grammars.
+const IDENTIFIERS: &[&str] = &[
+    "identifier",
+    "type_identifier",
+    "field_identifier",
+    "property_identifier",
+    "shorthand_property_identifier",
+    "namespace_identifier",
+    "simple_identifier",
+    "constant",
+    "name",
+];
+
+const CALLS: &[&str] = &[
+    "call_expression",
+    "call",
+    "method_invocation",
+    "invocation_expression",
+    "function_call_expression",
+    "member_call_expression",
+    "scoped_call_expression",
+    "macro_invocation",
+    "object_creation_expression",
+];
+
+const IMPORTS: &[&str] = &[
+    "use_declaration",
+    "import_statement",
+    "import_from_statement",
+    "import_declaration",
+    "import_header",
+    "preproc_include",
+    "using_directive",
+    "namespace_use_declaration",
+];
+
+const STRINGS: &[&str] = &[
+    "string",
+    "string_literal",
+    "interpreted_string_literal",
+    "raw_string_literal",
+    "system_lib_string",
+];
+
+/// Names too generic to be worth a reference row.
+const IGNORED_NAMES: &[&str] = &["self", "Self", "this", "super", "crate", "_"];
+
+struct Scope {
+    end_byte: usize,
+    name: String,
+    path: String,
+    function: bool,
+}
+
+/// Definitions, references, call edges and imports of one file. Returns
+/// `None` when no grammar is compiled in for `lang`.
+pub(crate) fn symbols(rel_path: &str, lang: Language, content: &str) -> Option<FileSymbols> {
+    let grammar = grammar(lang, rel_path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&grammar.language).ok()?;
+    let tree = parser.parse(content, None)?;
+
+    let lookup = TreeChunker {
+        file: rel_path,
+        src: content,
+        lines: &[],
+        definitions: grammar.definitions,
+        chunks: Vec::new(),
+    };
+    let mut out = FileSymbols::default();
+    let mut seen_refs = std::collections::HashSet::new();
+    // Name nodes of definitions and callees, which are not plain references.
+    let mut skip = std::collections::HashSet::new();
+    let mut scopes: Vec<Scope> = Vec::new();
+    let mut stack = vec![tree.root_node()];
+
+    while let Some(node) = stack.pop() {
+        while scopes
+            .last()
+            .is_some_and(|s| node.start_byte() >= s.end_byte)
+        {
+            scopes.pop();
+        }
+        let line = node.start_position().row + 1;
+        let caller = scopes.iter().rev().find(|s| s.function).or(scopes.last());
+
+        if let Some((_, definition, label)) = lookup.lookup(node) {
+            if let Some(name) = simple_name(node, content) {
+                let path = match scopes.last() {
+                    Some(parent) => format!("{}{PATH_SEPARATOR}{label}", parent.path),
+                    None => label.clone(),
+                };
+                if let Some(name_node) = node.child_by_field_name("name") {
+                    skip.insert(name_node.id());
+                }
+                let kind = label
+                    .strip_suffix(name.as_str())
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty() && !k.contains(' '))
+                    .unwrap_or_else(|| label.split(' ').next().unwrap_or_default());
+                out.definitions.push(SymbolDefinition {
+                    name: name.clone(),
+                    kind: kind.to_string(),
+                    path: path.clone(),
+                    start_line: line,
+                    end_line: last_row(node) + 1,
+                });
+                scopes.push(Scope {
+                    end_byte: node.end_byte(),
+                    name,
+                    path,
+                    function: matches!(definition.kind, ChunkKind::Function),
+                });
+            }
+        } else if IMPORTS.contains(&node.kind()) {
+            if let Some(target) = import_target(node, content) {
+                out.imports.push(SymbolImport { target, line });
+            }
+        } else if CALLS.contains(&node.kind()) {
+            if let Some(callee) = callee(node) {
+                skip.insert(callee.id());
+                let name = text(callee, content).to_string();
+                if matches!(name.as_str(), "require" | "require_relative") {
+                    if let Some(target) = import_target(node, content) {
+                        out.imports.push(SymbolImport { target, line });
+                    }
+                }
+                if seen_refs.insert((name.clone(), line, true)) {
+                    out.references.push(SymbolReference {
+                        name,
+                        line,
+                        call: true,
+                        caller: caller.map(|s| s.name.clone()),
+                        caller_path: caller.map(|s| s.path.clone()),
+                    });
+                }
+            }
+        } else if IDENTIFIERS.contains(&node.kind()) && !skip.contains(&node.id()) {
+            let name = text(node, content);
+            if name.len() >= 2
+                && !IGNORED_NAMES.contains(&name)
+                && seen_refs.insert((name.to_string(), line, false))
+            {
+                out.references.push(SymbolReference {
+                    name: name.to_string(),
+                    line,
+                    call: false,
+                    caller: caller.map(|s| s.name.clone()),
+                    caller_path: caller.map(|s| s.path.clone()),
+                });
+            }
+        }
+
+        let mut walker = node.walk();
+        let children: Vec<Node> = node.named_children(&mut walker).collect();
+        stack.extend(children.into_iter().rev());
+    }
+
+    Some(out)
+}
+
+/// The bare name a definition is looked up by: `search` for
+/// `func (s *Store) search`, `Store` for `impl Display for Store<T>`,
+/// `bar` for C++ `Foo::bar`.
+fn simple_name(node: Node, src: &str) -> Option<String> {
+    let full = match node.kind() {
+        "impl_item" => text(node.child_by_field_name("type")?, src).to_string(),
+        "method_declaration" if node.child_by_field_name("receiver").is_some() => {
+            text(node.child_by_field_name("name")?, src).to_string()
+        }
+        _ => definition_name(node, src)?,
+    };
+    let base = full.split(['<', '(', '[']).next().unwrap_or_default();
+    let name = base
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .find(|s| !s.is_empty())?;
+    Some(name.to_string())
+}
+
+/// The identifier naming what a call invokes: `search` in
+/// `self.store.search(q)`, `open` in `Store::open()`, `println` in `println!`.
+fn callee(call: Node) -> Option<Node> {
+    let mut target = ["function", "method", "name", "macro", "type", "constructor"]
+        .iter()
+        .find_map(|field| call.child_by_field_name(field))
+        .or_else(|| call.named_child(0))?;
+    loop {
+        if IDENTIFIERS.contains(&target.kind()) {
+            return Some(target);
+        }
+        target = ["name", "field", "property", "function", "method", "type"]
+            .iter()
+            .find_map(|field| target.child_by_field_name(field))
+            .or_else(|| {
+                let count = target.named_child_count();
+                target.named_child(count.checked_sub(1)?)
+            })?;
+    }
+}
+
+/// What an import statement points at, as written: the module path or file
+/// string, without keywords, quotes or trailing semicolons.
+fn import_target(node: Node, src: &str) -> Option<String> {
+    if let Some(module) = node.child_by_field_name("module_name") {
+        return Some(text(module, src).to_string());
+    }
+    let mut stack = vec![node];
+    while let Some(n) = stack.pop() {
+        if STRINGS.contains(&n.kind()) {
+            let s = text(n, src).trim_matches(|c| matches!(c, '"' | '\'' | '`' | '<' | '>'));
+            return (!s.is_empty()).then(|| s.to_string());
+        }
+        let mut walker = n.walk();
+        let children: Vec<Node> = n.named_children(&mut walker).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    if CALLS.contains(&node.kind()) {
+        return None;
+    }
+    let raw = text(node, src).trim().trim_end_matches(';');
+    let target = [
+        "#include",
+        "import",
+        "using",
+        "use",
+        "pub use",
+        "pub(crate) use",
+    ]
+    .iter()
+    .filter_map(|kw| raw.strip_prefix(kw))
+    .min_by_key(|rest| rest.len())
+    .unwrap_or(raw)
+    .trim();
+    (!target.is_empty()).then(|| target.to_string())
+}
@@ -1,10 +1,19 @@
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
 use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
 
 const MAX_FILES: usize = 50_000;
 const MAX_FILE_SIZE: u64 = 512 * 1024;
+/// Quiet period after the last event before a batch is handed out.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Upper bound on how long a steady stream of events can hold a batch back.
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone)]
 pub struct FileEntry {
@@ -25,6 +34,155 @@ pub fn hash_content(data: &[u8]) -> String {
     hex::encode(Sha256::digest(data))
 }
 
+/// Entry for a single project-relative path, or `None` if the file is gone
+/// or no longer something we index.
+pub fn file_entry_for(root: &Path, rel_path: &str) -> Option<FileEntry> {
+    let path = root.join(rel_path);
+    if !path.is_file() {
+        return None;
+    }
+    file_entry(path, rel_path.to_string())
+}
+
+/// Watches the project tree (inotify on Linux) and yields debounced batches
+/// of changed paths. Directories are watched individually so skipped and
+/// ignored trees like `target/` or `node_modules/` never cost a watch.
+pub struct ProjectWatcher {
+    root: PathBuf,
+    extra_exclude: Vec<String>,
+    gitignore: Vec<String>,
+    watcher: notify::RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<notify::Event>,
+}
+
+impl ProjectWatcher {
+    pub fn new(root: &Path, extra_exclude: &[String]) -> Result<Self> {
+        let (tx, events) = mpsc::unbounded_channel();
+        let watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    let _ = tx.send(event);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::debug!("Index watcher error: {e}"),
+            })?;
+
+        let mut this = Self {
+            root: root.to_path_buf(),
+            extra_exclude: extra_exclude.to_vec(),
+            gitignore: load_gitignore(root),
+            watcher,
+            events,
+        };
+        this.watch_tree(root);
+        Ok(this)
+    }
+
+    /// Wait for the next batch of changed project-relative paths. A path in
+    /// the batch may have been modified, created or deleted; files under a
+    /// newly created directory are listed individually. Returns `None` once
+    /// the underlying watcher has shut down.
+    pub async fn next_batch(&mut self) -> Option<Vec<String>> {
+        loop {
+            let mut events = vec![self.events.recv().await?];
+            let started = Instant::now();
+            while started.elapsed() < MAX_BATCH_DELAY {
+                match tokio::time::timeout(DEBOUNCE, self.events.recv()).await {
+                    Ok(Some(event)) => events.push(event),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+
+            let batch = self.resolve(events);
+            if !batch.is_empty() {
+                return Some(batch);
+            }
+        }
+    }
+
+    fn resolve(&mut self, events: Vec<notify::Event>) -> Vec<String> {
+        let gitignore_path = self.root.join(".gitignore");
+        let mut changed = BTreeSet::new();
+
+        for event in events {
+            let appeared = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+            );
+            for path in event.paths {
+                if path == gitignore_path {
+                    self.gitignore = load_gitignore(&self.root);
+                    continue;
+                }
+                let Some(rel) = self.tracked_rel(&path) else {
+                    continue;
+                };
+                if path.is_dir() {
+                    if appeared {
+                        // Created or moved in: start watching it and pick up
+                        // whatever landed there before the watch existed.
+                        self.watch_tree(&path);
+                        let mut entries = Vec::new();
+                        walk_dir(
+                            &self.root,
+                            &path,
+                            &self.gitignore,
+                            &self.extra_exclude,
+                            &mut entries,
+                        );
+                        changed.extend(entries.into_iter().map(|e| e.rel_path));
+                    }
+                } else {
+                    changed.insert(rel);
+                }
+            }
+        }
+
+        changed.into_iter().collect()
+    }
+
+    /// Project-relative path for `path` if `walk_project` would visit it.
+    fn tracked_rel(&self, path: &Path) -> Option<String> {
+        let rel_path = path.strip_prefix(&self.root).ok()?;
+        let mut rel = String::new();
+        for component in rel_path.components() {
+            let name = component.as_os_str().to_string_lossy();
+            if should_skip(&name) {
+                return None;
+            }
+            if !rel.is_empty() {
+                rel.push('/');
+            }
+            rel.push_str(&name);
+            if is_ignored(&rel, &self.gitignore, &self.extra_exclude) {
+                return None;
+            }
+        }
+        if rel.is_empty() {
+            None
+        } else {
+            Some(rel)
+        }
+    }
+
+    fn watch_tree(&mut self, dir: &Path) {
+        if let Err(e) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+            tracing::debug!("Cannot watch {}: {e}", dir.display());
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            let path = entry.path();
+            if is_dir && self.tracked_rel(&path).is_some() {
+                self.watch_tree(&path);
+            }
+        }
+    }
+}
+
 fn walk_dir(
     root: &Path,
     dir: &Path,
@@ -65,33 +223,35 @@ fn walk_dir(
         if path.is_dir() {
             walk_dir(root, &path, gitignore, extra_exclude, out);
         } else if path.is_file() {
-            if !is_indexable_ext(&path) {
-                continue;
-            }
-            let meta = match std::fs::metadata(&path) {
-                Ok(m) => m,
-                Err(_) => continue,
-            };
-            if meta.len() > MAX_FILE_SIZE {
-                continue;
+            if let Some(entry) = file_entry(path, rel) {
+                out.push(entry);
             }
+        }
+    }
+}
 
-            let content = match std::fs::read(&path) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
-            if content.len() > 512 && content[..512].contains(&0) {
-                continue;
-            }
+/// Read and hash a file if it is something we index: a known extension,
+/// under the size limit and not binary.
+fn file_entry(path: PathBuf, rel: String) -> Option<FileEntry> {
+    if !is_indexable_ext(&path) {
+        return None;
+    }
+    let meta = std::fs::metadata(&path).ok()?;
+    if meta.len() > MAX_FILE_SIZE {
+        return None;
+    }
 
-            let hash = hash_content(&content);
-            out.push(FileEntry {
-                rel_path: rel,
-                abs_path: path,
-                hash,
-            });
-        }
+    let content = std::fs::read(&path).ok()?;
+    if content.len() > 512 && content[..512].contains(&0) {
+        return None;
     }
+
+    let hash = hash_content(&content);
+    Some(FileEntry {
+        rel_path: rel,
+        abs_path: path,
+        hash,
+    })
 }
 
 fn should_skip(name: &str) -> bool {
@@ -151,6 +311,12 @@ fn is_indexable_ext(path: &Path) -> bool {
             | "scala"
             | "clj"
             | "lua"
+            | "php"
+            | "kts"
+            | "cc"
+            | "cxx"
+            | "hh"
+            | "pyi"
             | "sh"
             | "bash"
             | "zsh"
@@ -254,4 +420,40 @@ mod tests {
         assert!(!is_indexable_ext(Path::new("image.png")));
         assert!(!is_indexable_ext(Path::new("data.bin")));
     }
+
+    #[tokio::test]
+    async fn watcher_batches_changes_and_respects_ignores() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+
+        let mut watcher = ProjectWatcher::new(root, &["secret".to_string()]).unwrap();
+
+        std::fs::write(root.join("src/lib.rs"), "fn a() {}").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "fn b() {}").unwrap();
+        std::fs::write(root.join("target/out.rs"), "fn c() {}").unwrap();
+        std::fs::write(root.join("secret.rs"), "fn d() {}").unwrap();
+        std::fs::create_dir_all(root.join("generated")).unwrap();
+        std::fs::write(root.join("generated/x.rs"), "fn e() {}").unwrap();
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::write(root.join("src/nested/mod.rs"), "fn f() {}").unwrap();
+
+        let mut seen = BTreeSet::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !seen.contains("src/nested/mod.rs") && Instant::now() < deadline {
+            let batch = tokio::time::timeout(Duration::from_secs(10), watcher.next_batch())
+                .await
+                .expect("watcher batch")
+                .expect("watcher alive");
+            seen.extend(batch);
+        }
+
+        assert!(seen.contains("src/lib.rs"), "{seen:?}");
+        assert!(seen.contains("src/nested/mod.rs"), "{seen:?}");
+        assert!(seen.iter().all(|p| !p.starts_with("target")
+            && !p.starts_with("generated")
+            && !p.starts_with("secret")));
+    }
 }
@@ -0,0 +1,130 @@
+use std::sync::{Mutex, RwLock};
+
+use nyzhi_auth::Credential;
+
+/// The credential a provider sends, which can be swapped at runtime for
+/// another stored account when the current one is rate limited.
+pub struct AccountCredential {
+    current: RwLock<Credential>,
+    /// Auth store key the accounts live under; `None` when the credential
+    /// is fixed (config, environment or a single account).
+    store_key: Option<String>,
+    displaced: Mutex<Option<DisplacedAccount>>,
+}
+
+/// The account in use before the first rotation, restored once its
+/// cooldown has passed.
+struct DisplacedAccount {
+    label: Option<String>,
+    until: u64,
+}
+
+/// A change of account, described by the labels of both sides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountChange {
+    pub from: String,
+    pub to: String,
+}
+
+impl AccountCredential {
+    pub fn new(credential: Credential) -> Self {
+        Self {
+            current: RwLock::new(credential),
+            store_key: None,
+            displaced: Mutex::new(None),
+        }
+    }
+
+    /// Rotate through the accounts stored under `store_key` on rate limits.
+    pub fn with_accounts(mut self, store_key: Option<&str>) -> Self {
+        self.store_key = store_key.map(str::to_string);
+        self
+    }
+
+    pub fn get(&self) -> Credential {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn header_value(&self) -> String {
+        self.get().header_value()
+    }
+
+    pub fn set(&self, credential: Credential) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = credential;
+    }
+
+    pub fn is_rotatable(&self) -> bool {
+        self.store_key.is_some()
+    }
+
+    /// Mark the current account limited for `wait_seconds` and switch to the
+    /// next one. `None` when there is no other account to use.
+    pub async fn rotate(&self, wait_seconds: u64) -> Option<AccountChange> {
+        let store_key = self.store_key.as_deref()?;
+        let switch = match nyzhi_auth::handle_rate_limit(store_key, wait_seconds).await {
+            Ok(switch) => switch?,
+            Err(e) => {
+                tracing::warn!(provider = store_key, error = %e, "Failed to rotate account");
+                return None;
+            }
+        };
+        self.set(switch.credential);
+
+        let mut displaced = self.displaced.lock().unwrap_or_else(|e| e.into_inner());
+        // Only the account in use before the first rotation is restored;
+        // later rotations just move between spares.
+        if displaced.is_none() {
+            *displaced = Some(DisplacedAccount {
+                label: switch.from.clone(),
+                until: now_secs() + wait_seconds,
+            });
+        }
+        Some(AccountChange {
+            from: account_name(switch.from.as_deref()),
+            to: account_name(switch.to.as_deref()),
+        })
+    }
+
+    /// Switch back to the original account once its cooldown has passed.
+    pub async fn restore_if_due(&self) -> Option<AccountChange> {
+        let store_key = self.store_key.as_deref()?;
+        let original = {
+            let mut displaced = self.displaced.lock().unwrap_or_else(|e| e.into_inner());
+            if displaced.as_ref().map_or(true, |d| now_secs() < d.until) {
+                return None;
+            }
+            displaced.take()?
+        };
+        let from = nyzhi_auth::token_store::active_account(store_key)
+            .ok()
+            .flatten()
+            .and_then(|e| e.label);
+        match nyzhi_auth::restore_account(store_key, original.label.as_deref()).await {
+            Ok(Some(credential)) => self.set(credential),
+            // Removed or limited again since: stay on the current account.
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::warn!(provider = store_key, error = %e, "Failed to restore account");
+                return None;
+            }
+        }
+        Some(AccountChange {
+            from: account_name(from.as_deref()),
+            to: account_name(original.label.as_deref()),
+        })
+    }
+}
+
+fn account_name(label: Option<&str>) -> String {
+    label.unwrap_or("default").to_string()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -5,11 +5,14 @@ use serde_json::json;
 
 use crate::sse::parse_sse_stream;
 use crate::types::*;
-use crate::{Provider, ProviderError};
+use crate::vertex::VertexEndpoint;
+use crate::{AccountCredential, Provider, ProviderError};
 
 const DEFAULT_BASE_URL: &str = "https://api.anthropic.com/v1";
 const DEFAULT_MODEL: &str = "claude-sonnet-4-6-20260217";
 const API_VERSION: &str = "2023-06-01";
+const VERTEX_API_VERSION: &str = "vertex-2023-10-16";
+const VERTEX_DEFAULT_MODEL: &str = "claude-sonnet-4-6";
 
 pub fn default_models() -> Vec<ModelInfo> {
     vec![
@@ -67,9 +70,10 @@ pub fn default_models() -> Vec<ModelInfo> {
 pub struct AnthropicProvider {
     client: reqwest::Client,
     base_url: String,
-    api_key: String,
+    credential: AccountCredential,
     default_model: String,
     models: Vec<ModelInfo>,
+    vertex: Option<VertexEndpoint>,
 }
 
 impl AnthropicProvider {
@@ -80,9 +84,24 @@ impl AnthropicProvider {
                 .build()
                 .unwrap_or_default(),
             base_url: base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
-            api_key,
+            credential: AccountCredential::new(nyzhi_auth::Credential::ApiKey(api_key)),
             default_model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
             models: default_models(),
+            vertex: None,
+        }
+    }
+
+    /// Claude through Vertex AI's `rawPredict`, authenticated with Google
+    /// Cloud access tokens.
+    pub fn vertex(endpoint: VertexEndpoint, model: Option<String>) -> Self {
+        Self {
+            models: crate::vertex::anthropic_models(),
+            vertex: Some(endpoint),
+            ..Self::new(
+                String::new(),
+                None,
+                Some(model.unwrap_or_else(|| VERTEX_DEFAULT_MODEL.to_string())),
+            )
         }
     }
 
@@ -93,6 +112,12 @@ impl AnthropicProvider {
         self
     }
 
+    /// Rotate through the accounts stored under `store_key` on rate limits.
+    pub fn with_accounts(mut self, store_key: Option<&str>) -> Self {
+        self.credential = self.credential.with_accounts(store_key);
+        self
+    }
+
     pub fn from_config(config: &nyzhi_config::Config) -> Result<Self> {
         let entry = config.provider.entry("anthropic");
         let cred =
@@ -104,6 +129,30 @@ impl AnthropicProvider {
         ))
     }
 
+    async fn messages_request(
+        &self,
+        model: &str,
+        body: serde_json::Value,
+    ) -> Result<reqwest::RequestBuilder> {
+        let Some(vertex) = &self.vertex else {
+            return Ok(self
+                .client
+                .post(format!("{}/messages", self.base_url))
+                .header("x-api-key", self.credential.header_value())
+                .header("anthropic-version", API_VERSION)
+                .header("content-type", "application/json")
+                .json(&body));
+        };
+        let (method, body) = vertex_body(body);
+        let token = vertex.access_token().await?;
+        Ok(self
+            .client
+            .post(vertex.model_url("anthropic", model, method))
+            .bearer_auth(token)
+            .header("content-type", "application/json")
+            .json(&body))
+    }
+
     fn build_messages(&self, request: &ChatRequest) -> Vec<serde_json::Value> {
         request
             .messages
@@ -188,6 +237,10 @@ impl Provider for AnthropicProvider {
         &self.models
     }
 
+    fn accounts(&self) -> Option<&AccountCredential> {
+        self.credential.is_rotatable().then_some(&self.credential)
+    }
+
     async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
         let model = if request.model.is_empty() {
             &self.default_model
@@ -215,15 +268,7 @@ impl Provider for AnthropicProvider {
             body["tools"] = json!(self.build_tools(&request.tools));
         }
 
-        let resp = self
-            .client
-            .post(format!("{}/messages", self.base_url))
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", API_VERSION)
-            .header("content-type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+        let resp = self.messages_request(model, body).await?.send().await?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -371,15 +416,7 @@ impl Provider for AnthropicProvider {
             body["tools"] = json!(self.build_tools(&request.tools));
         }
 
-        let resp = self
-            .client
-            .post(format!("{}/messages", self.base_url))
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", API_VERSION)
-            .header("content-type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+        let resp = self.messages_request(model, body).await?.send().await?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -487,3 +524,37 @@ impl Provider for AnthropicProvider {
         Ok(Box::pin(event_stream))
     }
 }
+
+/// Vertex takes the model from the URL and the API version in the body.
+fn vertex_body(mut body: serde_json::Value) -> (&'static str, serde_json::Value) {
+    let method = if body["stream"] == json!(true) {
+        "streamRawPredict"
+    } else {
+        "rawPredict"
+    };
+    if let Some(fields) = body.as_object_mut() {
+        fields.remove("model");
+        fields.insert("anthropic_version".into(), json!(VERTEX_API_VERSION));
+    }
+    (method, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_body_moves_model_to_the_url() {
+        let (method, body) = vertex_body(json!({
+            "model": "claude-sonnet-4-6",
+            "max_tokens": 4096,
+            "stream": true,
+        }));
+        assert_eq!(method, "streamRawPredict");
+        assert!(body.get("model").is_none());
+        assert_eq!(body["anthropic_version"], VERTEX_API_VERSION);
+
+        let (method, _) = vertex_body(json!({"model": "claude-sonnet-4-6"}));
+        assert_eq!(method, "rawPredict");
+    }
+}
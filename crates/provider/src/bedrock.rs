@@ -0,0 +1,738 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use nyzhi_auth::aws::{self, AwsCredentials, SigningRequest};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::event_stream::{EventMessage, EventStreamDecoder};
+use crate::types::*;
+use crate::{Provider, ProviderError};
+
+const DEFAULT_REGION: &str = "us-east-1";
+const DEFAULT_MODEL: &str = "us.anthropic.claude-sonnet-4-6";
+const SIGNING_SERVICE: &str = "bedrock";
+
+/// The Anthropic models as cross-region inference profiles. Other regions
+/// use an `eu.`/`apac.` prefix; set `model` to pick one.
+pub fn default_models() -> Vec<ModelInfo> {
+    crate::anthropic::default_models()
+        .into_iter()
+        .filter_map(|m| {
+            let id = match m.id.as_str() {
+                "claude-opus-4-6-20260205" => "us.anthropic.claude-opus-4-6-v1",
+                "claude-sonnet-4-6-20260217" => "us.anthropic.claude-sonnet-4-6",
+                "claude-haiku-4-5-20251022" => "us.anthropic.claude-haiku-4-5-20251001-v1:0",
+                _ => return None,
+            };
+            Some(ModelInfo {
+                id: id.into(),
+                provider: "bedrock".into(),
+                ..m
+            })
+        })
+        .collect()
+}
+
+pub fn default_base_url(region: &str) -> String {
+    format!("https://bedrock-runtime.{region}.amazonaws.com")
+}
+
+enum BedrockAuth {
+    /// A Bedrock API key, sent as a bearer token.
+    ApiKey(String),
+    /// SigV4 with credentials from the AWS chain, re-resolved when
+    /// temporary ones are about to expire.
+    SigV4(Mutex<Option<AwsCredentials>>),
+}
+
+pub struct BedrockProvider {
+    client: reqwest::Client,
+    base_url: String,
+    region: String,
+    auth: BedrockAuth,
+    default_model: String,
+    models: Vec<ModelInfo>,
+}
+
+impl BedrockProvider {
+    pub fn new(region: Option<String>, base_url: Option<String>, model: Option<String>) -> Self {
+        let region = region
+            .or_else(|| aws::resolve_region(None))
+            .unwrap_or_else(|| DEFAULT_REGION.to_string());
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(120))
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.unwrap_or_else(|| default_base_url(&region)),
+            region,
+            auth: BedrockAuth::SigV4(Mutex::new(None)),
+            default_model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            models: default_models(),
+        }
+    }
+
+    /// Authenticate with a Bedrock API key instead of SigV4.
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.auth = BedrockAuth::ApiKey(api_key);
+        self
+    }
+
+    /// Sign with fixed credentials instead of resolving them from the chain.
+    pub fn with_credentials(mut self, credentials: AwsCredentials) -> Self {
+        self.auth = BedrockAuth::SigV4(Mutex::new(Some(credentials)));
+        self
+    }
+
+    pub fn with_models(mut self, models: Vec<ModelInfo>) -> Self {
+        if !models.is_empty() {
+            self.models = models;
+        }
+        self
+    }
+
+    fn model<'a>(&'a self, request: &'a ChatRequest) -> &'a str {
+        if request.model.is_empty() {
+            &self.default_model
+        } else {
+            &request.model
+        }
+    }
+
+    async fn post(
+        &self,
+        model: &str,
+        action: &str,
+        body: &serde_json::Value,
+    ) -> Result<reqwest::Response> {
+        let url = reqwest::Url::parse(&format!(
+            "{}/model/{}/{action}",
+            self.base_url.trim_end_matches('/'),
+            aws::uri_encode(model)
+        ))?;
+        let body = serde_json::to_vec(body)?;
+        let mut req = self
+            .client
+            .post(url.clone())
+            .header("content-type", "application/json");
+
+        match &self.auth {
+            BedrockAuth::ApiKey(key) => req = req.bearer_auth(key),
+            BedrockAuth::SigV4(cached) => {
+                let credentials = {
+                    let mut cached = cached.lock().await;
+                    match cached.as_ref().filter(|c| !c.is_expiring()) {
+                        Some(credentials) => credentials.clone(),
+                        None => {
+                            let credentials = aws::resolve_credentials(None).await?;
+                            *cached = Some(credentials.clone());
+                            credentials
+                        }
+                    }
+                };
+                let signed = aws::sign(
+                    &credentials,
+                    &self.region,
+                    SIGNING_SERVICE,
+                    &SigningRequest {
+                        method: "POST",
+                        url: &url,
+                        headers: &[("content-type", "application/json")],
+                        body: &body,
+                    },
+                    chrono::Utc::now(),
+                );
+                for (name, value) in signed {
+                    req = req.header(name, value);
+                }
+            }
+        }
+
+        let resp = req.body(body).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let retry_after = resp
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string());
+            let body = resp.text().await.unwrap_or_default();
+            return Err(
+                ProviderError::from_http(status.as_u16(), body, retry_after.as_deref()).into(),
+            );
+        }
+        Ok(resp)
+    }
+}
+
+/// Only Anthropic and Nova models accept cache points.
+fn supports_cache_points(model: &str) -> bool {
+    model.contains("anthropic.") || model.contains("amazon.nova")
+}
+
+fn cache_point() -> serde_json::Value {
+    json!({"cachePoint": {"type": "default"}})
+}
+
+/// `image/png` -> `png`, the format names Converse accepts.
+fn image_format(media_type: &str) -> &str {
+    match media_type.strip_prefix("image/").unwrap_or(media_type) {
+        "jpg" => "jpeg",
+        other => other,
+    }
+}
+
+fn build_messages(request: &ChatRequest) -> Vec<serde_json::Value> {
+    request
+        .messages
+        .iter()
+        .filter(|m| m.role != Role::System)
+        .map(|msg| {
+            let content: Vec<serde_json::Value> = match &msg.content {
+                MessageContent::Text(text) => vec![json!({"text": text})],
+                MessageContent::Parts(parts) => parts
+                    .iter()
+                    .filter_map(|p| match p {
+                        ContentPart::Text { text } if text.is_empty() => None,
+                        ContentPart::Text { text } => Some(json!({"text": text})),
+                        ContentPart::Image { media_type, data } => Some(json!({
+                            "image": {
+                                "format": image_format(media_type),
+                                "source": {"bytes": data},
+                            }
+                        })),
+                        ContentPart::ToolUse { id, name, input } => Some(json!({
+                            "toolUse": {"toolUseId": id, "name": name, "input": input}
+                        })),
+                        ContentPart::ToolResult {
+                            tool_use_id,
+                            content,
+                            ..
+                        } => Some(json!({
+                            "toolResult": {
+                                "toolUseId": tool_use_id,
+                                "content": [{"text": content}],
+                            }
+                        })),
+                    })
+                    .collect(),
+            };
+            json!({
+                "role": match msg.role {
+                    Role::Assistant => "assistant",
+                    _ => "user",
+                },
+                "content": content,
+            })
+        })
+        .collect()
+}
+
+fn build_body(request: &ChatRequest, model: &str) -> serde_json::Value {
+    let cache = supports_cache_points(model);
+    let thinking_enabled = request.thinking.as_ref().is_some_and(|t| t.enabled);
+    let max_tokens = if thinking_enabled {
+        request.max_tokens.unwrap_or(16_384).max(8192)
+    } else {
+        request.max_tokens.unwrap_or(4096)
+    };
+
+    let mut inference = json!({"maxTokens": max_tokens});
+    if !thinking_enabled {
+        if let Some(temp) = request.temperature {
+            inference["temperature"] = json!(temp);
+        }
+    }
+    let mut body = json!({
+        "messages": build_messages(request),
+        "inferenceConfig": inference,
+    });
+
+    if let Some(system) = &request.system {
+        let mut blocks = vec![json!({"text": system})];
+        if cache {
+            blocks.push(cache_point());
+        }
+        body["system"] = json!(blocks);
+    }
+    if !request.tools.is_empty() {
+        let mut tools: Vec<serde_json::Value> = request
+            .tools
+            .iter()
+            .map(|t| {
+                json!({"toolSpec": {
+                    "name": t.name,
+                    "description": t.description,
+                    "inputSchema": {"json": t.parameters},
+                }})
+            })
+            .collect();
+        if cache {
+            tools.push(cache_point());
+        }
+        body["toolConfig"] = json!({"tools": tools});
+    }
+    if thinking_enabled && model.contains("anthropic.") {
+        let thinking = request.thinking.as_ref();
+        body["additionalModelRequestFields"] =
+            if model.contains("opus-4-6") || model.contains("sonnet-4-6") {
+                json!({"thinking": {
+                    "type": "adaptive",
+                    "effort": thinking.and_then(|t| t.thinking_level.as_deref()).unwrap_or("high"),
+                }})
+            } else {
+                json!({"thinking": {
+                    "type": "enabled",
+                    "budget_tokens": thinking.and_then(|t| t.budget_tokens).unwrap_or(10_000),
+                }})
+            };
+    }
+    body
+}
+
+/// Converse reports uncached input separately; totals include the cache.
+fn usage_from(usage: &serde_json::Value) -> Usage {
+    let cache_read = usage["cacheReadInputTokens"].as_u64().unwrap_or(0) as u32;
+    let cache_creation = usage["cacheWriteInputTokens"].as_u64().unwrap_or(0) as u32;
+    Usage {
+        input_tokens: usage["inputTokens"].as_u64().unwrap_or(0) as u32
+            + cache_read
+            + cache_creation,
+        output_tokens: usage["outputTokens"].as_u64().unwrap_or(0) as u32,
+        cache_read_tokens: cache_read,
+        cache_creation_tokens: cache_creation,
+    }
+}
+
+/// Exceptions arrive as stream messages; map them to the HTTP status the
+/// same failure has as a response so retries behave alike.
+fn exception_error(message: &EventMessage) -> ProviderError {
+    let status = match message.header(":exception-type").unwrap_or_default() {
+        "throttlingException" => 429,
+        "validationException" => 400,
+        "serviceUnavailableException" => 503,
+        _ => 500,
+    };
+    ProviderError::from_http(
+        status,
+        String::from_utf8_lossy(&message.payload).into_owned(),
+        None,
+    )
+}
+
+/// Turns ConverseStream messages into stream events. Tool calls are numbered
+/// in order of appearance rather than by content block.
+#[derive(Default)]
+struct StreamState {
+    tool_indices: HashMap<u64, u32>,
+}
+
+impl StreamState {
+    fn events(&mut self, message: &EventMessage) -> Vec<Result<StreamEvent>> {
+        if message.header(":message-type") == Some("exception") {
+            return vec![Err(exception_error(message).into())];
+        }
+        let Ok(data) = serde_json::from_slice::<serde_json::Value>(&message.payload) else {
+            return vec![];
+        };
+        let block = data["contentBlockIndex"].as_u64().unwrap_or(0);
+
+        match message.header(":event-type").unwrap_or_default() {
+            "contentBlockStart" => {
+                let tool = &data["start"]["toolUse"];
+                if !tool.is_object() {
+                    return vec![];
+                }
+                let index = self.tool_indices.len() as u32;
+                self.tool_indices.insert(block, index);
+                vec![Ok(StreamEvent::ToolCallStart {
+                    index,
+                    id: tool["toolUseId"].as_str().unwrap_or("").to_string(),
+                    name: tool["name"].as_str().unwrap_or("").to_string(),
+                })]
+            }
+            "contentBlockDelta" => {
+                let delta = &data["delta"];
+                if let Some(text) = delta["text"].as_str() {
+                    vec![Ok(StreamEvent::TextDelta(text.to_string()))]
+                } else if let Some(text) = delta["reasoningContent"]["text"].as_str() {
+                    vec![Ok(StreamEvent::ThinkingDelta(text.to_string()))]
+                } else if let Some(input) = delta["toolUse"]["input"].as_str() {
+                    vec![Ok(StreamEvent::ToolCallDelta {
+                        index: self.tool_indices.get(&block).copied().unwrap_or(0),
+                        arguments_delta: input.to_string(),
+                    })]
+                } else {
+                    vec![]
+                }
+            }
+            "contentBlockStop" => match self.tool_indices.get(&block) {
+                Some(&index) => vec![Ok(StreamEvent::ToolCallDone { index })],
+                None => vec![],
+            },
+            "messageStop" => vec![Ok(StreamEvent::Done)],
+            "metadata" if data["usage"].is_object() => {
+                vec![Ok(StreamEvent::Usage(usage_from(&data["usage"])))]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for BedrockProvider {
+    fn name(&self) -> &str {
+        "bedrock"
+    }
+
+    fn supported_models(&self) -> &[ModelInfo] {
+        &self.models
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let model = self.model(request);
+        let resp = self
+            .post(model, "converse", &build_body(request, model))
+            .await?;
+        let data: serde_json::Value = resp.json().await?;
+
+        let mut parts = Vec::new();
+        for block in data["output"]["message"]["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let Some(text) = block["text"].as_str() {
+                if !text.is_empty() {
+                    parts.push(ContentPart::Text {
+                        text: text.to_string(),
+                    });
+                }
+            } else if block["toolUse"].is_object() {
+                let tool = &block["toolUse"];
+                parts.push(ContentPart::ToolUse {
+                    id: tool["toolUseId"].as_str().unwrap_or("").to_string(),
+                    name: tool["name"].as_str().unwrap_or("").to_string(),
+                    input: tool["input"].clone(),
+                });
+            }
+        }
+        let content = match parts.as_slice() {
+            [] => MessageContent::Text(String::new()),
+            [ContentPart::Text { text }] => MessageContent::Text(text.clone()),
+            _ => MessageContent::Parts(parts),
+        };
+
+        Ok(ChatResponse {
+            message: Message {
+                role: Role::Assistant,
+                content,
+            },
+            usage: Some(usage_from(&data["usage"])),
+            finish_reason: data["stopReason"].as_str().map(String::from),
+        })
+    }
+
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<BoxStream<'static, Result<StreamEvent>>> {
+        let model = self.model(request);
+        let resp = self
+            .post(model, "converse-stream", &build_body(request, model))
+            .await?;
+
+        let stream = futures::stream::unfold(
+            (
+                resp.bytes_stream(),
+                EventStreamDecoder::default(),
+                StreamState::default(),
+                VecDeque::new(),
+            ),
+            |(mut bytes, mut decoder, mut state, mut pending)| async move {
+                loop {
+                    if let Some(event) = pending.pop_front() {
+                        return Some((event, (bytes, decoder, state, pending)));
+                    }
+                    // Nothing can be framed after a corrupt message.
+                    if decoder.is_broken() {
+                        return None;
+                    }
+                    match decoder.next_message() {
+                        Ok(Some(message)) => {
+                            pending.extend(state.events(&message));
+                            continue;
+                        }
+                        Ok(None) => {}
+                        Err(e) => return Some((Err(e.into()), (bytes, decoder, state, pending))),
+                    }
+                    match bytes.next().await {
+                        Some(Ok(chunk)) => decoder.push(&chunk),
+                        Some(Err(e)) => {
+                            let e = ProviderError::StreamError(e.to_string());
+                            return Some((Err(e.into()), (bytes, decoder, state, pending)));
+                        }
+                        None => return None,
+                    }
+                }
+            },
+        );
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_stream::encode;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn credentials() -> AwsCredentials {
+        AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".into(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: Some("session".into()),
+            expires_at: None,
+        }
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "anthropic.claude-haiku-4-5-20251001-v1:0".into(),
+            messages: vec![
+                Message {
+                    role: Role::User,
+                    content: MessageContent::Parts(vec![
+                        ContentPart::Text {
+                            text: "What is this?".into(),
+                        },
+                        ContentPart::Image {
+                            media_type: "image/png".into(),
+                            data: "aGk=".into(),
+                        },
+                    ]),
+                },
+                Message {
+                    role: Role::Assistant,
+                    content: MessageContent::Parts(vec![ContentPart::ToolUse {
+                        id: "tooluse_1".into(),
+                        name: "read".into(),
+                        input: json!({"path": "a.png"}),
+                    }]),
+                },
+                Message {
+                    role: Role::User,
+                    content: MessageContent::Parts(vec![ContentPart::ToolResult {
+                        tool_use_id: "tooluse_1".into(),
+                        tool_name: Some("read".into()),
+                        content: "binary".into(),
+                    }]),
+                },
+            ],
+            tools: vec![ToolDefinition {
+                name: "read".into(),
+                description: "Read a file".into(),
+                parameters: json!({"type": "object"}),
+            }],
+            max_tokens: Some(1024),
+            temperature: Some(0.5),
+            system: Some("Be brief".into()),
+            stream: true,
+            thinking: None,
+        }
+    }
+
+    #[test]
+    fn converse_body_maps_parts_and_cache_points() {
+        let req = request();
+        let body = build_body(&req, &req.model);
+
+        assert_eq!(body["inferenceConfig"]["maxTokens"], 1024);
+        assert_eq!(body["system"][1]["cachePoint"]["type"], "default");
+        assert_eq!(body["toolConfig"]["tools"][0]["toolSpec"]["name"], "read");
+        assert!(body["toolConfig"]["tools"][1]["cachePoint"].is_object());
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["content"][1]["image"]["format"], "png");
+        assert_eq!(
+            messages[0]["content"][1]["image"]["source"]["bytes"],
+            "aGk="
+        );
+        assert_eq!(messages[1]["role"], "assistant");
+        assert_eq!(
+            messages[1]["content"][0]["toolUse"]["toolUseId"],
+            "tooluse_1"
+        );
+        assert_eq!(
+            messages[2]["content"][0]["toolResult"]["content"][0]["text"],
+            "binary"
+        );
+
+        let body = build_body(&req, "meta.llama3-70b-instruct-v1:0");
+        assert_eq!(body["system"].as_array().unwrap().len(), 1);
+    }
+
+    /// Answer one request, checking its signature the way Bedrock would.
+    async fn stub_server(listener: tokio::net::TcpListener, events: Vec<u8>) -> String {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 4096];
+        let (head, body) = loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            raw.extend_from_slice(&buf[..n]);
+            let Some(end) = raw.windows(4).position(|w| w == b"\r\n\r\n") else {
+                continue;
+            };
+            let head = String::from_utf8_lossy(&raw[..end]).to_string();
+            let len: usize = head
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse().unwrap())
+                })
+                .unwrap_or(0);
+            while raw.len() < end + 4 + len {
+                let n = socket.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+            }
+            break (head, raw[end + 4..end + 4 + len].to_vec());
+        };
+
+        let mut lines = head.lines();
+        let path = lines.next().unwrap().split(' ').nth(1).unwrap().to_string();
+        let headers: HashMap<String, String> = lines
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.to_lowercase(), v.trim().to_string()))
+            .collect();
+        let time = chrono::NaiveDateTime::parse_from_str(&headers["x-amz-date"], "%Y%m%dT%H%M%SZ")
+            .unwrap()
+            .and_utc();
+        let url = reqwest::Url::parse(&format!("http://{}{path}", headers["host"])).unwrap();
+        let expected = aws::sign(
+            &credentials(),
+            "us-west-2",
+            "bedrock",
+            &SigningRequest {
+                method: "POST",
+                url: &url,
+                headers: &[("content-type", &headers["content-type"])],
+                body: &body,
+            },
+            time,
+        );
+        let response = if headers.get("authorization") == Some(&expected.last().unwrap().1)
+            && headers.get("x-amz-security-token").map(String::as_str) == Some("session")
+        {
+            let mut response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/vnd.amazon.eventstream\r\ncontent-length: {}\r\n\r\n",
+                events.len()
+            )
+            .into_bytes();
+            response.extend_from_slice(&events);
+            response
+        } else {
+            let body = r#"{"message":"The request signature we calculated does not match"}"#;
+            format!(
+                "HTTP/1.1 403 Forbidden\r\ncontent-length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes()
+        };
+        socket.write_all(&response).await.unwrap();
+        path
+    }
+
+    fn event(event_type: &str, payload: serde_json::Value) -> Vec<u8> {
+        encode(
+            &[
+                (":event-type", event_type),
+                (":content-type", "application/json"),
+                (":message-type", "event"),
+            ],
+            payload.to_string().as_bytes(),
+        )
+    }
+
+    #[tokio::test]
+    async fn streams_from_signed_converse_stream() {
+        let events = [
+            event("messageStart", json!({"role": "assistant"})),
+            event("contentBlockDelta", json!({"contentBlockIndex": 0, "delta": {"text": "Reading"}})),
+            event("contentBlockStop", json!({"contentBlockIndex": 0})),
+            event(
+                "contentBlockStart",
+                json!({"contentBlockIndex": 1, "start": {"toolUse": {"toolUseId": "tooluse_2", "name": "read"}}}),
+            ),
+            event(
+                "contentBlockDelta",
+                json!({"contentBlockIndex": 1, "delta": {"toolUse": {"input": "{\"path\":\"b.rs\"}"}}}),
+            ),
+            event("contentBlockStop", json!({"contentBlockIndex": 1})),
+            event("messageStop", json!({"stopReason": "tool_use"})),
+            event(
+                "metadata",
+                json!({"usage": {"inputTokens": 10, "outputTokens": 5, "cacheReadInputTokens": 90}}),
+            ),
+        ]
+        .concat();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(stub_server(listener, events));
+
+        let provider = BedrockProvider::new(Some("us-west-2".into()), Some(base_url), None)
+            .with_credentials(credentials());
+        let mut stream = provider.chat_stream(&request()).await.unwrap();
+        let mut collected = Vec::new();
+        while let Some(event) = stream.next().await {
+            collected.push(event.unwrap());
+        }
+
+        assert_eq!(
+            server.await.unwrap(),
+            "/model/anthropic.claude-haiku-4-5-20251001-v1%3A0/converse-stream"
+        );
+        assert!(matches!(&collected[0], StreamEvent::TextDelta(t) if t == "Reading"));
+        assert!(matches!(
+            &collected[1],
+            StreamEvent::ToolCallStart { index: 0, id, name } if id == "tooluse_2" && name == "read"
+        ));
+        assert!(matches!(
+            &collected[2],
+            StreamEvent::ToolCallDelta { index: 0, arguments_delta } if arguments_delta == "{\"path\":\"b.rs\"}"
+        ));
+        assert!(matches!(
+            collected[3],
+            StreamEvent::ToolCallDone { index: 0 }
+        ));
+        assert!(matches!(collected[4], StreamEvent::Done));
+        assert!(matches!(
+            &collected[5],
+            StreamEvent::Usage(u) if u.input_tokens == 100 && u.cache_read_tokens == 90 && u.output_tokens == 5
+        ));
+    }
+
+    #[test]
+    fn throttling_exceptions_are_retryable() {
+        let bytes = encode(
+            &[
+                (":message-type", "exception"),
+                (":exception-type", "throttlingException"),
+            ],
+            br#"{"message":"Too many requests"}"#,
+        );
+        let mut decoder = EventStreamDecoder::default();
+        decoder.push(&bytes);
+        let message = decoder.next_message().unwrap().unwrap();
+        let events = StreamState::default().events(&message);
+        let err = events[0].as_ref().unwrap_err();
+        let pe = err.downcast_ref::<ProviderError>().unwrap();
+        assert!(pe.is_retryable());
+        assert!(matches!(pe, ProviderError::RateLimited { .. }));
+    }
+}
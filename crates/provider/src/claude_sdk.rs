@@ -4,7 +4,7 @@ use futures::stream::BoxStream;
 
 use crate::anthropic::AnthropicProvider;
 use crate::types::*;
-use crate::Provider;
+use crate::{AccountCredential, Provider};
 
 /// Claude SDK provider: delegates to AnthropicProvider with agent-oriented defaults.
 /// Uses the standard Anthropic Messages API with extended thinking enabled.
@@ -19,6 +19,12 @@ impl ClaudeSDKProvider {
             inner: AnthropicProvider::new(api_key, base_url, model),
         }
     }
+
+    /// Rotate through the accounts stored under `store_key` on rate limits.
+    pub fn with_accounts(mut self, store_key: Option<&str>) -> Self {
+        self.inner = self.inner.with_accounts(store_key);
+        self
+    }
 }
 
 #[async_trait]
@@ -31,6 +37,10 @@ impl Provider for ClaudeSDKProvider {
         self.inner.supported_models()
     }
 
+    fn accounts(&self) -> Option<&AccountCredential> {
+        self.inner.accounts()
+    }
+
     async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
         self.inner.chat(request).await
     }
@@ -491,14 +491,12 @@ fn parse_interaction_update(data: &[u8]) -> InteractionUpdate {
                     }
                 }
             }
-            (8, 2) => {
-                if text.is_none() {
-                    if let Some(bytes) = &field.bytes_value {
-                        for inner in parse_proto_fields(bytes) {
-                            if inner.field_number == 1 && inner.wire_type == 2 {
-                                if let Some(b) = &inner.bytes_value {
-                                    text = String::from_utf8(b.clone()).ok();
-                                }
+            (8, 2) if text.is_none() => {
+                if let Some(bytes) = &field.bytes_value {
+                    for inner in parse_proto_fields(bytes) {
+                        if inner.field_number == 1 && inner.wire_type == 2 {
+                            if let Some(b) = &inner.bytes_value {
+                                text = String::from_utf8(b.clone()).ok();
                             }
                         }
                     }
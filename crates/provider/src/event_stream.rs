@@ -0,0 +1,207 @@
+//! Decoder for the AWS `application/vnd.amazon.eventstream` framing.
+//!
+//! Each message is `total_len:u32 | headers_len:u32 | prelude_crc:u32 |
+//! headers | payload | message_crc:u32`, all big-endian, with CRC32 over the
+//! bytes before each checksum.
+
+use std::collections::HashMap;
+
+use crate::ProviderError;
+
+const PRELUDE_LEN: usize = 12;
+const MIN_MESSAGE_LEN: usize = PRELUDE_LEN + 4;
+
+#[derive(Debug)]
+pub struct EventMessage {
+    /// String-valued headers such as `:event-type`; others are skipped.
+    pub headers: HashMap<String, String>,
+    pub payload: Vec<u8>,
+}
+
+impl EventMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Default)]
+pub struct EventStreamDecoder {
+    buffer: Vec<u8>,
+    /// Set after a framing error; message boundaries are lost from there on.
+    broken: bool,
+}
+
+impl EventStreamDecoder {
+    pub fn push(&mut self, bytes: &[u8]) {
+        if !self.broken {
+            self.buffer.extend_from_slice(bytes);
+        }
+    }
+
+    /// Whether a corrupt message was seen; no more messages will follow.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// The next complete message, `None` until enough bytes have arrived.
+    /// A corrupt message is reported once; everything after it is dropped.
+    pub fn next_message(&mut self) -> Result<Option<EventMessage>, ProviderError> {
+        if self.buffer.len() < PRELUDE_LEN {
+            return Ok(None);
+        }
+        let total_len = read_u32(&self.buffer[0..4]) as usize;
+        let headers_len = read_u32(&self.buffer[4..8]) as usize;
+        if total_len < MIN_MESSAGE_LEN || headers_len > total_len - MIN_MESSAGE_LEN {
+            return Err(self.fail("invalid message length"));
+        }
+        if crc32fast::hash(&self.buffer[0..8]) != read_u32(&self.buffer[8..12]) {
+            return Err(self.fail("prelude checksum mismatch"));
+        }
+        if self.buffer.len() < total_len {
+            return Ok(None);
+        }
+
+        let message: Vec<u8> = self.buffer.drain(..total_len).collect();
+        let crc_at = total_len - 4;
+        if crc32fast::hash(&message[..crc_at]) != read_u32(&message[crc_at..]) {
+            return Err(self.fail("message checksum mismatch"));
+        }
+        let headers_end = PRELUDE_LEN + headers_len;
+        Ok(Some(EventMessage {
+            headers: parse_headers(&message[PRELUDE_LEN..headers_end])?,
+            payload: message[headers_end..crc_at].to_vec(),
+        }))
+    }
+
+    fn fail(&mut self, detail: &str) -> ProviderError {
+        self.broken = true;
+        self.buffer = Vec::new();
+        stream_error(detail)
+    }
+}
+
+fn parse_headers(mut bytes: &[u8]) -> Result<HashMap<String, String>, ProviderError> {
+    let mut headers = HashMap::new();
+    while !bytes.is_empty() {
+        let name_len = bytes[0] as usize;
+        let name = take(&mut bytes, 1 + name_len)?[1..].to_vec();
+        let value_type = take(&mut bytes, 1)?[0];
+        let value = match value_type {
+            // bool true / false
+            0 | 1 => None,
+            2 => take(&mut bytes, 1).map(|_| None)?,
+            3 => take(&mut bytes, 2).map(|_| None)?,
+            4 => take(&mut bytes, 4).map(|_| None)?,
+            5 | 8 => take(&mut bytes, 8).map(|_| None)?,
+            9 => take(&mut bytes, 16).map(|_| None)?,
+            // byte array / string, u16 length-prefixed
+            6 | 7 => {
+                let len = read_u16(take(&mut bytes, 2)?) as usize;
+                let value = take(&mut bytes, len)?;
+                (value_type == 7).then(|| String::from_utf8_lossy(value).into_owned())
+            }
+            other => return Err(stream_error(&format!("unknown header type {other}"))),
+        };
+        if let Some(value) = value {
+            headers.insert(String::from_utf8_lossy(&name).into_owned(), value);
+        }
+    }
+    Ok(headers)
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], ProviderError> {
+    if bytes.len() < n {
+        return Err(stream_error("truncated headers"));
+    }
+    let (head, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Ok(head)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn stream_error(detail: &str) -> ProviderError {
+    ProviderError::StreamError(format!("event stream: {detail}"))
+}
+
+/// Frame a message with string headers, as the service does.
+#[cfg(test)]
+pub fn encode(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+    let mut header_bytes = Vec::new();
+    for (name, value) in headers {
+        header_bytes.push(name.len() as u8);
+        header_bytes.extend_from_slice(name.as_bytes());
+        header_bytes.push(7);
+        header_bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        header_bytes.extend_from_slice(value.as_bytes());
+    }
+    let total_len = (MIN_MESSAGE_LEN + header_bytes.len() + payload.len()) as u32;
+    let mut message = Vec::with_capacity(total_len as usize);
+    message.extend_from_slice(&total_len.to_be_bytes());
+    message.extend_from_slice(&(header_bytes.len() as u32).to_be_bytes());
+    message.extend_from_slice(&crc32fast::hash(&message).to_be_bytes());
+    message.extend_from_slice(&header_bytes);
+    message.extend_from_slice(payload);
+    message.extend_from_slice(&crc32fast::hash(&message).to_be_bytes());
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_messages_split_across_chunks() {
+        let first = encode(
+            &[(":event-type", "messageStart"), (":message-type", "event")],
+            br#"{"role":"assistant"}"#,
+        );
+        let second = encode(&[(":event-type", "messageStop")], br#"{}"#);
+        let bytes = [first, second].concat();
+
+        let mut decoder = EventStreamDecoder::default();
+        let mut messages = Vec::new();
+        for chunk in bytes.chunks(7) {
+            decoder.push(chunk);
+            while let Some(message) = decoder.next_message().unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].header(":event-type"), Some("messageStart"));
+        assert_eq!(messages[0].payload, br#"{"role":"assistant"}"#);
+        assert_eq!(messages[1].header(":event-type"), Some("messageStop"));
+    }
+
+    #[test]
+    fn rejects_corrupted_messages() {
+        let mut bytes = encode(&[(":event-type", "messageStop")], br#"{}"#);
+        let last = bytes.len() - 5;
+        bytes[last] ^= 0xff;
+        let mut decoder = EventStreamDecoder::default();
+        decoder.push(&bytes);
+        assert!(decoder.next_message().is_err());
+    }
+
+    #[test]
+    fn a_corrupt_prelude_is_reported_once() {
+        let message = encode(&[(":event-type", "messageStop")], br#"{}"#);
+        let mut bytes = message.clone();
+        bytes[9] ^= 0xff;
+        let mut decoder = EventStreamDecoder::default();
+        decoder.push(&bytes);
+        assert!(decoder.next_message().is_err());
+        assert!(decoder.is_broken());
+        assert!(decoder.next_message().unwrap().is_none());
+
+        // Later bytes cannot be framed again, even if they look valid.
+        decoder.push(&message);
+        assert!(decoder.next_message().unwrap().is_none());
+    }
+}
@@ -5,7 +5,8 @@ use serde_json::json;
 
 use crate::sse::parse_sse_stream;
 use crate::types::*;
-use crate::{Provider, ProviderError};
+use crate::vertex::VertexEndpoint;
+use crate::{AccountCredential, Provider, ProviderError};
 
 const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
 const DEFAULT_MODEL: &str = "gemini-3-flash";
@@ -97,31 +98,18 @@ pub fn default_models() -> Vec<ModelInfo> {
     ]
 }
 
-pub enum GeminiAuthMode {
-    ApiKey(String),
-    Bearer(String),
-}
-
 pub struct GeminiProvider {
     client: reqwest::Client,
     base_url: String,
-    auth: GeminiAuthMode,
+    credential: AccountCredential,
     default_model: String,
     models: Vec<ModelInfo>,
+    vertex: Option<VertexEndpoint>,
 }
 
 impl GeminiProvider {
     pub fn new(api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
-        Self {
-            client: reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(120))
-                .build()
-                .unwrap_or_default(),
-            base_url: base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
-            auth: GeminiAuthMode::ApiKey(api_key),
-            default_model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
-            models: default_models(),
-        }
+        Self::with_credential(nyzhi_auth::Credential::ApiKey(api_key), base_url, model)
     }
 
     pub fn with_credential(
@@ -129,19 +117,26 @@ impl GeminiProvider {
         base_url: Option<String>,
         model: Option<String>,
     ) -> Self {
-        let auth = match credential {
-            nyzhi_auth::Credential::Bearer(token) => GeminiAuthMode::Bearer(token),
-            nyzhi_auth::Credential::ApiKey(key) => GeminiAuthMode::ApiKey(key),
-        };
         Self {
             client: reqwest::Client::builder()
                 .timeout(std::time::Duration::from_secs(120))
                 .build()
                 .unwrap_or_default(),
             base_url: base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
-            auth,
+            credential: AccountCredential::new(credential),
             default_model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
             models: default_models(),
+            vertex: None,
+        }
+    }
+
+    /// Gemini through Vertex AI, authenticated with Google Cloud access
+    /// tokens instead of an API key.
+    pub fn vertex(endpoint: VertexEndpoint, model: Option<String>) -> Self {
+        Self {
+            models: crate::vertex::gemini_models(),
+            vertex: Some(endpoint),
+            ..Self::with_credential(nyzhi_auth::Credential::Bearer(String::new()), None, model)
         }
     }
 
@@ -152,6 +147,12 @@ impl GeminiProvider {
         self
     }
 
+    /// Rotate through the accounts stored under `store_key` on rate limits.
+    pub fn with_accounts(mut self, store_key: Option<&str>) -> Self {
+        self.credential = self.credential.with_accounts(store_key);
+        self
+    }
+
     pub fn from_config(config: &nyzhi_config::Config) -> Result<Self> {
         let entry = config.provider.entry("gemini");
         let cred =
@@ -164,23 +165,32 @@ impl GeminiProvider {
     }
 
     fn build_url(&self, model: &str, action: &str) -> String {
-        match &self.auth {
-            GeminiAuthMode::ApiKey(key) => {
+        if let Some(vertex) = &self.vertex {
+            return vertex.model_url("google", model, action);
+        }
+        match self.credential.get() {
+            nyzhi_auth::Credential::ApiKey(key) => {
                 format!("{}/models/{}:{}?key={}", self.base_url, model, action, key)
             }
-            GeminiAuthMode::Bearer(_) => {
+            nyzhi_auth::Credential::Bearer(_) => {
                 format!("{}/models/{}:{}", self.base_url, model, action)
             }
         }
     }
 
-    fn apply_auth(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        match &self.auth {
-            GeminiAuthMode::ApiKey(_) => builder,
-            GeminiAuthMode::Bearer(token) => {
+    async fn apply_auth(
+        &self,
+        builder: reqwest::RequestBuilder,
+    ) -> Result<reqwest::RequestBuilder> {
+        if let Some(vertex) = &self.vertex {
+            return Ok(builder.bearer_auth(vertex.access_token().await?));
+        }
+        Ok(match self.credential.get() {
+            nyzhi_auth::Credential::ApiKey(_) => builder,
+            nyzhi_auth::Credential::Bearer(token) => {
                 builder.header("authorization", format!("Bearer {token}"))
             }
-        }
+        })
     }
 
     fn build_contents(&self, request: &ChatRequest) -> Vec<serde_json::Value> {
@@ -252,6 +262,10 @@ impl Provider for GeminiProvider {
         &self.models
     }
 
+    fn accounts(&self) -> Option<&AccountCredential> {
+        self.credential.is_rotatable().then_some(&self.credential)
+    }
+
     async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
         let model = if request.model.is_empty() {
             &self.default_model
@@ -288,7 +302,7 @@ impl Provider for GeminiProvider {
             .post(&url)
             .header("content-type", "application/json")
             .json(&body);
-        let resp = self.apply_auth(req).send().await?;
+        let resp = self.apply_auth(req).await?.send().await?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -430,7 +444,7 @@ impl Provider for GeminiProvider {
             .post(&url)
             .header("content-type", "application/json")
             .json(&body);
-        let resp = self.apply_auth(req).send().await?;
+        let resp = self.apply_auth(req).await?.send().await?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -1,6 +1,8 @@
 pub mod types;
 
+pub mod accounts;
 pub mod anthropic;
+pub mod bedrock;
 pub mod claude_sdk;
 pub mod codex;
 pub mod copilot;
@@ -9,10 +11,13 @@ pub mod gemini;
 pub mod list_models;
 pub mod model_cache;
 pub mod openai;
+pub mod vertex;
 
 mod error;
+mod event_stream;
 mod sse;
 
+pub use accounts::{AccountChange, AccountCredential};
 pub use error::ProviderError;
 pub use model_cache::{ModelCache, ModelCacheHandle};
 pub use types::*;
@@ -35,6 +40,12 @@ pub trait Provider: Send + Sync {
             .or_else(|| models.first())
     }
 
+    /// The credential this provider sends, when it can be swapped for
+    /// another stored account after a rate limit.
+    fn accounts(&self) -> Option<&AccountCredential> {
+        None
+    }
+
     async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse>;
 
     async fn chat_stream(
@@ -43,6 +54,74 @@ pub trait Provider: Send + Sync {
     ) -> Result<BoxStream<'static, Result<StreamEvent>>>;
 }
 
+/// The auth store key to rotate accounts under, when the provider's
+/// credential comes from several stored accounts.
+fn account_store_key<'a>(
+    name: &'a str,
+    entry: Option<&nyzhi_config::ProviderEntry>,
+) -> Option<&'a str> {
+    nyzhi_auth::has_rotatable_accounts(name, entry.and_then(|e| e.api_key.as_deref()))
+        .then_some(name)
+}
+
+/// Bedrock signs with AWS credentials unless a Bedrock API key is
+/// configured, set in the environment or stored.
+fn bedrock_provider(
+    name: &str,
+    entry: Option<&nyzhi_config::ProviderEntry>,
+) -> bedrock::BedrockProvider {
+    let provider = bedrock::BedrockProvider::new(
+        entry.and_then(|e| e.region.clone()),
+        entry.and_then(|e| e.base_url.clone()),
+        entry.and_then(|e| e.model.clone()),
+    );
+    match nyzhi_auth::resolve_credential(name, entry.and_then(|e| e.api_key.as_deref())) {
+        Ok(cred) => provider.with_api_key(cred.header_value()),
+        Err(_) => provider,
+    }
+}
+
+const AZURE_DEFAULT_API_VERSION: &str = "2024-10-21";
+
+/// Azure OpenAI routes by deployment, so the configured model is the
+/// deployment name and the base URL is the resource endpoint.
+fn azure_openai_provider(
+    api_key: String,
+    entry: Option<&nyzhi_config::ProviderEntry>,
+) -> Result<openai::OpenAIProvider> {
+    let endpoint = entry
+        .and_then(|e| e.base_url.clone())
+        .or_else(|| std::env::var("AZURE_OPENAI_ENDPOINT").ok())
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Azure OpenAI needs a resource endpoint. Set `base_url` for the provider or AZURE_OPENAI_ENDPOINT"
+            )
+        })?;
+    let api_version = entry
+        .and_then(|e| e.api_version.clone())
+        .or_else(|| std::env::var("AZURE_OPENAI_API_VERSION").ok())
+        .unwrap_or_else(|| AZURE_DEFAULT_API_VERSION.to_string());
+    Ok(
+        openai::OpenAIProvider::new(api_key, Some(endpoint), entry.and_then(|e| e.model.clone()))
+            .with_azure(api_version),
+    )
+}
+
+/// The Vertex styles authenticate with Application Default Credentials
+/// rather than an API key.
+fn vertex_provider(
+    style: &str,
+    entry: Option<&nyzhi_config::ProviderEntry>,
+) -> Result<Box<dyn Provider>> {
+    let endpoint = vertex::VertexEndpoint::from_config(entry)?;
+    let model = entry.and_then(|e| e.model.clone());
+    Ok(match style {
+        "vertex-anthropic" => Box::new(anthropic::AnthropicProvider::vertex(endpoint, model)),
+        _ => Box::new(gemini::GeminiProvider::vertex(endpoint, model)),
+    })
+}
+
 fn resolve_api_style(name: &str, config: &nyzhi_config::Config) -> String {
     if let Some(entry) = config.provider.entry(name) {
         if let Some(style) = &entry.api_style {
@@ -66,11 +145,14 @@ pub fn create_provider(name: &str, config: &nyzhi_config::Config) -> Result<Box<
             let base_url = entry.and_then(|e| e.base_url.clone()).or_else(|| {
                 nyzhi_config::find_provider_def(name).map(|d| d.default_base_url.to_string())
             });
-            Ok(Box::new(openai::OpenAIProvider::new(
-                cred.header_value(),
-                base_url,
-                entry.and_then(|e| e.model.clone()),
-            )))
+            Ok(Box::new(
+                openai::OpenAIProvider::new(
+                    cred.header_value(),
+                    base_url,
+                    entry.and_then(|e| e.model.clone()),
+                )
+                .with_accounts(account_store_key(name, entry)),
+            ))
         }
         "anthropic" => {
             let cred =
@@ -78,11 +160,14 @@ pub fn create_provider(name: &str, config: &nyzhi_config::Config) -> Result<Box<
             let base_url = entry.and_then(|e| e.base_url.clone()).or_else(|| {
                 nyzhi_config::find_provider_def(name).map(|d| d.default_base_url.to_string())
             });
-            Ok(Box::new(anthropic::AnthropicProvider::new(
-                cred.header_value(),
-                base_url,
-                entry.and_then(|e| e.model.clone()),
-            )))
+            Ok(Box::new(
+                anthropic::AnthropicProvider::new(
+                    cred.header_value(),
+                    base_url,
+                    entry.and_then(|e| e.model.clone()),
+                )
+                .with_accounts(account_store_key(name, entry)),
+            ))
         }
         "gemini" => {
             let cred =
@@ -90,11 +175,14 @@ pub fn create_provider(name: &str, config: &nyzhi_config::Config) -> Result<Box<
             let base_url = entry.and_then(|e| e.base_url.clone()).or_else(|| {
                 nyzhi_config::find_provider_def(name).map(|d| d.default_base_url.to_string())
             });
-            Ok(Box::new(gemini::GeminiProvider::with_credential(
-                cred,
-                base_url,
-                entry.and_then(|e| e.model.clone()),
-            )))
+            Ok(Box::new(
+                gemini::GeminiProvider::with_credential(
+                    cred,
+                    base_url,
+                    entry.and_then(|e| e.model.clone()),
+                )
+                .with_accounts(account_store_key(name, entry)),
+            ))
         }
         "claude-sdk" => {
             let cred =
@@ -114,11 +202,14 @@ pub fn create_provider(name: &str, config: &nyzhi_config::Config) -> Result<Box<
                     .entry("anthropic")
                     .and_then(|e| e.base_url.clone())
             });
-            Ok(Box::new(claude_sdk::ClaudeSDKProvider::new(
-                cred.header_value(),
-                base_url,
-                entry.and_then(|e| e.model.clone()),
-            )))
+            Ok(Box::new(
+                claude_sdk::ClaudeSDKProvider::new(
+                    cred.header_value(),
+                    base_url,
+                    entry.and_then(|e| e.model.clone()),
+                )
+                .with_accounts(account_store_key(name, entry)),
+            ))
         }
         "codex" => {
             let cred =
@@ -138,12 +229,25 @@ pub fn create_provider(name: &str, config: &nyzhi_config::Config) -> Result<Box<
                     .entry("openai")
                     .and_then(|e| e.base_url.clone())
             });
-            Ok(Box::new(codex::CodexProvider::new(
-                cred.header_value(),
-                base_url,
-                entry.and_then(|e| e.model.clone()),
-            )))
+            Ok(Box::new(
+                codex::CodexProvider::new(
+                    cred.header_value(),
+                    base_url,
+                    entry.and_then(|e| e.model.clone()),
+                )
+                .with_accounts(account_store_key(name, entry)),
+            ))
         }
+        "bedrock" => Ok(Box::new(bedrock_provider(name, entry))),
+        "azure-openai" => {
+            let cred =
+                nyzhi_auth::resolve_credential(name, entry.and_then(|e| e.api_key.as_deref()))?;
+            Ok(Box::new(
+                azure_openai_provider(cred.header_value(), entry)?
+                    .with_accounts(account_store_key(name, entry)),
+            ))
+        }
+        "vertex" | "vertex-anthropic" => vertex_provider(&style, entry),
         "cursor" => {
             let cred =
                 nyzhi_auth::resolve_credential(name, entry.and_then(|e| e.api_key.as_deref()))?;
@@ -205,11 +309,14 @@ pub async fn create_provider_async(
                     .entry("anthropic")
                     .and_then(|e| e.base_url.clone())
             });
-            return Ok(Box::new(claude_sdk::ClaudeSDKProvider::new(
-                cred.header_value(),
-                base_url,
-                entry.and_then(|e| e.model.clone()),
-            )));
+            return Ok(Box::new(
+                claude_sdk::ClaudeSDKProvider::new(
+                    cred.header_value(),
+                    base_url,
+                    entry.and_then(|e| e.model.clone()),
+                )
+                .with_accounts(account_store_key(name, entry)),
+            ));
         }
         "codex" => {
             let cred = nyzhi_auth::resolve_credential_async(
@@ -230,12 +337,17 @@ pub async fn create_provider_async(
                     .entry("openai")
                     .and_then(|e| e.base_url.clone())
             });
-            return Ok(Box::new(codex::CodexProvider::new(
-                cred.header_value(),
-                base_url,
-                entry.and_then(|e| e.model.clone()),
-            )));
+            return Ok(Box::new(
+                codex::CodexProvider::new(
+                    cred.header_value(),
+                    base_url,
+                    entry.and_then(|e| e.model.clone()),
+                )
+                .with_accounts(account_store_key(name, entry)),
+            ));
         }
+        "bedrock" => return Ok(Box::new(bedrock_provider(name, entry))),
+        "vertex" | "vertex-anthropic" => return vertex_provider(&style, entry),
         "cursor" => {
             let cred = nyzhi_auth::resolve_credential_async(
                 name,
@@ -280,21 +392,34 @@ pub async fn create_provider_async(
         .or_else(|| nyzhi_config::find_provider_def(name).map(|d| d.default_base_url.to_string()));
 
     match style.as_str() {
-        "openai" => Ok(Box::new(openai::OpenAIProvider::new(
-            cred.header_value(),
-            base_url,
-            entry.and_then(|e| e.model.clone()),
-        ))),
-        "anthropic" => Ok(Box::new(anthropic::AnthropicProvider::new(
-            cred.header_value(),
-            base_url,
-            entry.and_then(|e| e.model.clone()),
-        ))),
-        "gemini" => Ok(Box::new(gemini::GeminiProvider::with_credential(
-            cred,
-            base_url,
-            entry.and_then(|e| e.model.clone()),
-        ))),
+        "openai" => Ok(Box::new(
+            openai::OpenAIProvider::new(
+                cred.header_value(),
+                base_url,
+                entry.and_then(|e| e.model.clone()),
+            )
+            .with_accounts(account_store_key(name, entry)),
+        )),
+        "anthropic" => Ok(Box::new(
+            anthropic::AnthropicProvider::new(
+                cred.header_value(),
+                base_url,
+                entry.and_then(|e| e.model.clone()),
+            )
+            .with_accounts(account_store_key(name, entry)),
+        )),
+        "gemini" => Ok(Box::new(
+            gemini::GeminiProvider::with_credential(
+                cred,
+                base_url,
+                entry.and_then(|e| e.model.clone()),
+            )
+            .with_accounts(account_store_key(name, entry)),
+        )),
+        "azure-openai" => Ok(Box::new(
+            azure_openai_provider(cred.header_value(), entry)?
+                .with_accounts(account_store_key(name, entry)),
+        )),
         other => anyhow::bail!("Unsupported api_style '{other}' for provider '{name}'"),
     }
 }
@@ -310,6 +435,17 @@ impl ModelRegistry {
         models.insert("openai".into(), openai::default_models());
         models.insert("anthropic".into(), anthropic::default_models());
         models.insert("gemini".into(), gemini::default_models());
+        models.insert("bedrock".into(), bedrock::default_models());
+        let azure = openai::default_models()
+            .into_iter()
+            .map(|m| ModelInfo {
+                provider: "azure-openai".into(),
+                ..m
+            })
+            .collect();
+        models.insert("azure-openai".into(), azure);
+        models.insert("vertex".into(), vertex::gemini_models());
+        models.insert("vertex-anthropic".into(), vertex::anthropic_models());
         models.insert("deepseek".into(), deepseek_models());
         models.insert("groq".into(), groq_models());
         let kimi = kimi_models();
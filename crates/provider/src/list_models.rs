@@ -572,7 +572,7 @@ async fn resolve_copilot_token(stored_access_token: &str) -> (String, String) {
 
     if let Ok(Some(stored)) = nyzhi_auth::token_store::load_token("github-copilot") {
         let now = chrono::Utc::now().timestamp();
-        if stored.expires_at.map_or(false, |exp| now < exp - 60) {
+        if stored.expires_at.is_some_and(|exp| now < exp - 60) {
             return (stored.access_token, default_endpoint);
         }
         if let Some(ref refresh) = stored.refresh_token {
@@ -6,7 +6,7 @@ use serde_json::json;
 
 use crate::sse::parse_sse_stream;
 use crate::types::*;
-use crate::{Provider, ProviderError};
+use crate::{AccountCredential, Provider, ProviderError};
 
 const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
 const CODEX_BASE_URL: &str = "https://chatgpt.com/backend-api/codex";
@@ -68,22 +68,19 @@ pub fn default_models() -> Vec<ModelInfo> {
 pub struct OpenAIProvider {
     client: reqwest::Client,
     base_url: String,
-    api_key: String,
+    credential: AccountCredential,
     default_model: String,
     models: Vec<ModelInfo>,
     is_codex_sub: bool,
-    account_id: Option<String>,
     is_openrouter: bool,
+    /// Set for Azure OpenAI, which routes by deployment name and takes the
+    /// key in an `api-key` header.
+    azure_api_version: Option<String>,
 }
 
 impl OpenAIProvider {
     pub fn new(api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
         let is_codex_sub = api_key.starts_with("ey");
-        let account_id = if is_codex_sub {
-            extract_account_id_from_jwt(&api_key)
-        } else {
-            None
-        };
         let effective_base = if is_codex_sub
             && (base_url.is_none() || base_url.as_deref() == Some(DEFAULT_BASE_URL))
         {
@@ -98,15 +95,23 @@ impl OpenAIProvider {
                 .build()
                 .unwrap_or_default(),
             base_url: effective_base,
-            api_key,
+            credential: AccountCredential::new(nyzhi_auth::Credential::ApiKey(api_key)),
             default_model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
             models: default_models(),
             is_codex_sub,
-            account_id,
             is_openrouter,
+            azure_api_version: None,
         }
     }
 
+    /// Talk to an Azure OpenAI resource at `base_url`, treating model ids
+    /// as deployment names.
+    pub fn with_azure(mut self, api_version: String) -> Self {
+        self.is_codex_sub = false;
+        self.azure_api_version = Some(api_version);
+        self
+    }
+
     pub fn with_models(mut self, models: Vec<ModelInfo>) -> Self {
         if !models.is_empty() {
             self.models = models;
@@ -114,6 +119,12 @@ impl OpenAIProvider {
         self
     }
 
+    /// Rotate through the accounts stored under `store_key` on rate limits.
+    pub fn with_accounts(mut self, store_key: Option<&str>) -> Self {
+        self.credential = self.credential.with_accounts(store_key);
+        self
+    }
+
     pub fn from_config(config: &nyzhi_config::Config) -> Result<Self> {
         let entry = config.provider.entry("openai");
         let cred =
@@ -125,11 +136,27 @@ impl OpenAIProvider {
         ))
     }
 
+    fn chat_completions_url(&self, model: &str) -> String {
+        match &self.azure_api_version {
+            Some(version) => format!(
+                "{}/openai/deployments/{model}/chat/completions?api-version={version}",
+                self.base_url.trim_end_matches('/')
+            ),
+            None => format!("{}/chat/completions", self.base_url),
+        }
+    }
+
     fn chat_request(&self, url: &str) -> reqwest::RequestBuilder {
-        let mut req = self
-            .client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key));
+        if self.azure_api_version.is_some() {
+            return self
+                .client
+                .post(url)
+                .header("api-key", self.credential.header_value());
+        }
+        let mut req = self.client.post(url).header(
+            "Authorization",
+            format!("Bearer {}", self.credential.header_value()),
+        );
         if self.is_openrouter {
             req = req
                 .header("HTTP-Referer", "https://github.com/nyzhi/code")
@@ -220,13 +247,14 @@ impl OpenAIProvider {
     }
 
     fn build_codex_request(&self, model: &str) -> reqwest::RequestBuilder {
+        let token = self.credential.header_value();
         let mut req = self
             .client
             .post(format!("{}/responses", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Authorization", format!("Bearer {token}"))
             .header("OpenAI-Beta", "responses=experimental")
             .header("originator", "codex_cli_rs");
-        if let Some(ref acct) = self.account_id {
+        if let Some(acct) = extract_account_id_from_jwt(&token) {
             req = req.header("chatgpt-account-id", acct);
         }
         tracing::debug!(model, base_url = %self.base_url, "Codex sub request");
@@ -274,6 +302,10 @@ impl Provider for OpenAIProvider {
         &self.models
     }
 
+    fn accounts(&self) -> Option<&AccountCredential> {
+        self.credential.is_rotatable().then_some(&self.credential)
+    }
+
     async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
         let model = if request.model.is_empty() {
             &self.default_model
@@ -300,7 +332,7 @@ impl Provider for OpenAIProvider {
             body["tools"] = json!(self.build_tools_chat(&request.tools));
         }
 
-        let url = format!("{}/chat/completions", self.base_url);
+        let url = self.chat_completions_url(model);
         let resp = self.chat_request(&url).json(&body).send().await?;
 
         let status = resp.status();
@@ -427,7 +459,7 @@ impl Provider for OpenAIProvider {
             body["tools"] = json!(self.build_tools_chat(&request.tools));
         }
 
-        let url = format!("{}/chat/completions", self.base_url);
+        let url = self.chat_completions_url(model);
         let resp = self.chat_request(&url).json(&body).send().await?;
 
         let status = resp.status();
@@ -728,3 +760,27 @@ impl OpenAIProvider {
         Ok(Box::pin(event_stream))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn azure_routes_by_deployment_with_api_key_header() {
+        let provider = OpenAIProvider::new(
+            "azure-key".into(),
+            Some("https://acme.openai.azure.com/".into()),
+            None,
+        )
+        .with_azure("2024-10-21".into());
+        let url = provider.chat_completions_url("gpt-4o-prod");
+        assert_eq!(
+            url,
+            "https://acme.openai.azure.com/openai/deployments/gpt-4o-prod/chat/completions?api-version=2024-10-21"
+        );
+
+        let request = provider.chat_request(&url).build().unwrap();
+        assert_eq!(request.headers()["api-key"], "azure-key");
+        assert!(request.headers().get("authorization").is_none());
+    }
+}
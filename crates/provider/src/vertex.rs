@@ -0,0 +1,143 @@
+//! Google Vertex AI endpoints shared by the Gemini and Anthropic providers:
+//! regional URLs, project resolution and ADC access tokens.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use nyzhi_auth::gcp::AccessTokenSource;
+
+use crate::types::*;
+
+const DEFAULT_REGION: &str = "global";
+
+#[derive(Clone)]
+pub struct VertexEndpoint {
+    tokens: Arc<AccessTokenSource>,
+    base_url: String,
+    project: String,
+    region: String,
+}
+
+impl VertexEndpoint {
+    pub fn new(
+        tokens: Arc<AccessTokenSource>,
+        project: String,
+        region: String,
+        base_url: Option<String>,
+    ) -> Self {
+        let base_url = base_url.unwrap_or_else(|| default_base_url(&region));
+        Self {
+            tokens,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            project,
+            region,
+        }
+    }
+
+    /// Credentials from ADC; the project from config, `GOOGLE_CLOUD_PROJECT`
+    /// or the credentials file, and the region from config,
+    /// `GOOGLE_CLOUD_LOCATION` or `global`.
+    pub fn from_config(entry: Option<&nyzhi_config::ProviderEntry>) -> Result<Self> {
+        let tokens = AccessTokenSource::from_adc()?;
+        let project = entry
+            .and_then(|e| e.project.clone())
+            .or_else(|| env_non_empty("GOOGLE_CLOUD_PROJECT"))
+            .or_else(|| tokens.credentials().project_id().map(str::to_string))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No Google Cloud project configured. Set `project` for the provider or GOOGLE_CLOUD_PROJECT"
+                )
+            })?;
+        let region = entry
+            .and_then(|e| e.region.clone())
+            .or_else(|| env_non_empty("GOOGLE_CLOUD_LOCATION"))
+            .unwrap_or_else(|| DEFAULT_REGION.to_string());
+        Ok(Self::new(
+            Arc::new(tokens),
+            project,
+            region,
+            entry.and_then(|e| e.base_url.clone()),
+        ))
+    }
+
+    pub fn model_url(&self, publisher: &str, model: &str, method: &str) -> String {
+        format!(
+            "{}/projects/{}/locations/{}/publishers/{publisher}/models/{model}:{method}",
+            self.base_url, self.project, self.region
+        )
+    }
+
+    pub async fn access_token(&self) -> Result<String> {
+        self.tokens.token().await
+    }
+}
+
+fn default_base_url(region: &str) -> String {
+    if region == DEFAULT_REGION {
+        "https://aiplatform.googleapis.com/v1".to_string()
+    } else {
+        format!("https://{region}-aiplatform.googleapis.com/v1")
+    }
+}
+
+fn env_non_empty(var: &str) -> Option<String> {
+    std::env::var(var).ok().filter(|v| !v.is_empty())
+}
+
+/// Gemini models as served by Vertex.
+pub fn gemini_models() -> Vec<ModelInfo> {
+    crate::gemini::default_models()
+        .into_iter()
+        .map(|m| ModelInfo {
+            provider: "vertex".into(),
+            ..m
+        })
+        .collect()
+}
+
+/// Claude models under their Vertex model ids.
+pub fn anthropic_models() -> Vec<ModelInfo> {
+    crate::anthropic::default_models()
+        .into_iter()
+        .filter_map(|m| {
+            let id = match m.id.as_str() {
+                "claude-opus-4-6-20260205" => "claude-opus-4-6",
+                "claude-sonnet-4-6-20260217" => "claude-sonnet-4-6",
+                "claude-haiku-4-5-20251022" => "claude-haiku-4-5@20251001",
+                _ => return None,
+            };
+            Some(ModelInfo {
+                id: id.into(),
+                provider: "vertex-anthropic".into(),
+                ..m
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(region: &str) -> VertexEndpoint {
+        let tokens = AccessTokenSource::new(
+            serde_json::from_str(
+                r#"{"type": "authorized_user", "client_id": "id", "client_secret": "secret", "refresh_token": "rt"}"#,
+            )
+            .unwrap(),
+        );
+        VertexEndpoint::new(Arc::new(tokens), "acme-ml".into(), region.into(), None)
+    }
+
+    #[test]
+    fn model_urls_use_regional_hosts() {
+        assert_eq!(
+            endpoint("europe-west4").model_url("google", "gemini-3-flash", "generateContent"),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/acme-ml/locations/europe-west4/publishers/google/models/gemini-3-flash:generateContent"
+        );
+        assert_eq!(
+            endpoint("global").model_url("anthropic", "claude-sonnet-4-6", "rawPredict"),
+            "https://aiplatform.googleapis.com/v1/projects/acme-ml/locations/global/publishers/anthropic/models/claude-sonnet-4-6:rawPredict"
+        );
+    }
+}
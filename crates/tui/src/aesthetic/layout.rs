@@ -19,9 +19,7 @@ pub fn compute(
     input_lines: u16,
 ) -> Regions {
     let header_h = if show_header { HEADER_H } else { 0 };
-    let input_h = (input_lines + STATUS_BAR_H + SP_1)
-        .max(INPUT_MIN_H)
-        .min(INPUT_MAX_H);
+    let input_h = (input_lines + STATUS_BAR_H + SP_1).clamp(INPUT_MIN_H, INPUT_MAX_H);
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
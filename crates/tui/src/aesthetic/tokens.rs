@@ -1,5 +1,5 @@
-/// Spacing scale. Terminal cells are ~2:1 (taller than wide),
-/// so horizontal values are doubled for visual symmetry.
+//! Spacing scale. Terminal cells are ~2:1 (taller than wide),
+//! so horizontal values are doubled for visual symmetry.
 
 pub const SP_0: u16 = 0;
 pub const SP_1: u16 = 1;
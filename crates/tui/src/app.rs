@@ -11,7 +11,8 @@ use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::ExecutableCommand;
 use nyzhi_core::agent::{AgentConfig, AgentEvent, SessionUsage};
 use nyzhi_core::conversation::Thread;
-use nyzhi_core::tools::{ToolContext, ToolRegistry};
+use nyzhi_core::tools::permission::{ApprovalResponse, ApprovalScope};
+use nyzhi_core::tools::{CancellationToken, ToolContext, ToolRegistry};
 use nyzhi_core::workspace::WorkspaceContext;
 use nyzhi_provider::{MessageContent, Provider};
 use ratatui::prelude::*;
@@ -113,10 +114,16 @@ pub struct TurnResult {
     pub result: anyhow::Result<()>,
 }
 
+/// How long a cancelled turn gets to stop its tools and record partial
+/// output before it is abandoned and the thread rolled back.
+const CANCEL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 pub struct ForegroundTask {
     pub join_handle: tokio::task::JoinHandle<TurnResult>,
     pub thread_snapshot: Thread,
     pub label: String,
+    pub cancel: CancellationToken,
+    pub cancelled_at: Option<std::time::Instant>,
 }
 
 pub struct BackgroundTask {
@@ -124,6 +131,16 @@ pub struct BackgroundTask {
     pub label: String,
     pub join_handle: tokio::task::JoinHandle<TurnResult>,
     pub started: std::time::Instant,
+    pub cancel: CancellationToken,
+}
+
+impl BackgroundTask {
+    /// Stop the task now. Its shell commands and sub-agents are stopped
+    /// through the token; the turn itself is dropped.
+    pub fn kill(&self) {
+        self.cancel.cancel();
+        self.join_handle.abort();
+    }
 }
 
 pub struct App {
@@ -144,11 +161,17 @@ pub struct App {
     pub session_start: std::time::Instant,
     pub workspace: WorkspaceContext,
     pub mcp_manager: Option<std::sync::Arc<nyzhi_core::mcp::McpManager>>,
-    pub pending_approval:
-        Option<std::sync::Arc<tokio::sync::Mutex<Option<tokio::sync::oneshot::Sender<bool>>>>>,
+    pub pending_approval: Option<
+        std::sync::Arc<
+            tokio::sync::Mutex<
+                Option<tokio::sync::oneshot::Sender<nyzhi_core::tools::permission::ApprovalResponse>>,
+            >,
+        >,
+    >,
     pub pending_approval_context: Option<(String, String)>,
+    /// Rule remembered if the pending call is approved beyond this once.
+    pub pending_approval_rule: Option<String>,
     pub approval_cursor: usize,
-    pub session_approved_tools: std::collections::HashSet<String>,
     pub pending_images: Vec<PendingImage>,
     pub trust_mode: nyzhi_config::TrustMode,
     pub selector: Option<crate::components::selector::SelectorState>,
@@ -163,6 +186,8 @@ pub struct App {
     pub turn_start: Option<std::time::Instant>,
     pub last_prompt: Option<String>,
     pub initial_session: Option<(Thread, nyzhi_core::session::SessionMeta)>,
+    /// Warnings gathered before launch, e.g. plugin conflicts.
+    pub startup_notices: Vec<String>,
     pub hooks_config: Vec<nyzhi_config::HookConfig>,
     pub hook_rx: Option<tokio::sync::mpsc::UnboundedReceiver<String>>,
     hook_tx: Option<tokio::sync::mpsc::UnboundedSender<String>>,
@@ -218,6 +243,8 @@ pub struct App {
     pub last_turn_duration: Option<f64>,
     pub subagent_model_overrides: nyzhi_core::agent_roles::SubagentModelOverrides,
     pub shared_context: std::sync::Arc<tokio::sync::Mutex<nyzhi_core::context_briefing::SharedContext>>,
+    pub processes: nyzhi_core::processes::ProcessManagerHandle,
+    pub process_panel: Option<crate::components::process_panel::ProcessPanelState>,
     pub config: nyzhi_config::Config,
     pub logo_anim: crate::logo_anim::LogoAnimation,
 }
@@ -249,8 +276,8 @@ impl App {
             mcp_manager: None,
             pending_approval: None,
             pending_approval_context: None,
+            pending_approval_rule: None,
             approval_cursor: 0,
-            session_approved_tools: std::collections::HashSet::new(),
             pending_images: Vec::new(),
             trust_mode: nyzhi_config::TrustMode::Off,
             selector: None,
@@ -267,6 +294,7 @@ impl App {
             turn_start: None,
             last_prompt: None,
             initial_session: None,
+            startup_notices: Vec::new(),
             hooks_config: Vec::new(),
             hook_rx: None,
             hook_tx: None,
@@ -320,6 +348,8 @@ impl App {
             last_turn_duration: None,
             subagent_model_overrides: nyzhi_core::agent_roles::SubagentModelOverrides::new(),
             shared_context: std::sync::Arc::new(tokio::sync::Mutex::new(nyzhi_core::context_briefing::SharedContext::default())),
+            processes: nyzhi_core::processes::shared_manager(),
+            process_panel: None,
             config: nyzhi_config::Config::default(),
             logo_anim: crate::logo_anim::LogoAnimation::new(),
         }
@@ -378,6 +408,27 @@ impl App {
         self.search_match_idx = 0;
     }
 
+    /// Show the messages of the thread's active branch.
+    pub fn push_thread_items(&mut self, thread: &Thread) {
+        for msg in thread.messages() {
+            let role = match msg.role {
+                nyzhi_provider::Role::User => "user",
+                nyzhi_provider::Role::Assistant => "assistant",
+                _ => "system",
+            };
+            let mut text = msg.content.as_text().to_string();
+            if msg.content.has_images() {
+                text.push_str("\n[image attached]");
+            }
+            if !text.is_empty() {
+                self.items.push(DisplayItem::Message {
+                    role: role.to_string(),
+                    content: text,
+                });
+            }
+        }
+    }
+
     fn try_save_session(&self, thread: Option<&nyzhi_core::conversation::Thread>) {
         if self.ephemeral {
             return;
@@ -393,6 +444,57 @@ impl App {
         }
     }
 
+    /// Move the streamed text into the transcript and return to input.
+    fn finish_stream(&mut self) {
+        if !self.current_stream.is_empty() {
+            self.items.push(DisplayItem::Message {
+                role: "assistant".to_string(),
+                content: std::mem::take(&mut self.current_stream),
+            });
+        }
+        self.thinking_stream.clear();
+        self.stream_start = None;
+        self.stream_token_count = 0;
+        self.turn_start = None;
+        self.mode = AppMode::Input;
+    }
+
+    fn handle_process_panel_key(&mut self, key: crossterm::event::KeyEvent) {
+        let Some(panel) = self.process_panel.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.process_panel = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => panel.move_up(),
+            KeyCode::Down | KeyCode::Char('j') => panel.move_down(),
+            KeyCode::Char('x') => {
+                if let Some(id) = panel.selected().map(|p| p.id) {
+                    let _ = self.processes.kill(id);
+                    panel.refresh(&self.processes);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn report_cancelled(&mut self, project_root: &std::path::Path) {
+        if let Some(mut ap) = self.autopilot.take() {
+            ap.cancel();
+            let _ = nyzhi_core::autopilot::save_state(project_root, &ap);
+            self.items.push(DisplayItem::Message {
+                role: "system".to_string(),
+                content: "Autopilot cancelled.".to_string(),
+            });
+        } else {
+            self.items.push(DisplayItem::Message {
+                role: "system".to_string(),
+                content: "Cancelled.".to_string(),
+            });
+        }
+    }
+
     pub async fn run(
         &mut self,
         mut provider: Option<std::sync::Arc<dyn Provider>>,
@@ -409,6 +511,12 @@ impl App {
                 content: format!("Post-update warning: {w}"),
             });
         }
+        for notice in std::mem::take(&mut self.startup_notices) {
+            self.items.push(DisplayItem::Message {
+                role: "system".to_string(),
+                content: notice,
+            });
+        }
 
         if self.checkpoint_manager.is_none()
             && nyzhi_core::git_undo::is_git_repo(&self.workspace.project_root)
@@ -454,6 +562,8 @@ impl App {
             let index_options = nyzhi_index::IndexOptions {
                 embedding_mode: config.index.embedding.clone(),
                 embedding_model: config.index.embedding_model.clone(),
+                embedding_url: config.index.embedding_url.clone(),
+                embedding_model_path: config.index.embedding_model_path.clone(),
                 exclude: config.index.exclude.clone(),
                 api_keys,
             };
@@ -463,6 +573,11 @@ impl App {
             ) {
                 Ok(index) => {
                     let handle = std::sync::Arc::new(index);
+                    if config.index.watch {
+                        if let Err(e) = handle.start_watching() {
+                            tracing::debug!("Failed to watch project for index updates: {e}");
+                        }
+                    }
                     self.codebase_index = Some(handle.clone());
                     self.index_progress = Some((0, 0, false));
                     tokio::spawn(async move {
@@ -491,23 +606,7 @@ impl App {
         let (event_tx, mut event_rx) = broadcast::channel::<AgentEvent>(256);
         let mut thread: Option<Thread> = Some(
             if let Some((loaded_thread, loaded_meta)) = self.initial_session.take() {
-                for msg in loaded_thread.messages() {
-                    let role = match msg.role {
-                        nyzhi_provider::Role::User => "user",
-                        nyzhi_provider::Role::Assistant => "assistant",
-                        _ => "system",
-                    };
-                    let mut text = msg.content.as_text().to_string();
-                    if msg.content.has_images() {
-                        text.push_str("\n[image attached]");
-                    }
-                    if !text.is_empty() {
-                        self.items.push(DisplayItem::Message {
-                            role: role.to_string(),
-                            content: text,
-                        });
-                    }
-                }
+                self.push_thread_items(&loaded_thread);
                 self.items.push(DisplayItem::Message {
                     role: "system".to_string(),
                     content: format!(
@@ -550,7 +649,7 @@ impl App {
                 })
         });
 
-        let supports_vision = provider.as_ref().map_or(false, |p| {
+        let supports_vision = provider.as_ref().is_some_and(|p| {
             model_info_idx
                 .map(|i| p.supported_models()[i].supports_vision)
                 .unwrap_or(false)
@@ -588,6 +687,7 @@ impl App {
             compact_instructions: config.agent.compact_instructions.clone(),
             auto_context: config.index.auto_context,
             auto_context_chunks: config.index.auto_context_chunks,
+            fallbacks: nyzhi_core::fallback::build_chain(config).await,
             ..AgentConfig::default()
         };
         self.trust_mode = agent_config.trust.mode.clone();
@@ -612,7 +712,7 @@ impl App {
             });
         }
 
-        let tool_ctx = ToolContext {
+        let mut tool_ctx = ToolContext {
             session_id: sid,
             cwd,
             project_root: self.workspace.project_root.clone(),
@@ -626,12 +726,22 @@ impl App {
             todo_store: Some(
                 self.todo_store
                     .clone()
-                    .unwrap_or_else(|| nyzhi_core::tools::todo::shared_store()),
+                    .unwrap_or_else(nyzhi_core::tools::todo::shared_store),
             ),
             index: self.codebase_index.clone(),
             sandbox_level: nyzhi_config::SandboxLevel::default(),
+            sandbox_config: (&config.shell.sandbox).into(),
+            approvals: Some(nyzhi_core::tools::permission::shared_memory(
+                &self.workspace.project_root,
+                config.agent.trust.remember_approvals,
+            )),
             subagent_model_overrides: Some(self.subagent_model_overrides.clone()),
             shared_context: Some(self.shared_context.clone()),
+            processes: Some(self.processes.clone()),
+            shell: config.shell.persistent.then(|| {
+                nyzhi_core::persistent_shell::PersistentShell::handle((&config.shell).into())
+            }),
+            cancel: CancellationToken::new(),
         };
 
         let agent_manager = if let Some(ref p) = provider {
@@ -683,6 +793,9 @@ impl App {
         }
 
         let registry = Arc::new(registry);
+        if let Some(mgr) = &self.mcp_manager {
+            nyzhi_core::mcp::tool_adapter::keep_registry_in_sync(mgr, &registry);
+        }
 
         // Background update check
         let update_config = config.update.clone();
@@ -748,21 +861,21 @@ impl App {
             }
 
             if let Some(ref idx) = self.codebase_index {
-                if let Some((_, _, complete)) = self.index_progress {
-                    if !complete {
-                        use futures::FutureExt;
-                        if let Some(p) = idx.progress().now_or_never() {
-                            self.index_progress = Some((p.indexed, p.total, p.complete));
-                            if p.complete && !p.errors.is_empty() {
-                                let n = p.errors.len();
-                                let first = &p.errors[0];
-                                let msg = if n == 1 {
-                                    format!("Index: 1 file skipped ({})", first.1)
-                                } else {
-                                    format!("Index: {} files skipped (first: {})", n, first.1)
-                                };
-                                self.index_error = Some(msg);
-                            }
+                // Keep polling after the initial build: the watcher re-indexes
+                // changed files and reports through the same progress.
+                if let Some((_, _, was_complete)) = self.index_progress {
+                    use futures::FutureExt;
+                    if let Some(p) = idx.progress().now_or_never() {
+                        self.index_progress = Some((p.indexed, p.total, p.complete));
+                        if p.complete && !was_complete && !p.errors.is_empty() {
+                            let n = p.errors.len();
+                            let first = &p.errors[0];
+                            let msg = if n == 1 {
+                                format!("Index: 1 file skipped ({})", first.1)
+                            } else {
+                                format!("Index: {} files skipped (first: {})", n, first.1)
+                            };
+                            self.index_error = Some(msg);
                         }
                     }
                 }
@@ -806,6 +919,8 @@ impl App {
                                 }
                                 _ => {}
                             }
+                        } else if self.process_panel.is_some() {
+                            self.handle_process_panel_key(key);
                         } else if self.text_prompt.is_some() {
                             self.handle_text_prompt_key(key, config).await;
                         } else if self.settings_panel.is_some() {
@@ -857,6 +972,7 @@ impl App {
                                     label: fg.label,
                                     join_handle: fg.join_handle,
                                     started: std::time::Instant::now(),
+                                    cancel: fg.cancel,
                                 });
                                 if !self.current_stream.is_empty() {
                                     self.current_stream.clear();
@@ -874,40 +990,23 @@ impl App {
                         } else if key.code == KeyCode::Esc
                             && matches!(self.mode, AppMode::Streaming)
                         {
-                            if let Some(fg) = self.foreground_task.take() {
-                                fg.join_handle.abort();
-                                thread = Some(fg.thread_snapshot);
-                            }
-                            if !self.current_stream.is_empty() {
-                                self.items.push(DisplayItem::Message {
-                                    role: "assistant".to_string(),
-                                    content: std::mem::take(&mut self.current_stream),
-                                });
-                            }
-                            self.thinking_stream.clear();
-                            self.stream_start = None;
-                            self.stream_token_count = 0;
-                            self.turn_start = None;
-                            self.mode = AppMode::Input;
-                            self.try_save_session(thread.as_ref());
-                            if self.autopilot.is_some() {
-                                if let Some(ref mut ap) = self.autopilot {
-                                    ap.cancel();
-                                    let _ = nyzhi_core::autopilot::save_state(
-                                        &tool_ctx.project_root,
-                                        ap,
-                                    );
+                            match self.foreground_task.as_mut() {
+                                Some(fg) if fg.cancelled_at.is_none() => {
+                                    // The turn stops its tools and records what
+                                    // it has; completion is handled below.
+                                    fg.cancel.cancel();
+                                    fg.cancelled_at = Some(std::time::Instant::now());
+                                    self.items.push(DisplayItem::Message {
+                                        role: "system".to_string(),
+                                        content: "Cancelling...".to_string(),
+                                    });
+                                }
+                                Some(_) => {}
+                                None => {
+                                    self.finish_stream();
+                                    self.try_save_session(thread.as_ref());
+                                    self.report_cancelled(&tool_ctx.project_root);
                                 }
-                                self.autopilot = None;
-                                self.items.push(DisplayItem::Message {
-                                    role: "system".to_string(),
-                                    content: "Autopilot cancelled.".to_string(),
-                                });
-                            } else {
-                                self.items.push(DisplayItem::Message {
-                                    role: "system".to_string(),
-                                    content: "Cancelled.".to_string(),
-                                });
                             }
                         } else if key.code == KeyCode::Char('f')
                             && key.modifiers.contains(KeyModifiers::CONTROL)
@@ -917,7 +1016,7 @@ impl App {
                             if self.ctrl_f_pending {
                                 let count = self.background_tasks.len();
                                 for bg in self.background_tasks.drain(..) {
-                                    bg.join_handle.abort();
+                                    bg.kill();
                                 }
                                 self.ctrl_f_pending = false;
                                 self.items.push(DisplayItem::Message {
@@ -937,35 +1036,45 @@ impl App {
                         } else if matches!(self.mode, AppMode::AwaitingApproval) {
                             match key.code {
                                 KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {
-                                    self.respond_approval(false).await;
+                                    self.respond_approval(ApprovalResponse::once(false)).await;
                                 }
                                 KeyCode::Char('y') | KeyCode::Char('Y') => {
-                                    self.respond_approval(true).await;
+                                    self.respond_approval(ApprovalResponse::once(true)).await;
                                 }
-                                KeyCode::Left => {
-                                    if self.approval_cursor > 0 {
-                                        self.approval_cursor -= 1;
-                                    }
+                                KeyCode::Char('s') | KeyCode::Char('S') => {
+                                    self.respond_approval(ApprovalResponse::remember(
+                                        ApprovalScope::Session,
+                                    ))
+                                    .await;
                                 }
-                                KeyCode::Right => {
-                                    if self.approval_cursor < 2 {
-                                        self.approval_cursor += 1;
-                                    }
+                                KeyCode::Char('p') | KeyCode::Char('P') => {
+                                    self.respond_approval(ApprovalResponse::remember(
+                                        ApprovalScope::Project,
+                                    ))
+                                    .await;
+                                }
+                                KeyCode::Char('a') | KeyCode::Char('A') => {
+                                    self.respond_approval(ApprovalResponse::remember(
+                                        ApprovalScope::Always,
+                                    ))
+                                    .await;
+                                }
+                                KeyCode::Left if self.approval_cursor > 0 => {
+                                    self.approval_cursor -= 1;
+                                }
+                                KeyCode::Right if self.approval_cursor < 4 => {
+                                    self.approval_cursor += 1;
+                                }
+                                KeyCode::Enter => {
+                                    let response = match self.approval_cursor {
+                                        0 => ApprovalResponse::once(true),
+                                        1 => ApprovalResponse::once(false),
+                                        2 => ApprovalResponse::remember(ApprovalScope::Session),
+                                        3 => ApprovalResponse::remember(ApprovalScope::Project),
+                                        _ => ApprovalResponse::remember(ApprovalScope::Always),
+                                    };
+                                    self.respond_approval(response).await;
                                 }
-                                KeyCode::Enter => match self.approval_cursor {
-                                    0 => self.respond_approval(true).await,
-                                    1 => self.respond_approval(false).await,
-                                    2 => {
-                                        if let Some((ref tool, _)) =
-                                            self.pending_approval_context
-                                        {
-                                            self.session_approved_tools
-                                                .insert(tool.clone());
-                                        }
-                                        self.respond_approval(true).await;
-                                    }
-                                    _ => {}
-                                },
                                 _ => {}
                             }
                         } else {
@@ -1126,6 +1235,11 @@ impl App {
                     continue;
                 };
                 let mi_c = model_info_idx.map(|i| provider.supported_models()[i].clone());
+                if let Some(mgr) = &self.mcp_manager {
+                    // Servers' sampling requests follow the model of the latest turn.
+                    let model = mi_c.as_ref().map_or(self.model_name.as_str(), |m| m.id.as_str());
+                    mgr.set_sampling_model(provider.clone(), model);
+                }
                 if req.is_background {
                     let bg_thread = thread.as_ref().unwrap().clone();
                     let bg_usage = self.session_usage.clone();
@@ -1134,7 +1248,12 @@ impl App {
                     let registry_c = registry.clone();
                     let mut config_c = agent_config.clone();
                     config_c.plan_mode = self.plan_mode || config_c.plan_mode;
-                    let tool_ctx_c = tool_ctx.clone();
+                    let cancel = CancellationToken::new();
+                    let tool_ctx_c = ToolContext {
+                        cancel: cancel.clone(),
+                        shell: tool_ctx.shell.as_ref().map(|s| s.fork()),
+                        ..tool_ctx.clone()
+                    };
                     let join_handle = tokio::spawn(async move {
                         let mut t = bg_thread;
                         let mut u = bg_usage;
@@ -1178,6 +1297,7 @@ impl App {
                         label: req.label.clone(),
                         join_handle,
                         started: std::time::Instant::now(),
+                        cancel,
                     });
                     self.items.push(DisplayItem::Message {
                         role: "system".to_string(),
@@ -1211,7 +1331,11 @@ impl App {
                         config_c.max_steps = config_c.max_steps.max(200);
                     }
                     let event_tx_c = event_tx.clone();
-                    let tool_ctx_c = tool_ctx.clone();
+                    let cancel = CancellationToken::new();
+                    let tool_ctx_c = ToolContext {
+                        cancel: cancel.clone(),
+                        ..tool_ctx.clone()
+                    };
                     let join_handle = tokio::spawn(async move {
                         let mut t = fg_thread;
                         let mut u = fg_usage;
@@ -1252,47 +1376,59 @@ impl App {
                         join_handle,
                         thread_snapshot: snapshot,
                         label: req.label,
+                        cancel,
+                        cancelled_at: None,
                     });
                 }
             }
 
             // --- Foreground task completion ---
-            if self
-                .foreground_task
-                .as_ref()
-                .is_some_and(|f| f.join_handle.is_finished())
+            let cancel_expired = self.foreground_task.as_ref().is_some_and(|f| {
+                f.cancelled_at
+                    .is_some_and(|at| at.elapsed() >= CANCEL_TIMEOUT)
+            });
+            if cancel_expired
+                || self
+                    .foreground_task
+                    .as_ref()
+                    .is_some_and(|f| f.join_handle.is_finished())
             {
                 let fg = self.foreground_task.take().unwrap();
-                match fg.join_handle.await {
-                    Ok(result) => {
-                        self.session_usage = result.session_usage;
-                        thread = Some(result.thread);
-                        if let Err(e) = &result.result {
+                let cancelled = fg.cancel.is_cancelled();
+                if fg.join_handle.is_finished() {
+                    match fg.join_handle.await {
+                        Ok(result) => {
+                            self.session_usage = result.session_usage;
+                            thread = Some(result.thread);
+                            if let Err(e) = &result.result {
+                                self.items.push(DisplayItem::Message {
+                                    role: "system".to_string(),
+                                    content: format!("Turn error: {e}"),
+                                });
+                            }
+                        }
+                        Err(e) => {
                             self.items.push(DisplayItem::Message {
                                 role: "system".to_string(),
-                                content: format!("Turn error: {e}"),
+                                content: format!("Task panicked: {e}"),
                             });
                         }
                     }
-                    Err(e) => {
-                        self.items.push(DisplayItem::Message {
-                            role: "system".to_string(),
-                            content: format!("Task panicked: {e}"),
-                        });
-                    }
-                }
-                if !self.current_stream.is_empty() {
-                    self.items.push(DisplayItem::Message {
-                        role: "assistant".to_string(),
-                        content: std::mem::take(&mut self.current_stream),
-                    });
+                } else {
+                    // The turn did not wind down in time; drop it and roll
+                    // back to the thread as it was before the turn.
+                    fg.join_handle.abort();
+                    thread = Some(fg.thread_snapshot);
                 }
-                self.thinking_stream.clear();
-                self.stream_start = None;
-                self.stream_token_count = 0;
-                self.turn_start = None;
-                self.mode = AppMode::Input;
+                self.finish_stream();
                 self.try_save_session(thread.as_ref());
+                if cancelled {
+                    self.report_cancelled(&tool_ctx.project_root);
+                }
+            }
+
+            if let Some(panel) = self.process_panel.as_mut() {
+                panel.refresh(&self.processes);
             }
 
             // --- Background task completion ---
@@ -1476,11 +1612,13 @@ impl App {
 
                                 let fmt_path = rel.clone();
                                 let fmt_root = self.workspace.project_root.clone();
+                                let fmt_launcher = tool_ctx.launcher();
                                 if let Some(tx) = self.hook_tx.clone() {
                                     tokio::spawn(async move {
                                         if let Some(r) = nyzhi_core::formatter::format_file_async(
                                             fmt_path,
                                             fmt_root,
+                                            fmt_launcher,
                                         ).await {
                                             if r.success {
                                                 let _ = tx.send(format!("Formatted with {}", r.formatter));
@@ -1540,6 +1678,7 @@ impl App {
                             let tracker = change_tracker.clone();
                             let hooks = self.hooks_config.clone();
                             let hook_cwd = tool_ctx.cwd.clone();
+                            let launcher = tool_ctx.launcher();
                             if let Some(tx) = self.hook_tx.clone() {
                                 tokio::spawn(async move {
                                     let changed_file = {
@@ -1548,7 +1687,7 @@ impl App {
                                     };
                                     if let Some(file) = changed_file {
                                         let results = nyzhi_core::hooks::run_after_edit_hooks(
-                                            &hooks, &file, &hook_cwd,
+                                            &hooks, &file, &hook_cwd, &launcher,
                                         )
                                         .await;
                                         for r in results {
@@ -1584,29 +1723,24 @@ impl App {
                     AgentEvent::ApprovalRequest {
                         tool_name,
                         args_summary,
+                        suggested_rules,
                         respond,
                     } => {
-                        if self.session_approved_tools.contains(&tool_name) {
-                            let mut guard = respond.lock().await;
-                            if let Some(sender) = guard.take() {
-                                let _ = sender.send(true);
-                            }
-                        } else {
-                            if let Some(DisplayItem::ToolCall {
-                                name: ref item_name,
-                                status,
-                                ..
-                            }) = self.items.last_mut()
-                            {
-                                if *item_name == tool_name {
-                                    *status = ToolStatus::WaitingApproval;
-                                }
+                        if let Some(DisplayItem::ToolCall {
+                            name: ref item_name,
+                            status,
+                            ..
+                        }) = self.items.last_mut()
+                        {
+                            if *item_name == tool_name {
+                                *status = ToolStatus::WaitingApproval;
                             }
-                            self.pending_approval = Some(respond);
-                            self.pending_approval_context =
-                                Some((tool_name.clone(), args_summary));
-                            self.mode = AppMode::AwaitingApproval;
                         }
+                        self.pending_approval = Some(respond);
+                        self.pending_approval_context = Some((tool_name.clone(), args_summary));
+                        self.pending_approval_rule =
+                            (!suggested_rules.is_empty()).then(|| suggested_rules.join(", "));
+                        self.mode = AppMode::AwaitingApproval;
                     }
                     AgentEvent::Retrying {
                         attempt,
@@ -1638,10 +1772,18 @@ impl App {
                             content: msg.clone(),
                         });
                     }
-                    AgentEvent::RoutedModel { model_name, tier } => {
+                    AgentEvent::RoutedModel {
+                        model_name,
+                        tier,
+                        reason,
+                    } => {
+                        let content = match reason {
+                            Some(reason) => format!("Falling back to {model_name}: {reason}"),
+                            None => format!("Routed to {model_name} (tier: {tier})"),
+                        };
                         self.items.push(DisplayItem::Message {
                             role: "system".to_string(),
-                            content: format!("Routed to {model_name} (tier: {tier})"),
+                            content,
                         });
                     }
                     AgentEvent::SubAgentSpawned { nickname, role, .. } => {
@@ -1718,7 +1860,14 @@ impl App {
                     AgentEvent::Usage(usage) => {
                         self.session_usage = usage;
                     }
+                    AgentEvent::CwdChanged(cwd) => {
+                        tool_ctx.cwd = cwd;
+                    }
                     AgentEvent::TurnComplete => {
+                        let turn_cancelled = self
+                            .foreground_task
+                            .as_ref()
+                            .is_some_and(|f| f.cancel.is_cancelled());
                         if !self.thinking_stream.is_empty() {
                             self.items.push(DisplayItem::Thinking(std::mem::take(
                                 &mut self.thinking_stream,
@@ -1830,11 +1979,13 @@ impl App {
                         if !self.hooks_config.is_empty() {
                             let hooks = self.hooks_config.clone();
                             let hook_cwd = tool_ctx.cwd.clone();
+                            let launcher = tool_ctx.launcher();
                             if let Some(tx) = self.hook_tx.clone() {
                                 tokio::spawn(async move {
-                                    let results =
-                                        nyzhi_core::hooks::run_after_turn_hooks(&hooks, &hook_cwd)
-                                            .await;
+                                    let results = nyzhi_core::hooks::run_after_turn_hooks(
+                                        &hooks, &hook_cwd, &launcher,
+                                    )
+                                    .await;
                                     for r in results {
                                         let _ = tx.send(r.summary());
                                     }
@@ -1842,7 +1993,7 @@ impl App {
                             }
                         }
 
-                        if let Some(ref mut ap) = self.autopilot {
+                        if let Some(ap) = self.autopilot.as_mut().filter(|_| !turn_cancelled) {
                             let last_output = self
                                 .items
                                 .iter()
@@ -2022,9 +2173,11 @@ impl App {
         }
 
         for bg in self.background_tasks.drain(..) {
-            bg.join_handle.abort();
+            bg.kill();
         }
+        self.processes.kill_all();
         if let Some(fg) = self.foreground_task.take() {
+            fg.cancel.cancel();
             fg.join_handle.abort();
         }
         self.try_save_session(thread.as_ref());
@@ -2683,6 +2836,7 @@ impl App {
         let mut items = Vec::new();
         let categories = [
             ("popular", "Popular"),
+            ("cloud", "Cloud"),
             ("agents", "Agents"),
             ("other", "Other"),
         ];
@@ -2854,6 +3008,8 @@ impl App {
                     "/export",
                     "/search",
                     "/retry",
+                    "/fork",
+                    "/branches",
                 ],
             ),
             (
@@ -2872,6 +3028,7 @@ impl App {
                 "View",
                 &[
                     "/status", "/context", "/changes", "/todo", "/plan", "/notepad", "/bg",
+                    "/ps",
                 ],
             ),
             ("UI", &["/settings", "/theme", "/accent", "/thinking", "/notify", "/image"]),
@@ -2924,6 +3081,20 @@ impl App {
             }
         }
 
+        let mcp_prompts = self
+            .mcp_manager
+            .as_ref()
+            .map(|mgr| mgr.prompts())
+            .unwrap_or_default();
+        if !mcp_prompts.is_empty() {
+            items.push(SelectorItem::header("MCP prompts"));
+            for prompt in mcp_prompts {
+                let slash_name = format!("/{}", prompt.command_name());
+                let label = format!("{:<18} {}", slash_name, prompt.description);
+                items.push(SelectorItem::entry(&label, &slash_name));
+            }
+        }
+
         self.selector = Some(SelectorState::new(
             SelectorKind::Command,
             "Commands",
@@ -3201,7 +3372,7 @@ impl App {
 
         match nyzhi_core::session::list_sessions() {
             Ok(mut sessions) => {
-                sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+                sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
                 if sessions.is_empty() {
                     self.items.push(DisplayItem::Message {
                         role: "system".to_string(),
@@ -3239,13 +3410,15 @@ impl App {
         }
     }
 
-    async fn respond_approval(&mut self, approved: bool) {
+    async fn respond_approval(&mut self, response: ApprovalResponse) {
         self.pending_approval_context = None;
+        self.pending_approval_rule = None;
         self.approval_cursor = 0;
+        let approved = response.approved;
         if let Some(respond) = self.pending_approval.take() {
             let mut guard = respond.lock().await;
             if let Some(sender) = guard.take() {
-                let _ = sender.send(approved);
+                let _ = sender.send(response);
             }
         }
         if !approved {
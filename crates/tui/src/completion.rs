@@ -47,6 +47,16 @@ pub const SLASH_COMMANDS: &[SlashCommandDef] = &[
         description: "manage background tasks",
         kind: CommandKind::Instant,
     },
+    SlashCommandDef {
+        name: "/branch",
+        description: "switch to another conversation branch",
+        kind: CommandKind::Instant,
+    },
+    SlashCommandDef {
+        name: "/branches",
+        description: "list conversation branches",
+        kind: CommandKind::Instant,
+    },
     SlashCommandDef {
         name: "/bug",
         description: "generate a bug report",
@@ -162,6 +172,11 @@ pub const SLASH_COMMANDS: &[SlashCommandDef] = &[
         description: "export conversation as markdown",
         kind: CommandKind::Instant,
     },
+    SlashCommandDef {
+        name: "/fork",
+        description: "branch from an earlier prompt and edit it",
+        kind: CommandKind::Instant,
+    },
     SlashCommandDef {
         name: "/handoff",
         description: "create session handoff for continuation",
@@ -192,6 +207,11 @@ pub const SLASH_COMMANDS: &[SlashCommandDef] = &[
         description: "disable auto-context for this session",
         kind: CommandKind::Instant,
     },
+    SlashCommandDef {
+        name: "/index recall",
+        description: "measure ANN search recall against an exact scan",
+        kind: CommandKind::Instant,
+    },
     SlashCommandDef {
         name: "/index status",
         description: "show index stats (files, chunks, db size)",
@@ -262,6 +282,11 @@ pub const SLASH_COMMANDS: &[SlashCommandDef] = &[
         description: "view or create execution plans",
         kind: CommandKind::Instant,
     },
+    SlashCommandDef {
+        name: "/ps",
+        description: "list and kill background processes",
+        kind: CommandKind::Instant,
+    },
     SlashCommandDef {
         name: "/qa",
         description: "run autonomous QA cycling",
@@ -289,7 +314,7 @@ pub const SLASH_COMMANDS: &[SlashCommandDef] = &[
     },
     SlashCommandDef {
         name: "/retry",
-        description: "resend the last prompt",
+        description: "resend the last prompt on a new branch",
         kind: CommandKind::Prompt,
     },
     SlashCommandDef {
@@ -516,11 +541,7 @@ pub fn classify_command(input: &str) -> CommandKind {
             return def.kind;
         }
     }
-    if cmd.starts_with('/') {
-        CommandKind::Prompt
-    } else {
-        CommandKind::Prompt
-    }
+    CommandKind::Prompt
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -758,7 +779,7 @@ fn fuzzy_file_search(query: &str, root: &Path, limit: usize) -> Vec<(String, u32
     }
 
     walk(root, root, &query_lower, &mut results, 0);
-    results.sort_by(|a, b| b.1.cmp(&a.1));
+    results.sort_by_key(|r| std::cmp::Reverse(r.1));
     results.truncate(limit);
     results
 }
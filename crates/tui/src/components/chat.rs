@@ -844,8 +844,6 @@ fn render_diff_line<'a>(line: &str, theme: &Theme) -> Line<'a> {
         theme.success
     } else if line.starts_with('-') {
         theme.danger
-    } else if line.starts_with("@@") {
-        theme.text_tertiary
     } else {
         theme.text_tertiary
     };
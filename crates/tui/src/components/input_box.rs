@@ -278,7 +278,13 @@ fn render_streaming(
 }
 
 fn render_approval(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
-    let buttons: [(&str, usize); 3] = [("Allow", 0), ("Deny", 1), ("Always", 2)];
+    let buttons: [(&str, usize); 5] = [
+        ("Allow", 0),
+        ("Deny", 1),
+        ("Session", 2),
+        ("Project", 3),
+        ("Always", 4),
+    ];
     let w = area.width as usize;
 
     let mut lines: Vec<Line> = Vec::new();
@@ -305,8 +311,16 @@ fn render_approval(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
         }
         lines.push(Line::from(row1));
 
-        // Row 2: additional context lines (if area tall enough and args multi-line)
-        if area.height >= 3 {
+        // Row 2: the rule a remembering button would save, otherwise
+        // additional context lines (if area tall enough and args multi-line)
+        if area.height >= 3 && app.approval_cursor >= 2 {
+            if let Some(ref rule) = app.pending_approval_rule {
+                lines.push(Line::from(vec![
+                    Span::styled("  remember ", ty::caption(theme)),
+                    Span::styled(rule.clone(), Style::default().fg(theme.text_primary)),
+                ]));
+            }
+        } else if area.height >= 3 {
             let arg_lines: Vec<&str> = args.lines().skip(1).take(1).collect();
             for arg_line in arg_lines {
                 let max_ctx = w.saturating_sub(4);
@@ -325,7 +339,7 @@ fn render_approval(frame: &mut Frame, area: Rect, app: &App, theme: &Theme) {
         ]));
     }
 
-    // Button row: centered [Allow] [Deny] [Always]
+    // Button row: centered [Allow] [Deny] [Session] [Project] [Always]
     let mut btn_spans: Vec<Span> = Vec::new();
     for (i, (label, idx)) in buttons.iter().enumerate() {
         if i > 0 {
@@ -4,6 +4,7 @@ pub mod header;
 pub mod input_box;
 pub mod plan_banner;
 pub mod plan_panel;
+pub mod process_panel;
 pub mod selector;
 pub mod settings_panel;
 pub mod text_prompt;
@@ -0,0 +1,177 @@
+use nyzhi_core::processes::{ProcessInfo, ProcessManager, ProcessStatus};
+use ratatui::prelude::*;
+use ratatui::widgets::*;
+
+use crate::aesthetic::primitives;
+use crate::aesthetic::tokens::*;
+use crate::aesthetic::typography as ty;
+use crate::theme::Theme;
+
+#[derive(Debug, Default)]
+pub struct ProcessPanelState {
+    pub items: Vec<ProcessInfo>,
+    pub cursor: usize,
+}
+
+impl ProcessPanelState {
+    pub fn new(manager: &ProcessManager) -> Self {
+        Self {
+            items: manager.list(),
+            cursor: 0,
+        }
+    }
+
+    /// Pick up new processes and status changes.
+    pub fn refresh(&mut self, manager: &ProcessManager) {
+        self.items = manager.list();
+        self.cursor = self.cursor.min(self.items.len().saturating_sub(1));
+    }
+
+    pub fn selected(&self) -> Option<&ProcessInfo> {
+        self.items.get(self.cursor)
+    }
+
+    pub fn move_up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.cursor + 1 < self.items.len() {
+            self.cursor += 1;
+        }
+    }
+}
+
+fn format_uptime(info: &ProcessInfo) -> String {
+    let secs = (chrono::Utc::now() - info.started_at).num_seconds().max(0);
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+pub fn draw(frame: &mut Frame, state: &ProcessPanelState, theme: &Theme) {
+    primitives::blur_overlay(frame, theme);
+
+    let area = frame.area();
+    let total = state.items.len();
+    let running = state
+        .items
+        .iter()
+        .filter(|p| p.status == ProcessStatus::Running)
+        .count();
+
+    let popup_w = (POPUP_MAX_W_PCT as u32 * area.width as u32 / 100) as u16;
+    let popup_w = popup_w.min(area.width.saturating_sub(POPUP_MARGIN));
+    let content_rows = total as u16 * 2 + SP_2;
+    let popup_h = (content_rows + SP_4)
+        .min(area.height.saturating_sub(POPUP_MARGIN))
+        .max(8);
+    let popup_area = primitives::centered_popup(area, popup_w, popup_h);
+
+    let title = format!("Background processes ({running} running)");
+    let footer_spans = vec![
+        Span::styled(" x", Style::default().fg(theme.accent).bold()),
+        Span::styled(": kill ", ty::disabled(theme)),
+        Span::styled("esc", Style::default().fg(theme.accent).bold()),
+        Span::styled(": close ", ty::disabled(theme)),
+    ];
+
+    let card = primitives::Card::new(theme)
+        .title(&title)
+        .border(theme.accent)
+        .title_bottom_spans(footer_spans);
+    let inner = card.render_frame(frame, popup_area);
+
+    if total == 0 {
+        let empty = Paragraph::new(Line::from(vec![
+            Span::styled("  No background processes. ", ty::disabled(theme)),
+            Span::styled(
+                "The agent starts them with process_start.",
+                ty::muted(theme),
+            ),
+        ]))
+        .style(ty::on_elevated(theme));
+        frame.render_widget(empty, inner);
+        return;
+    }
+
+    let inner_w = inner.width as usize;
+    let mut lines: Vec<Line> = Vec::new();
+
+    for (i, item) in state.items.iter().enumerate() {
+        let is_selected = i == state.cursor;
+        let row_bg = if is_selected {
+            theme.accent
+        } else {
+            theme.bg_elevated
+        };
+        let primary_fg = if is_selected {
+            theme.bg_page
+        } else {
+            theme.text_primary
+        };
+
+        let (marker, marker_fg) = match item.status {
+            ProcessStatus::Running => ("\u{25CF}", theme.success),
+            ProcessStatus::Exited(Some(0)) => ("\u{2713}", theme.text_secondary),
+            ProcessStatus::Exited(_) => ("\u{2717}", theme.danger),
+            ProcessStatus::Killed => ("\u{25A0}", theme.text_disabled),
+        };
+        let marker_fg = if is_selected {
+            theme.bg_page
+        } else {
+            marker_fg
+        };
+
+        let status = format!(" {} \u{00B7} {} ", item.status, format_uptime(item));
+        let max_command = inner_w.saturating_sub(status.chars().count() + SP_8 as usize);
+        let command: String = if item.command.chars().count() > max_command {
+            let kept: String = item
+                .command
+                .chars()
+                .take(max_command.saturating_sub(1))
+                .collect();
+            format!("{kept}\u{2026}")
+        } else {
+            item.command.clone()
+        };
+
+        let mut spans = vec![
+            Span::styled(" ", Style::default().bg(row_bg)),
+            Span::styled(
+                format!(" {marker} "),
+                Style::default().fg(marker_fg).bg(row_bg).bold(),
+            ),
+            Span::styled(
+                format!("#{} ", item.id),
+                Style::default().fg(primary_fg).bg(row_bg).bold(),
+            ),
+            Span::styled(command, Style::default().fg(primary_fg).bg(row_bg)),
+        ];
+        let used: usize = spans.iter().map(|s| s.width()).sum();
+        let gap = inner_w.saturating_sub(used + status.chars().count());
+        spans.push(Span::styled(" ".repeat(gap), Style::default().bg(row_bg)));
+        spans.push(Span::styled(
+            status,
+            Style::default().fg(primary_fg).bg(row_bg),
+        ));
+        lines.push(Line::from(spans));
+
+        lines.push(Line::from(Span::styled(
+            format!("      {}", item.log_path.display()),
+            ty::muted(theme),
+        )));
+    }
+
+    // Keep the selected row in view.
+    let visible = inner.height as usize;
+    let skip = (state.cursor * 2 + 2).saturating_sub(visible);
+    let display_lines: Vec<Line> = lines.into_iter().skip(skip).take(visible).collect();
+
+    let paragraph = Paragraph::new(display_lines).style(ty::on_elevated(theme));
+    frame.render_widget(paragraph, inner);
+}
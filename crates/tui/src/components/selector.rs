@@ -404,7 +404,7 @@ pub fn draw(frame: &mut Frame, selector: &SelectorState, theme: &Theme) {
         ));
 
         if let Some(color) = item.preview_color {
-            let swatch_fg = if is_cursor { color } else { color };
+            let swatch_fg = color;
             spans.push(Span::styled(
                 "\u{2588} ",
                 Style::default().fg(swatch_fg).bg(row_bg),
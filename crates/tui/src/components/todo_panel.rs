@@ -113,17 +113,9 @@ pub fn draw(frame: &mut Frame, state: &TodoPanelState, theme: &Theme) {
 
     let inner_w = inner.width as usize;
     let bar_width = inner_w.saturating_sub(PAD_H as usize * 2 + SP_8 as usize);
-    let filled = if total > 0 {
-        (done * bar_width) / total
-    } else {
-        0
-    };
+    let filled = (done * bar_width).checked_div(total).unwrap_or(0);
     let empty_bar = bar_width.saturating_sub(filled);
-    let pct = if total > 0 {
-        (done * 100) / total
-    } else {
-        0
-    };
+    let pct = (done * 100).checked_div(total).unwrap_or(0);
 
     let bar_line = Line::from(vec![
         Span::raw("  "),
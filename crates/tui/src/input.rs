@@ -251,8 +251,9 @@ pub async fn handle_key(
                     Some(focus_hint)
                 };
 
-                if model_info.is_some() {
-                    let est = thread.estimated_tokens(&agent_config.system_prompt);
+                if let Some(mi) = model_info {
+                    let counter = nyzhi_core::context::TokenCounter::for_model(&mi.provider, &mi.id);
+                    let est = thread.estimated_tokens(&counter, &agent_config.system_prompt);
                     let hint_msg = focus.map(|h| format!(" (focus: {h})")).unwrap_or_default();
                     app.items.push(DisplayItem::Message {
                         role: "system".to_string(),
@@ -269,13 +270,15 @@ pub async fn handle_key(
                     let threshold = agent_config.auto_compact_threshold.unwrap_or(0.85);
                     let cw = model_info.map(|m| m.context_window).unwrap_or(200_000);
 
-                    let savings = nyzhi_core::context::progressive_compact(
-                        thread.messages_mut(),
-                        &microcompact_dir,
-                        est,
-                        cw,
-                        threshold,
-                    );
+                    let savings = thread.edit_messages(|messages| {
+                        nyzhi_core::context::progressive_compact(
+                            messages,
+                            &microcompact_dir,
+                            est,
+                            cw,
+                            threshold,
+                        )
+                    });
                     for (desc, saved) in &savings {
                         app.items.push(DisplayItem::Message {
                             role: "system".to_string(),
@@ -331,7 +334,7 @@ pub async fn handle_key(
                                             .filter(|e| {
                                                 e.path()
                                                     .extension()
-                                                    .map_or(false, |ext| ext == "md")
+                                                    .is_some_and(|ext| ext == "md")
                                             })
                                             .max_by_key(|e| {
                                                 e.metadata().ok().and_then(|m| m.modified().ok())
@@ -356,7 +359,8 @@ pub async fn handle_key(
                                 plan_content.as_deref(),
                                 notepad_content.as_deref(),
                             );
-                            let new_est = thread.estimated_tokens(&agent_config.system_prompt);
+                            let new_est =
+                                thread.estimated_tokens(&counter, &agent_config.system_prompt);
                             app.items.push(DisplayItem::Message {
                                 role: "system".to_string(),
                                 content: format!(
@@ -389,7 +393,11 @@ pub async fn handle_key(
                 let thread_msgs = thread.as_ref().map(|t| t.messages()).unwrap_or(&empty_msgs);
                 let threshold = agent_config.auto_compact_threshold.unwrap_or(0.85);
                 let cw = model_info.map(|m| m.context_window).unwrap_or(0);
+                let counter = model_info
+                    .map(|mi| nyzhi_core::context::TokenCounter::for_model(&mi.provider, &mi.id))
+                    .unwrap_or_default();
                 let breakdown = nyzhi_core::context::ContextBreakdown::compute(
+                    &counter,
                     thread_msgs,
                     &agent_config.system_prompt,
                     cw,
@@ -413,7 +421,7 @@ pub async fn handle_key(
 
             if input.starts_with("/sessions ") {
                 let query = input.strip_prefix("/sessions ").unwrap().trim();
-                match nyzhi_core::session::find_sessions(query) {
+                match nyzhi_core::session::search_sessions(query) {
                     Ok(sessions) => {
                         if sessions.is_empty() {
                             app.items.push(DisplayItem::Message {
@@ -427,13 +435,17 @@ pub async fn handle_key(
                             let items: Vec<SelectorItem> = sessions
                                 .iter()
                                 .take(20)
-                                .map(|s| {
-                                    let label = format!(
+                                .map(|m| {
+                                    let s = &m.meta;
+                                    let mut label = format!(
                                         "{} ({} msgs, {})",
                                         s.title,
                                         s.message_count,
                                         s.updated_at.format("%m/%d %H:%M"),
                                     );
+                                    if let Some(snippet) = &m.snippet {
+                                        label.push_str(&format!(" — {}", snippet.replace('\n', " ")));
+                                    }
                                     SelectorItem::entry(&label, &s.id)
                                 })
                                 .collect();
@@ -490,23 +502,7 @@ pub async fn handle_key(
                                         app.session_usage =
                                             nyzhi_core::agent::SessionUsage::default();
 
-                                        for msg in loaded_thread.messages() {
-                                            let role = match msg.role {
-                                                nyzhi_provider::Role::User => "user",
-                                                nyzhi_provider::Role::Assistant => "assistant",
-                                                _ => "system",
-                                            };
-                                            let mut text = msg.content.as_text().to_string();
-                                            if msg.content.has_images() {
-                                                text.push_str("\n[image attached]");
-                                            }
-                                            if !text.is_empty() {
-                                                app.items.push(DisplayItem::Message {
-                                                    role: role.to_string(),
-                                                    content: text,
-                                                });
-                                            }
-                                        }
+                                        app.push_thread_items(&loaded_thread);
                                         app.items.push(DisplayItem::Message {
                                             role: "system".to_string(),
                                             content: format!(
@@ -977,10 +973,11 @@ pub async fn handle_key(
                 if let Ok(entries) = std::fs::read_dir(&handoff_dir) {
                     for e in entries.flatten() {
                         let name = e.file_name().to_string_lossy().to_string();
-                        if name.starts_with("handoff-") && name.ends_with(".md") {
-                            if latest.as_ref().map_or(true, |(n, _)| name > *n) {
-                                latest = Some((name, e.path()));
-                            }
+                        if name.starts_with("handoff-")
+                            && name.ends_with(".md")
+                            && latest.as_ref().map_or(true, |(n, _)| name > *n)
+                        {
+                            latest = Some((name, e.path()));
                         }
                     }
                 }
@@ -1266,8 +1263,13 @@ pub async fn handle_key(
                     } else {
                         let mut lines = vec![format!("MCP servers ({}):", servers.len())];
                         for s in &servers {
+                            let prompts = if s.prompt_count > 0 {
+                                format!(", {} prompts", s.prompt_count)
+                            } else {
+                                String::new()
+                            };
                             lines.push(format!(
-                                "  {}  ({} tools: {})",
+                                "  {}  ({} tools{prompts}: {})",
                                 s.name,
                                 s.tool_count,
                                 s.tool_names.join(", "),
@@ -2373,9 +2375,11 @@ pub async fn handle_key(
                     let msg = if let Some(ref idx) = app.codebase_index {
                         match idx.stats() {
                             Ok(s) => format!(
-                                "Index: {} files, {} chunks, {} vectors\n  Ready: {}",
+                                "Index: {} files, {} chunks, {} vectors\n  Ready: {}\n  Watching: {}\n  Search: {}",
                                 s.file_count, s.chunk_count, s.vector_count,
-                                idx.is_ready()
+                                idx.is_ready(),
+                                idx.is_watching(),
+                                idx.search_backend()
                             ),
                             Err(e) => format!("Index stats error: {e}"),
                         }
@@ -2386,7 +2390,26 @@ pub async fn handle_key(
                         role: "system".to_string(),
                         content: msg,
                     });
+                } else if sub == "recall" {
+                    let msg = match app.codebase_index.as_ref() {
+                        Some(idx) => match idx.ann_recall(100, 10) {
+                            Some(r) => format!(
+                                "ANN recall@10 vs exact scan: {:.3} (100 sampled queries, {} search in use)",
+                                r,
+                                idx.search_backend()
+                            ),
+                            None => "Index is empty.".to_string(),
+                        },
+                        None => "Index: not initialized".to_string(),
+                    };
+                    app.items.push(DisplayItem::Message {
+                        role: "system".to_string(),
+                        content: msg,
+                    });
                 } else if sub == "off" {
+                    if let Some(ref idx) = app.codebase_index {
+                        idx.stop_watching();
+                    }
                     app.codebase_index = None;
                     app.index_progress = None;
                     app.items.push(DisplayItem::Message {
@@ -3086,7 +3109,7 @@ pub async fn handle_key(
                     if let Ok(id) = rest.parse::<usize>() {
                         if let Some(pos) = app.background_tasks.iter().position(|b| b.id == id) {
                             let bg = app.background_tasks.remove(pos);
-                            bg.join_handle.abort();
+                            bg.kill();
                             app.items.push(DisplayItem::Message {
                                 role: "system".to_string(),
                                 content: format!("Killed background task #{id}: {}", bg.label),
@@ -3114,6 +3137,15 @@ pub async fn handle_key(
                 return;
             }
 
+            if input == "/ps" {
+                app.process_panel = Some(
+                    crate::components::process_panel::ProcessPanelState::new(&app.processes),
+                );
+                app.input.clear();
+                app.cursor_pos = 0;
+                return;
+            }
+
             if input == "/enable_exa" {
                 // Check if already configured
                 let config_check = nyzhi_config::Config::load().ok();
@@ -3400,7 +3432,10 @@ pub async fn handle_key(
                         "  /accent         Choose accent color",
                         "  /trust          Choose trust mode (off/limited/autoedit/full)",
                         "  /editor         Open $EDITOR for multi-line input",
-                        "  /retry          Resend the last prompt",
+                        "  /retry          Resend the last prompt on a new branch",
+                        "  /fork [n]       List prompts, or branch before prompt n to edit it",
+                        "  /branches       List conversation branches",
+                        "  /branch <n>     Switch to branch n",
                         "  /undo           Undo the last file change",
                         "  /undo all       Undo all file changes in this session",
                         "  /changes        List all file changes in this session",
@@ -3409,6 +3444,7 @@ pub async fn handle_key(
                         "  /think          Toggle extended thinking (on/off/budget N)",
                         "  /bg             List background tasks",
                         "  /bg kill <id>   Cancel a background task",
+                        "  /ps             Show background processes (x to kill)",
                         "  /notify         Show notification settings",
                         "  /notify bell|desktop on|off  Toggle notifications",
                         "  /notify duration <ms>        Set min turn duration threshold",
@@ -3419,6 +3455,7 @@ pub async fn handle_key(
                         "  git_commit, git_checkout (require approval)",
                         "  spawn_agent (delegate to a sub-agent by role)",
                         "  send_input, wait, close_agent (manage sub-agents)",
+                        "  process_start, process_output, process_send, process_kill (background processes)",
                         "",
                         "Auth:",
                         "  /connect             Default provider setup (OAuth first, API key fallback)",
@@ -3466,11 +3503,142 @@ pub async fn handle_key(
                 return;
             }
 
+            if input == "/fork" || input.starts_with("/fork ") {
+                app.input.clear();
+                app.cursor_pos = 0;
+                let Some(ref mut t) = thread else {
+                    return;
+                };
+                let prompts = t.prompt_indices();
+                let arg = input.strip_prefix("/fork").unwrap().trim();
+                if arg.is_empty() {
+                    let content = if prompts.is_empty() {
+                        "No prompts to fork from".to_string()
+                    } else {
+                        let mut lines = vec!["Prompts on this branch:".to_string()];
+                        for (n, idx) in prompts.iter().enumerate() {
+                            lines.push(format!(
+                                "  {}. {}",
+                                n + 1,
+                                truncate_label(t.messages()[*idx].content.as_text())
+                            ));
+                        }
+                        lines.push("Use /fork <n> to branch before a prompt and edit it.".into());
+                        lines.join("\n")
+                    };
+                    app.items.push(DisplayItem::Message {
+                        role: "system".to_string(),
+                        content,
+                    });
+                    return;
+                }
+                let Some(idx) = arg
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|n| prompts.get(n).copied())
+                else {
+                    app.items.push(DisplayItem::Message {
+                        role: "system".to_string(),
+                        content: format!(
+                            "Usage: /fork <n> with n between 1 and {}",
+                            prompts.len()
+                        ),
+                    });
+                    return;
+                };
+                let prompt = t.messages()[idx].content.as_text().to_string();
+                let branch = t.fork(idx);
+                app.items.clear();
+                app.push_thread_items(t);
+                app.items.push(DisplayItem::Message {
+                    role: "system".to_string(),
+                    content: format!(
+                        "Started branch {} before prompt {arg}. Edit the prompt and press Enter; /branches lists the others.",
+                        branch + 1
+                    ),
+                });
+                app.input = prompt;
+                app.cursor_pos = app.input.len();
+                return;
+            }
+
+            if input == "/branches" || input == "/branch" {
+                app.input.clear();
+                app.cursor_pos = 0;
+                let Some(ref t) = thread else {
+                    return;
+                };
+                let mut lines = vec!["Branches:".to_string()];
+                for b in t.branches() {
+                    let marker = if b.active { "*" } else { " " };
+                    let prompt = if b.last_prompt.is_empty() {
+                        "(empty)".to_string()
+                    } else {
+                        truncate_label(&b.last_prompt)
+                    };
+                    lines.push(format!(
+                        " {marker}{:>2}. {prompt}  ({} messages, {} shared)",
+                        b.index + 1,
+                        b.message_count,
+                        b.shared_with_active,
+                    ));
+                }
+                lines.push("Use /branch <n> to switch.".into());
+                app.items.push(DisplayItem::Message {
+                    role: "system".to_string(),
+                    content: lines.join("\n"),
+                });
+                return;
+            }
+
+            if let Some(arg) = input.strip_prefix("/branch ") {
+                app.input.clear();
+                app.cursor_pos = 0;
+                let Some(ref mut t) = thread else {
+                    return;
+                };
+                let result = arg
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Usage: /branch <n>"))
+                    .and_then(|n| t.switch_branch(n.saturating_sub(1)));
+                match result {
+                    Ok(()) => {
+                        app.items.clear();
+                        app.push_thread_items(t);
+                        app.items.push(DisplayItem::Message {
+                            role: "system".to_string(),
+                            content: format!(
+                                "Switched to branch {} ({} messages)",
+                                t.active_branch() + 1,
+                                t.message_count()
+                            ),
+                        });
+                    }
+                    Err(e) => {
+                        app.items.push(DisplayItem::Message {
+                            role: "system".to_string(),
+                            content: e.to_string(),
+                        });
+                    }
+                }
+                return;
+            }
+
             if input == "/retry" {
                 if let Some(ref last) = app.last_prompt {
                     let retry_input = last.clone();
                     app.input.clear();
                     app.cursor_pos = 0;
+                    // Keep the previous answer on its own branch.
+                    if let Some(ref mut t) = thread {
+                        if let Some(idx) = t.prompt_indices().last().copied() {
+                            t.fork(idx);
+                            app.items.clear();
+                            app.push_thread_items(t);
+                        }
+                    }
                     let label = truncate_label(&retry_input);
                     app.items.push(DisplayItem::Message {
                         role: "user".to_string(),
@@ -3494,6 +3662,44 @@ pub async fn handle_key(
                 return;
             }
 
+            let mcp_prompt = app.mcp_manager.as_ref().and_then(|mgr| {
+                mgr.prompts().into_iter().find(|p| {
+                    let name = format!("/{}", p.command_name());
+                    input == name || input.starts_with(&format!("{name} "))
+                })
+            });
+            if let (Some(prompt), Some(mgr)) = (mcp_prompt, app.mcp_manager.clone()) {
+                let name = format!("/{}", prompt.command_name());
+                let args = input.strip_prefix(&name).unwrap_or("").trim();
+                app.input.clear();
+                app.cursor_pos = 0;
+                match mgr.get_prompt(&prompt, args).await {
+                    Ok(expanded) => {
+                        app.last_prompt = Some(expanded.clone());
+                        app.history.push(input.clone());
+                        let label = truncate_label(format!("{name} {args}").trim());
+                        app.items.push(DisplayItem::Message {
+                            role: "user".to_string(),
+                            content: format!("{name} {args}").trim().to_string(),
+                        });
+                        app.mode = AppMode::Streaming;
+                        app.turn_request = Some(TurnRequest {
+                            input: expanded,
+                            content: None,
+                            is_background: false,
+                            label,
+                        });
+                    }
+                    Err(e) => {
+                        app.items.push(DisplayItem::Message {
+                            role: "system".to_string(),
+                            content: format!("{e:#}"),
+                        });
+                    }
+                }
+                return;
+            }
+
             if let Some(cmd) = app.custom_commands.iter().find(|c| {
                 input == format!("/{}", c.name) || input.starts_with(&format!("/{} ", c.name))
             }) {
@@ -3707,7 +3913,7 @@ pub async fn handle_key(
             });
 
             let mentions = nyzhi_core::context_files::parse_mentions(&input);
-            let context_files = if mentions.is_empty() {
+            let mut context_files = if mentions.is_empty() {
                 Vec::new()
             } else {
                 nyzhi_core::context_files::resolve_context_files(
@@ -3716,6 +3922,9 @@ pub async fn handle_key(
                     &tool_ctx.cwd,
                 )
             };
+            if let (Some(mgr), false) = (&app.mcp_manager, mentions.is_empty()) {
+                context_files.extend(mgr.resolve_mentions(&mentions).await);
+            }
 
             let has_images = !app.pending_images.is_empty();
             let has_context = !context_files.is_empty();
@@ -3801,15 +4010,11 @@ pub async fn handle_key(
                 try_open_completion(app, &tool_ctx.cwd);
             }
         }
-        KeyCode::Left => {
-            if app.cursor_pos > 0 {
-                app.cursor_pos -= 1;
-            }
+        KeyCode::Left if app.cursor_pos > 0 => {
+            app.cursor_pos -= 1;
         }
-        KeyCode::Right => {
-            if app.cursor_pos < app.input.len() {
-                app.cursor_pos += 1;
-            }
+        KeyCode::Right if app.cursor_pos < app.input.len() => {
+            app.cursor_pos += 1;
         }
         KeyCode::Home => {
             let before = &app.input[..app.cursor_pos];
@@ -3886,10 +4091,8 @@ fn handle_history_search_key(app: &mut App, key: KeyEvent) {
                 search.selected += 1;
             }
         }
-        KeyCode::Down => {
-            if search.selected > 0 {
-                search.selected -= 1;
-            }
+        KeyCode::Down if search.selected > 0 => {
+            search.selected -= 1;
         }
         KeyCode::Char(c) => {
             if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'r' {
@@ -4021,13 +4224,29 @@ fn cycle_thinking_level(app: &mut App, model_info: Option<&ModelInfo>, reverse:
     app.scroll_offset = 0;
 }
 
+/// Custom commands plus the prompt templates of MCP servers.
+fn completable_commands(app: &App) -> Vec<nyzhi_core::commands::CustomCommand> {
+    let mut commands = app.custom_commands.clone();
+    if let Some(mgr) = &app.mcp_manager {
+        commands.extend(mgr.prompts().into_iter().map(|p| {
+            nyzhi_core::commands::CustomCommand {
+                name: p.command_name(),
+                prompt_template: String::new(),
+                description: p.description,
+            }
+        }));
+    }
+    commands
+}
+
 fn try_open_completion(app: &mut App, cwd: &std::path::Path) {
     use crate::completion::{detect_context, generate_candidates, CompletionState};
 
     let Some((ctx, prefix, start)) = detect_context(&app.input, app.cursor_pos) else {
         return;
     };
-    let (candidates, descriptions) = generate_candidates(&ctx, &prefix, cwd, &app.custom_commands);
+    let commands = completable_commands(app);
+    let (candidates, descriptions) = generate_candidates(&ctx, &prefix, cwd, &commands);
     if candidates.is_empty() {
         return;
     }
@@ -4052,8 +4271,8 @@ fn accept_completion(app: &mut App, cwd: &std::path::Path) {
 
     if is_dir {
         if let Some((ctx, prefix, start)) = detect_context(&app.input, app.cursor_pos) {
-            let (candidates, descriptions) =
-                generate_candidates(&ctx, &prefix, cwd, &app.custom_commands);
+            let commands = completable_commands(app);
+            let (candidates, descriptions) = generate_candidates(&ctx, &prefix, cwd, &commands);
             if !candidates.is_empty() {
                 app.completion = Some(CompletionState {
                     candidates,
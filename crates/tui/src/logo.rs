@@ -1,3 +1,4 @@
+#[allow(dead_code)]
 pub const LOGO_SPLASH: &str = r#"
       .+##########@@@@#####.
    .+##+...................+@@#.
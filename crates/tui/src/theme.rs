@@ -267,7 +267,7 @@ impl ThemePreset {
     }
 
     pub fn from_name(name: &str) -> Self {
-        let s = name.to_lowercase().replace(' ', "-").replace('_', "-");
+        let s = name.to_lowercase().replace([' ', '_'], "-");
         match s.as_str() {
             "dark" | "nyzhi-dark" | "nyzhidark" => ThemePreset::NyzhiDark,
             "light" | "nyzhi-light" | "nyzhi_light" | "nyzhilight" => ThemePreset::NyzhiLight,
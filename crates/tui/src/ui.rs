@@ -4,8 +4,8 @@ use crate::aesthetic::layout as aes_layout;
 use crate::aesthetic::typography as ty;
 use crate::app::App;
 use crate::components::{
-    chat, footer, header, input_box, plan_banner, plan_panel, selector, settings_panel,
-    text_prompt, todo_panel, update_banner, welcome,
+    chat, footer, header, input_box, plan_banner, plan_panel, process_panel, selector,
+    settings_panel, text_prompt, todo_panel, update_banner, welcome,
 };
 use crate::spinner::SpinnerState;
 use crate::theme::Theme;
@@ -76,6 +76,10 @@ pub fn draw(frame: &mut Frame, app: &App, theme: &Theme, spinner: &SpinnerState)
         todo_panel::draw(frame, tp, theme);
     }
 
+    if let Some(ref pp) = app.process_panel {
+        process_panel::draw(frame, pp, theme);
+    }
+
     if let Some(ref panel) = app.settings_panel {
         settings_panel::draw(frame, panel, theme);
     }